@@ -0,0 +1,86 @@
+#[macro_use]
+extern crate bencher;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use bencher::Bencher;
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{self, Read};
+
+use serde_cbor::de::IoRead;
+use serde_cbor::{to_vec, Deserializer};
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    id: u32,
+    name: String,
+    tags: Vec<String>,
+    score: f64,
+}
+
+fn records() -> Vec<u8> {
+    let records: Vec<Record> = (0..1000)
+        .map(|id| Record {
+            id,
+            name: format!("record {}", id),
+            tags: vec!["a".to_string(), "bc".to_string()],
+            score: f64::from(id) / 3.0,
+        })
+        .collect();
+    to_vec(&records).unwrap()
+}
+
+fn decode<R: Read>(read: IoRead<R>) {
+    let mut deserializer = Deserializer::new(read);
+    Vec::<Record>::deserialize(&mut deserializer).unwrap();
+}
+
+// An in-memory reader that is not specialized for, to measure the cost of each call to `read`.
+struct Unbuffered<'a>(&'a [u8]);
+
+impl<'a> Read for Unbuffered<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+fn memory_unbuffered(b: &mut Bencher) {
+    let bytes = records();
+    b.bytes = bytes.len() as u64;
+    b.iter(|| decode(IoRead::with_capacity(Unbuffered(&bytes), 1)));
+}
+
+fn memory_buffered(b: &mut Bencher) {
+    let bytes = records();
+    b.bytes = bytes.len() as u64;
+    b.iter(|| decode(IoRead::new(Unbuffered(&bytes))));
+}
+
+fn file(b: &mut Bencher, capacity: usize) {
+    let bytes = records();
+    let path = std::env::temp_dir().join(format!("serde_cbor_bench_{}", capacity));
+    fs::write(&path, &bytes).unwrap();
+    b.bytes = bytes.len() as u64;
+    b.iter(|| decode(IoRead::with_capacity(File::open(&path).unwrap(), capacity)));
+    fs::remove_file(&path).unwrap();
+}
+
+fn file_unbuffered(b: &mut Bencher) {
+    file(b, 1);
+}
+
+fn file_buffered(b: &mut Bencher) {
+    file(b, 8 * 1024);
+}
+
+benchmark_group!(
+    benches,
+    memory_unbuffered,
+    memory_buffered,
+    file_unbuffered,
+    file_buffered
+);
+benchmark_main!(benches);
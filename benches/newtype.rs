@@ -0,0 +1,56 @@
+#[macro_use]
+extern crate bencher;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use bencher::Bencher;
+
+use serde_cbor::{from_slice, to_vec};
+
+#[derive(Serialize, Deserialize)]
+struct UserId(u64);
+
+#[derive(Serialize, Deserialize)]
+struct Email(String);
+
+#[derive(Serialize, Deserialize)]
+struct Label(String);
+
+#[derive(Serialize, Deserialize)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct User {
+    id: UserId,
+    email: Email,
+    location: Point,
+    labels: Vec<Label>,
+}
+
+fn users() -> Vec<u8> {
+    let users: Vec<User> = (0..1000)
+        .map(|id| User {
+            id: UserId(id),
+            email: Email(format!("user{}@example.com", id)),
+            location: Point {
+                x: id as f64 / 3.0,
+                y: id as f64 / 7.0,
+            },
+            labels: vec![Label("a".to_string()), Label("bc".to_string())],
+        })
+        .collect();
+    to_vec(&users).unwrap()
+}
+
+fn newtypes(b: &mut Bencher) {
+    let bytes = users();
+    b.bytes = bytes.len() as u64;
+    b.iter(|| from_slice::<Vec<User>>(&bytes).unwrap());
+}
+
+benchmark_group!(benches, newtypes);
+benchmark_main!(benches);
@@ -0,0 +1,274 @@
+// Reading CBOR from asynchronous input.
+//
+// Serde deserializes synchronously, so data items are first read into a buffer as they arrive,
+// until a scan of their structure finds them complete, and then deserialized from the buffer.
+// This is the work of `Incremental`, which is fed here from the reader. The buffer holds a
+// single data item rather than a whole frame of the application.
+#[cfg(feature = "futures-io")]
+use futures_io::AsyncRead;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+#[cfg(feature = "tokio")]
+use tokio::io::ReadBuf;
+
+use error::{Error, ErrorCode, Result};
+use incremental::Incremental;
+
+// How much is read from the reader at once.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// An asynchronous source of input for `AsyncIoRead`.
+///
+/// This is implemented for readers of `futures::io::AsyncRead` with the `futures-io` feature, and
+/// for readers of `tokio::io::AsyncRead` wrapped in `TokioIo` with the `tokio` feature.
+pub trait AsyncSource {
+    /// Attempts to read into `buf`, returning the number of bytes read, which is zero at the end
+    /// of the input.
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8])
+        -> Poll<io::Result<usize>>;
+}
+
+#[cfg(feature = "futures-io")]
+impl<R> AsyncSource for R
+where
+    R: AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        AsyncRead::poll_read(self, cx, buf)
+    }
+}
+
+/// Wraps a reader of `tokio::io::AsyncRead` to be read by `AsyncIoRead`, or a writer of
+/// `tokio::io::AsyncWrite` to be written by `AsyncIoWrite`.
+///
+/// This type is available with the `tokio` feature.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct TokioIo<R>(pub R);
+
+#[cfg(feature = "tokio")]
+impl<R> AsyncSource for TokioIo<R>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        match tokio::io::AsyncRead::poll_read(Pin::new(&mut self.0), cx, &mut buf) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// CBOR input source that reads data items one after another from asynchronous input.
+///
+/// Each data item is read into a buffer as it arrives and then deserialized from there, so it
+/// may borrow nothing from the input. Bytes after the end of a data item may be taken from the
+/// reader, and are kept for the next one.
+///
+/// ```
+/// # extern crate futures_executor;
+/// # extern crate serde_cbor;
+/// use futures_executor::block_on;
+/// use serde_cbor::de::AsyncIoRead;
+///
+/// # fn main() {
+/// # #[cfg(feature = "futures-io")] {
+/// let mut read = AsyncIoRead::new(&b"\x01\x62hi"[..]);
+/// assert_eq!(block_on(read.next_value::<u8>()).unwrap(), Some(1));
+/// assert_eq!(block_on(read.next_value::<String>()).unwrap(), Some("hi".to_string()));
+/// assert_eq!(block_on(read.next_value::<u8>()).unwrap(), None);
+/// # }
+/// # }
+/// ```
+///
+/// This type is available with the `futures-io` or the `tokio` feature.
+#[derive(Debug)]
+pub struct AsyncIoRead<R> {
+    reader: R,
+    incremental: Incremental,
+}
+
+impl<R> AsyncIoRead<R>
+where
+    R: AsyncSource + Unpin,
+{
+    /// Creates a CBOR input source to read from an asynchronous reader.
+    pub fn new(reader: R) -> AsyncIoRead<R> {
+        AsyncIoRead {
+            reader,
+            incremental: Incremental::new(),
+        }
+    }
+
+    /// Reads and deserializes the next data item, or returns `None` at the end of the input.
+    ///
+    /// If the data item is well-formed but cannot be deserialized as a `T`, it is skipped, so
+    /// that the next call continues with the data item after it.
+    pub fn next_value<'a, T>(&'a mut self) -> NextValue<'a, R, T>
+    where
+        T: DeserializeOwned,
+    {
+        NextValue {
+            read: self,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of bytes of the input that have been deserialized so far.
+    pub fn byte_offset(&self) -> u64 {
+        self.incremental.byte_offset()
+    }
+
+    /// Unwraps the reader. Input that has been read but not deserialized yet is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // Reads until the next data item is complete and returns its length, or `None` if the input
+    // ends before it starts.
+    fn poll_item(&mut self, cx: &mut Context) -> Poll<Result<Option<usize>>> {
+        loop {
+            match self.incremental.item() {
+                Ok(Some(len)) => return Poll::Ready(Ok(Some(len))),
+                Ok(None) => {}
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+            match self.poll_fill(cx) {
+                Poll::Ready(Ok(0)) if self.incremental.is_empty() => return Poll::Ready(Ok(None)),
+                Poll::Ready(Ok(0)) => return Poll::Ready(self.incremental.end().map(|_| None)),
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    // Reads another chunk of input into the buffer and returns its length.
+    fn poll_fill(&mut self, cx: &mut Context) -> Poll<Result<usize>> {
+        loop {
+            let result = {
+                let spare = self.incremental.spare(CHUNK_SIZE);
+                Pin::new(&mut self.reader).poll_read(cx, spare)
+            };
+            let count = match result {
+                Poll::Ready(Ok(count)) => count,
+                _ => 0,
+            };
+            self.incremental.filled(CHUNK_SIZE, count);
+            match result {
+                Poll::Ready(Ok(count)) => return Poll::Ready(Ok(count)),
+                Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(Error::io(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Future returned by `AsyncIoRead::next_value`.
+#[derive(Debug)]
+pub struct NextValue<'a, R: 'a, T> {
+    read: &'a mut AsyncIoRead<R>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, R, T> Future for NextValue<'a, R, T>
+where
+    R: AsyncSource + Unpin,
+    T: DeserializeOwned,
+{
+    type Output = Result<Option<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<Option<T>>> {
+        match self.read.poll_item(cx) {
+            Poll::Ready(Ok(Some(len))) => Poll::Ready(self.read.incremental.take(len).map(Some)),
+            Poll::Ready(Ok(None)) => Poll::Ready(Ok(None)),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Decodes a value from an asynchronous reader, which must end after it.
+///
+/// ```
+/// # extern crate futures_executor;
+/// # extern crate serde_cbor;
+/// use futures_executor::block_on;
+/// use serde_cbor::de::from_async_reader;
+///
+/// # fn main() {
+/// # #[cfg(feature = "futures-io")] {
+/// let value: Vec<u8> = block_on(from_async_reader(&b"\x82\x01\x02"[..])).unwrap();
+/// assert_eq!(value, [1, 2]);
+/// # }
+/// # }
+/// ```
+///
+/// This function is available with the `futures-io` or the `tokio` feature.
+pub fn from_async_reader<R, T>(reader: R) -> FromAsyncReader<R, T>
+where
+    R: AsyncSource + Unpin,
+    T: DeserializeOwned,
+{
+    FromAsyncReader {
+        read: AsyncIoRead::new(reader),
+        value: None,
+    }
+}
+
+/// Future returned by `from_async_reader`.
+#[derive(Debug)]
+pub struct FromAsyncReader<R, T> {
+    read: AsyncIoRead<R>,
+    // The value once it has been read, while checking that nothing follows it.
+    value: Option<T>,
+}
+
+impl<R, T> Future for FromAsyncReader<R, T>
+where
+    R: AsyncSource + Unpin,
+    T: DeserializeOwned + Unpin,
+{
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<T>> {
+        let this = &mut *self;
+        if this.value.is_none() {
+            let value = match this.read.poll_item(cx) {
+                Poll::Ready(Ok(Some(len))) => this.read.incremental.take(len),
+                Poll::Ready(Ok(None)) => Err(Error::syntax(ErrorCode::EofWhileParsingValue, 0)),
+                Poll::Ready(Err(e)) => Err(e),
+                Poll::Pending => return Poll::Pending,
+            };
+            match value {
+                Ok(value) => this.value = Some(value),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+        while this.read.incremental.is_empty() {
+            match this.read.poll_fill(cx) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Ok(this.value.take().unwrap())),
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let offset = this.read.incremental.byte_offset() + 1;
+        Poll::Ready(Err(Error::syntax(ErrorCode::TrailingData, offset)))
+    }
+}
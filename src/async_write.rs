@@ -0,0 +1,271 @@
+// Writing CBOR to asynchronous output.
+//
+// Serde serializes synchronously, so each value is first encoded into a buffer, which is then
+// written out as the writer accepts it. The buffer holds a single value rather than everything
+// written so far, and is reused from one value to the next.
+#[cfg(feature = "futures-io")]
+use futures_io::AsyncWrite;
+use serde::Serialize;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "tokio")]
+use async_read::TokioIo;
+use error::{Error, Result};
+use ser::Serializer;
+
+/// An asynchronous sink for the output of `AsyncIoWrite`.
+///
+/// This is implemented for writers of `futures::io::AsyncWrite` with the `futures-io` feature,
+/// and for writers of `tokio::io::AsyncWrite` wrapped in `TokioIo` with the `tokio` feature.
+pub trait AsyncSink {
+    /// Attempts to write from `buf`, returning the number of bytes written.
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>>;
+
+    /// Attempts to flush buffered output to its destination.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>>;
+}
+
+#[cfg(feature = "futures-io")]
+impl<W> AsyncSink for W
+where
+    W: AsyncWrite,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        AsyncWrite::poll_write(self, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        AsyncWrite::poll_flush(self, cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<W> AsyncSink for TokioIo<W>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.0), cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.0), cx)
+    }
+}
+
+/// CBOR output sink that writes values one after another to asynchronous output.
+///
+/// Each value is encoded into a buffer and then written from there. A value whose future is
+/// dropped before it is done is still written before the next one, so the output never holds
+/// part of a data item followed by another.
+///
+/// ```
+/// # extern crate futures_executor;
+/// # extern crate serde_cbor;
+/// use futures_executor::block_on;
+/// use serde_cbor::ser::AsyncIoWrite;
+///
+/// # fn main() {
+/// # #[cfg(feature = "futures-io")] {
+/// let mut write = AsyncIoWrite::new(Vec::new());
+/// block_on(write.write_value(&1)).unwrap();
+/// block_on(write.write_value("hi")).unwrap();
+/// assert_eq!(write.into_inner(), b"\x01\x62hi");
+/// # }
+/// # }
+/// ```
+///
+/// This type is available with the `futures-io` or the `tokio` feature.
+#[derive(Debug)]
+pub struct AsyncIoWrite<W> {
+    writer: W,
+    // Encoded output, of which `buf[start..]` is yet to be written.
+    buf: Vec<u8>,
+    start: usize,
+    offset: u64,
+}
+
+impl<W> AsyncIoWrite<W>
+where
+    W: AsyncSink + Unpin,
+{
+    /// Creates a CBOR output sink to write to an asynchronous writer.
+    pub fn new(writer: W) -> AsyncIoWrite<W> {
+        AsyncIoWrite {
+            writer,
+            buf: Vec::new(),
+            start: 0,
+            offset: 0,
+        }
+    }
+
+    /// Encodes a value and writes it out.
+    ///
+    /// The value is encoded right away, and a value that fails to encode writes nothing.
+    pub fn write_value<'a, T>(&'a mut self, value: &T) -> WriteValue<'a, W>
+    where
+        T: Serialize + ?Sized,
+    {
+        let error = self.encode(value).err();
+        WriteValue { write: self, error }
+    }
+
+    /// Writes out what is left of the values so far and flushes the writer.
+    pub fn flush<'a>(&'a mut self) -> Flush<'a, W> {
+        Flush { write: self }
+    }
+
+    /// Returns the number of bytes that have been written so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.offset
+    }
+
+    /// Unwraps the writer. Output that has been encoded but not written yet is lost.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn encode<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        if self.start == self.buf.len() {
+            self.buf.clear();
+            self.start = 0;
+        }
+        let len = self.buf.len();
+        let result = value.serialize(&mut Serializer::new(&mut self.buf));
+        if result.is_err() {
+            self.buf.truncate(len);
+        }
+        result
+    }
+
+    // Writes until the buffer is empty.
+    fn poll_drain(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        while self.start < self.buf.len() {
+            let result = Pin::new(&mut self.writer).poll_write(cx, &self.buf[self.start..]);
+            match result {
+                Poll::Ready(Ok(0)) => {
+                    let e = io::Error::new(io::ErrorKind::WriteZero, "failed to write whole value");
+                    return Poll::Ready(Err(Error::io(e)));
+                }
+                Poll::Ready(Ok(count)) => {
+                    self.start += count;
+                    self.offset += count as u64;
+                }
+                Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(Error::io(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        match self.poll_drain(cx) {
+            Poll::Ready(Ok(())) => {}
+            other => return other,
+        }
+        match Pin::new(&mut self.writer).poll_flush(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(Error::io(e))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Future returned by `AsyncIoWrite::write_value`.
+#[derive(Debug)]
+pub struct WriteValue<'a, W: 'a> {
+    write: &'a mut AsyncIoWrite<W>,
+    // The error from encoding the value, if it failed.
+    error: Option<Error>,
+}
+
+impl<'a, W> Future for WriteValue<'a, W>
+where
+    W: AsyncSink + Unpin,
+{
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        match self.error.take() {
+            Some(e) => Poll::Ready(Err(e)),
+            None => self.write.poll_drain(cx),
+        }
+    }
+}
+
+/// Future returned by `AsyncIoWrite::flush`.
+#[derive(Debug)]
+pub struct Flush<'a, W: 'a> {
+    write: &'a mut AsyncIoWrite<W>,
+}
+
+impl<'a, W> Future for Flush<'a, W>
+where
+    W: AsyncSink + Unpin,
+{
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        self.write.poll_flush(cx)
+    }
+}
+
+/// Serializes a value to an asynchronous writer, and flushes it.
+///
+/// ```
+/// # extern crate futures_executor;
+/// # extern crate serde_cbor;
+/// use futures_executor::block_on;
+/// use serde_cbor::ser::to_async_writer;
+///
+/// # fn main() {
+/// # #[cfg(feature = "futures-io")] {
+/// let mut bytes = Vec::new();
+/// block_on(to_async_writer(&mut bytes, &[1, 2])).unwrap();
+/// assert_eq!(bytes, b"\x82\x01\x02");
+/// # }
+/// # }
+/// ```
+///
+/// This function is available with the `futures-io` or the `tokio` feature.
+pub fn to_async_writer<W, T>(writer: W, value: &T) -> ToAsyncWriter<W>
+where
+    W: AsyncSink + Unpin,
+    T: Serialize + ?Sized,
+{
+    let mut write = AsyncIoWrite::new(writer);
+    let error = write.encode(value).err();
+    ToAsyncWriter { write, error }
+}
+
+/// Future returned by `to_async_writer`.
+#[derive(Debug)]
+pub struct ToAsyncWriter<W> {
+    write: AsyncIoWrite<W>,
+    error: Option<Error>,
+}
+
+impl<W> Future for ToAsyncWriter<W>
+where
+    W: AsyncSink + Unpin,
+{
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        match self.error.take() {
+            Some(e) => Poll::Ready(Err(e)),
+            None => self.write.poll_flush(cx),
+        }
+    }
+}
@@ -0,0 +1,1709 @@
+//! Validation of CBOR against CDDL schemas.
+//!
+//! [CDDL](https://tools.ietf.org/html/rfc8610) is the schema language IETF specifications use
+//! to describe the shape of CBOR messages. A `Schema` is parsed from the text of such a
+//! specification and accepts the data items described by its first rule. Validation reports
+//! every violation it finds together with the byte offset of the offending data item:
+//!
+//! ```
+//! use serde_cbor::cddl::Schema;
+//!
+//! let schema = Schema::parse(r#"point = {"x": int, "y": int, ? "label": tstr}"#).unwrap();
+//! // {"x": 1, "y": "2"}
+//! let violations = schema.validate(b"\xa2\x61x\x01\x61y\x612");
+//! assert_eq!(violations.len(), 1);
+//! assert_eq!(violations[0].to_string(), "expected int, found a text string at offset 6");
+//! ```
+//!
+//! The supported subset of CDDL covers type and group rules including choices and the `/=` and
+//! `//=` extensions, the standard prelude, literals, ranges, occurrence indicators, arrays, maps,
+//! tags, major types written as `#n.n`, and the `.size`, `.lt`, `.le`, `.gt`, `.ge`, `.eq`,
+//! `.ne`, `.default`, `.cbor`, `.cborseq`, `.and` and `.within` control operators. Schemas using
+//! generic rules, the `~` and `&` operators, base64 byte string literals or other control
+//! operators are rejected when parsing.
+use serde::de::{Deserialize, DeserializeOwned};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io;
+use std::result;
+use std::str;
+
+use byteorder::{BigEndian, ByteOrder};
+use half::f16;
+
+use de;
+use error::{Error, ErrorCode, Result};
+
+// The standard prelude of RFC 8610, appendix D, as far as it can be expressed in this subset.
+const PRELUDE: &str = "
+any = #
+uint = #0
+nint = #1
+int = uint / nint
+bstr = #2
+bytes = bstr
+tstr = #3
+text = tstr
+tdate = #6.0(tstr)
+time = #6.1(number)
+number = int / float
+biguint = #6.2(bstr)
+bignint = #6.3(bstr)
+bigint = biguint / bignint
+integer = int / bigint
+unsigned = uint / biguint
+decfrac = #6.4([e10: int, m: integer])
+bigfloat = #6.5([e2: int, m: integer])
+eb64url = #6.21(any)
+eb64legacy = #6.22(any)
+eb16 = #6.23(any)
+encoded-cbor = #6.24(bstr)
+uri = #6.32(tstr)
+b64url = #6.33(tstr)
+b64legacy = #6.34(tstr)
+regexp = #6.35(tstr)
+mime-message = #6.36(tstr)
+cbor-any = #6.55799(any)
+float16 = #7.25
+float32 = #7.26
+float64 = #7.27
+float16-32 = float16 / float32
+float32-64 = float32 / float64
+float = float16-32 / float64
+false = #7.20
+true = #7.21
+bool = false / true
+nil = #7.22
+null = nil
+undefined = #7.23
+";
+
+const CONTROLS: &[&str] = &[
+    "size", "lt", "le", "gt", "ge", "eq", "ne", "default", "cbor", "cborseq", "and", "within",
+];
+
+// Limits the nesting of data items and of rule references, so that deep input or recursive
+// rules can't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+const UNBOUNDED: usize = usize::max_value();
+
+/// A violation of a schema found while validating input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    offset: u64,
+    message: String,
+    // Set for violations saying that the data item at `offset` has the wrong type altogether.
+    mismatch: bool,
+}
+
+impl Violation {
+    fn new(offset: usize, message: String) -> Violation {
+        Violation {
+            offset: offset as u64,
+            message,
+            mismatch: false,
+        }
+    }
+
+    /// The byte offset of the data item that violates the schema.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// A description of the violation.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+/// A schema written in CDDL.
+#[derive(Clone, Debug)]
+pub struct Schema {
+    rules: BTreeMap<String, Group>,
+    root: String,
+}
+
+impl Schema {
+    /// Parses a schema from the text of a CDDL specification.
+    ///
+    /// The offset of an error is the byte offset into `cddl` at which parsing failed.
+    pub fn parse(cddl: &str) -> Result<Schema> {
+        let mut rules = BTreeMap::new();
+        let mut parser = Parser::new(cddl);
+        parser.rules(&mut rules)?;
+        let root = match parser.first {
+            Some(ref root) => root.clone(),
+            None => return Err(parser.error("expected a rule")),
+        };
+        let mut prelude = BTreeMap::new();
+        Parser::new(PRELUDE).rules(&mut prelude)?;
+        for (name, group) in prelude {
+            rules.entry(name).or_insert(group);
+        }
+        let undefined = parser
+            .references
+            .iter()
+            .find(|(name, _)| !rules.contains_key(name))
+            .cloned();
+        if let Some((name, pos)) = undefined {
+            parser.pos = pos;
+            return Err(parser.error(&format!("undefined rule `{}`", name)));
+        }
+        Ok(Schema { rules, root })
+    }
+
+    /// The name of the rule that data items are validated against, which is the first rule of
+    /// the schema.
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// Validates a single CBOR data item and returns the violations found, which is empty if the
+    /// data item matches the schema.
+    pub fn validate(&self, bytes: &[u8]) -> Vec<Violation> {
+        let item = match decode(bytes, 0) {
+            Ok(item) => item,
+            Err(violation) => return vec![violation],
+        };
+        let mut violations = Vec::new();
+        Validator::new(self).check_rule(&self.root, &item, &mut violations);
+        violations
+    }
+
+    /// Reads the input to its end and validates it as a single CBOR data item.
+    pub fn validate_reader<R>(&self, mut reader: R) -> Result<Vec<Violation>>
+    where
+        R: io::Read,
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(Error::io)?;
+        Ok(self.validate(&bytes))
+    }
+
+    /// Validates a CBOR data item and deserializes it if it matches the schema.
+    ///
+    /// If it doesn't, the error describes the first violation.
+    pub fn from_slice<'a, T>(&self, bytes: &'a [u8]) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        match self.validate(bytes).into_iter().next() {
+            Some(violation) => Err(Error::syntax(
+                ErrorCode::Message(violation.message),
+                violation.offset,
+            )),
+            None => de::from_slice(bytes),
+        }
+    }
+
+    /// Reads the input to its end, validates it as a CBOR data item and deserializes it if it
+    /// matches the schema.
+    pub fn from_reader<T, R>(&self, mut reader: R) -> Result<T>
+    where
+        T: DeserializeOwned,
+        R: io::Read,
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(Error::io)?;
+        self.from_slice(&bytes)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Group {
+    choices: Vec<Vec<Entry>>,
+}
+
+impl Group {
+    // Returns the type if the group consists of nothing but a single type.
+    fn as_type(&self) -> Option<&Type> {
+        match *self.choices.as_slice() {
+            [ref entries] => match *entries.as_slice() {
+                [Entry {
+                    min: 1,
+                    max: 1,
+                    key: None,
+                    member: Member::Type(ref ty),
+                }] => Some(ty),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn as_type_mut(&mut self) -> Option<&mut Type> {
+        self.as_type()?;
+        match self.choices[0][0].member {
+            Member::Type(ref mut ty) => Some(ty),
+            Member::Group(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    min: usize,
+    max: usize,
+    key: Option<Key>,
+    member: Member,
+}
+
+#[derive(Clone, Debug)]
+enum Key {
+    Value(Value),
+    Type(Type1),
+}
+
+#[derive(Clone, Debug)]
+enum Member {
+    Type(Type),
+    Group(Group),
+}
+
+#[derive(Clone, Debug)]
+struct Type(Vec<Type1>);
+
+#[derive(Clone, Debug)]
+struct Type1 {
+    base: Type2,
+    op: Option<(Op, Type2)>,
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Range { inclusive: bool },
+    Control(String),
+}
+
+#[derive(Clone, Debug)]
+enum Type2 {
+    Value(Value),
+    Name(String),
+    Paren(Type),
+    Map(Group),
+    Array(Group),
+    Tag(Option<u64>, Type),
+    Major(u8, Option<u64>),
+    Any,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Int(i128),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    fn matches(&self, item: &Item) -> bool {
+        match (self, &item.kind) {
+            (&Value::Int(a), &Kind::Int(b)) => a == b,
+            (&Value::Float(a), &Kind::Float(b, _)) => a == b,
+            (Value::Text(a), Kind::Text(b)) => a == b,
+            (Value::Bytes(a), Kind::Bytes(b, _)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    first: Option<String>,
+    // Every rule name used in a type along with its position, to be checked once all rules are
+    // known.
+    references: Vec<(String, usize)>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser {
+            input: input.as_bytes(),
+            pos: 0,
+            first: None,
+            references: Vec::new(),
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::syntax(ErrorCode::InvalidSchema(msg.to_string()), self.pos as u64)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.input.get(self.pos + n).cloned()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.input[self.pos..].starts_with(token.as_bytes()) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", token)))
+        }
+    }
+
+    // Skips whitespace and comments.
+    fn space(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                b' ' | b'\t' | b'\r' | b'\n' => self.pos += 1,
+                b';' => match self.input[self.pos..].iter().position(|&c| c == b'\n') {
+                    Some(i) => self.pos += i + 1,
+                    None => self.pos = self.input.len(),
+                },
+                _ => return,
+            }
+        }
+    }
+
+    fn name(&mut self) -> Option<String> {
+        fn is_start(c: u8) -> bool {
+            c.is_ascii_alphabetic() || c == b'@' || c == b'_' || c == b'$'
+        }
+        let start = self.pos;
+        match self.peek() {
+            Some(c) if is_start(c) => self.pos += 1,
+            _ => return None,
+        }
+        loop {
+            // Dashes and dots are allowed inside names, but not at their end.
+            let mut end = self.pos;
+            while let Some(b'-') | Some(b'.') = self.input.get(end).cloned() {
+                end += 1;
+            }
+            match self.input.get(end).cloned() {
+                Some(c) if is_start(c) || c.is_ascii_digit() => self.pos = end + 1,
+                _ => break,
+            }
+        }
+        Some(String::from_utf8(self.input[start..self.pos].to_vec()).unwrap())
+    }
+
+    // Checks whether the next rule starts here.
+    fn at_rule(&mut self) -> bool {
+        let start = self.pos;
+        let result = self.name().is_some() && {
+            self.space();
+            self.input[self.pos..].starts_with(b"/=")
+                || self.input[self.pos..].starts_with(b"//=")
+                || (self.peek() == Some(b'=') && self.peek_at(1) != Some(b'>'))
+        };
+        self.pos = start;
+        result
+    }
+
+    fn rules(&mut self, rules: &mut BTreeMap<String, Group>) -> Result<()> {
+        loop {
+            self.space();
+            if self.pos == self.input.len() {
+                return Ok(());
+            }
+            let start = self.pos;
+            let name = match self.name() {
+                Some(name) => name,
+                None => return Err(self.error("expected a rule name")),
+            };
+            if self.peek() == Some(b'<') {
+                return Err(self.error("generic rules are not supported"));
+            }
+            self.space();
+            let operator = if self.eat("//=") {
+                "//="
+            } else if self.eat("/=") {
+                "/="
+            } else {
+                self.expect("=")?;
+                "="
+            };
+            let body = self.group(None)?;
+            if body.choices.iter().all(|entries| entries.is_empty()) {
+                return Err(self.error("expected a type or group"));
+            }
+            match (operator, rules.get_mut(&name)) {
+                ("=", Some(_)) => {
+                    self.pos = start;
+                    return Err(self.error(&format!("rule `{}` is defined twice", name)));
+                }
+                ("/=", Some(rule)) => match (rule.as_type_mut(), body.as_type()) {
+                    (Some(ty), Some(choices)) => ty.0.extend(choices.0.iter().cloned()),
+                    _ => {
+                        self.pos = start;
+                        return Err(self.error("`/=` extends type rules only"));
+                    }
+                },
+                ("//=", Some(rule)) => rule.choices.extend(body.choices),
+                (_, None) => {
+                    rules.insert(name.clone(), body);
+                }
+                _ => unreachable!(),
+            }
+            self.first.get_or_insert(name);
+        }
+    }
+
+    // Parses group choices up to the closing delimiter or, at the top level of a rule, up to the
+    // start of the next rule.
+    fn group(&mut self, close: Option<u8>) -> Result<Group> {
+        let mut choices = vec![Vec::new()];
+        loop {
+            self.space();
+            match (self.peek(), close) {
+                (None, None) => break,
+                (None, Some(close)) => {
+                    return Err(self.error(&format!("expected `{}`", close as char)))
+                }
+                (Some(c), Some(close)) if c == close => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => {}
+            }
+            if close.is_none() && self.at_rule() {
+                break;
+            }
+            if self.eat("//") {
+                choices.push(Vec::new());
+                continue;
+            }
+            let entry = self.entry()?;
+            choices.last_mut().unwrap().push(entry);
+            self.space();
+            self.eat(",");
+        }
+        Ok(Group { choices })
+    }
+
+    fn occurrence(&mut self) -> (usize, usize) {
+        if self.eat("?") {
+            return (0, 1);
+        }
+        if self.eat("+") {
+            return (1, UNBOUNDED);
+        }
+        let start = self.pos;
+        let min = self.uint();
+        if self.eat("*") {
+            return (min.unwrap_or(0), self.uint().unwrap_or(UNBOUNDED));
+        }
+        self.pos = start;
+        (1, 1)
+    }
+
+    fn uint(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        str::from_utf8(&self.input[start..self.pos])
+            .unwrap()
+            .parse()
+            .ok()
+    }
+
+    fn entry(&mut self) -> Result<Entry> {
+        let (min, max) = self.occurrence();
+        self.space();
+        let entry = |key, member| Entry {
+            min,
+            max,
+            key,
+            member,
+        };
+        let start = self.pos;
+        if let Some(name) = self.name() {
+            self.space();
+            if self.eat(":") {
+                let ty = self.type_()?;
+                return Ok(entry(Some(Key::Value(Value::Text(name))), Member::Type(ty)));
+            }
+            self.pos = start;
+        }
+        let first = if self.eat("(") {
+            let group = self.group(Some(b')'))?;
+            match group.as_type() {
+                Some(ty) => {
+                    let base = Type2::Paren(ty.clone());
+                    self.type1_from(base)?
+                }
+                None => return Ok(entry(None, Member::Group(group))),
+            }
+        } else {
+            self.type1()?
+        };
+        self.space();
+        let cut = self.eat("^");
+        self.space();
+        if self.eat("=>") {
+            let ty = self.type_()?;
+            return Ok(entry(Some(Key::Type(first)), Member::Type(ty)));
+        } else if cut {
+            return Err(self.error("expected `=>`"));
+        }
+        if let (Some(b':'), Type2::Value(value), None) = (self.peek(), &first.base, &first.op) {
+            self.pos += 1;
+            let ty = self.type_()?;
+            return Ok(entry(Some(Key::Value(value.clone())), Member::Type(ty)));
+        }
+        let ty = self.type_rest(first)?;
+        Ok(entry(None, Member::Type(ty)))
+    }
+
+    fn type_(&mut self) -> Result<Type> {
+        let first = self.type1()?;
+        self.type_rest(first)
+    }
+
+    fn type_rest(&mut self, first: Type1) -> Result<Type> {
+        let mut choices = vec![first];
+        loop {
+            let start = self.pos;
+            self.space();
+            match (self.peek(), self.peek_at(1)) {
+                (Some(b'/'), Some(b'/')) | (Some(b'/'), Some(b'=')) => {}
+                (Some(b'/'), _) => {
+                    self.pos += 1;
+                    choices.push(self.type1()?);
+                    continue;
+                }
+                _ => {}
+            }
+            self.pos = start;
+            return Ok(Type(choices));
+        }
+    }
+
+    fn type1(&mut self) -> Result<Type1> {
+        let base = self.type2()?;
+        self.type1_from(base)
+    }
+
+    fn type1_from(&mut self, base: Type2) -> Result<Type1> {
+        let start = self.pos;
+        self.space();
+        let op = if self.eat("...") {
+            Op::Range { inclusive: false }
+        } else if self.eat("..") {
+            Op::Range { inclusive: true }
+        } else if self.peek() == Some(b'.')
+            && self.peek_at(1).is_some_and(|c| c.is_ascii_alphabetic())
+        {
+            self.pos += 1;
+            let name = self.name().unwrap();
+            if !CONTROLS.contains(&name.as_str()) {
+                return Err(self.error(&format!("unsupported control operator `.{}`", name)));
+            }
+            Op::Control(name)
+        } else {
+            self.pos = start;
+            return Ok(Type1 { base, op: None });
+        };
+        self.space();
+        let other = self.type2()?;
+        Ok(Type1 {
+            base,
+            op: Some((op, other)),
+        })
+    }
+
+    fn type2(&mut self) -> Result<Type2> {
+        self.space();
+        if self.eat("\"") {
+            let text = self.quoted(b'"')?;
+            return Ok(Type2::Value(Value::Text(String::from_utf8(text).unwrap())));
+        } else if self.eat("'") {
+            return Ok(Type2::Value(Value::Bytes(self.quoted(b'\'')?)));
+        } else if self.eat("h'") {
+            return Ok(Type2::Value(Value::Bytes(self.hex()?)));
+        } else if self.eat("(") {
+            let ty = self.type_()?;
+            self.space();
+            self.expect(")")?;
+            return Ok(Type2::Paren(ty));
+        } else if self.eat("{") {
+            return Ok(Type2::Map(self.group(Some(b'}'))?));
+        } else if self.eat("[") {
+            return Ok(Type2::Array(self.group(Some(b']'))?));
+        } else if self.eat("#") {
+            return self.major();
+        }
+        match self.peek() {
+            Some(b'-') | Some(b'0'...b'9') => self.number(),
+            Some(b'~') | Some(b'&') => {
+                Err(self.error("the `~` and `&` operators are not supported"))
+            }
+            _ => {
+                let start = self.pos;
+                match self.name() {
+                    Some(ref name) if name == "b64" && self.peek() == Some(b'\'') => {
+                        self.pos = start;
+                        Err(self.error("base64 byte strings are not supported"))
+                    }
+                    Some(_) if self.peek() == Some(b'<') => {
+                        Err(self.error("generic rules are not supported"))
+                    }
+                    Some(name) => {
+                        self.references.push((name.clone(), start));
+                        Ok(Type2::Name(name))
+                    }
+                    None => Err(self.error("expected a type")),
+                }
+            }
+        }
+    }
+
+    // Parses the rest of a quoted string, in which a backslash escapes the next character.
+    fn quoted(&mut self, quote: u8) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        loop {
+            match self.peek() {
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(buf);
+                }
+                Some(b'\\') if self.peek_at(1).is_some() => {
+                    buf.push(self.input[self.pos + 1]);
+                    self.pos += 2;
+                }
+                Some(c) => {
+                    buf.push(c);
+                    self.pos += 1;
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    // Parses the rest of a `h'...'` byte string.
+    fn hex(&mut self) -> Result<Vec<u8>> {
+        let mut digits = Vec::new();
+        loop {
+            self.space();
+            match self.peek() {
+                Some(b'\'') => break,
+                Some(c) if c.is_ascii_hexdigit() => {
+                    digits.push(c);
+                    self.pos += 1;
+                }
+                _ => return Err(self.error("expected a hex digit")),
+            }
+        }
+        if digits.len() % 2 != 0 {
+            return Err(self.error("odd number of hex digits"));
+        }
+        self.pos += 1;
+        Ok(digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect())
+    }
+
+    fn number(&mut self) -> Result<Type2> {
+        let start = self.pos;
+        let negative = self.eat("-");
+        if self.eat("0x") {
+            let digits = self.pos;
+            while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                self.pos += 1;
+            }
+            let text = str::from_utf8(&self.input[digits..self.pos]).unwrap();
+            return match i128::from_str_radix(text, 16) {
+                Ok(value) => Ok(Type2::Value(Value::Int(if negative {
+                    -value
+                } else {
+                    value
+                }))),
+                Err(_) => {
+                    self.pos = start;
+                    Err(self.error("invalid number"))
+                }
+            };
+        }
+        let mut float = false;
+        self.uint();
+        if self.peek() == Some(b'.') && self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+            self.uint();
+            float = true;
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.pos += 1;
+            if !self.eat("+") {
+                self.eat("-");
+            }
+            self.uint();
+            float = true;
+        }
+        let text = str::from_utf8(&self.input[start..self.pos]).unwrap();
+        let value = if float {
+            text.parse().ok().map(Value::Float)
+        } else {
+            text.parse().ok().map(Value::Int)
+        };
+        match value {
+            Some(value) => Ok(Type2::Value(value)),
+            None => {
+                self.pos = start;
+                Err(self.error("invalid number"))
+            }
+        }
+    }
+
+    // Parses the rest of a `#` type after the `#`.
+    fn major(&mut self) -> Result<Type2> {
+        let major = match self.peek() {
+            Some(c @ b'0'...b'7') => c - b'0',
+            _ => return Ok(Type2::Any),
+        };
+        self.pos += 1;
+        let argument = if self.eat(".") {
+            match self.uint() {
+                Some(argument) => Some(argument as u64),
+                None => return Err(self.error("expected a number")),
+            }
+        } else {
+            None
+        };
+        if major == 6 && self.eat("(") {
+            let ty = self.type_()?;
+            self.space();
+            self.expect(")")?;
+            return Ok(Type2::Tag(argument, ty));
+        }
+        Ok(Type2::Major(major, argument))
+    }
+}
+
+struct Item {
+    // The absolute byte offset of the item.
+    start: usize,
+    kind: Kind,
+}
+
+enum Kind {
+    Int(i128),
+    // The contents, and the offset at which they start.
+    Bytes(Vec<u8>, usize),
+    Text(String),
+    Array(Vec<Item>),
+    Map(Vec<(Item, Item)>),
+    Tag(u64, Box<Item>),
+    // The value, and the additional information of its head telling its precision.
+    Float(f64, u64),
+    Simple(u8),
+}
+
+// Decodes a single data item that makes up all of `input`, which starts at offset `base`.
+fn decode(input: &[u8], base: usize) -> result::Result<Item, Violation> {
+    let mut decoder = Decoder {
+        input,
+        pos: 0,
+        base,
+        depth: 0,
+    };
+    let item = decoder.item()?;
+    if decoder.pos != input.len() {
+        return Err(decoder.violation("trailing data"));
+    }
+    Ok(item)
+}
+
+// Decodes a sequence of data items making up all of `input`, which starts at offset `base`.
+fn decode_sequence(input: &[u8], base: usize) -> result::Result<Vec<Item>, Violation> {
+    let mut decoder = Decoder {
+        input,
+        pos: 0,
+        base,
+        depth: 0,
+    };
+    let mut items = Vec::new();
+    while decoder.pos != input.len() {
+        items.push(decoder.item()?);
+    }
+    Ok(items)
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    pos: usize,
+    base: usize,
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn violation(&self, msg: &str) -> Violation {
+        Violation::new(self.base + self.pos, msg.to_string())
+    }
+
+    fn take(&mut self, n: u64) -> result::Result<&'a [u8], Violation> {
+        if n > (self.input.len() - self.pos) as u64 {
+            return Err(self.violation("unexpected end of input"));
+        }
+        let buf = &self.input[self.pos..self.pos + n as usize];
+        self.pos += n as usize;
+        Ok(buf)
+    }
+
+    fn peek(&self) -> result::Result<u8, Violation> {
+        match self.input.get(self.pos) {
+            Some(&byte) => Ok(byte),
+            None => Err(self.violation("unexpected end of input")),
+        }
+    }
+
+    // Reads the argument of a head. Returns `None` for indefinite lengths.
+    fn argument(&mut self, info: u8) -> result::Result<Option<u64>, Violation> {
+        Ok(Some(match info {
+            0...23 => u64::from(info),
+            24 => u64::from(self.take(1)?[0]),
+            25 => u64::from(BigEndian::read_u16(self.take(2)?)),
+            26 => u64::from(BigEndian::read_u32(self.take(4)?)),
+            27 => BigEndian::read_u64(self.take(8)?),
+            31 => return Ok(None),
+            _ => {
+                self.pos -= 1;
+                return Err(self.violation("invalid additional information"));
+            }
+        }))
+    }
+
+    fn item(&mut self) -> result::Result<Item, Violation> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.violation("nested too deeply"));
+        }
+        self.depth += 1;
+        let result = self.kind();
+        self.depth -= 1;
+        result
+    }
+
+    fn kind(&mut self) -> result::Result<Item, Violation> {
+        let start = self.pos;
+        let byte = self.take(1)?[0];
+        let (major, info) = (byte >> 5, byte & 0x1f);
+        if major == 7 {
+            return self.simple(start, info);
+        }
+        let argument = self.argument(info)?;
+        let kind = match (major, argument) {
+            (0, Some(value)) => Kind::Int(i128::from(value)),
+            (1, Some(value)) => Kind::Int(-1 - i128::from(value)),
+            (2, _) | (3, _) => {
+                let (bytes, offset) = match argument {
+                    Some(len) => {
+                        let offset = self.base + self.pos;
+                        (self.take(len)?.to_vec(), offset)
+                    }
+                    None => (self.chunks(major)?, self.base + start),
+                };
+                if major == 2 {
+                    Kind::Bytes(bytes, offset)
+                } else {
+                    match String::from_utf8(bytes) {
+                        Ok(text) => Kind::Text(text),
+                        Err(_) => {
+                            self.pos = start;
+                            return Err(self.violation("invalid UTF-8"));
+                        }
+                    }
+                }
+            }
+            (4, len) => {
+                let mut items = Vec::new();
+                while !self.at_end(len, items.len())? {
+                    items.push(self.item()?);
+                }
+                Kind::Array(items)
+            }
+            (5, len) => {
+                let mut pairs = Vec::new();
+                while !self.at_end(len, pairs.len())? {
+                    let key = self.item()?;
+                    pairs.push((key, self.item()?));
+                }
+                Kind::Map(pairs)
+            }
+            (6, Some(tag)) => Kind::Tag(tag, Box::new(self.item()?)),
+            _ => {
+                self.pos = start;
+                return Err(self.violation("invalid indefinite length"));
+            }
+        };
+        Ok(Item {
+            start: self.base + start,
+            kind,
+        })
+    }
+
+    // Checks whether an array or map has no more elements, consuming the break of indefinite
+    // ones.
+    fn at_end(&mut self, len: Option<u64>, count: usize) -> result::Result<bool, Violation> {
+        match len {
+            Some(len) => Ok(count as u64 == len),
+            None if self.peek()? == 0xff => {
+                self.pos += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn chunks(&mut self, major: u8) -> result::Result<Vec<u8>, Violation> {
+        let mut buf = Vec::new();
+        loop {
+            let byte = self.take(1)?[0];
+            if byte == 0xff {
+                return Ok(buf);
+            }
+            match self.argument(byte & 0x1f)? {
+                Some(len) if byte >> 5 == major => buf.extend_from_slice(self.take(len)?),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.violation("invalid chunk of an indefinite length string"));
+                }
+            }
+        }
+    }
+
+    fn simple(&mut self, start: usize, info: u8) -> result::Result<Item, Violation> {
+        let kind = match info {
+            0...23 => Kind::Simple(info),
+            24 => match self.take(1)?[0] {
+                value @ 32...255 => Kind::Simple(value),
+                _ => {
+                    self.pos = start;
+                    return Err(self.violation("invalid simple value"));
+                }
+            },
+            25 => {
+                let bits = BigEndian::read_u16(self.take(2)?);
+                Kind::Float(f16::from_bits(bits).to_f64(), 25)
+            }
+            26 => Kind::Float(f64::from(BigEndian::read_f32(self.take(4)?)), 26),
+            27 => Kind::Float(BigEndian::read_f64(self.take(8)?), 27),
+            _ => {
+                self.pos = start;
+                return Err(self.violation("unexpected code"));
+            }
+        };
+        Ok(Item {
+            start: self.base + start,
+            kind,
+        })
+    }
+}
+
+// Describes the type of a data item for messages.
+fn found(item: &Item) -> String {
+    match item.kind {
+        Kind::Int(value) => value.to_string(),
+        Kind::Float(value, _) => format!("{:?}", value),
+        Kind::Bytes(..) => "a byte string".to_string(),
+        Kind::Text(_) => "a text string".to_string(),
+        Kind::Array(_) => "an array".to_string(),
+        Kind::Map(_) => "a map".to_string(),
+        Kind::Tag(tag, _) => format!("tag {}", tag),
+        Kind::Simple(20) => "false".to_string(),
+        Kind::Simple(21) => "true".to_string(),
+        Kind::Simple(22) => "null".to_string(),
+        Kind::Simple(23) => "undefined".to_string(),
+        Kind::Simple(value) => format!("simple({})", value),
+    }
+}
+
+// Describes a map key for messages.
+fn key(item: &Item) -> String {
+    match item.kind {
+        Kind::Text(ref text) => format!("{:?}", text),
+        Kind::Bytes(ref bytes, _) => Value::Bytes(bytes.clone()).to_string(),
+        _ => found(item),
+    }
+}
+
+fn expected<T: fmt::Display>(ty: &T, item: &Item) -> Violation {
+    let mut violation = Violation::new(
+        item.start,
+        format!("expected {}, found {}", ty, found(item)),
+    );
+    violation.mismatch = true;
+    violation
+}
+
+// Checks whether all violations just say that `item` has the wrong type.
+fn mismatched(violations: &[Violation], item: &Item) -> bool {
+    violations
+        .iter()
+        .all(|violation| violation.mismatch && violation.offset == item.start as u64)
+}
+
+struct Validator<'s> {
+    rules: &'s BTreeMap<String, Group>,
+    depth: usize,
+}
+
+impl<'s> Validator<'s> {
+    fn new(schema: &'s Schema) -> Validator<'s> {
+        Validator {
+            rules: &schema.rules,
+            depth: 0,
+        }
+    }
+
+    fn rule_type(&self, name: &str) -> Option<&'s Type> {
+        self.rules.get(name).and_then(Group::as_type)
+    }
+
+    // Returns the group an entry stands for, if it isn't a single type.
+    fn entry_group(&self, entry: &'s Entry) -> Option<&'s Group> {
+        match entry.member {
+            Member::Group(ref group) => Some(group),
+            Member::Type(Type(ref choices)) => match *choices.as_slice() {
+                [Type1 {
+                    base: Type2::Name(ref name),
+                    op: None,
+                }] if self.rule_type(name).is_none() => self.rules.get(name),
+                _ => None,
+            },
+        }
+    }
+
+    // Returns the literal value a type stands for, if any.
+    fn value_of(&self, ty: &'s Type2) -> Option<&'s Value> {
+        let choices = match *ty {
+            Type2::Value(ref value) => return Some(value),
+            Type2::Name(ref name) => &self.rule_type(name)?.0,
+            Type2::Paren(ref ty) => &ty.0,
+            _ => return None,
+        };
+        match *choices.as_slice() {
+            [Type1 { ref base, op: None }] => self.value_of(base),
+            _ => None,
+        }
+    }
+
+    // Returns the inclusive range of integers a `.size` argument allows.
+    fn bounds(&self, ty: &'s Type2) -> Option<(i128, i128)> {
+        if let Some(&Value::Int(value)) = self.value_of(ty) {
+            return Some((value, value));
+        }
+        let choices = match *ty {
+            Type2::Name(ref name) => &self.rule_type(name)?.0,
+            Type2::Paren(ref ty) => &ty.0,
+            _ => return None,
+        };
+        match *choices.as_slice() {
+            [Type1 {
+                ref base,
+                op: Some((Op::Range { inclusive }, ref other)),
+            }] => match (self.value_of(base), self.value_of(other)) {
+                (Some(&Value::Int(low)), Some(&Value::Int(high))) => {
+                    Some((low, if inclusive { high } else { high - 1 }))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn matches(&mut self, ty: &'s Type, item: &Item) -> bool {
+        let mut violations = Vec::new();
+        self.check_type(ty, item, &mut violations);
+        violations.is_empty()
+    }
+
+    fn check_type(&mut self, ty: &'s Type, item: &Item, out: &mut Vec<Violation>) {
+        if ty.0.len() == 1 {
+            return self.check_type1(&ty.0[0], item, out);
+        }
+        // Report the violations of the choice that got furthest, and of those the one with the
+        // fewest violations.
+        let mut best: Option<(Score, Vec<Violation>)> = None;
+        for choice in &ty.0 {
+            let mut violations = Vec::new();
+            self.check_type1(choice, item, &mut violations);
+            if violations.is_empty() {
+                return;
+            }
+            let score = (
+                violations.iter().map(Violation::offset).max().unwrap(),
+                !mismatched(&violations, item),
+                Reverse(violations.len()),
+            );
+            if best.as_ref().map_or(true, |best| score > best.0) {
+                best = Some((score, violations));
+            }
+        }
+        let violations = best.unwrap().1;
+        if mismatched(&violations, item) {
+            out.push(expected(ty, item));
+        } else {
+            out.extend(violations);
+        }
+    }
+
+    fn check_type1(&mut self, ty: &'s Type1, item: &Item, out: &mut Vec<Violation>) {
+        let (op, other) = match ty.op {
+            Some((ref op, ref other)) => (op, other),
+            None => return self.check_type2(&ty.base, item, out),
+        };
+        let ok = match *op {
+            Op::Range { inclusive } => {
+                match (self.value_of(&ty.base), self.value_of(other), &item.kind) {
+                    (Some(&Value::Int(low)), Some(&Value::Int(high)), &Kind::Int(value)) => {
+                        low <= value && (value < high || (inclusive && value == high))
+                    }
+                    (
+                        Some(&Value::Float(low)),
+                        Some(&Value::Float(high)),
+                        &Kind::Float(value, _),
+                    ) => low <= value && (value < high || (inclusive && value == high)),
+                    _ => false,
+                }
+            }
+            Op::Control(ref control) => {
+                let mut violations = Vec::new();
+                self.check_type2(&ty.base, item, &mut violations);
+                if !violations.is_empty() {
+                    out.extend(violations);
+                    return;
+                }
+                match control.as_str() {
+                    "size" => match (self.bounds(other), &item.kind) {
+                        (Some((_, high)), &Kind::Int(value)) if value >= 0 => {
+                            high >= 16 || value < 1 << (8 * high.max(0))
+                        }
+                        (Some((low, high)), Kind::Bytes(bytes, _)) => {
+                            low <= bytes.len() as i128 && bytes.len() as i128 <= high
+                        }
+                        (Some((low, high)), Kind::Text(text)) => {
+                            low <= text.len() as i128 && text.len() as i128 <= high
+                        }
+                        _ => false,
+                    },
+                    "lt" | "le" | "gt" | "ge" => {
+                        let ordering = match (self.value_of(other), &item.kind) {
+                            (Some(&Value::Int(limit)), &Kind::Int(value)) => {
+                                value.partial_cmp(&limit)
+                            }
+                            (Some(&Value::Float(limit)), &Kind::Float(value, _)) => {
+                                value.partial_cmp(&limit)
+                            }
+                            _ => None,
+                        };
+                        match (control.as_str(), ordering) {
+                            (_, None) => false,
+                            ("lt", Some(ordering)) => ordering.is_lt(),
+                            ("le", Some(ordering)) => ordering.is_le(),
+                            ("gt", Some(ordering)) => ordering.is_gt(),
+                            (_, Some(ordering)) => ordering.is_ge(),
+                        }
+                    }
+                    "eq" => self
+                        .value_of(other)
+                        .is_some_and(|value| value.matches(item)),
+                    "ne" => self
+                        .value_of(other)
+                        .is_some_and(|value| !value.matches(item)),
+                    "and" | "within" => return self.check_type2(other, item, out),
+                    "cbor" | "cborseq" => {
+                        let (bytes, offset) = match item.kind {
+                            Kind::Bytes(ref bytes, offset) => (bytes, offset),
+                            _ => return out.push(expected(ty, item)),
+                        };
+                        if control == "cbor" {
+                            match decode(bytes, offset) {
+                                Ok(inner) => self.check_type2(other, &inner, out),
+                                Err(violation) => out.push(violation),
+                            }
+                        } else {
+                            match decode_sequence(bytes, offset) {
+                                Ok(items) => self.check_sequence(other, item, &items, out),
+                                Err(violation) => out.push(violation),
+                            }
+                        }
+                        return;
+                    }
+                    _ => true,
+                }
+            }
+        };
+        if !ok {
+            out.push(expected(ty, item));
+        }
+    }
+
+    fn check_type2(&mut self, ty: &'s Type2, item: &Item, out: &mut Vec<Violation>) {
+        let ok = match *ty {
+            Type2::Value(ref value) => value.matches(item),
+            Type2::Name(ref name) => return self.check_rule(name, item, out),
+            Type2::Paren(ref ty) => return self.check_type(ty, item, out),
+            Type2::Array(ref group) => match item.kind {
+                Kind::Array(ref items) => return self.check_array(ty, group, item, items, out),
+                _ => false,
+            },
+            Type2::Map(ref group) => match item.kind {
+                Kind::Map(ref pairs) => return self.check_map(group, item, pairs, out),
+                _ => false,
+            },
+            Type2::Tag(number, ref ty) => match item.kind {
+                Kind::Tag(tag, ref inner) if number.map_or(true, |number| number == tag) => {
+                    return self.check_type(ty, inner, out)
+                }
+                _ => false,
+            },
+            Type2::Major(major, argument) => match (major, &item.kind) {
+                (0, &Kind::Int(value)) => value >= 0,
+                (1, &Kind::Int(value)) => value < 0,
+                (2, &Kind::Bytes(..)) | (3, &Kind::Text(_)) => true,
+                (4, &Kind::Array(_)) | (5, &Kind::Map(_)) => true,
+                (6, &Kind::Tag(tag, _)) => argument.map_or(true, |argument| argument == tag),
+                (7, &Kind::Float(_, info)) => argument.map_or(true, |argument| argument == info),
+                (7, &Kind::Simple(value)) => argument.map_or(true, |argument| {
+                    argument == u64::from(value) || argument == 24 && value >= 24
+                }),
+                _ => false,
+            },
+            Type2::Any => true,
+        };
+        if !ok {
+            out.push(expected(ty, item));
+        }
+    }
+
+    fn check_rule(&mut self, name: &'s str, item: &Item, out: &mut Vec<Violation>) {
+        let ty = match self.rule_type(name) {
+            Some(ty) => ty,
+            None => {
+                let message = format!("the group `{}` is used as a type", name);
+                return out.push(Violation::new(item.start, message));
+            }
+        };
+        if self.depth == MAX_DEPTH {
+            return out.push(Violation::new(
+                item.start,
+                "rules nest too deeply".to_string(),
+            ));
+        }
+        self.depth += 1;
+        let mut violations = Vec::new();
+        self.check_type(ty, item, &mut violations);
+        self.depth -= 1;
+        if !violations.is_empty() && mismatched(&violations, item) {
+            out.push(expected(&name, item));
+        } else {
+            out.extend(violations);
+        }
+    }
+
+    // Checks the items of a `.cborseq` byte string.
+    fn check_sequence(
+        &mut self,
+        ty: &'s Type2,
+        item: &Item,
+        items: &[Item],
+        out: &mut Vec<Violation>,
+    ) {
+        match *ty {
+            Type2::Array(ref group) => self.check_array(ty, group, item, items, out),
+            _ => {
+                for item in items {
+                    self.check_type2(ty, item, out);
+                }
+            }
+        }
+    }
+
+    // Returns the positions in `items` at which a match of `group` starting at `start` can end.
+    fn group_ends(&mut self, group: &'s Group, items: &[Item], start: usize) -> BTreeSet<usize> {
+        let mut ends = BTreeSet::new();
+        if self.depth == MAX_DEPTH {
+            return ends;
+        }
+        self.depth += 1;
+        for entries in &group.choices {
+            let mut positions: BTreeSet<usize> = Some(start).into_iter().collect();
+            for entry in entries {
+                positions = self.entry_ends(entry, items, &positions);
+            }
+            ends.extend(positions);
+        }
+        self.depth -= 1;
+        ends
+    }
+
+    fn entry_ends(
+        &mut self,
+        entry: &'s Entry,
+        items: &[Item],
+        from: &BTreeSet<usize>,
+    ) -> BTreeSet<usize> {
+        let mut ends = BTreeSet::new();
+        if entry.min == 0 {
+            ends.extend(from);
+        }
+        let mut current = from.clone();
+        let mut count = 0;
+        while count < entry.max && count <= items.len() {
+            let mut next = BTreeSet::new();
+            for &position in &current {
+                match (self.entry_group(entry), &entry.member) {
+                    (Some(group), _) => next.extend(self.group_ends(group, items, position)),
+                    (None, Member::Type(ty)) => {
+                        if position < items.len() && self.matches(ty, &items[position]) {
+                            next.insert(position + 1);
+                        }
+                    }
+                    (None, &Member::Group(_)) => unreachable!(),
+                }
+            }
+            count += 1;
+            if next.is_empty() || next == current {
+                break;
+            }
+            if count >= entry.min {
+                ends.extend(&next);
+            }
+            current = next;
+        }
+        ends
+    }
+
+    fn check_array(
+        &mut self,
+        ty: &'s Type2,
+        group: &'s Group,
+        array: &Item,
+        items: &[Item],
+        out: &mut Vec<Violation>,
+    ) {
+        if self.group_ends(group, items, 0).contains(&items.len()) {
+            return;
+        }
+        let start = out.len();
+        self.explain_array(group, array, items, out);
+        if out.len() == start {
+            out.push(expected(ty, array));
+        }
+    }
+
+    // Finds out where the items of an array stop matching a group with a single choice of plain
+    // types, by matching each entry as often as possible.
+    fn explain_array(
+        &mut self,
+        group: &'s Group,
+        array: &Item,
+        items: &[Item],
+        out: &mut Vec<Violation>,
+    ) {
+        let entries = match *group.choices.as_slice() {
+            [ref entries] => entries,
+            _ => return,
+        };
+        let mut position = 0;
+        let mut stopped = None;
+        for entry in entries {
+            let ty = match (self.entry_group(entry), &entry.member) {
+                (None, Member::Type(ty)) => ty,
+                _ => return,
+            };
+            let mut count = 0;
+            loop {
+                while count < entry.max
+                    && position < items.len()
+                    && self.matches(ty, &items[position])
+                {
+                    position += 1;
+                    count += 1;
+                }
+                if count >= entry.min {
+                    break;
+                }
+                // Report the element in the way and carry on as if it had matched.
+                match items.get(position) {
+                    Some(item) => self.check_type(ty, item, out),
+                    None => {
+                        let message = format!("missing array element {}", ty);
+                        return out.push(Violation::new(array.start, message));
+                    }
+                }
+                position += 1;
+                count += 1;
+            }
+            if stopped.is_some_and(|(_, at)| at != position) {
+                stopped = None;
+            }
+            if count < entry.max && stopped.is_none() {
+                stopped = Some((ty, position));
+            }
+        }
+        if let Some(item) = items.get(position) {
+            match stopped {
+                Some((ty, at)) if at == position => self.check_type(ty, item, out),
+                _ => out.push(Violation::new(
+                    item.start,
+                    "unexpected array element".to_string(),
+                )),
+            }
+        }
+    }
+
+    fn check_map(
+        &mut self,
+        group: &'s Group,
+        map: &Item,
+        pairs: &[(Item, Item)],
+        out: &mut Vec<Violation>,
+    ) {
+        let mut claims = Claims {
+            used: vec![false; pairs.len()],
+            rejected: vec![Vec::new(); pairs.len()],
+        };
+        self.map_group(group, map, pairs, &mut claims, out);
+        for (i, pair) in pairs.iter().enumerate() {
+            if claims.used[i] {
+                continue;
+            }
+            if claims.rejected[i].is_empty() {
+                out.push(Violation::new(
+                    pair.0.start,
+                    format!("unexpected key {}", key(&pair.0)),
+                ));
+            } else {
+                out.append(&mut claims.rejected[i]);
+            }
+        }
+    }
+
+    fn map_group(
+        &mut self,
+        group: &'s Group,
+        map: &Item,
+        pairs: &[(Item, Item)],
+        claims: &mut Claims,
+        out: &mut Vec<Violation>,
+    ) {
+        if group.choices.len() == 1 {
+            return self.map_entries(&group.choices[0], map, pairs, claims, out);
+        }
+        // Go with the choice that leaves the fewest problems.
+        let mut best: Option<(usize, Claims, Vec<Violation>)> = None;
+        for entries in &group.choices {
+            let mut choice_claims = claims.clone();
+            let mut violations = Vec::new();
+            self.map_entries(entries, map, pairs, &mut choice_claims, &mut violations);
+            let problems =
+                violations.len() + choice_claims.used.iter().filter(|used| !**used).count();
+            if best.as_ref().map_or(true, |best| problems < best.0) {
+                best = Some((problems, choice_claims, violations));
+            }
+        }
+        let (_, choice_claims, violations) = best.unwrap();
+        *claims = choice_claims;
+        out.extend(violations);
+    }
+
+    fn map_entries(
+        &mut self,
+        entries: &'s [Entry],
+        map: &Item,
+        pairs: &[(Item, Item)],
+        claims: &mut Claims,
+        out: &mut Vec<Violation>,
+    ) {
+        for entry in entries {
+            if let Some(group) = self.entry_group(entry) {
+                // Optional groups are only checked if they match.
+                let mut group_claims = claims.clone();
+                let mut violations = Vec::new();
+                if self.depth < MAX_DEPTH {
+                    self.depth += 1;
+                    self.map_group(group, map, pairs, &mut group_claims, &mut violations);
+                    self.depth -= 1;
+                }
+                if entry.min > 0 || violations.is_empty() {
+                    *claims = group_claims;
+                    out.extend(violations);
+                }
+                continue;
+            }
+            let ty = match entry.member {
+                Member::Type(ref ty) => ty,
+                Member::Group(_) => unreachable!(),
+            };
+            let key_value = match entry.key {
+                Some(Key::Value(ref value)) => Some(value),
+                Some(Key::Type(Type1 { ref base, op: None })) => self.value_of(base),
+                Some(Key::Type(_)) => None,
+                None => {
+                    let message = format!("map entry {} has no key", ty);
+                    out.push(Violation::new(map.start, message));
+                    continue;
+                }
+            };
+            let mut count = 0;
+            for (i, pair) in pairs.iter().enumerate() {
+                if count == entry.max {
+                    break;
+                }
+                let key_matches = match (key_value, &entry.key) {
+                    (Some(value), _) => value.matches(&pair.0),
+                    (None, &Some(Key::Type(ref key))) => {
+                        let mut violations = Vec::new();
+                        self.check_type1(key, &pair.0, &mut violations);
+                        violations.is_empty()
+                    }
+                    _ => false,
+                };
+                if claims.used[i] || !key_matches {
+                    continue;
+                }
+                let mut violations = Vec::new();
+                self.check_type(ty, &pair.1, &mut violations);
+                if violations.is_empty() || key_value.is_some() {
+                    claims.used[i] = true;
+                    count += 1;
+                    out.extend(violations);
+                } else if claims.rejected[i].is_empty() {
+                    // Another entry may still take the pair, so hold on to the violations until
+                    // it is clear that none does.
+                    claims.rejected[i] = violations;
+                }
+            }
+            if count < entry.min {
+                let message = match key_value {
+                    Some(value) => format!("missing key {}", value),
+                    None => format!("missing entry {}", entry),
+                };
+                out.push(Violation::new(map.start, message));
+            }
+        }
+    }
+}
+
+// How far a choice got in matching an item, as compared when picking the one to report.
+type Score = (u64, bool, Reverse<usize>);
+
+// Tracks which pairs of a map have been matched by an entry, and why the values of pairs whose
+// keys matched an entry didn't.
+#[derive(Clone)]
+struct Claims {
+    used: Vec<bool>,
+    rejected: Vec<Vec<Violation>>,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Text(ref text) => write!(f, "{:?}", text),
+            Value::Bytes(ref bytes) => {
+                f.write_str("h'")?;
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                f.write_str("'")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, choice) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" / ")?;
+            }
+            write!(f, "{}", choice)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Type1 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.base)?;
+        match self.op {
+            Some((Op::Range { inclusive: true }, ref other)) => write!(f, "..{}", other),
+            Some((Op::Range { inclusive: false }, ref other)) => write!(f, "...{}", other),
+            Some((Op::Control(ref control), ref other)) => write!(f, " .{} {}", control, other),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Type2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type2::Value(ref value) => write!(f, "{}", value),
+            Type2::Name(ref name) => f.write_str(name),
+            Type2::Paren(ref ty) => write!(f, "({})", ty),
+            Type2::Map(ref group) => write!(f, "{{{}}}", group),
+            Type2::Array(ref group) => write!(f, "[{}]", group),
+            Type2::Tag(Some(tag), ref ty) => write!(f, "#6.{}({})", tag, ty),
+            Type2::Tag(None, ref ty) => write!(f, "#6({})", ty),
+            Type2::Major(major, Some(argument)) => write!(f, "#{}.{}", major, argument),
+            Type2::Major(major, None) => write!(f, "#{}", major),
+            Type2::Any => f.write_str("#"),
+        }
+    }
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, entries) in self.choices.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" // ")?;
+            }
+            for (j, entry) in entries.iter().enumerate() {
+                if j != 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", entry)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.min, self.max) {
+            (1, 1) => {}
+            (0, 1) => f.write_str("? ")?,
+            (0, UNBOUNDED) => f.write_str("* ")?,
+            (1, UNBOUNDED) => f.write_str("+ ")?,
+            (min, UNBOUNDED) => write!(f, "{}* ", min)?,
+            (min, max) => write!(f, "{}*{} ", min, max)?,
+        }
+        match self.key {
+            Some(Key::Value(ref value)) => write!(f, "{}: ", value)?,
+            Some(Key::Type(ref ty)) => write!(f, "{} => ", ty)?,
+            None => {}
+        }
+        match self.member {
+            Member::Type(ref ty) => write!(f, "{}", ty),
+            Member::Group(ref group) => write!(f, "({})", group),
+        }
+    }
+}
@@ -0,0 +1,554 @@
+//! Conversion between CBOR and JSON following section 6 of RFC 8949.
+//!
+//! CBOR can express more than JSON, so converting CBOR to JSON involves choosing a
+//! representation for the data items JSON has no equivalent for. By default the recommendations
+//! of the RFC are followed:
+//!
+//! * Byte strings become text in base64url without padding. Byte strings in a data item tagged
+//!   with one of the expected conversion tags 21 (base64url), 22 (base64) or 23 (base16) use
+//!   that encoding, unless a data item within it has another of these tags.
+//! * Bignums (tags 2 and 3) become text with their magnitude in base64url, prefixed by `~` if
+//!   they are negative.
+//! * Negative integers below the range of `i64` become floats.
+//! * Map keys other than text strings become text in diagnostic notation, such as `1` or
+//!   `h'01'`. A map in which two keys end up as the same text, such as `1` and `"1"`, fails to
+//!   convert.
+//! * Infinite and NaN floats, `undefined` and unassigned simple values become `null`.
+//! * Other tags are dropped and their content is converted.
+//!
+//! `Options` can be used to pick another byte string encoding or to reject what JSON cannot
+//! represent faithfully instead.
+//!
+//! Converting JSON to CBOR is straightforward: numbers become integers if they are integral and
+//! the shortest float that represents them exactly otherwise.
+//!
+//! Both directions work on `serde_json::Value`, or on JSON text with `cbor_to_json_string` and
+//! `json_str_to_cbor`. A `Value` is converted to JSON directly, the way its encoding would be,
+//! with `Options::value_to_json` or `serde_json::Value::try_from`, and back with `Value::from`.
+//!
+//! This module is available with the `json` feature.
+//!
+//! ```
+//! # extern crate serde_cbor;
+//! # extern crate serde_json;
+//! use serde_cbor::convert::json;
+//!
+//! # fn main() {
+//! // {1: h'cafe', "b": [true, undefined]}
+//! let cbor = b"\xa2\x01\x42\xca\xfe\x61b\x82\xf5\xf7";
+//! let value = json::cbor_to_json(cbor).unwrap();
+//! assert_eq!(value.to_string(), r#"{"1":"yv4","b":[true,null]}"#);
+//! # }
+//! ```
+use byteorder::{BigEndian, ByteOrder};
+use half::f16;
+use serde_json::{Map, Number, Value as Json};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::mem;
+
+use diag;
+use error::{Error, ErrorCode, Result};
+use text::{encode_base64, BASE64, BASE64URL};
+use value::Value;
+
+/// Converts a CBOR data item to JSON with the default options.
+pub fn cbor_to_json(bytes: &[u8]) -> Result<Json> {
+    Options::new().cbor_to_json(bytes)
+}
+
+/// Converts a CBOR data item to JSON text with the default options.
+///
+/// ```
+/// use serde_cbor::convert::json;
+///
+/// // [2(h'010000000000000000'), 1.5]
+/// let cbor = b"\x82\xc2\x49\x01\0\0\0\0\0\0\0\0\xf9\x3e\x00";
+/// assert_eq!(json::cbor_to_json_string(cbor).unwrap(), r#"["AQAAAAAAAAAA",1.5]"#);
+/// ```
+pub fn cbor_to_json_string(bytes: &[u8]) -> Result<String> {
+    Options::new().cbor_to_json_string(bytes)
+}
+
+/// Converts JSON to a CBOR data item.
+pub fn json_to_cbor(value: &Json) -> Result<Vec<u8>> {
+    ::ser::to_vec(value)
+}
+
+/// Converts JSON text to a CBOR data item.
+///
+/// The offset of an error in the text is the byte offset into `json` at which parsing failed.
+///
+/// ```
+/// use serde_cbor::convert::json;
+///
+/// let cbor = json::json_str_to_cbor(r#"{"a": [1, 0.5]}"#).unwrap();
+/// assert_eq!(cbor, b"\xa1\x61a\x82\x01\xf9\x38\x00");
+/// assert_eq!(json::json_str_to_cbor("[1,]").unwrap_err().offset(), 3);
+/// ```
+pub fn json_str_to_cbor(json: &str) -> Result<Vec<u8>> {
+    let value = ::serde_json::from_str(json).map_err(|error| json_error(json, &error))?;
+    json_to_cbor(&value)
+}
+
+// Converts an error in parsing JSON text into one at its byte offset in the text.
+fn json_error(json: &str, error: &::serde_json::Error) -> Error {
+    if error.is_eof() {
+        return Error::syntax(ErrorCode::EofWhileParsingValue, json.len() as u64);
+    }
+    let lines = json.split('\n').take(error.line().saturating_sub(1));
+    let offset = lines.map(|line| line.len() + 1).sum::<usize>() + error.column().saturating_sub(1);
+    // The message is followed by the line and column, which the offset replaces.
+    let mut message = error.to_string();
+    if let Some(end) = message.rfind(" at line ") {
+        message.truncate(end);
+    }
+    Error::syntax(ErrorCode::InvalidJson(message), offset as u64)
+}
+
+// Fails on a key that two keys of a map have turned into.
+fn duplicate_key(key: String, offset: u64) -> Error {
+    Error::syntax(ErrorCode::DuplicateKey(Json::String(key).to_string()), offset)
+}
+
+/// The text encoding used to represent byte strings in JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteEncoding {
+    /// The URL and filename safe base64 alphabet without padding.
+    Base64Url,
+    /// The standard base64 alphabet with padding.
+    Base64,
+    /// Lower case hexadecimal digits.
+    Base16,
+}
+
+/// Policies for converting CBOR to JSON.
+#[derive(Clone, Debug)]
+pub struct Options {
+    byte_strings: ByteEncoding,
+    reject_non_text_keys: bool,
+    reject_non_finite: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options::new()
+    }
+}
+
+impl Options {
+    /// Creates the default options recommended by RFC 8949.
+    pub fn new() -> Options {
+        Options {
+            byte_strings: ByteEncoding::Base64Url,
+            reject_non_text_keys: false,
+            reject_non_finite: false,
+        }
+    }
+
+    /// Sets the encoding of byte strings that are not tagged with an expected conversion.
+    pub fn byte_strings(mut self, encoding: ByteEncoding) -> Self {
+        self.byte_strings = encoding;
+        self
+    }
+
+    /// Fails on map keys that are not text strings instead of converting them to text.
+    pub fn reject_non_text_keys(mut self) -> Self {
+        self.reject_non_text_keys = true;
+        self
+    }
+
+    /// Fails on infinite and NaN floats instead of converting them to `null`.
+    pub fn reject_non_finite(mut self) -> Self {
+        self.reject_non_finite = true;
+        self
+    }
+
+    /// Converts a CBOR data item to JSON.
+    pub fn cbor_to_json(&self, bytes: &[u8]) -> Result<Json> {
+        let mut converter = Converter {
+            options: self,
+            input: bytes,
+            pos: 0,
+            remaining_depth: 128,
+            encoding: self.byte_strings,
+        };
+        let value = converter.item()?;
+        if converter.pos != bytes.len() {
+            return Err(converter.error(ErrorCode::TrailingData));
+        }
+        Ok(value)
+    }
+
+    /// Converts a CBOR data item to JSON text.
+    pub fn cbor_to_json_string(&self, bytes: &[u8]) -> Result<String> {
+        self.cbor_to_json(bytes).map(|value| value.to_string())
+    }
+
+    /// Converts a `Value` to JSON the way its encoding would be converted.
+    ///
+    /// ```
+    /// # extern crate serde_cbor;
+    /// # extern crate serde_json;
+    /// use serde_cbor::convert::json::{ByteEncoding, Options};
+    /// use serde_cbor::Value;
+    ///
+    /// # fn main() {
+    /// let value = Value::Array(vec![Value::Bytes(vec![0xca, 0xfe]), Value::Undefined]);
+    /// let options = Options::new().byte_strings(ByteEncoding::Base16);
+    /// assert_eq!(options.value_to_json(&value).unwrap().to_string(), r#"["cafe",null]"#);
+    /// # }
+    /// ```
+    pub fn value_to_json(&self, value: &Value) -> Result<Json> {
+        self.value(value, None, self.byte_strings)
+    }
+
+    // Converts a value after its innermost tag `tag`, with byte strings in `encoding` unless
+    // the tag says otherwise.
+    fn value(&self, value: &Value, tag: Option<u64>, encoding: ByteEncoding) -> Result<Json> {
+        let json = match *value {
+            Value::Integer(v) => match (u64::try_from(v), i64::try_from(v)) {
+                (Ok(v), _) => Json::from(v),
+                (_, Ok(v)) => Json::from(v),
+                _ => Json::from(v as f64),
+            },
+            Value::Bytes(ref bytes) => Json::String(match tag {
+                Some(2) => encode(bytes, ByteEncoding::Base64Url),
+                Some(3) => format!("~{}", encode(bytes, ByteEncoding::Base64Url)),
+                _ => encode(bytes, encoding),
+            }),
+            Value::String(ref s) => Json::String(s.clone()),
+            Value::Array(ref array) => {
+                let array = array.iter().map(|v| self.value(v, None, encoding));
+                Json::Array(array.collect::<Result<_>>()?)
+            }
+            Value::Object(ref map) => {
+                let mut object = Map::new();
+                for (key, value) in map {
+                    let key = match *key {
+                        Value::String(ref s) => s.clone(),
+                        _ if self.reject_non_text_keys => {
+                            let msg = "map key is not a text string".to_string();
+                            return Err(Error::syntax(ErrorCode::Message(msg), 0));
+                        }
+                        _ => diag::to_diag_string(key)?,
+                    };
+                    if object.contains_key(&key) {
+                        return Err(duplicate_key(key, 0));
+                    }
+                    object.insert(key, self.value(value, None, encoding)?);
+                }
+                Json::Object(object)
+            }
+            Value::Tag(tag, ref content) => {
+                let encoding = match tag {
+                    21 => ByteEncoding::Base64Url,
+                    22 => ByteEncoding::Base64,
+                    23 => ByteEncoding::Base16,
+                    _ => encoding,
+                };
+                return self.value(content, Some(tag), encoding);
+            }
+            Value::F64(v) => match Number::from_f64(v) {
+                Some(number) => Json::Number(number),
+                None if self.reject_non_finite => {
+                    let msg = format!("{} cannot be represented in JSON", v);
+                    return Err(Error::syntax(ErrorCode::Message(msg), 0));
+                }
+                None => Json::Null,
+            },
+            Value::Bool(v) => Json::Bool(v),
+            Value::Null | Value::Undefined | Value::Simple(_) => Json::Null,
+        };
+        Ok(json)
+    }
+}
+
+impl TryFrom<Value> for Json {
+    type Error = Error;
+
+    /// Converts a `Value` to JSON with the default options.
+    fn try_from(value: Value) -> Result<Json> {
+        Options::new().value_to_json(&value)
+    }
+}
+
+impl From<Json> for Value {
+    /// Converts JSON to a `Value` the way its encoding would be deserialized, which cannot fail.
+    /// `Value::try_from` works as well.
+    fn from(json: Json) -> Value {
+        match json {
+            Json::Null => Value::Null,
+            Json::Bool(v) => Value::Bool(v),
+            Json::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(v), _) => Value::from(v),
+                (_, Some(v)) => Value::from(v),
+                _ => Value::F64(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Json::String(s) => Value::String(s),
+            Json::Array(array) => Value::Array(array.into_iter().map(Value::from).collect()),
+            Json::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| (Value::String(key), Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+struct Converter<'a> {
+    options: &'a Options,
+    input: &'a [u8],
+    pos: usize,
+    remaining_depth: u8,
+    // The encoding of byte strings set by the innermost expected conversion tag around them.
+    encoding: ByteEncoding,
+}
+
+impl<'a> Converter<'a> {
+    fn error(&self, code: ErrorCode) -> Error {
+        Error::syntax(code, self.pos as u64)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        match self.pos.checked_add(n) {
+            Some(end) if end <= self.input.len() => {
+                let buf = &self.input[self.pos..end];
+                self.pos = end;
+                Ok(buf)
+            }
+            _ => Err(Error::syntax(
+                ErrorCode::EofWhileParsingValue,
+                self.input.len() as u64,
+            )),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        self.take(1).map(|buf| buf[0])
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    // Reads the argument of a head. Returns `None` for indefinite lengths.
+    fn argument(&mut self, info: u8) -> Result<Option<u64>> {
+        Ok(Some(match info {
+            0...23 => u64::from(info),
+            24 => u64::from(self.byte()?),
+            25 => u64::from(BigEndian::read_u16(self.take(2)?)),
+            26 => u64::from(BigEndian::read_u32(self.take(4)?)),
+            27 => BigEndian::read_u64(self.take(8)?),
+            31 => return Ok(None),
+            _ => return Err(self.error(ErrorCode::UnassignedCode)),
+        }))
+    }
+
+    fn definite(&mut self, info: u8) -> Result<u64> {
+        match self.argument(info)? {
+            Some(value) => Ok(value),
+            None => Err(self.error(ErrorCode::UnassignedCode)),
+        }
+    }
+
+    fn length(&mut self, info: u8) -> Result<Option<usize>> {
+        match self.argument(info)? {
+            Some(len) if len > usize::max_value() as u64 => {
+                Err(self.error(ErrorCode::LengthOutOfRange))
+            }
+            len => Ok(len.map(|len| len as usize)),
+        }
+    }
+
+    fn recursion_checked<F>(&mut self, f: F) -> Result<Json>
+    where
+        F: FnOnce(&mut Converter<'a>) -> Result<Json>,
+    {
+        if self.remaining_depth == 1 {
+            return Err(self.error(ErrorCode::RecursionLimitExceeded));
+        }
+        self.remaining_depth -= 1;
+        let r = f(self);
+        self.remaining_depth += 1;
+        r
+    }
+
+    fn item(&mut self) -> Result<Json> {
+        // only the innermost of several tags determines the conversion
+        let mut tag = None;
+        let mut encoding = None;
+        let byte = loop {
+            let byte = self.byte()?;
+            if byte >> 5 != 6 {
+                break byte;
+            }
+            tag = Some(self.definite(byte & 0x1f)?);
+            encoding = match tag {
+                Some(21) => Some(ByteEncoding::Base64Url),
+                Some(22) => Some(ByteEncoding::Base64),
+                Some(23) => Some(ByteEncoding::Base16),
+                _ => encoding,
+            };
+        };
+        match encoding {
+            Some(encoding) => {
+                let outer = mem::replace(&mut self.encoding, encoding);
+                let value = self.content(tag, byte);
+                self.encoding = outer;
+                value
+            }
+            None => self.content(tag, byte),
+        }
+    }
+
+    // Converts the data item that starts with `byte`, after its innermost tag `tag`.
+    fn content(&mut self, tag: Option<u64>, byte: u8) -> Result<Json> {
+        let (major, info) = (byte >> 5, byte & 0x1f);
+        match major {
+            0 => Ok(Json::from(self.definite(info)?)),
+            1 => {
+                let value = self.definite(info)?;
+                if value <= i64::max_value() as u64 {
+                    Ok(Json::from(-1 - value as i64))
+                } else {
+                    Ok(Json::from(-1.0 - value as f64))
+                }
+            }
+            2 => {
+                let bytes = self.string(major, info)?;
+                Ok(Json::String(match tag {
+                    Some(2) => encode(&bytes, ByteEncoding::Base64Url),
+                    Some(3) => format!("~{}", encode(&bytes, ByteEncoding::Base64Url)),
+                    _ => encode(&bytes, self.encoding),
+                }))
+            }
+            3 => {
+                let start = self.pos;
+                let bytes = self.string(major, info)?;
+                match String::from_utf8(bytes.into_owned()) {
+                    Ok(s) => Ok(Json::String(s)),
+                    Err(e) => {
+                        let offset = start + e.utf8_error().valid_up_to();
+                        Err(Error::syntax(ErrorCode::InvalidUtf8, offset as u64))
+                    }
+                }
+            }
+            4 => {
+                let len = self.length(info)?;
+                self.recursion_checked(|c| {
+                    let mut array = Vec::new();
+                    while c.more(len, array.len(), ErrorCode::EofWhileParsingArray)? {
+                        array.push(c.item()?);
+                    }
+                    Ok(Json::Array(array))
+                })
+            }
+            5 => {
+                let len = self.length(info)?;
+                self.recursion_checked(|c| {
+                    let mut map = Map::new();
+                    let mut count = 0;
+                    while c.more(len, count, ErrorCode::EofWhileParsingMap)? {
+                        let start = c.pos;
+                        let key = c.key()?;
+                        if map.contains_key(&key) {
+                            return Err(duplicate_key(key, start as u64));
+                        }
+                        let value = c.item()?;
+                        map.insert(key, value);
+                        count += 1;
+                    }
+                    Ok(Json::Object(map))
+                })
+            }
+            _ => self.simple(info),
+        }
+    }
+
+    // Checks whether another item follows in an array or map of `len` items, `count` of which
+    // have been read, consuming the break code of an indefinite-length one.
+    fn more(&mut self, len: Option<usize>, count: usize, eof: ErrorCode) -> Result<bool> {
+        match len {
+            Some(len) => Ok(count < len),
+            None => match self.peek() {
+                Some(0xff) => {
+                    self.pos += 1;
+                    Ok(false)
+                }
+                Some(_) => Ok(true),
+                None => Err(self.error(eof)),
+            },
+        }
+    }
+
+    fn key(&mut self) -> Result<String> {
+        let start = self.pos;
+        match self.item()? {
+            Json::String(ref s) if self.input[start] & 0xe0 == 0x60 => Ok(s.clone()),
+            _ if self.options.reject_non_text_keys => Err(Error::syntax(
+                ErrorCode::Message("map key is not a text string".to_string()),
+                start as u64,
+            )),
+            _ => Ok(diag::to_string(&self.input[start..self.pos])),
+        }
+    }
+
+    fn string(&mut self, major: u8, info: u8) -> Result<Cow<'a, [u8]>> {
+        if let Some(len) = self.length(info)? {
+            return Ok(Cow::Borrowed(self.take(len)?));
+        }
+        let mut buf = Vec::new();
+        loop {
+            let byte = self.byte()?;
+            if byte == 0xff {
+                return Ok(Cow::Owned(buf));
+            }
+            if byte >> 5 != major {
+                return Err(self.error(ErrorCode::UnexpectedCode));
+            }
+            match self.length(byte & 0x1f)? {
+                Some(len) => buf.extend_from_slice(self.take(len)?),
+                None => return Err(self.error(ErrorCode::UnexpectedCode)),
+            }
+        }
+    }
+
+    fn simple(&mut self, info: u8) -> Result<Json> {
+        let value = match info {
+            20 => return Ok(Json::Bool(false)),
+            21 => return Ok(Json::Bool(true)),
+            0...19 | 22 | 23 => return Ok(Json::Null),
+            24 => {
+                self.byte()?;
+                return Ok(Json::Null);
+            }
+            25 => f64::from(f16::from_bits(BigEndian::read_u16(self.take(2)?))),
+            26 => f64::from(BigEndian::read_f32(self.take(4)?)),
+            27 => BigEndian::read_f64(self.take(8)?),
+            31 => return Err(self.error(ErrorCode::UnexpectedCode)),
+            _ => return Err(self.error(ErrorCode::UnassignedCode)),
+        };
+        match Number::from_f64(value) {
+            Some(number) => Ok(Json::Number(number)),
+            None if self.options.reject_non_finite => Err(self.error(ErrorCode::Message(
+                format!("{} cannot be represented in JSON", value),
+            ))),
+            None => Ok(Json::Null),
+        }
+    }
+}
+
+fn encode(bytes: &[u8], encoding: ByteEncoding) -> String {
+    match encoding {
+        ByteEncoding::Base16 => {
+            let mut out = String::with_capacity(bytes.len() * 2);
+            for byte in bytes {
+                out.push_str(&format!("{:02x}", byte));
+            }
+            out
+        }
+        ByteEncoding::Base64 => encode_base64(bytes, BASE64, true),
+        ByteEncoding::Base64Url => encode_base64(bytes, BASE64URL, false),
+    }
+}
@@ -0,0 +1,3 @@
+//! Conversion between CBOR and other data formats.
+
+pub mod json;
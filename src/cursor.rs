@@ -0,0 +1,94 @@
+// Reading encoded data items head by head from a slice, for the passes that rewrite CBOR before
+// it is deserialized.
+use byteorder::{BigEndian, ByteOrder};
+
+use error::{Error, ErrorCode, Result};
+
+pub(crate) struct Cursor<'a> {
+    pub(crate) input: &'a [u8],
+    pub(crate) offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn new(input: &'a [u8]) -> Cursor<'a> {
+        Cursor { input, offset: 0 }
+    }
+
+    pub(crate) fn error(&self, code: ErrorCode) -> Error {
+        Error::syntax(code, self.offset as u64)
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.offset == self.input.len()
+    }
+
+    pub(crate) fn at_break(&self) -> bool {
+        self.input.get(self.offset) == Some(&0xff)
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.input.len() - self.offset < len {
+            let offset = self.input.len() as u64;
+            return Err(Error::syntax(ErrorCode::EofWhileParsingValue, offset));
+        }
+        let bytes = &self.input[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    // Returns the input from `start` up to where the cursor is.
+    pub(crate) fn since(&self, start: usize) -> &'a [u8] {
+        &self.input[start..self.offset]
+    }
+
+    // Reads the head of a data item, returning its major type and argument, or `None` as the
+    // argument if its length is indefinite.
+    pub(crate) fn head(&mut self) -> Result<(u8, Option<u64>)> {
+        let byte = self.take(1)?[0];
+        let (major, info) = (byte >> 5, byte & 0x1f);
+        let arg = match info {
+            0...23 => u64::from(info),
+            24 => u64::from(self.take(1)?[0]),
+            25 => u64::from(BigEndian::read_u16(self.take(2)?)),
+            26 => u64::from(BigEndian::read_u32(self.take(4)?)),
+            27 => BigEndian::read_u64(self.take(8)?),
+            31 if major >= 2 && major != 6 => return Ok((major, None)),
+            _ => {
+                self.offset -= 1;
+                return Err(self.error(ErrorCode::UnassignedCode));
+            }
+        };
+        Ok((major, Some(arg)))
+    }
+
+    // Converts the argument of a string head to a length.
+    pub(crate) fn len(&self, arg: u64) -> Result<usize> {
+        if arg > usize::max_value() as u64 {
+            return Err(self.error(ErrorCode::LengthOutOfRange));
+        }
+        Ok(arg as usize)
+    }
+
+    // Skips the content of a string whose head was just read, checking the chunks of one of
+    // indefinite length.
+    pub(crate) fn skip_string(&mut self, major: u8, arg: Option<u64>) -> Result<()> {
+        if let Some(len) = arg {
+            let len = self.len(len)?;
+            return self.take(len).map(|_| ());
+        }
+        while !self.at_break() {
+            let chunk = self.offset;
+            match self.head()? {
+                (m, Some(len)) if m == major => {
+                    let len = self.len(len)?;
+                    self.take(len)?;
+                }
+                _ => {
+                    self.offset = chunk;
+                    return Err(self.error(ErrorCode::UnexpectedCode));
+                }
+            }
+        }
+        self.take(1).map(|_| ())
+    }
+}
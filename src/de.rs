@@ -1,17 +1,99 @@
 //! Deserialization.
 
 use byteorder::{ByteOrder, BigEndian};
+use core::cmp;
+use core::iter::FusedIterator;
+#[cfg(feature = "float")]
+use core::f32;
+use core::marker::PhantomData;
+use core::mem;
+use core::result;
+use core::str;
+#[cfg(feature = "float")]
 use half::f16;
 use serde::de;
+#[cfg(feature = "dcbor")]
+use unicode_normalization::is_nfc;
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+#[cfg(feature = "std")]
 use std::io;
-use std::str;
-use std::f32;
-use std::result;
-use std::marker::PhantomData;
 
+#[cfg(all(feature = "std", feature = "bytes"))]
+use bytes::Buf;
+
+#[cfg(all(feature = "std", feature = "smallvec"))]
+use smallvec::SmallVec;
+
+#[cfg(feature = "std")]
+use diag;
 use error::{Error, Result, ErrorCode};
+#[cfg(feature = "std")]
+use error::{PathSegment, Warning, WarningKind};
+#[cfg(not(feature = "unsealed-read"))]
 use read::Reference;
-pub use read::{Read, IoRead, SliceRead};
+#[cfg(all(not(feature = "std"), not(feature = "unsealed-read")))]
+use read::Scratch;
+#[cfg(feature = "std")]
+use ser::KeyOrder;
+use tags;
+pub use read::{Read, SliceRead};
+#[cfg(feature = "unsealed-read")]
+pub use read::{Reference, Scratch};
+#[cfg(feature = "std")]
+pub use read::IoRead;
+#[cfg(all(feature = "std", feature = "bytes"))]
+pub use read::BytesRead;
+#[cfg(feature = "std")]
+pub use incremental::{Incremental, Status};
+#[cfg(all(feature = "std", feature = "memmap2"))]
+pub use mmap::MappedFile;
+#[cfg(all(feature = "std", any(feature = "futures-io", feature = "tokio")))]
+pub use async_read::{from_async_reader, AsyncIoRead, AsyncSource, FromAsyncReader, NextValue};
+#[cfg(all(feature = "std", feature = "tokio"))]
+pub use async_read::TokioIo;
+
+// Buffer for input that cannot be borrowed. With the `smallvec` feature, short strings and map
+// keys are kept inline instead of on the heap.
+#[cfg(all(feature = "std", not(feature = "smallvec")))]
+type ScratchBuf = Vec<u8>;
+
+#[cfg(all(feature = "std", feature = "smallvec"))]
+type ScratchBuf = SmallVec<[u8; 64]>;
+
+#[cfg(not(feature = "std"))]
+type ScratchBuf = ::read::NoScratch;
+
+// How deeply data items may be nested in each other.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+// How deep CTAP2 messages may nest arrays and maps.
+#[cfg(feature = "std")]
+const CTAP2_MAX_DEPTH: usize = 4;
+
+// An array or map that `skip_value` is inside of.
+#[derive(Clone, Copy)]
+enum Pending {
+    // The number of data items left, counting keys and values separately.
+    Items(u64),
+    // An array or map of indefinite length by its major type, and whether a key (or an array
+    // element) or the break comes next.
+    Indefinite(u8, bool),
+}
+
+// Validates text strings, returning the length of the valid prefix on failure. With the
+// `simdutf8` feature this uses SIMD instructions where the CPU supports them.
+#[cfg(not(feature = "simdutf8"))]
+#[inline]
+fn from_utf8(buf: &[u8]) -> result::Result<&str, usize> {
+    str::from_utf8(buf).map_err(|e| e.valid_up_to())
+}
+
+#[cfg(feature = "simdutf8")]
+#[inline]
+fn from_utf8(buf: &[u8]) -> result::Result<&str, usize> {
+    simdutf8::compat::from_utf8(buf).map_err(|e| e.valid_up_to())
+}
 
 /// Decodes a value from CBOR data in a slice.
 ///
@@ -34,6 +116,17 @@ pub use read::{Read, IoRead, SliceRead};
 /// let value: &str = de::from_slice(&v[..]).unwrap();
 /// assert_eq!(value, "foobar");
 /// ```
+///
+/// Byte strings of definite length are borrowed the same way. Indefinite-length byte strings
+/// are split into chunks and have to be copied, so a `Cow<[u8]>` with `#[serde(borrow)]` takes
+/// both while a `&[u8]` only accepts the former.
+///
+/// ```
+/// # use serde_cbor::de;
+/// let value: &[u8] = de::from_slice(b"\x43\x01\x02\x03").unwrap();
+/// assert_eq!(value, [1, 2, 3]);
+/// assert!(de::from_slice::<&[u8]>(b"\x5f\x41\x01\x42\x02\x03\xff").is_err());
+/// ```
 pub fn from_slice<'a, T>(slice: &'a [u8]) -> Result<T>
 where
     T: de::Deserialize<'a>,
@@ -46,6 +139,9 @@ where
 
 /// Decodes a value from CBOR data in a reader.
 ///
+/// The input is read in chunks, as `IoRead` does by default, so this need not be given a
+/// `BufReader`.
+///
 /// # Examples
 ///
 /// Deserialize a `String`
@@ -65,6 +161,7 @@ where
 /// let value: &str = de::from_reader(&v[..]).unwrap();
 /// assert_eq!(value, "foobar");
 /// ```
+#[cfg(feature = "std")]
 pub fn from_reader<T, R>(reader: R) -> Result<T>
 where
     T: de::DeserializeOwned,
@@ -76,13 +173,200 @@ where
     Ok(value)
 }
 
+/// Decodes a value from CBOR data in a `bytes::Buf`, which may be split into several chunks.
+///
+/// ```
+/// # extern crate bytes;
+/// # extern crate serde_cbor;
+/// use bytes::{Buf, Bytes};
+/// use serde_cbor::de;
+///
+/// # fn main() {
+/// let buf = Bytes::from_static(b"\x66fo").chain(Bytes::from_static(b"obar"));
+/// let value: String = de::from_buf(buf).unwrap();
+/// assert_eq!(value, "foobar");
+/// # }
+/// ```
+///
+/// This function is available with the `bytes` feature.
+#[cfg(all(feature = "std", feature = "bytes"))]
+pub fn from_buf<T, B>(buf: B) -> Result<T>
+where
+    T: de::DeserializeOwned,
+    B: Buf,
+{
+    let mut deserializer = Deserializer::from_buf(buf);
+    let value = de::Deserialize::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Decodes a value from CBOR data in a reader behind a trait object.
+///
+/// Unlike `from_reader`, this instantiates the parser only once for all kinds of readers, which
+/// keeps binaries small where code size matters more than speed, such as in firmware or
+/// WebAssembly.
+///
+/// ```
+/// # use serde_cbor::de;
+/// let v: Vec<u8> = vec![0x66, 0x66, 0x6f, 0x6f, 0x62, 0x61, 0x72];
+/// let value: String = de::from_dyn_reader(&mut &v[..]).unwrap();
+/// assert_eq!(value, "foobar");
+/// ```
+#[cfg(feature = "std")]
+pub fn from_dyn_reader<T>(reader: &mut io::Read) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    from_reader(reader)
+}
+
+/// Decodes the items of a CBOR sequence (RFC 8742) in parallel.
+///
+/// The input is split into the encodings of its items first, which are then decoded on the
+/// rayon thread pool. Errors report offsets into the whole input and the index of the item they
+/// occurred in. Available with the `rayon` feature.
+///
+/// ```
+/// # use serde_cbor::de;
+/// let items: Vec<u32> = de::par_from_sequence(b"\x01\x18\x64\x19\x03\xe8").unwrap();
+/// assert_eq!(items, [1, 100, 1000]);
+/// ```
+#[cfg(all(feature = "std", feature = "rayon"))]
+pub fn par_from_sequence<'a, T>(slice: &'a [u8]) -> Result<Vec<T>>
+where
+    T: de::Deserialize<'a> + Send,
+{
+    let mut de = Deserializer::from_slice(slice);
+    let mut items = Vec::new();
+    while de.peek()?.is_some() {
+        items.push(de.split_item(slice)?);
+    }
+    par_from_items(slice, items)
+}
+
+/// Decodes the elements of an array in parallel.
+///
+/// This works like `par_from_sequence` for the elements of a top-level array. Available with the
+/// `rayon` feature.
+#[cfg(all(feature = "std", feature = "rayon"))]
+pub fn par_from_array<'a, T>(slice: &'a [u8]) -> Result<Vec<T>>
+where
+    T: de::Deserialize<'a> + Send,
+{
+    let mut de = Deserializer::from_slice(slice);
+    let mut items = Vec::new();
+    let byte = de.parse_untagged_u8()?;
+    match byte {
+        0x80...0x9b => {
+            let len = de.parse_len(byte)?;
+            for _ in 0..len {
+                items.push(de.split_item(slice)?);
+            }
+        }
+        0x9f => loop {
+            match de.peek()? {
+                Some(0xff) => {
+                    de.consume();
+                    break;
+                }
+                Some(_) => items.push(de.split_item(slice)?),
+                None => return Err(de.error(ErrorCode::EofWhileParsingArray)),
+            }
+        },
+        _ => return Err(de.error(ErrorCode::UnexpectedCode)),
+    }
+    de.end()?;
+    par_from_items(slice, items)
+}
+
+#[cfg(all(feature = "std", feature = "rayon"))]
+fn par_from_items<'a, T>(slice: &'a [u8], items: Vec<&'a [u8]>) -> Result<Vec<T>>
+where
+    T: de::Deserialize<'a> + Send,
+{
+    use rayon::prelude::*;
+
+    let base = slice.as_ptr() as usize;
+    items
+        .into_par_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            from_slice(item).map_err(|e| {
+                let offset = item.as_ptr() as usize - base;
+                e.shifted(offset as u64).at(PathSegment::Index(index))
+            })
+        })
+        .collect()
+}
+
+/// Bundles of checks on the form of the input, which `Deserializer::profile` makes at once.
+///
+/// All profiles accept any input that is in the form they check for.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Accepts all well-formed input. This is the default.
+    Lenient,
+    /// Accepts only the preferred serialization of RFC 8949, without duplicate map keys.
+    ///
+    /// Integers, lengths and tags must have their arguments in the shortest form, floats must
+    /// be in the shortest form that keeps their value, and indefinite lengths are rejected.
+    Rfc8949Strict,
+    /// Accepts only the canonical form of CTAP2.
+    ///
+    /// Arguments must be in the shortest form, and there must be no indefinite lengths,
+    /// duplicate map keys or tags. The keys of each map must be in the order of
+    /// `KeyOrder::Ctap2`, and arrays and maps may be nested at most four levels deep. Floats
+    /// are left as they are, as CTAP2 does not change their representation.
+    Ctap2,
+    /// Accepts only dCBOR, the deterministic profile of CBOR used by Blockchain Commons.
+    ///
+    /// This is `Rfc8949Strict` with the keys of each map sorted bytewise, and further without
+    /// floats that could be encoded as integers, NaNs other than `f9 7e00`, `undefined`, and
+    /// text strings that are not in Unicode Normalization Form C. Text strings and map keys are
+    /// checked as they are decoded, and not when they are skipped.
+    ///
+    /// This variant is available with the `dcbor` feature.
+    #[cfg(feature = "dcbor")]
+    Dcbor,
+}
+
+// Checks on the input beyond its being well-formed, which are all off by default. Some are only
+// made with `std`.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+#[derive(Clone, Copy, Default)]
+struct Checks {
+    duplicate_keys: bool,
+    non_minimal: bool,
+    indefinite: bool,
+    floats: bool,
+    tags: bool,
+    #[cfg(feature = "std")]
+    key_order: Option<KeyOrder>,
+    #[cfg(feature = "dcbor")]
+    dcbor: bool,
+}
+
 /// A Serde `Deserialize`r of CBOR data.
+///
+/// Decoding an array or map hands its items to the `Deserialize` implementation of the type
+/// it is decoded into, so the call stack grows with each level of nesting. Input is therefore
+/// rejected once it nests more than 128 levels deep, which bounds the stack that decoding takes.
+/// Only data items that are skipped, such as unknown fields or `IgnoredAny`, are passed over
+/// without recursion.
 pub struct Deserializer<R> {
     read: R,
-    buf: Vec<u8>,
+    buf: ScratchBuf,
     remaining_depth: u8,
+    prealloc_limit: usize,
+    check_utf8: bool,
+    checks: Checks,
+    #[cfg(feature = "std")]
+    warnings: Option<Vec<Warning>>,
 }
 
+#[cfg(feature = "std")]
 impl<R> Deserializer<IoRead<R>>
 where
     R: io::Read,
@@ -93,6 +377,17 @@ where
     }
 }
 
+#[cfg(all(feature = "std", feature = "bytes"))]
+impl<B> Deserializer<BytesRead<B>>
+where
+    B: Buf,
+{
+    /// Constructs a `Deserializer` which reads from a `bytes::Buf`.
+    pub fn from_buf(buf: B) -> Deserializer<BytesRead<B>> {
+        Deserializer::new(BytesRead::new(buf))
+    }
+}
+
 impl<'a> Deserializer<SliceRead<'a>> {
     /// Constructs a `Deserializer` which reads from a slice.
     ///
@@ -112,11 +407,278 @@ where
     pub fn new(read: R) -> Self {
         Deserializer {
             read,
-            buf: Vec::new(),
-            remaining_depth: 128,
+            buf: ScratchBuf::new(),
+            remaining_depth: MAX_DEPTH as u8,
+            prealloc_limit: 4096,
+            check_utf8: true,
+            checks: Checks::default(),
+            #[cfg(feature = "std")]
+            warnings: None,
+        }
+    }
+
+    /// Limits the number of elements collections and the number of bytes strings may
+    /// preallocate room for.
+    ///
+    /// The lengths of arrays and maps are passed on to `Deserialize` implementations as size
+    /// hints so that e.g. a `Vec` can be allocated up front. As the length comes from the
+    /// input and may be far larger than the data that follows it, the hint never exceeds this
+    /// limit, which defaults to 4096. Longer collections still decode but grow as they are
+    /// filled. Likewise, strings that are copied from a reader are read in pieces of at most
+    /// this many bytes, so that the buffer only grows as far as the input actually goes.
+    pub fn preallocation_limit(mut self, limit: usize) -> Self {
+        self.prealloc_limit = limit;
+        self
+    }
+
+    /// Skips the validation of text strings, for input known to be well-formed.
+    ///
+    /// Checking that text strings are valid UTF-8 can dominate the time spent decoding
+    /// string-heavy data. Where the input was produced by a trusted encoder, such as this crate
+    /// within the same pipeline, that check can be turned off. Available with the
+    /// `unchecked-utf8` feature.
+    ///
+    /// # Safety
+    ///
+    /// Every text string in the input must be valid UTF-8. Otherwise `str` and `String` values
+    /// handed out by the deserializer are invalid, which is undefined behavior.
+    #[cfg(feature = "unchecked-utf8")]
+    pub unsafe fn trust_utf8(mut self) -> Self {
+        self.check_utf8 = false;
+        self
+    }
+
+    /// Fails on a map key that is identical to an earlier key of the same map.
+    ///
+    /// RFC 8949 leaves it to decoders what to do with duplicate keys, and by default the value
+    /// of the last one wins or the `Deserialize` implementation decides. Protocols where two
+    /// parties must agree on the meaning of a message can rule duplicates out instead. The error
+    /// names the key and gives the offset it starts at. Keys are compared by their encoding, and
+    /// every key of a map is kept until the map ends.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Deserialize;
+    /// use serde_cbor::{Deserializer, Value};
+    ///
+    /// # fn main() {
+    /// // {"a": 1, "b": 2, "a": 3}
+    /// let bytes = b"\xa3\x61a\x01\x61b\x02\x61a\x03";
+    /// let mut de = Deserializer::from_slice(bytes).reject_duplicate_keys();
+    /// let error = Value::deserialize(&mut de).unwrap_err();
+    /// assert_eq!(error.to_string(), "duplicate map key \"a\" at offset 7");
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn reject_duplicate_keys(mut self) -> Self {
+        self.checks.duplicate_keys = true;
+        self
+    }
+
+    /// Rejects integers, lengths and tags whose argument is not in its shortest form.
+    ///
+    /// RFC 8949 allows the argument of a head to take more bytes than its value needs, so that
+    /// one value has several encodings. Deterministic protocols allow only the shortest. The
+    /// error gives the offset of the head and the number of bytes it wastes.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Deserialize;
+    /// use serde_cbor::Deserializer;
+    ///
+    /// # fn main() {
+    /// // [1, 1 in nine bytes]
+    /// let bytes = b"\x82\x01\x1b\x00\x00\x00\x00\x00\x00\x00\x01";
+    /// let mut de = Deserializer::from_slice(bytes).reject_non_minimal_arguments();
+    /// let error = Vec::<u64>::deserialize(&mut de).unwrap_err();
+    /// assert!(error.is_data());
+    /// assert_eq!(error.offset(), 2);
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "argument not in its shortest form, wasting 8 bytes at offset 2 in [1]"
+    /// );
+    /// # }
+    /// ```
+    pub fn reject_non_minimal_arguments(mut self) -> Self {
+        self.checks.non_minimal = true;
+        self
+    }
+
+    /// Rejects indefinite-length strings, arrays and maps.
+    ///
+    /// Canonical forms such as that of CTAP2 allow only definite lengths. With this check, the
+    /// head of an indefinite-length item is an error at the offset of the head, whether
+    /// the item is decoded or skipped.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Deserialize;
+    /// use serde_cbor::Deserializer;
+    ///
+    /// # fn main() {
+    /// // [_ 1, 2]
+    /// let mut de = Deserializer::from_slice(b"\x9f\x01\x02\xff").reject_indefinite_lengths();
+    /// let error = Vec::<u8>::deserialize(&mut de).unwrap_err();
+    /// assert!(error.is_data());
+    /// assert_eq!(error.to_string(), "indefinite-length item");
+    /// # }
+    /// ```
+    pub fn reject_indefinite_lengths(mut self) -> Self {
+        self.checks.indefinite = true;
+        self
+    }
+
+    /// Sets which checks on the form of the input are made, as a bundle.
+    ///
+    /// Profiles spare applications from learning every check a protocol needs. A profile
+    /// replaces the checks made so far.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Deserialize;
+    /// use serde_cbor::de::Profile;
+    /// use serde_cbor::Deserializer;
+    ///
+    /// # fn main() {
+    /// // 1 encoded in two bytes
+    /// let mut de = Deserializer::from_slice(b"\x18\x01").profile(Profile::Rfc8949Strict);
+    /// assert!(u8::deserialize(&mut de).unwrap_err().is_data());
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.checks = match profile {
+            Profile::Lenient => Checks::default(),
+            Profile::Rfc8949Strict => Checks {
+                duplicate_keys: true,
+                non_minimal: true,
+                indefinite: true,
+                floats: true,
+                ..Checks::default()
+            },
+            Profile::Ctap2 => Checks {
+                duplicate_keys: true,
+                non_minimal: true,
+                indefinite: true,
+                tags: true,
+                key_order: Some(KeyOrder::Ctap2),
+                ..Checks::default()
+            },
+            #[cfg(feature = "dcbor")]
+            Profile::Dcbor => Checks {
+                duplicate_keys: true,
+                non_minimal: true,
+                indefinite: true,
+                floats: true,
+                key_order: Some(KeyOrder::Bytewise),
+                dcbor: true,
+                ..Checks::default()
+            },
+        };
+        self.remaining_depth = match profile {
+            Profile::Ctap2 => CTAP2_MAX_DEPTH as u8 + 1,
+            _ => MAX_DEPTH as u8,
+        };
+        self
+    }
+
+    /// Records non-fatal issues with the input instead of silently accepting it.
+    ///
+    /// Decoding stays as lenient as before, but non-minimal integer encodings, unsorted or
+    /// duplicate map keys and indefinite-length items are noted and can be inspected with
+    /// `warnings` once a value has been deserialized.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Deserialize;
+    /// use serde_cbor::Deserializer;
+    /// use serde_cbor::error::WarningKind;
+    ///
+    /// # fn main() {
+    /// let mut de = Deserializer::from_slice(b"\x18\x01").collect_warnings();
+    /// let value = u8::deserialize(&mut de).unwrap();
+    /// assert_eq!(value, 1);
+    /// assert_eq!(de.warnings()[0].kind(), WarningKind::NonMinimalArgument);
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn collect_warnings(mut self) -> Self {
+        self.warnings = Some(Vec::new());
+        self
+    }
+
+    /// Returns the warnings collected so far.
+    ///
+    /// This is always empty unless `collect_warnings` was called.
+    #[cfg(feature = "std")]
+    pub fn warnings(&self) -> &[Warning] {
+        match self.warnings {
+            Some(ref warnings) => warnings,
+            None => &[],
+        }
+    }
+
+    /// Removes and returns the warnings collected so far.
+    #[cfg(feature = "std")]
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        match self.warnings {
+            Some(ref mut warnings) => mem::take(warnings),
+            None => Vec::new(),
         }
     }
 
+    /// Decodes CBOR data embedded in a byte string, as in a tag 24 item.
+    ///
+    /// The embedded data is decoded with the options of this deserializer and counts towards its
+    /// recursion limit, and the scratch buffer is shared, so that layered structures such as
+    /// COSE messages do not allocate anew for every layer. Offsets in errors are relative to
+    /// `bytes`, and warnings about the embedded data are not collected. Text strings in the
+    /// embedded data are always checked to be valid UTF-8, as `trust_utf8` only vouches for the
+    /// input of this deserializer.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_bytes;
+    /// # extern crate serde_cbor;
+    /// use serde::Deserialize;
+    /// use serde_bytes::ByteBuf;
+    /// use serde_cbor::Deserializer;
+    ///
+    /// # fn main() {
+    /// // 24(h'6449455446')
+    /// let mut de = Deserializer::from_slice(b"\xd8\x18\x45\x64\x49\x45\x54\x46");
+    /// let bytes = ByteBuf::deserialize(&mut de).unwrap();
+    /// let value: String = de.embedded(&bytes).unwrap();
+    /// assert_eq!(value, "IETF");
+    /// # }
+    /// ```
+    pub fn embedded<'b, T>(&mut self, bytes: &'b [u8]) -> Result<T>
+    where
+        T: de::Deserialize<'b>,
+    {
+        let mut de = Deserializer {
+            read: SliceRead::new(bytes),
+            buf: mem::take(&mut self.buf),
+            remaining_depth: self.remaining_depth,
+            prealloc_limit: self.prealloc_limit,
+            check_utf8: true,
+            checks: self.checks,
+            #[cfg(feature = "std")]
+            warnings: None,
+        };
+        let value = de::Deserialize::deserialize(&mut de).and_then(|value| {
+            de.end()?;
+            Ok(value)
+        });
+        self.buf = de.buf;
+        value
+    }
+
     /// This method should be called after a value has been deserialized to ensure there is no
     /// trailing data in the input source.
     pub fn end(&mut self) -> Result<()> {
@@ -133,17 +695,59 @@ where
     {
         StreamDeserializer {
             de: self,
+            offset: 0,
+            failed: false,
             output: PhantomData,
             lifetime: PhantomData,
         }
     }
 
+    /// Turns a CBOR deserializer into an iterator over the elements of a top-level array.
+    ///
+    /// The head of the array is read right away, and fails if the input does not start with an
+    /// array. The elements are then deserialized one at a time as the iterator is advanced, so
+    /// the array never has to be in memory as a whole. After the last element the iterator
+    /// checks that the input ends.
+    ///
+    /// ```
+    /// # use serde_cbor::Deserializer;
+    /// let bytes = b"\x83\x01\x02\x03";
+    /// let mut total = 0;
+    /// for element in Deserializer::from_slice(bytes).into_array_iter::<u32>().unwrap() {
+    ///     total += element.unwrap();
+    /// }
+    /// assert_eq!(total, 6);
+    /// ```
+    pub fn into_array_iter<T>(mut self) -> Result<ArrayDeserializer<'de, R, T>>
+    where
+        T: de::Deserialize<'de>,
+    {
+        let byte = self.parse_untagged_u8()?;
+        let len = match byte {
+            0x80...0x9b => Some(self.parse_len(byte)?),
+            0x9f => {
+                self.check_indefinite()?;
+                None
+            }
+            _ => return Err(self.error(ErrorCode::UnexpectedCode)),
+        };
+        self.remaining_depth -= 1;
+        Ok(ArrayDeserializer {
+            de: self,
+            len,
+            index: 0,
+            done: false,
+            output: PhantomData,
+            lifetime: PhantomData,
+        })
+    }
+
     fn next(&mut self) -> Result<Option<u8>> {
-        self.read.next().map_err(Error::io)
+        self.read.next()
     }
 
     fn peek(&mut self) -> Result<Option<u8>> {
-        self.read.peek().map_err(Error::io)
+        self.read.peek()
     }
 
     fn consume(&mut self) {
@@ -155,6 +759,233 @@ where
         Error::syntax(reason, offset)
     }
 
+    #[cfg(feature = "std")]
+    fn warn(&mut self, kind: WarningKind, offset: u64) {
+        if let Some(ref mut warnings) = self.warnings {
+            warnings.push(Warning::new(kind, offset));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn warn_with_key(&mut self, kind: WarningKind, offset: u64, key: String) {
+        if let Some(ref mut warnings) = self.warnings {
+            warnings.push(Warning::with_key(kind, offset, key));
+        }
+    }
+
+    // Checks that an argument of `width` bytes that was just read could not have been encoded
+    // in fewer bytes.
+    fn check_argument(&mut self, value: u64, width: u64) -> Result<()> {
+        if !self.checks.non_minimal && !self.collects_warnings() {
+            return Ok(());
+        }
+        let minimal = if value < 24 {
+            0
+        } else if value <= u64::from(u8::max_value()) {
+            1
+        } else if value <= u64::from(u16::max_value()) {
+            2
+        } else if value <= u64::from(u32::max_value()) {
+            4
+        } else {
+            8
+        };
+        if width > minimal {
+            let offset = self.read.offset() - 1 - width;
+            if self.checks.non_minimal {
+                let code = ErrorCode::NonMinimalArgument((width - minimal) as u8);
+                return Err(Error::syntax(code, offset));
+            }
+            #[cfg(feature = "std")]
+            self.warn(WarningKind::NonMinimalArgument, offset);
+        }
+        Ok(())
+    }
+
+    // Checks an indefinite-length head that was just read.
+    fn check_indefinite(&mut self) -> Result<()> {
+        let offset = self.read.offset() - 1;
+        if self.checks.indefinite {
+            return Err(Error::syntax(ErrorCode::IndefiniteLength, offset));
+        }
+        #[cfg(feature = "std")]
+        self.warn(WarningKind::IndefiniteLength, offset);
+        Ok(())
+    }
+
+    // Checks the head of a tag whose initial byte was just read.
+    fn check_tag(&mut self) -> Result<()> {
+        if self.checks.tags {
+            let offset = self.read.offset() - 1;
+            return Err(Error::syntax(ErrorCode::TagNotAllowed, offset));
+        }
+        Ok(())
+    }
+
+    // Checks that a float of `width` bytes that was just read could not have been encoded in
+    // fewer bytes without changing its value.
+    #[cfg(feature = "float")]
+    fn check_float(&mut self, shorter: bool, width: u64) -> Result<()> {
+        if self.checks.floats && shorter {
+            let offset = self.read.offset() - 1 - width;
+            return Err(Error::syntax(ErrorCode::NonPreferredFloat, offset));
+        }
+        Ok(())
+    }
+
+    // Checks that a float of `width` bytes that was just read is one dCBOR allows: neither an
+    // integer that fits an integer encoding, nor a NaN other than `f9 7e00`.
+    #[cfg(feature = "dcbor")]
+    fn check_dcbor_float(&mut self, value: f64, odd_nan: bool, width: u64) -> Result<()> {
+        // Both bounds are powers of two, and so exact.
+        let integer = value.trunc() == value
+            && (-9_223_372_036_854_775_808.0..18_446_744_073_709_551_616.0).contains(&value);
+        if self.checks.dcbor && (integer || odd_nan) {
+            let offset = self.read.offset() - 1 - width;
+            return Err(Error::syntax(ErrorCode::NonPreferredFloat, offset));
+        }
+        Ok(())
+    }
+
+    // Checks a simple value other than a float or a boolean or null, whose initial byte was just
+    // read.
+    fn check_simple(&mut self) -> Result<()> {
+        #[cfg(feature = "dcbor")]
+        {
+            if self.checks.dcbor {
+                let offset = self.read.offset() - 1;
+                return Err(Error::syntax(ErrorCode::SimpleValueNotAllowed, offset));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn collects_warnings(&self) -> bool {
+        self.warnings.is_some()
+    }
+
+    // Without `std` there are no warnings to collect.
+    #[cfg(not(feature = "std"))]
+    fn collects_warnings(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "std")]
+    fn keeps_keys(&self) -> bool {
+        self.read.borrows_raw()
+            || self.checks.duplicate_keys
+            || self.checks.key_order.is_some()
+            || self.warnings.is_some()
+    }
+
+    // Without `std` nothing looks at the keys.
+    #[cfg(not(feature = "std"))]
+    fn keeps_keys(&self) -> bool {
+        false
+    }
+
+    fn parse_arg_u8(&mut self) -> Result<u8> {
+        let value = self.parse_u8()?;
+        self.check_argument(u64::from(value), 1)?;
+        Ok(value)
+    }
+
+    fn parse_arg_u16(&mut self) -> Result<u16> {
+        let value = self.parse_u16()?;
+        self.check_argument(u64::from(value), 2)?;
+        Ok(value)
+    }
+
+    fn parse_arg_u32(&mut self) -> Result<u32> {
+        let value = self.parse_u32()?;
+        self.check_argument(u64::from(value), 4)?;
+        Ok(value)
+    }
+
+    fn parse_arg_u64(&mut self) -> Result<u64> {
+        let value = self.parse_u64()?;
+        self.check_argument(value, 8)?;
+        Ok(value)
+    }
+
+    // Deserializes a map key, keeping its encoding in `keys` so that the order of keys can be
+    // checked and errors in the corresponding value can name it. Keys are only kept when they
+    // are checked or can be borrowed from the input, as copying them is expensive.
+    fn parse_key<K>(&mut self, seed: K, keys: &mut MapKeys<'de>) -> Result<K::Value>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        keys.rotate();
+        keys.kept = self.keeps_keys();
+        if !keys.kept {
+            return seed.deserialize(&mut *self);
+        }
+        let start = self.read.begin_raw();
+        let value = seed.deserialize(&mut *self);
+        keys.current = match self.read.end_raw(start, &mut keys.current_buf) {
+            Reference::Borrowed(buf) => Some(buf),
+            Reference::Copied => None,
+        };
+        let value = value?;
+
+        #[cfg(feature = "std")]
+        if self.checks.duplicate_keys {
+            let key = keys.current().to_vec();
+            let offset = self.read.offset() - key.len() as u64;
+            if keys.seen.contains(&key) {
+                let code = ErrorCode::DuplicateKey(diag::to_string(&key));
+                return Err(Error::syntax(code, offset));
+            }
+            keys.seen.insert(key);
+        }
+
+        #[cfg(feature = "std")]
+        if let Some(order) = self.checks.key_order {
+            if let Some(last) = keys.last() {
+                let current = keys.current();
+                if order.compare(current, last) == cmp::Ordering::Less {
+                    let offset = self.read.offset() - current.len() as u64;
+                    let code = ErrorCode::UnsortedKey(diag::to_string(current));
+                    return Err(Error::syntax(code, offset));
+                }
+            }
+        }
+
+        #[cfg(feature = "std")]
+        if self.warnings.is_some() {
+            let kind = match keys.last() {
+                Some(last) if keys.current() == last => Some(WarningKind::DuplicateMapKey),
+                Some(last) if keys.current() < last => Some(WarningKind::UnsortedMapKey),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                let offset = self.read.offset() - keys.current().len() as u64;
+                let key = diag::to_string(keys.current());
+                self.warn_with_key(kind, offset, key);
+            }
+        }
+        Ok(value)
+    }
+
+    // Reads the length of a definite-length string whose initial byte was just consumed.
+    fn parse_len(&mut self, byte: u8) -> Result<usize> {
+        match byte & 0x1f {
+            len @ 0x00...0x17 => Ok(len as usize),
+            0x18 => Ok(self.parse_arg_u8()? as usize),
+            0x19 => Ok(self.parse_arg_u16()? as usize),
+            0x1a => Ok(self.parse_arg_u32()? as usize),
+            0x1b => {
+                let len = self.parse_arg_u64()?;
+                if len > usize::max_value() as u64 {
+                    return Err(self.error(ErrorCode::LengthOutOfRange));
+                }
+                Ok(len as usize)
+            }
+            _ => unreachable!(),
+        }
+    }
+
     fn parse_u8(&mut self) -> Result<u8> {
         match self.next()? {
             Some(byte) => Ok(byte),
@@ -185,24 +1016,39 @@ where
         V: de::Visitor<'de>,
     {
         self.buf.clear();
-        match self.read.read(len, &mut self.buf, 0)? {
+        match self.read.read(len, &mut self.buf, 0, self.prealloc_limit)? {
             Reference::Borrowed(buf) => visitor.visit_borrowed_bytes(buf),
             Reference::Copied => visitor.visit_bytes(&self.buf),
         }
     }
 
+    // Reads a definite-length byte string straight into an owned buffer.
+    #[cfg(feature = "std")]
+    fn parse_byte_buf<V>(&mut self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut buf = Vec::new();
+        match self.read.read(len, &mut buf, 0, self.prealloc_limit)? {
+            Reference::Borrowed(bytes) => visitor.visit_byte_buf(bytes.to_vec()),
+            Reference::Copied => visitor.visit_byte_buf(buf),
+        }
+    }
+
+    #[cfg(feature = "std")]
     fn parse_indefinite_bytes(&mut self) -> Result<&[u8]> {
+        self.check_indefinite()?;
         let mut offset = 0;
         self.buf.clear();
         loop {
             let byte = self.parse_u8()?;
             let len = match byte {
                 0x40...0x57 => byte as usize - 0x40,
-                0x58 => self.parse_u8()? as usize,
-                0x59 => self.parse_u16()? as usize,
-                0x5a => self.parse_u32()? as usize,
+                0x58 => self.parse_arg_u8()? as usize,
+                0x59 => self.parse_arg_u16()? as usize,
+                0x5a => self.parse_arg_u32()? as usize,
                 0x5b => {
-                    let len = self.parse_u64()?;
+                    let len = self.parse_arg_u64()?;
                     if len > usize::max_value() as u64 {
                         return Err(self.error(ErrorCode::LengthOutOfRange));
                     }
@@ -212,7 +1058,7 @@ where
                 _ => return Err(self.error(ErrorCode::UnexpectedCode)),
             };
 
-            match self.read.read(len, &mut self.buf, offset)? {
+            match self.read.read(len, &mut self.buf, offset, self.prealloc_limit)? {
                 Reference::Borrowed(buf) => {
                     let new_len = offset + len;
                     if new_len > self.buf.len() {
@@ -229,11 +1075,26 @@ where
         Ok(&self.buf[..offset])
     }
 
+    // Without `std` there is no buffer to join the chunks of indefinite-length strings in.
+    #[cfg(not(feature = "std"))]
+    fn parse_indefinite_bytes(&mut self) -> Result<&[u8]> {
+        Err(self.error(ErrorCode::IndefiniteString))
+    }
+
     fn convert_str<'a>(&self, buf: &'a [u8]) -> Result<&'a str> {
-        match str::from_utf8(buf) {
+        if !self.check_utf8 {
+            // the caller of `trust_utf8` vouched for the input
+            return Ok(unsafe { str::from_utf8_unchecked(buf) });
+        }
+        match from_utf8(buf) {
+            #[cfg(feature = "dcbor")]
+            Ok(s) if self.checks.dcbor && !is_nfc(s) => {
+                let offset = self.read.offset() - buf.len() as u64;
+                Err(Error::syntax(ErrorCode::NonNfcText, offset))
+            }
             Ok(s) => Ok(s),
-            Err(e) => {
-                let shift = buf.len() - e.valid_up_to();
+            Err(valid_up_to) => {
+                let shift = buf.len() - valid_up_to;
                 let offset = self.read.offset() - shift as u64;
                 Err(Error::syntax(ErrorCode::InvalidUtf8, offset))
             }
@@ -245,7 +1106,7 @@ where
         V: de::Visitor<'de>,
     {
         self.buf.clear();
-        match self.read.read(len, &mut self.buf, 0)? {
+        match self.read.read(len, &mut self.buf, 0, self.prealloc_limit)? {
             Reference::Borrowed(buf) => {
                 let s = self.convert_str(buf)?;
                 visitor.visit_borrowed_str(s)
@@ -257,18 +1118,36 @@ where
         }
     }
 
+    // Reads a definite-length text string straight into an owned buffer and validates it once.
+    #[cfg(feature = "std")]
+    fn parse_string<V>(&mut self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut buf = Vec::new();
+        let buf = match self.read.read(len, &mut buf, 0, self.prealloc_limit)? {
+            Reference::Borrowed(bytes) => bytes.to_vec(),
+            Reference::Copied => buf,
+        };
+        self.convert_str(&buf)?;
+        // the buffer was validated above
+        visitor.visit_string(unsafe { String::from_utf8_unchecked(buf) })
+    }
+
+    #[cfg(feature = "std")]
     fn parse_indefinite_str(&mut self) -> Result<&str> {
+        self.check_indefinite()?;
         let mut offset = 0;
         self.buf.clear();
         loop {
             let byte = self.parse_u8()?;
             let len = match byte {
                 0x60...0x77 => byte as usize - 0x60,
-                0x78 => self.parse_u8()? as usize,
-                0x79 => self.parse_u16()? as usize,
-                0x7a => self.parse_u32()? as usize,
+                0x78 => self.parse_arg_u8()? as usize,
+                0x79 => self.parse_arg_u16()? as usize,
+                0x7a => self.parse_arg_u32()? as usize,
                 0x7b => {
-                    let len = self.parse_u64()?;
+                    let len = self.parse_arg_u64()?;
                     if len > usize::max_value() as u64 {
                         return Err(self.error(ErrorCode::LengthOutOfRange));
                     }
@@ -278,7 +1157,7 @@ where
                 _ => return Err(self.error(ErrorCode::UnexpectedCode)),
             };
 
-            match self.read.read(len, &mut self.buf, offset)? {
+            match self.read.read(len, &mut self.buf, offset, self.prealloc_limit)? {
                 Reference::Borrowed(buf) => {
                     let new_len = offset + len;
                     if new_len > self.buf.len() {
@@ -295,12 +1174,21 @@ where
         self.convert_str(&self.buf[..offset])
     }
 
+    #[cfg(not(feature = "std"))]
+    fn parse_indefinite_str(&mut self) -> Result<&str> {
+        Err(self.error(ErrorCode::IndefiniteString))
+    }
+
+    // Runs `f` one level of nesting deeper. Decoding recurses through the visitors of the types
+    // decoded into, so this limit is all that keeps deeply nested input from exhausting the
+    // stack.
     fn recursion_checked<F, T>(&mut self, f: F) -> Result<T>
     where
         F: FnOnce(&mut Deserializer<R>) -> Result<T>,
     {
         self.remaining_depth -= 1;
         if self.remaining_depth == 0 {
+            self.remaining_depth += 1;
             return Err(self.error(ErrorCode::RecursionLimitExceeded));
         }
         let r = f(self);
@@ -313,7 +1201,11 @@ where
         V: de::Visitor<'de>,
     {
         self.recursion_checked(|de| {
-            let value = visitor.visit_seq(SeqAccess { de, len: &mut len })?;
+            let value = visitor.visit_seq(SeqAccess {
+                de,
+                len: &mut len,
+                index: 0,
+            })?;
 
             if len != 0 {
                 Err(de.error(ErrorCode::TrailingData))
@@ -327,8 +1219,9 @@ where
     where
         V: de::Visitor<'de>,
     {
+        self.check_indefinite()?;
         self.recursion_checked(|de| {
-            let value = visitor.visit_seq(IndefiniteSeqAccess { de })?;
+            let value = visitor.visit_seq(IndefiniteSeqAccess { de, index: 0 })?;
             match de.next()? {
                 Some(0xff) => Ok(value),
                 Some(_) => Err(de.error(ErrorCode::TrailingData)),
@@ -342,7 +1235,11 @@ where
         V: de::Visitor<'de>,
     {
         self.recursion_checked(|de| {
-            let value = visitor.visit_map(MapAccess { de, len: &mut len })?;
+            let value = visitor.visit_map(MapAccess {
+                de,
+                len: &mut len,
+                keys: MapKeys::new(),
+            })?;
 
             if len != 0 {
                 Err(de.error(ErrorCode::TrailingData))
@@ -356,8 +1253,12 @@ where
     where
         V: de::Visitor<'de>,
     {
+        self.check_indefinite()?;
         self.recursion_checked(|de| {
-            let value = visitor.visit_map(IndefiniteMapAccess { de })?;
+            let value = visitor.visit_map(IndefiniteMapAccess {
+                de,
+                keys: MapKeys::new(),
+            })?;
             match de.next()? {
                 Some(0xff) => Ok(value),
                 Some(_) => Err(de.error(ErrorCode::TrailingData)),
@@ -372,7 +1273,11 @@ where
     {
         self.recursion_checked(|de| {
             let value = visitor.visit_enum(VariantAccess {
-                seq: SeqAccess { de, len: &mut len },
+                seq: SeqAccess {
+                    de,
+                    len: &mut len,
+                    index: 0,
+                },
             })?;
 
             if len != 0 {
@@ -387,9 +1292,10 @@ where
     where
         V: de::Visitor<'de>,
     {
+        self.check_indefinite()?;
         self.recursion_checked(|de| {
             let value = visitor.visit_enum(
-                VariantAccess { seq: IndefiniteSeqAccess { de } },
+                VariantAccess { seq: IndefiniteSeqAccess { de, index: 0 } },
             )?;
             match de.next()? {
                 Some(0xff) => Ok(value),
@@ -399,44 +1305,312 @@ where
         })
     }
 
+    #[cfg(feature = "float")]
     fn parse_f16(&mut self) -> Result<f32> {
-        Ok(f32::from(f16::from_bits(self.parse_u16()?)))
+        let bits = self.parse_u16()?;
+        let value = f32::from(f16::from_bits(bits));
+        #[cfg(feature = "dcbor")]
+        self.check_dcbor_float(f64::from(value), value.is_nan() && bits != 0x7e00, 2)?;
+        Ok(value)
     }
 
+    #[cfg(feature = "float")]
     fn parse_f32(&mut self) -> Result<f32> {
         let mut buf = [0; 4];
         self.read.read_into(&mut buf)?;
-        Ok(BigEndian::read_f32(&buf))
+        let value = BigEndian::read_f32(&buf);
+        self.check_float(value.is_nan() || f32::from(f16::from_f32(value)) == value, 4)?;
+        #[cfg(feature = "dcbor")]
+        self.check_dcbor_float(f64::from(value), false, 4)?;
+        Ok(value)
     }
 
+    #[cfg(feature = "float")]
     fn parse_f64(&mut self) -> Result<f64> {
         let mut buf = [0; 8];
         self.read.read_into(&mut buf)?;
-        Ok(BigEndian::read_f64(&buf))
+        let value = BigEndian::read_f64(&buf);
+        self.check_float(!value.is_finite() || value as f32 as f64 == value, 8)?;
+        #[cfg(feature = "dcbor")]
+        self.check_dcbor_float(value, false, 8)?;
+        Ok(value)
+    }
+
+    // Reads initial bytes until one that does not start a semantic tag. Tags are skipped in a
+    // loop rather than by recursion so that long chains of them neither cost a call each nor
+    // exhaust the stack.
+    fn parse_untagged_u8(&mut self) -> Result<u8> {
+        loop {
+            let byte = self.parse_u8()?;
+            if let 0xc0...0xdb = byte {
+                self.check_tag()?;
+            }
+            match byte {
+                0xc0...0xd7 => {}
+                0xd8 => {
+                    self.parse_arg_u8()?;
+                }
+                0xd9 => {
+                    self.parse_arg_u16()?;
+                }
+                0xda => {
+                    self.parse_arg_u32()?;
+                }
+                0xdb => {
+                    self.parse_arg_u64()?;
+                }
+                _ => return Ok(byte),
+            }
+        }
+    }
+
+    // Reads the tag in front of the next data item, if there is one. Any further tags are
+    // skipped along with the head of the item. A self-describe tag at the start of the input
+    // only marks it as CBOR, and is skipped as well.
+    fn parse_tag(&mut self) -> Result<Option<u64>> {
+        let start = self.read.offset() == 0;
+        let byte = match self.peek()? {
+            Some(byte @ 0xc0...0xdb) => byte,
+            _ => return Ok(None),
+        };
+        self.consume();
+        self.check_tag()?;
+        let tag = match byte {
+            0xd8 => u64::from(self.parse_arg_u8()?),
+            0xd9 => u64::from(self.parse_arg_u16()?),
+            0xda => u64::from(self.parse_arg_u32()?),
+            0xdb => self.parse_arg_u64()?,
+            _ => u64::from(byte & 0x1f),
+        };
+        if start && tag == 55799 {
+            return self.parse_tag();
+        }
+        Ok(Some(tag))
+    }
+
+    // Parses an integer, or a bignum with tag 2 or 3, and hands it to the visitor as a 128-bit
+    // integer. Anything else is parsed as usual, for the visitor to reject.
+    fn parse_integer128<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let tag = self.parse_tag()?;
+        let byte = match self.peek()? {
+            Some(byte) => byte,
+            None => return Err(self.error(ErrorCode::EofWhileParsingValue)),
+        };
+        let negative = match (tag, byte) {
+            (None, 0x00...0x1b) | (None, 0x20...0x3b) => {
+                self.consume();
+                let value = match byte & 0x1f {
+                    value @ 0x00...0x17 => u64::from(value),
+                    0x18 => u64::from(self.parse_arg_u8()?),
+                    0x19 => u64::from(self.parse_arg_u16()?),
+                    0x1a => u64::from(self.parse_arg_u32()?),
+                    _ => self.parse_arg_u64()?,
+                };
+                return if byte < 0x20 {
+                    visitor.visit_u128(u128::from(value))
+                } else {
+                    visitor.visit_i128(-1 - i128::from(value))
+                };
+            }
+            (Some(2), 0x40...0x5b) => false,
+            (Some(3), 0x40...0x5b) => true,
+            _ => return self.parse_value(visitor),
+        };
+        self.consume();
+        let offset = self.read.offset() - 1;
+        let len = self.parse_len(byte)?;
+        self.buf.clear();
+        let value = {
+            let bytes = match self.read.read(len, &mut self.buf, 0, self.prealloc_limit)? {
+                Reference::Borrowed(bytes) => bytes,
+                Reference::Copied => &self.buf[..],
+            };
+            // Leading zeros are allowed, however many there are.
+            let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(len);
+            if len - start > 16 {
+                return Err(Error::syntax(ErrorCode::NumberOutOfRange, offset));
+            }
+            bytes[start..]
+                .iter()
+                .fold(0, |value, byte| value << 8 | u128::from(*byte))
+        };
+        if !negative {
+            visitor.visit_u128(value)
+        } else if value <= i128::max_value() as u128 {
+            visitor.visit_i128(-1 - value as i128)
+        } else {
+            Err(Error::syntax(ErrorCode::NumberOutOfRange, offset))
+        }
+    }
+
+    // Skips the next data item of `slice`, which this deserializer reads, and returns its
+    // encoding.
+    #[cfg(all(feature = "std", feature = "rayon"))]
+    fn split_item<'a>(&mut self, slice: &'a [u8]) -> Result<&'a [u8]> {
+        let start = self.read.offset() as usize;
+        self.skip_value()?;
+        Ok(&slice[start..self.read.offset() as usize])
+    }
+
+    // Passes over a data item without handing it to a visitor. Strings are skipped in the
+    // input rather than copied, and as their contents are never looked at, text strings are not
+    // checked for valid UTF-8.
+    //
+    // Nested arrays and maps are kept track of on a bounded stack rather than by recursion, so
+    // that skipping costs a fixed amount of the call stack however deep the input is nested.
+    // Decoding is not done this way, as it goes through visitors; see `recursion_checked`.
+    fn skip_value(&mut self) -> Result<()> {
+        let mut stack = [Pending::Items(0); MAX_DEPTH];
+        let mut depth = 0;
+        loop {
+            if let Some(pending) = self.skip_head()? {
+                depth += 1;
+                if depth >= usize::from(self.remaining_depth) {
+                    return Err(self.error(ErrorCode::RecursionLimitExceeded));
+                }
+                stack[depth - 1] = pending;
+            }
+            // Leaves the arrays and maps that have been skipped entirely.
+            loop {
+                if depth == 0 {
+                    return Ok(());
+                }
+                match stack[depth - 1] {
+                    Pending::Items(ref mut len) if *len != 0 => {
+                        *len -= 1;
+                        break;
+                    }
+                    Pending::Items(_) => {}
+                    Pending::Indefinite(major, ref mut at_key) => {
+                        if !*at_key {
+                            *at_key = true;
+                            break;
+                        }
+                        match self.peek()? {
+                            Some(0xff) => self.consume(),
+                            Some(_) => {
+                                *at_key = major == 4;
+                                break;
+                            }
+                            None if major == 4 => {
+                                return Err(self.error(ErrorCode::EofWhileParsingArray))
+                            }
+                            None => return Err(self.error(ErrorCode::EofWhileParsingMap)),
+                        }
+                    }
+                }
+                depth -= 1;
+            }
+        }
+    }
+
+    // Skips a data item up to its content if it is an array or map, which is then returned.
+    fn skip_head(&mut self) -> Result<Option<Pending>> {
+        let byte = self.parse_untagged_u8()?;
+        let (major, info) = (byte >> 5, byte & 0x1f);
+        if major == 7 {
+            return match byte {
+                0xf4...0xf6 => Ok(None),
+                0xe0...0xf3 | 0xf7 => self.check_simple().map(|()| None),
+                0xf8 => {
+                    self.check_simple()?;
+                    // The simple values below 32 only have the short form.
+                    if self.parse_arg_u8()? < 32 {
+                        return Err(self.error(ErrorCode::UnexpectedCode));
+                    }
+                    Ok(None)
+                }
+                #[cfg(feature = "float")]
+                0xf9 if self.checks.floats => self.parse_f16().map(|_| None),
+                0xf9 => self.read.skip(2).map(|()| None),
+                #[cfg(feature = "float")]
+                0xfa if self.checks.floats => self.parse_f32().map(|_| None),
+                #[cfg(feature = "float")]
+                0xfb if self.checks.floats => self.parse_f64().map(|_| None),
+                0xfa => self.read.skip(4).map(|()| None),
+                0xfb => self.read.skip(8).map(|()| None),
+                0xff => Err(self.error(ErrorCode::UnexpectedCode)),
+                _ => Err(self.error(ErrorCode::UnassignedCode)),
+            };
+        }
+        let len = match info {
+            0x00...0x17 => u64::from(info),
+            0x18 => u64::from(self.parse_arg_u8()?),
+            0x19 => u64::from(self.parse_arg_u16()?),
+            0x1a => u64::from(self.parse_arg_u32()?),
+            0x1b => self.parse_arg_u64()?,
+            0x1f if major >= 2 => return self.skip_indefinite(major),
+            _ => return Err(self.error(ErrorCode::UnassignedCode)),
+        };
+        match major {
+            0 | 1 => Ok(None),
+            2 | 3 => {
+                if len > usize::max_value() as u64 {
+                    return Err(self.error(ErrorCode::LengthOutOfRange));
+                }
+                self.read.skip(len as usize)?;
+                Ok(None)
+            }
+            4 => Ok(Some(Pending::Items(len))),
+            _ => match len.checked_mul(2) {
+                Some(len) => Ok(Some(Pending::Items(len))),
+                None => Err(self.error(ErrorCode::LengthOutOfRange)),
+            },
+        }
+    }
+
+    fn skip_indefinite(&mut self, major: u8) -> Result<Option<Pending>> {
+        self.check_indefinite()?;
+        if major == 4 || major == 5 {
+            return Ok(Some(Pending::Indefinite(major, true)));
+        }
+        loop {
+            let byte = self.parse_u8()?;
+            if byte == 0xff {
+                return Ok(None);
+            }
+            if byte >> 5 != major || byte & 0x1f > 0x1b {
+                return Err(self.error(ErrorCode::UnexpectedCode));
+            }
+            let len = self.parse_len(byte)?;
+            self.read.skip(len)?;
+        }
     }
 
     fn parse_value<V>(&mut self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let byte = self.parse_u8()?;
+        self.parse_item(false, visitor)
+    }
+
+    // Parses a data item. With `full`, the visitor is that of `Value`, which also takes negative
+    // integers beyond `i64` and the simple values that have no type in serde's data model.
+    fn parse_item<V>(&mut self, full: bool, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let byte = self.parse_untagged_u8()?;
         match byte {
             // Major type 0: an unsigned integer
             0x00...0x17 => visitor.visit_u8(byte),
             0x18 => {
-                let value = self.parse_u8()?;
+                let value = self.parse_arg_u8()?;
                 visitor.visit_u8(value)
             }
             0x19 => {
-                let value = self.parse_u16()?;
+                let value = self.parse_arg_u16()?;
                 visitor.visit_u16(value)
             }
             0x1a => {
-                let value = self.parse_u32()?;
+                let value = self.parse_arg_u32()?;
                 visitor.visit_u32(value)
             }
             0x1b => {
-                let value = self.parse_u64()?;
+                let value = self.parse_arg_u64()?;
                 visitor.visit_u64(value)
             }
             0x1c...0x1f => Err(self.error(ErrorCode::UnassignedCode)),
@@ -444,20 +1618,23 @@ where
             // Major type 1: a negative integer
             0x20...0x37 => visitor.visit_i8(-1 - (byte - 0x20) as i8),
             0x38 => {
-                let value = self.parse_u8()?;
+                let value = self.parse_arg_u8()?;
                 visitor.visit_i16(-1 - i16::from(value))
             }
             0x39 => {
-                let value = self.parse_u16()?;
+                let value = self.parse_arg_u16()?;
                 visitor.visit_i32(-1 - i32::from(value))
             }
             0x3a => {
-                let value = self.parse_u32()?;
+                let value = self.parse_arg_u32()?;
                 visitor.visit_i64(-1 - i64::from(value))
             }
             0x3b => {
-                let value = self.parse_u64()?;
+                let value = self.parse_arg_u64()?;
                 if value > i64::max_value() as u64 {
+                    if full {
+                        return visitor.visit_i128(-1 - i128::from(value));
+                    }
                     return Err(self.error(ErrorCode::NumberOutOfRange));
                 }
                 visitor.visit_i64(-1 - value as i64)
@@ -467,19 +1644,19 @@ where
             // Major type 2: a byte string
             0x40...0x57 => self.parse_bytes(byte as usize - 0x40, visitor),
             0x58 => {
-                let len = self.parse_u8()?;
+                let len = self.parse_arg_u8()?;
                 self.parse_bytes(len as usize, visitor)
             }
             0x59 => {
-                let len = self.parse_u16()?;
+                let len = self.parse_arg_u16()?;
                 self.parse_bytes(len as usize, visitor)
             }
             0x5a => {
-                let len = self.parse_u32()?;
+                let len = self.parse_arg_u32()?;
                 self.parse_bytes(len as usize, visitor)
             }
             0x5b => {
-                let len = self.parse_u64()?;
+                let len = self.parse_arg_u64()?;
                 if len > usize::max_value() as u64 {
                     return Err(self.error(ErrorCode::LengthOutOfRange));
                 }
@@ -494,19 +1671,19 @@ where
             // Major type 3: a text string
             0x60...0x77 => self.parse_str(byte as usize - 0x60, visitor),
             0x78 => {
-                let len = self.parse_u8()?;
+                let len = self.parse_arg_u8()?;
                 self.parse_str(len as usize, visitor)
             }
             0x79 => {
-                let len = self.parse_u16()?;
+                let len = self.parse_arg_u16()?;
                 self.parse_str(len as usize, visitor)
             }
             0x7a => {
-                let len = self.parse_u32()?;
+                let len = self.parse_arg_u32()?;
                 self.parse_str(len as usize, visitor)
             }
             0x7b => {
-                let len = self.parse_u64()?;
+                let len = self.parse_arg_u64()?;
                 if len > usize::max_value() as u64 {
                     return Err(self.error(ErrorCode::LengthOutOfRange));
                 }
@@ -521,19 +1698,19 @@ where
             // Major type 4: an array of data items
             0x80...0x97 => self.parse_array(byte as usize - 0x80, visitor),
             0x98 => {
-                let len = self.parse_u8()?;
+                let len = self.parse_arg_u8()?;
                 self.parse_array(len as usize, visitor)
             }
             0x99 => {
-                let len = self.parse_u16()?;
+                let len = self.parse_arg_u16()?;
                 self.parse_array(len as usize, visitor)
             }
             0x9a => {
-                let len = self.parse_u32()?;
+                let len = self.parse_arg_u32()?;
                 self.parse_array(len as usize, visitor)
             }
             0x9b => {
-                let len = self.parse_u64()?;
+                let len = self.parse_arg_u64()?;
                 if len > usize::max_value() as u64 {
                     return Err(self.error(ErrorCode::LengthOutOfRange));
                 }
@@ -545,19 +1722,19 @@ where
             // Major type 5: a map of pairs of data items
             0xa0...0xb7 => self.parse_map(byte as usize - 0xa0, visitor),
             0xb8 => {
-                let len = self.parse_u8()?;
+                let len = self.parse_arg_u8()?;
                 self.parse_map(len as usize, visitor)
             }
             0xb9 => {
-                let len = self.parse_u16()?;
+                let len = self.parse_arg_u16()?;
                 self.parse_map(len as usize, visitor)
             }
             0xba => {
-                let len = self.parse_u32()?;
+                let len = self.parse_arg_u32()?;
                 self.parse_map(len as usize, visitor)
             }
             0xbb => {
-                let len = self.parse_u64()?;
+                let len = self.parse_arg_u64()?;
                 if len > usize::max_value() as u64 {
                     return Err(self.error(ErrorCode::LengthOutOfRange));
                 }
@@ -566,45 +1743,52 @@ where
             0xbc...0xbe => Err(self.error(ErrorCode::UnassignedCode)),
             0xbf => self.parse_indefinite_map(visitor),
 
-            // Major type 6: optional semantic tagging of other major types
-            0xc0...0xd7 => self.parse_value(visitor),
-            0xd8 => {
-                self.parse_u8()?;
-                self.parse_value(visitor)
-            }
-            0xd9 => {
-                self.parse_u16()?;
-                self.parse_value(visitor)
-            }
-            0xda => {
-                self.parse_u32()?;
-                self.parse_value(visitor)
-            }
-            0xdb => {
-                self.parse_u64()?;
-                self.parse_value(visitor)
-            }
+            // Major type 6: optional semantic tagging of other major types, already skipped
             0xdc...0xdf => Err(self.error(ErrorCode::UnassignedCode)),
 
             // Major type 7: floating-point numbers and other simple data types that need no content
+            0xe0...0xf3 if full => {
+                self.check_simple()?;
+                visit_simple(byte - 0xe0, visitor)
+            }
             0xe0...0xf3 => Err(self.error(ErrorCode::UnassignedCode)),
             0xf4 => visitor.visit_bool(false),
             0xf5 => visitor.visit_bool(true),
             0xf6 => visitor.visit_unit(),
-            0xf7 => visitor.visit_unit(),
+            0xf7 => {
+                self.check_simple()?;
+                if full {
+                    return visit_simple(23, visitor);
+                }
+                visitor.visit_unit()
+            }
+            0xf8 if full => {
+                self.check_simple()?;
+                let value = self.parse_arg_u8()?;
+                // The simple values below 32 only have the short form.
+                if value < 32 {
+                    return Err(self.error(ErrorCode::UnexpectedCode));
+                }
+                visit_simple(value, visitor)
+            }
             0xf8 => Err(self.error(ErrorCode::UnassignedCode)),
+            #[cfg(feature = "float")]
             0xf9 => {
                 let value = self.parse_f16()?;
                 visitor.visit_f32(value)
             }
+            #[cfg(feature = "float")]
             0xfa => {
                 let value = self.parse_f32()?;
                 visitor.visit_f32(value)
             }
+            #[cfg(feature = "float")]
             0xfb => {
                 let value = self.parse_f64()?;
                 visitor.visit_f64(value)
             }
+            #[cfg(not(feature = "float"))]
+            0xf9...0xfb => Err(self.error(ErrorCode::Float)),
             0xfc...0xfe => Err(self.error(ErrorCode::UnassignedCode)),
             0xff => Err(self.error(ErrorCode::UnexpectedCode)),
 
@@ -643,10 +1827,47 @@ where
     }
 
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        // Newtypes of applications are transparent. The reserved names all start with a NUL,
+        // which spares them comparing against each.
+        if !name.starts_with('\0') {
+            return visitor.visit_newtype_struct(self);
+        }
+        if name == tags::CBOR_NEWTYPE_NAME {
+            let tag = self.parse_tag()?;
+            tags::set_tag(tag);
+            // Visitors such as that of `Value` deserialize the content the same way again, so
+            // each tag counts as a level of nesting.
+            let result = match tag {
+                Some(_) => self.recursion_checked(|de| visitor.visit_newtype_struct(de)),
+                None => visitor.visit_newtype_struct(&mut *self),
+            };
+            tags::set_tag(None);
+            return result;
+        }
+        if name == tags::VALUE_NEWTYPE_NAME {
+            let tag = self.parse_tag()?;
+            if tag.is_none() {
+                return self.parse_item(true, visitor);
+            }
+            tags::set_tag(tag);
+            let result = self.recursion_checked(|de| visitor.visit_newtype_struct(de));
+            tags::set_tag(None);
+            return result;
+        }
+        if name == tags::RAW_NEWTYPE_NAME {
+            let start = self.read.begin_raw();
+            let result = self.skip_value();
+            let raw = self.read.end_raw(start, &mut self.buf);
+            result?;
+            return match raw {
+                Reference::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+                Reference::Copied => visitor.visit_bytes(&self.buf),
+            };
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -669,19 +1890,19 @@ where
                 match byte {
                     0x80...0x97 => self.parse_enum(byte as usize - 0x80, visitor),
                     0x98 => {
-                        let len = self.parse_u8()?;
+                        let len = self.parse_arg_u8()?;
                         self.parse_enum(len as usize, visitor)
                     }
                     0x99 => {
-                        let len = self.parse_u16()?;
+                        let len = self.parse_arg_u16()?;
                         self.parse_enum(len as usize, visitor)
                     }
                     0x9a => {
-                        let len = self.parse_u32()?;
+                        let len = self.parse_arg_u32()?;
                         self.parse_enum(len as usize, visitor)
                     }
                     0x9b => {
-                        let len = self.parse_u64()?;
+                        let len = self.parse_arg_u64()?;
                         if len > usize::max_value() as u64 {
                             return Err(self.error(ErrorCode::LengthOutOfRange));
                         }
@@ -698,18 +1919,134 @@ where
         }
     }
 
+    // Definite-length byte strings are copied once, directly into the buffer handed to the
+    // visitor.
+    #[cfg(feature = "std")]
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.peek()? {
+            Some(byte @ 0x40...0x5b) => {
+                self.consume();
+                let len = self.parse_len(byte)?;
+                self.parse_byte_buf(len, visitor)
+            }
+            _ => self.parse_value(visitor),
+        }
+    }
+
+    // Without `std` the visitor is offered the string borrowed and has to copy it itself.
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.parse_value(visitor)
+    }
+
+    // Definite-length text strings are copied once and validated in place, without going
+    // through the scratch buffer.
+    #[cfg(feature = "std")]
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.peek()? {
+            Some(byte @ 0x60...0x7b) => {
+                self.consume();
+                let len = self.parse_len(byte)?;
+                self.parse_string(len, visitor)
+            }
+            _ => self.parse_value(visitor),
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.parse_value(visitor)
+    }
+
+    // Bignums are accepted for 128-bit integers, as long as their value fits.
+    #[inline]
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.parse_integer128(visitor)
+    }
+
+    #[inline]
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.parse_integer128(visitor)
+    }
+
+    #[inline]
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.skip_value()?;
+        visitor.visit_unit()
+    }
+
     #[inline]
     fn is_human_readable(&self) -> bool {
         false
     }
 
     forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit
-        unit_struct seq tuple tuple_struct map struct identifier ignored_any
-        bytes byte_buf
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str unit
+        unit_struct seq tuple tuple_struct map struct identifier bytes
     }
 }
 
+// Hands a simple value to the visitor of `Value` as a unit.
+fn visit_simple<'de, V>(value: u8, visitor: V) -> Result<V::Value>
+where
+    V: de::Visitor<'de>,
+{
+    tags::set_simple(Some(value));
+    let result = visitor.visit_unit();
+    tags::set_simple(None);
+    result
+}
+
+// Records where in the document an error occurred. Without `std` errors have no path.
+#[cfg(feature = "std")]
+fn at_index(error: Error, index: usize) -> Error {
+    error.at(PathSegment::Index(index))
+}
+
+#[cfg(not(feature = "std"))]
+fn at_index(error: Error, _: usize) -> Error {
+    error
+}
+
+#[cfg(feature = "std")]
+fn at_key(error: Error, keys: &MapKeys) -> Error {
+    if keys.kept {
+        error.at(PathSegment::Key(diag::to_string(keys.current())))
+    } else {
+        error.at(PathSegment::Entry(keys.count - 1))
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn at_key(error: Error, _: &MapKeys) -> Error {
+    error
+}
+
 trait MakeError {
     fn error(&self, code: ErrorCode) -> Error;
 }
@@ -717,6 +2054,7 @@ trait MakeError {
 struct SeqAccess<'a, R: 'a> {
     de: &'a mut Deserializer<R>,
     len: &'a mut usize,
+    index: usize,
 }
 
 impl<'de, 'a, R> de::SeqAccess<'de> for SeqAccess<'a, R>
@@ -734,12 +2072,16 @@ where
         }
         *self.len -= 1;
 
-        let value = seed.deserialize(&mut *self.de)?;
+        let index = self.index;
+        self.index += 1;
+        let value = seed
+            .deserialize(&mut *self.de)
+            .map_err(|e| at_index(e, index))?;
         Ok(Some(value))
     }
 
     fn size_hint(&self) -> Option<usize> {
-        Some(*self.len)
+        Some(cmp::min(*self.len, self.de.prealloc_limit))
     }
 }
 
@@ -754,6 +2096,7 @@ where
 
 struct IndefiniteSeqAccess<'a, R: 'a> {
     de: &'a mut Deserializer<R>,
+    index: usize,
 }
 
 impl<'de, 'a, R> de::SeqAccess<'de> for IndefiniteSeqAccess<'a, R>
@@ -772,7 +2115,11 @@ where
             None => return Err(self.de.error(ErrorCode::EofWhileParsingArray)),
         }
 
-        let value = seed.deserialize(&mut *self.de)?;
+        let index = self.index;
+        self.index += 1;
+        let value = seed
+            .deserialize(&mut *self.de)
+            .map_err(|e| at_index(e, index))?;
         Ok(Some(value))
     }
 }
@@ -786,12 +2133,63 @@ where
     }
 }
 
-struct MapAccess<'a, R: 'a> {
+// The encoding of the current and the preceding key of a map. Keys are borrowed from the input
+// where possible and copied otherwise.
+struct MapKeys<'de> {
+    count: usize,
+    current: Option<&'de [u8]>,
+    current_buf: ScratchBuf,
+    last: Option<&'de [u8]>,
+    last_buf: ScratchBuf,
+    // Whether the encoding of the current key was kept.
+    kept: bool,
+    // The encodings of all keys so far, if duplicates are rejected.
+    #[cfg(feature = "std")]
+    seen: BTreeSet<Vec<u8>>,
+}
+
+impl<'de> MapKeys<'de> {
+    fn new() -> MapKeys<'de> {
+        MapKeys {
+            count: 0,
+            current: None,
+            current_buf: ScratchBuf::new(),
+            last: None,
+            last_buf: ScratchBuf::new(),
+            kept: false,
+            #[cfg(feature = "std")]
+            seen: BTreeSet::new(),
+        }
+    }
+
+    fn rotate(&mut self) {
+        self.count += 1;
+        self.last = self.current.take();
+        mem::swap(&mut self.last_buf, &mut self.current_buf);
+    }
+
+    #[cfg(feature = "std")]
+    fn current(&self) -> &[u8] {
+        self.current.unwrap_or(&self.current_buf[..])
+    }
+
+    #[cfg(feature = "std")]
+    fn last(&self) -> Option<&[u8]> {
+        if self.count > 1 {
+            Some(self.last.unwrap_or(&self.last_buf[..]))
+        } else {
+            None
+        }
+    }
+}
+
+struct MapAccess<'de, 'a, R: 'a> {
     de: &'a mut Deserializer<R>,
     len: &'a mut usize,
+    keys: MapKeys<'de>,
 }
 
-impl<'de, 'a, R> de::MapAccess<'de> for MapAccess<'a, R>
+impl<'de, 'a, R> de::MapAccess<'de> for MapAccess<'de, 'a, R>
 where
     R: Read<'de>,
 {
@@ -806,7 +2204,7 @@ where
         }
         *self.len -= 1;
 
-        let value = seed.deserialize(&mut *self.de)?;
+        let value = self.de.parse_key(seed, &mut self.keys)?;
         Ok(Some(value))
     }
 
@@ -814,19 +2212,22 @@ where
     where
         V: de::DeserializeSeed<'de>,
     {
+        let keys = &self.keys;
         seed.deserialize(&mut *self.de)
+            .map_err(|e| at_key(e, keys))
     }
 
     fn size_hint(&self) -> Option<usize> {
-        Some(*self.len)
+        Some(cmp::min(*self.len, self.de.prealloc_limit))
     }
 }
 
-struct IndefiniteMapAccess<'a, R: 'a> {
+struct IndefiniteMapAccess<'de, 'a, R: 'a> {
     de: &'a mut Deserializer<R>,
+    keys: MapKeys<'de>,
 }
 
-impl<'de, 'a, R> de::MapAccess<'de> for IndefiniteMapAccess<'a, R>
+impl<'de, 'a, R> de::MapAccess<'de> for IndefiniteMapAccess<'de, 'a, R>
 where
     R: Read<'de>,
 {
@@ -842,7 +2243,7 @@ where
             None => return Err(self.de.error(ErrorCode::EofWhileParsingMap)),
         }
 
-        let value = seed.deserialize(&mut *self.de)?;
+        let value = self.de.parse_key(seed, &mut self.keys)?;
         Ok(Some(value))
     }
 
@@ -850,7 +2251,9 @@ where
     where
         V: de::DeserializeSeed<'de>,
     {
+        let keys = &self.keys;
         seed.deserialize(&mut *self.de)
+            .map_err(|e| at_key(e, keys))
     }
 }
 
@@ -1005,10 +2408,27 @@ where
 
 /// Iterator that deserializes a stream into multiple CBOR values.
 ///
+/// This reads a CBOR sequence as defined in RFC 8742, which is data items written one after
+/// another without anything in between, such as a log file of records. The iterator ends at the
+/// end of the input, or after the first error, since the input cannot be trusted to continue
+/// with a data item after one that failed.
+///
 /// A stream deserializer can be created from any CBOR deserializer using the
 /// `Deserializer::into_iter` method.
+///
+/// ```
+/// # use serde_cbor::Deserializer;
+/// let records = b"\x01\x02\x03";
+/// let sum: u32 = Deserializer::from_slice(records)
+///     .into_iter::<u32>()
+///     .map(|record| record.unwrap())
+///     .sum();
+/// assert_eq!(sum, 6);
+/// ```
 pub struct StreamDeserializer<'de, R, T> {
     de: Deserializer<R>,
+    offset: u64,
+    failed: bool,
     output: PhantomData<T>,
     lifetime: PhantomData<&'de ()>,
 }
@@ -1023,15 +2443,38 @@ where
     ///
     /// Typically it is more convenient to use one of these methods instead:
     ///
-    /// * `Deserializer::from_bytes(...).into_iter()`
+    /// * `Deserializer::from_slice(...).into_iter()`
     /// * `Deserializer::from_reader(...).into_iter()`
     pub fn new(read: R) -> StreamDeserializer<'de, R, T> {
         StreamDeserializer {
             de: Deserializer::new(read),
+            offset: 0,
+            failed: false,
             output: PhantomData,
             lifetime: PhantomData,
         }
     }
+
+    /// Returns the number of bytes consumed by the values successfully deserialized so far.
+    ///
+    /// After an error this is the offset at which the damaged item started, so a consumer of a
+    /// sequence of items can skip ahead and resume parsing from a known boundary.
+    ///
+    /// ```
+    /// # use serde_cbor::{Deserializer, Value};
+    /// let slice = b"\x01\x1c\x02";
+    /// let mut it = Deserializer::from_slice(slice).into_iter::<Value>();
+    /// assert_eq!(it.next().unwrap().unwrap(), Value::Integer(1));
+    /// assert!(it.next().unwrap().is_err());
+    /// assert_eq!(it.byte_offset(), 1);
+    ///
+    /// let rest = &slice[it.byte_offset() as usize + 1..];
+    /// let mut it = Deserializer::from_slice(rest).into_iter::<Value>();
+    /// assert_eq!(it.next().unwrap().unwrap(), Value::Integer(2));
+    /// ```
+    pub fn byte_offset(&self) -> u64 {
+        self.offset
+    }
 }
 
 impl<'de, R, T> Iterator for StreamDeserializer<'de, R, T>
@@ -1042,10 +2485,107 @@ where
     type Item = Result<T>;
 
     fn next(&mut self) -> Option<Result<T>> {
-        match self.de.peek() {
-            Ok(Some(_)) => Some(T::deserialize(&mut self.de)),
-            Ok(None) => None,
-            Err(e) => Some(Err(e)),
+        if self.failed {
+            return None;
+        }
+        let value = match self.de.peek() {
+            Ok(Some(_)) => T::deserialize(&mut self.de),
+            Ok(None) => return None,
+            Err(e) => Err(e),
+        };
+        match value {
+            Ok(_) => self.offset = self.de.read.offset(),
+            Err(_) => self.failed = true,
+        }
+        Some(value)
+    }
+}
+
+impl<'de, R, T> FusedIterator for StreamDeserializer<'de, R, T>
+where
+    R: Read<'de>,
+    T: de::Deserialize<'de>,
+{
+}
+
+/// Iterator that deserializes the elements of a top-level CBOR array one at a time.
+///
+/// It is created with the `Deserializer::into_array_iter` method, and ends after the first
+/// error.
+pub struct ArrayDeserializer<'de, R, T> {
+    de: Deserializer<R>,
+    // The number of elements left, or `None` for an array of indefinite length.
+    len: Option<usize>,
+    index: usize,
+    done: bool,
+    output: PhantomData<T>,
+    lifetime: PhantomData<&'de ()>,
+}
+
+impl<'de, R, T> ArrayDeserializer<'de, R, T>
+where
+    R: Read<'de>,
+    T: de::Deserialize<'de>,
+{
+    // Returns whether the array has no more elements, reading its end if it has one.
+    fn at_end(&mut self) -> Result<bool> {
+        match self.len {
+            Some(0) => Ok(true),
+            Some(ref mut len) => {
+                *len -= 1;
+                Ok(false)
+            }
+            None => match self.de.peek()? {
+                Some(0xff) => {
+                    self.de.consume();
+                    Ok(true)
+                }
+                Some(_) => Ok(false),
+                None => Err(self.de.error(ErrorCode::EofWhileParsingArray)),
+            },
+        }
+    }
+}
+
+impl<'de, R, T> Iterator for ArrayDeserializer<'de, R, T>
+where
+    R: Read<'de>,
+    T: de::Deserialize<'de>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.done {
+            return None;
+        }
+        let value = match self.at_end() {
+            Ok(true) => {
+                self.done = true;
+                return self.de.end().err().map(Err);
+            }
+            Ok(false) => {
+                let index = self.index;
+                self.index += 1;
+                T::deserialize(&mut self.de).map_err(|e| at_index(e, index))
+            }
+            Err(e) => Err(e),
+        };
+        self.done = value.is_err();
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.len {
+            _ if self.done => (0, Some(0)),
+            Some(len) => (0, len.checked_add(1)),
+            None => (0, None),
         }
     }
 }
+
+impl<'de, R, T> FusedIterator for ArrayDeserializer<'de, R, T>
+where
+    R: Read<'de>,
+    T: de::Deserialize<'de>,
+{
+}
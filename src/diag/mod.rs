@@ -0,0 +1,453 @@
+//! Rendering of CBOR in diagnostic notation.
+//!
+//! Diagnostic notation, defined in section 8 of RFC 8949 and extended in appendix G of
+//! RFC 8610, is a human-readable text form of CBOR similar to JSON. It shows byte strings as
+//! `h'cafe'`, tags as `1(1363896240)` and CBOR embedded in byte strings of tag 24 as `<<1, 2>>`,
+//! which makes it the notation of choice for looking at payloads and writing them down in
+//! specifications and tests.
+//!
+//! ```
+//! use serde_cbor::diag;
+//!
+//! let value = (1, "two", vec![3.5]);
+//! assert_eq!(diag::to_diag_string(&value).unwrap(), r#"[1, "two", [3.5]]"#);
+//! ```
+//!
+//! Test vectors written in diagnostic notation can be read back into a `Value` with `parse` or
+//! into other types with `from_diag_str`, or turned into CBOR with `diag_to_bytes`.
+//!
+//! Encoded CBOR can also be rendered through `core::fmt` with `display`, which does not allocate
+//! and is all of this module that is available without `std`. This lets devices without a heap
+//! log the payloads they send and receive:
+//!
+//! ```
+//! use serde_cbor::diag;
+//!
+//! let bytes = [0x82, 0x01, 0x41, 0xff];
+//! assert_eq!(format!("{}", diag::display(&bytes)), "[1, h'ff']");
+//! assert_eq!(format!("{:#}", diag::display(&bytes)), "[\n  1,\n  h'ff'\n]");
+//! ```
+use byteorder::{BigEndian, ByteOrder};
+use core::fmt::{self, Write};
+use core::str;
+#[cfg(feature = "float")]
+use half::f16;
+#[cfg(feature = "std")]
+use serde::ser::Serialize;
+#[cfg(feature = "std")]
+use std::io;
+
+use de::MAX_DEPTH;
+#[cfg(feature = "std")]
+use error::{self, Error};
+#[cfg(feature = "std")]
+use ser::to_vec;
+
+#[cfg(feature = "std")]
+mod parse;
+
+#[cfg(feature = "std")]
+pub use self::parse::{diag_to_bytes, from_diag_str, parse};
+
+/// Renders the first data item in `bytes` in diagnostic notation when formatted.
+///
+/// The alternate flag, as in `{:#}`, puts every element of an array and entry of a map on a line
+/// of its own. Malformed input is rendered up to the point where it stops making sense.
+pub fn display<'a>(bytes: &'a [u8]) -> Diag<'a> {
+    Diag { bytes }
+}
+
+/// Encoded CBOR that is rendered in diagnostic notation when formatted.
+///
+/// This is returned by `display`.
+#[derive(Clone, Copy, Debug)]
+pub struct Diag<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> fmt::Display for Diag<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = Tracking {
+            out: f,
+            failed: false,
+        };
+        let mut renderer = Renderer::new(self.bytes);
+        renderer.pretty = out.out.alternate();
+        match renderer.item(&mut out) {
+            // Errors caused by the input only cut the output short.
+            Err(_) if out.failed => Err(fmt::Error),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Tells errors of the writer apart from those of the renderer, which both are `fmt::Error`.
+struct Tracking<'a, W: 'a> {
+    out: &'a mut W,
+    failed: bool,
+}
+
+impl<'a, W> Write for Tracking<'a, W>
+where
+    W: Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let result = self.out.write_str(s);
+        self.failed |= result.is_err();
+        result
+    }
+}
+
+// Accepts and forgets everything written to it.
+struct Discard;
+
+impl Write for Discard {
+    fn write_str(&mut self, _: &str) -> fmt::Result {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+/// Serializes a value and renders it in diagnostic notation.
+pub fn to_diag_string<T>(value: &T) -> error::Result<String>
+where
+    T: Serialize,
+{
+    Ok(render(&to_vec(value)?, false))
+}
+
+#[cfg(feature = "std")]
+/// Serializes a value and renders it in diagnostic notation, with every element of an array and
+/// entry of a map on a line of its own.
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_cbor::diag;
+///
+/// let mut value = BTreeMap::new();
+/// value.insert("a", vec![1, 2]);
+/// assert_eq!(diag::to_diag_string_pretty(&value).unwrap(), "{\n  \"a\": [\n    1,\n    2\n  ]\n}");
+/// ```
+pub fn to_diag_string_pretty<T>(value: &T) -> error::Result<String>
+where
+    T: Serialize,
+{
+    Ok(render(&to_vec(value)?, true))
+}
+
+#[cfg(feature = "std")]
+/// Serializes a value and writes it in diagnostic notation to a writer.
+pub fn to_diag_writer<W, T>(mut writer: W, value: &T) -> error::Result<()>
+where
+    W: io::Write,
+    T: Serialize,
+{
+    writer
+        .write_all(to_diag_string(value)?.as_bytes())
+        .map_err(Error::io)
+}
+
+#[cfg(feature = "std")]
+/// Renders the first data item in `bytes` in diagnostic notation.
+///
+/// Malformed input is rendered up to the point where it stops making sense.
+pub(crate) fn to_string(bytes: &[u8]) -> String {
+    render(bytes, false)
+}
+
+#[cfg(feature = "std")]
+fn render(bytes: &[u8], pretty: bool) -> String {
+    let mut out = String::new();
+    let mut renderer = Renderer::new(bytes);
+    renderer.pretty = pretty;
+    let _ = renderer.item(&mut out);
+    out
+}
+
+struct Renderer<'a> {
+    input: &'a [u8],
+    pos: usize,
+    pretty: bool,
+    depth: usize,
+    // The number of data items the next one is nested in, counting tags and embedded CBOR.
+    nesting: usize,
+    // Set for the dry run of `embedded`, in which byte strings of tag 24 need not be looked into.
+    checking: bool,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(input: &'a [u8]) -> Renderer<'a> {
+        Renderer {
+            input,
+            pos: 0,
+            pretty: false,
+            depth: 0,
+            nesting: 0,
+            checking: false,
+        }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], fmt::Error> {
+        match self.pos.checked_add(n) {
+            Some(end) if end <= self.input.len() => {
+                let buf = &self.input[self.pos..end];
+                self.pos = end;
+                Ok(buf)
+            }
+            _ => Err(fmt::Error),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, fmt::Error> {
+        self.take(1).map(|buf| buf[0])
+    }
+
+    fn peek(&self) -> Result<u8, fmt::Error> {
+        self.input.get(self.pos).cloned().ok_or(fmt::Error)
+    }
+
+    // Reads the argument of a head. Returns `None` for indefinite lengths.
+    fn argument(&mut self, info: u8) -> Result<Option<u64>, fmt::Error> {
+        Ok(Some(match info {
+            0...23 => u64::from(info),
+            24 => u64::from(self.byte()?),
+            25 => u64::from(BigEndian::read_u16(self.take(2)?)),
+            26 => u64::from(BigEndian::read_u32(self.take(4)?)),
+            27 => BigEndian::read_u64(self.take(8)?),
+            31 => return Ok(None),
+            _ => return Err(fmt::Error),
+        }))
+    }
+
+    fn length(&mut self, info: u8) -> Result<Option<usize>, fmt::Error> {
+        match self.argument(info)? {
+            Some(len) if len > usize::max_value() as u64 => Err(fmt::Error),
+            len => Ok(len.map(|len| len as usize)),
+        }
+    }
+
+    // Input nested deeper than the deserializer accepts is cut short, as the stack is not
+    // endless.
+    fn item<W: Write>(&mut self, out: &mut W) -> fmt::Result {
+        if self.nesting == MAX_DEPTH {
+            return Err(fmt::Error);
+        }
+        self.nesting += 1;
+        let result = self.nested_item(out);
+        self.nesting -= 1;
+        result
+    }
+
+    fn nested_item<W: Write>(&mut self, out: &mut W) -> fmt::Result {
+        let byte = self.byte()?;
+        let (major, info) = (byte >> 5, byte & 0x1f);
+        match major {
+            0 => match self.argument(info)? {
+                Some(value) => write!(out, "{}", value),
+                None => Err(fmt::Error),
+            },
+            1 => match self.argument(info)? {
+                Some(value) if value == u64::max_value() => out.write_str("-18446744073709551616"),
+                Some(value) => write!(out, "-{}", value + 1),
+                None => Err(fmt::Error),
+            },
+            2 | 3 => match self.length(info)? {
+                Some(len) => self.string(major, len, out),
+                None => {
+                    out.write_str("(_ ")?;
+                    let mut first = true;
+                    while self.peek()? != 0xff {
+                        if !first {
+                            out.write_str(", ")?;
+                        }
+                        first = false;
+                        let byte = self.byte()?;
+                        if byte >> 5 != major {
+                            return Err(fmt::Error);
+                        }
+                        match self.length(byte & 0x1f)? {
+                            Some(len) => self.string(major, len, out)?,
+                            None => return Err(fmt::Error),
+                        }
+                    }
+                    self.pos += 1;
+                    out.write_str(")")
+                }
+            },
+            4 => {
+                let len = self.length(info)?;
+                out.write_str("[")?;
+                self.items(len, false, out)?;
+                out.write_str("]")
+            }
+            5 => {
+                let len = self.length(info)?;
+                out.write_str("{")?;
+                self.items(len, true, out)?;
+                out.write_str("}")
+            }
+            6 => match self.argument(info)? {
+                Some(tag) => {
+                    write!(out, "{}(", tag)?;
+                    if tag != 24 || !self.embedded(out)? {
+                        self.item(out)?;
+                    }
+                    out.write_str(")")
+                }
+                None => Err(fmt::Error),
+            },
+            _ => self.simple(info, out),
+        }
+    }
+
+    fn items<W: Write>(&mut self, len: Option<usize>, pairs: bool, out: &mut W) -> fmt::Result {
+        if len.is_none() {
+            out.write_str(if self.pretty { "_" } else { "_ " })?;
+        }
+        self.depth += 1;
+        let mut index = 0;
+        loop {
+            match len {
+                Some(len) if index == len => break,
+                None if self.peek()? == 0xff => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => {}
+            }
+            if index != 0 {
+                out.write_str(",")?;
+            }
+            if self.pretty {
+                self.newline(out)?;
+            } else if index != 0 {
+                out.write_str(" ")?;
+            }
+            self.item(out)?;
+            if pairs {
+                out.write_str(": ")?;
+                self.item(out)?;
+            }
+            index += 1;
+        }
+        self.depth -= 1;
+        if self.pretty && index != 0 {
+            self.newline(out)?;
+        }
+        Ok(())
+    }
+
+    fn newline<W: Write>(&self, out: &mut W) -> fmt::Result {
+        out.write_char('\n')?;
+        for _ in 0..self.depth {
+            out.write_str("  ")?;
+        }
+        Ok(())
+    }
+
+    // Renders a byte string holding a sequence of well-formed CBOR data items as `<<...>>`.
+    // Returns false without consuming anything if the next item is anything else, which takes a
+    // dry run to find out as nothing can be taken back once written. Any byte string renders,
+    // either way, so the dry run does not look into the ones it contains, which would make the
+    // work grow exponentially with their nesting.
+    fn embedded<W: Write>(&mut self, out: &mut W) -> Result<bool, fmt::Error> {
+        if self.checking {
+            return Ok(false);
+        }
+        let start = self.pos;
+        let byte = self.byte()?;
+        let bytes = match self.length(byte & 0x1f) {
+            Ok(Some(len)) if byte >> 5 == 2 => self.take(len),
+            _ => Err(fmt::Error),
+        };
+        let bytes = match bytes {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            _ => {
+                self.pos = start;
+                return Ok(false);
+            }
+        };
+        let mut check = Renderer::new(bytes);
+        check.nesting = self.nesting;
+        check.checking = true;
+        if check.sequence(bytes, &mut Discard).is_err() {
+            self.pos = start;
+            return Ok(false);
+        }
+        out.write_str("<<")?;
+        self.sequence(bytes, out)?;
+        out.write_str(">>")?;
+        Ok(true)
+    }
+
+    // Renders the data items in `bytes` separated by commas.
+    fn sequence<W: Write>(&self, bytes: &[u8], out: &mut W) -> fmt::Result {
+        let mut inner = Renderer::new(bytes);
+        inner.pretty = self.pretty;
+        inner.depth = self.depth;
+        inner.nesting = self.nesting;
+        inner.checking = self.checking;
+        while inner.pos < bytes.len() {
+            if inner.pos != 0 {
+                out.write_str(", ")?;
+            }
+            inner.item(out)?;
+        }
+        Ok(())
+    }
+
+    fn string<W: Write>(&mut self, major: u8, len: usize, out: &mut W) -> fmt::Result {
+        let buf = self.take(len)?;
+        if major == 2 {
+            out.write_str("h'")?;
+            for byte in buf {
+                write!(out, "{:02x}", byte)?;
+            }
+            out.write_str("'")
+        } else {
+            let s = str::from_utf8(buf).map_err(|_| fmt::Error)?;
+            out.write_char('"')?;
+            for c in s.chars() {
+                match c {
+                    '"' => out.write_str("\\\"")?,
+                    '\\' => out.write_str("\\\\")?,
+                    c if c < ' ' => write!(out, "\\u{:04x}", c as u32)?,
+                    c => out.write_char(c)?,
+                }
+            }
+            out.write_char('"')
+        }
+    }
+
+    fn simple<W: Write>(&mut self, info: u8, out: &mut W) -> fmt::Result {
+        match info {
+            20 => out.write_str("false"),
+            21 => out.write_str("true"),
+            22 => out.write_str("null"),
+            23 => out.write_str("undefined"),
+            24 => write!(out, "simple({})", self.byte()?),
+            #[cfg(feature = "float")]
+            25 => {
+                let bits = BigEndian::read_u16(self.take(2)?);
+                float(f64::from(f32::from(f16::from_bits(bits))), out)
+            }
+            #[cfg(feature = "float")]
+            26 => float(f64::from(BigEndian::read_f32(self.take(4)?)), out),
+            #[cfg(feature = "float")]
+            27 => float(BigEndian::read_f64(self.take(8)?), out),
+            0...19 => write!(out, "simple({})", info),
+            _ => Err(fmt::Error),
+        }
+    }
+}
+
+#[cfg(feature = "float")]
+fn float<W: Write>(value: f64, out: &mut W) -> fmt::Result {
+    if value.is_nan() {
+        out.write_str("NaN")
+    } else if value.is_infinite() {
+        out.write_str(if value > 0.0 { "Infinity" } else { "-Infinity" })
+    } else {
+        write!(out, "{:?}", value)
+    }
+}
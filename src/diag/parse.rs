@@ -0,0 +1,611 @@
+//! Parsing of diagnostic notation into CBOR.
+use serde::de::DeserializeOwned;
+use std::char;
+use std::f64;
+use std::str;
+
+use de::from_slice;
+use error::{self, Error, ErrorCode};
+use ser::{encode_head, to_vec};
+use value::Value;
+
+// Limits the nesting of arrays, maps, tags and embedded items so deep input can't overflow the
+// stack.
+const MAX_DEPTH: usize = 128;
+
+/// Parses a data item written in diagnostic notation and returns its CBOR encoding.
+///
+/// Besides the notation of RFC 8949 this accepts the extensions of appendix G of RFC 8610:
+/// `b64'...'`, `b32'...'`, `h32'...'` and single-quoted byte strings, strings written next to
+/// each other, which are concatenated, embedded CBOR as `<<...>>`, chunked strings as `(_ ...)`
+/// and comments between `/` characters or from `#` to the end of the line. Integers may be
+/// written in hexadecimal, octal or binary with a `0x`, `0o` or `0b` prefix. Numbers are
+/// encoded in their preferred serialization; integers that don't fit in 64 bits become bignums.
+///
+/// The offset of an error is the byte offset into `diag` at which parsing failed.
+///
+/// ```
+/// use serde_cbor::diag;
+///
+/// let bytes = diag::diag_to_bytes("[1, h'cafe', 24(<<\"a\">>)]").unwrap();
+/// assert_eq!(bytes, b"\x83\x01\x42\xca\xfe\xd8\x18\x42\x61a");
+/// ```
+pub fn diag_to_bytes(diag: &str) -> error::Result<Vec<u8>> {
+    let mut parser = Parser::new(diag);
+    let mut out = Vec::new();
+    parser.item(&mut out)?;
+    parser.space()?;
+    if parser.pos != parser.input.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(out)
+}
+
+/// Parses a data item written in diagnostic notation and deserializes it.
+///
+/// Errors found while deserializing report offsets into the encoded CBOR rather than into
+/// `diag`.
+///
+/// ```
+/// use serde_cbor::diag;
+///
+/// let value: (u8, String, Vec<f32>) = diag::from_diag_str(r#"[1, "two", [3.5]]"#).unwrap();
+/// assert_eq!(value, (1, "two".to_string(), vec![3.5]));
+/// ```
+pub fn from_diag_str<T>(diag: &str) -> error::Result<T>
+where
+    T: DeserializeOwned,
+{
+    from_slice(&diag_to_bytes(diag)?)
+}
+
+/// Parses a data item written in diagnostic notation into a `Value`.
+///
+/// Every data item can be parsed, so this fails only on invalid notation, at the byte offset
+/// into `diag` where parsing failed.
+///
+/// ```
+/// use serde_cbor::{diag, Value};
+///
+/// let value = diag::parse("[1, h'cafe' / two bytes /, 24(<<\"a\">>)]").unwrap();
+/// let embedded = Value::Tag(24, Box::new(Value::Bytes(b"\x61a".to_vec())));
+/// let expected = vec![Value::Integer(1), Value::Bytes(vec![0xca, 0xfe]), embedded];
+/// assert_eq!(value, Value::Array(expected));
+/// ```
+pub fn parse(diag: &str) -> error::Result<Value> {
+    from_diag_str(diag)
+}
+
+fn head(major: u8, value: u64, out: &mut Vec<u8>) {
+    let mut buf = [0; 9];
+    let len = encode_head(major, value, &mut buf);
+    out.extend_from_slice(&buf[..len]);
+}
+
+fn float(value: f64, out: &mut Vec<u8>) -> error::Result<()> {
+    out.extend_from_slice(&to_vec(&value)?);
+    Ok(())
+}
+
+// A character of base64 in either the standard or the URL-safe alphabet.
+fn base64_digit(c: u8) -> Option<u8> {
+    match c {
+        b'A'...b'Z' => Some(c - b'A'),
+        b'a'...b'z' => Some(c - b'a' + 26),
+        b'0'...b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+fn base32_digit(c: u8) -> Option<u8> {
+    match c {
+        b'A'...b'Z' => Some(c - b'A'),
+        b'2'...b'7' => Some(c - b'2' + 26),
+        _ => None,
+    }
+}
+
+fn base32hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'...b'9' => Some(c - b'0'),
+        b'A'...b'V' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser {
+            input: input.as_bytes(),
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::syntax(
+            ErrorCode::InvalidDiagnostic(msg.to_string()),
+            self.pos as u64,
+        )
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.input[self.pos..].starts_with(token.as_bytes()) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> error::Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", token)))
+        }
+    }
+
+    // Skips whitespace and comments.
+    fn space(&mut self) -> error::Result<()> {
+        loop {
+            match self.peek() {
+                Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') => self.pos += 1,
+                Some(b'#') => match self.input[self.pos..].iter().position(|&c| c == b'\n') {
+                    Some(i) => self.pos += i + 1,
+                    None => self.pos = self.input.len(),
+                },
+                Some(b'/') => match self.input[self.pos + 1..].iter().position(|&c| c == b'/') {
+                    Some(i) => self.pos += i + 2,
+                    None => return Err(self.error("unterminated comment")),
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn item(&mut self, out: &mut Vec<u8>) -> error::Result<()> {
+        self.space()?;
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.depth += 1;
+        let result = self.value(out);
+        self.depth -= 1;
+        result
+    }
+
+    fn value(&mut self, out: &mut Vec<u8>) -> error::Result<()> {
+        if let Some((major, bytes)) = self.string()? {
+            head(major, bytes.len() as u64, out);
+            out.extend_from_slice(&bytes);
+            return Ok(());
+        }
+        match self.peek() {
+            Some(b'[') => {
+                self.pos += 1;
+                self.collection(4, "]", out)
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                self.collection(5, "}", out)
+            }
+            Some(b'(') => {
+                self.pos += 1;
+                self.chunks(out)
+            }
+            Some(b'-') | Some(b'0'...b'9') => self.number(out),
+            Some(b'a'...b'z') | Some(b'A'...b'Z') => self.keyword(out),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    // Parses a text or byte string if one starts here, returning its major type and contents.
+    // Strings that follow each other are concatenated into one of the type of the first.
+    fn string(&mut self) -> error::Result<Option<(u8, Vec<u8>)>> {
+        let start = self.pos;
+        let (major, mut bytes) = match self.string_part()? {
+            Some(string) => string,
+            None => return Ok(None),
+        };
+        loop {
+            let end = self.pos;
+            self.space()?;
+            match self.string_part()? {
+                Some((_, more)) => bytes.extend_from_slice(&more),
+                None => {
+                    self.pos = end;
+                    break;
+                }
+            }
+        }
+        if major == 3 && str::from_utf8(&bytes).is_err() {
+            self.pos = start;
+            return Err(self.error("concatenated text string is not valid UTF-8"));
+        }
+        Ok(Some((major, bytes)))
+    }
+
+    fn string_part(&mut self) -> error::Result<Option<(u8, Vec<u8>)>> {
+        let string = if self.eat("\"") {
+            (3, self.quoted(b'"')?)
+        } else if self.eat("'") {
+            (2, self.quoted(b'\'')?)
+        } else if self.eat("h'") {
+            (2, self.hex()?)
+        } else if self.eat("b64'") {
+            (2, self.radix(6, base64_digit)?)
+        } else if self.eat("b32'") {
+            (2, self.radix(5, base32_digit)?)
+        } else if self.eat("h32'") {
+            (2, self.radix(5, base32hex_digit)?)
+        } else if self.eat("<<") {
+            (2, self.embedded()?)
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(string))
+    }
+
+    fn collection(&mut self, major: u8, close: &str, out: &mut Vec<u8>) -> error::Result<()> {
+        self.space()?;
+        let indefinite = self.eat("_");
+        let mut body = Vec::new();
+        let mut len = 0;
+        self.space()?;
+        if !self.eat(close) {
+            loop {
+                self.item(&mut body)?;
+                if major == 5 {
+                    self.space()?;
+                    self.expect(":")?;
+                    self.item(&mut body)?;
+                }
+                len += 1;
+                self.space()?;
+                if self.eat(close) {
+                    break;
+                }
+                if !self.eat(",") {
+                    return Err(self.error(&format!("expected `,` or `{}`", close)));
+                }
+            }
+        }
+        if indefinite {
+            out.push(major << 5 | 31);
+            out.extend_from_slice(&body);
+            out.push(0xff);
+        } else {
+            head(major, len, out);
+            out.extend_from_slice(&body);
+        }
+        Ok(())
+    }
+
+    // Parses the chunks of an indefinite length string after the opening parenthesis.
+    fn chunks(&mut self, out: &mut Vec<u8>) -> error::Result<()> {
+        self.space()?;
+        self.expect("_")?;
+        let mut major = None;
+        let mut body = Vec::new();
+        self.space()?;
+        if !self.eat(")") {
+            loop {
+                self.space()?;
+                let start = self.pos;
+                let (chunk_major, bytes) = match self.string()? {
+                    Some(string) => string,
+                    None => return Err(self.error("expected a string")),
+                };
+                if major.get_or_insert(chunk_major) != &chunk_major {
+                    self.pos = start;
+                    return Err(self.error("text and byte string chunks can't be mixed"));
+                }
+                head(chunk_major, bytes.len() as u64, &mut body);
+                body.extend_from_slice(&bytes);
+                self.space()?;
+                if self.eat(")") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return Err(self.error("expected `,` or `)`"));
+                }
+            }
+        }
+        out.push(major.unwrap_or(2) << 5 | 31);
+        out.extend_from_slice(&body);
+        out.push(0xff);
+        Ok(())
+    }
+
+    // Parses the rest of a quoted string, resolving escapes.
+    fn quoted(&mut self, quote: u8) -> error::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += 1;
+            if c == quote {
+                return Ok(buf);
+            } else if c != b'\\' {
+                buf.push(c);
+                continue;
+            }
+            let escaped = match self.peek() {
+                Some(c @ b'"') | Some(c @ b'\'') | Some(c @ b'\\') | Some(c @ b'/') => c,
+                Some(b'b') => b'\x08',
+                Some(b'f') => b'\x0c',
+                Some(b'n') => b'\n',
+                Some(b'r') => b'\r',
+                Some(b't') => b'\t',
+                Some(b'u') => {
+                    self.pos += 1;
+                    let c = self.unicode_escape()?;
+                    let mut encoded = [0; 4];
+                    buf.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+                    continue;
+                }
+                _ => return Err(self.error("invalid escape")),
+            };
+            self.pos += 1;
+            buf.push(escaped);
+        }
+    }
+
+    // Parses the digits of a `\u` escape, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> error::Result<char> {
+        let start = self.pos - 2;
+        let mut code = self.hex4()?;
+        if (0xd800..0xdc00).contains(&code) && self.eat("\\u") {
+            let low = self.hex4()?;
+            if (0xdc00..0xe000).contains(&low) {
+                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+            }
+        }
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => {
+                self.pos = start;
+                Err(self.error("invalid unicode escape"))
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> error::Result<u32> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .and_then(|digits| str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok());
+        match digits {
+            Some(code) => {
+                self.pos += 4;
+                Ok(code)
+            }
+            None => Err(self.error("expected four hex digits")),
+        }
+    }
+
+    // Parses the rest of a `h'...'` byte string.
+    fn hex(&mut self) -> error::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        loop {
+            self.space()?;
+            if self.eat("'") {
+                return Ok(buf);
+            }
+            let high = self.hex_digit()?;
+            let low = self.hex_digit()?;
+            buf.push(high << 4 | low);
+        }
+    }
+
+    fn hex_digit(&mut self) -> error::Result<u8> {
+        let digit = match self.peek() {
+            Some(c @ b'0'...b'9') => c - b'0',
+            Some(c @ b'a'...b'f') => c - b'a' + 10,
+            Some(c @ b'A'...b'F') => c - b'A' + 10,
+            _ => return Err(self.error("expected a hex digit")),
+        };
+        self.pos += 1;
+        Ok(digit)
+    }
+
+    // Parses the rest of a `b64'...'`, `b32'...'` or `h32'...'` byte string, whose characters
+    // `digit` turns into `width` bits each, with or without padding.
+    fn radix(&mut self, width: u32, digit: fn(u8) -> Option<u8>) -> error::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let mut bits = 0u32;
+        let mut nbits = 0;
+        loop {
+            // Comments are not allowed, as `/` is a digit in base64.
+            while let Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') = self.peek() {
+                self.pos += 1;
+            }
+            let value = match self.peek() {
+                Some(b'\'') => break,
+                Some(b'=') => {
+                    self.pos += 1;
+                    continue;
+                }
+                Some(c) => digit(c),
+                None => None,
+            };
+            let value = match value {
+                Some(value) => value,
+                None => return Err(self.error("invalid character in byte string")),
+            };
+            self.pos += 1;
+            bits = bits << width | u32::from(value);
+            nbits += width;
+            if nbits >= 8 {
+                nbits -= 8;
+                buf.push((bits >> nbits) as u8);
+                bits &= (1 << nbits) - 1;
+            }
+        }
+        // Leftover bits that make up a whole character belong to a byte that is cut off.
+        if nbits >= width {
+            return Err(self.error("truncated byte string"));
+        }
+        self.pos += 1;
+        Ok(buf)
+    }
+
+    // Parses the rest of a `<<...>>` sequence of embedded data items.
+    fn embedded(&mut self) -> error::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.space()?;
+        if self.eat(">>") {
+            return Ok(buf);
+        }
+        loop {
+            self.item(&mut buf)?;
+            self.space()?;
+            if self.eat(">>") {
+                return Ok(buf);
+            }
+            if !self.eat(",") {
+                return Err(self.error("expected `,` or `>>`"));
+            }
+        }
+    }
+
+    fn number(&mut self, out: &mut Vec<u8>) -> error::Result<()> {
+        let start = self.pos;
+        let negative = self.eat("-");
+        if self.eat("Infinity") {
+            return float(if negative { f64::NEG_INFINITY } else { f64::INFINITY }, out);
+        }
+        let radix = if self.eat("0x") {
+            16
+        } else if self.eat("0o") {
+            8
+        } else if self.eat("0b") {
+            2
+        } else {
+            10
+        };
+        let digits = self.digits(radix);
+        if digits == self.pos {
+            return Err(self.error("expected digits"));
+        }
+        let fraction = match self.peek() {
+            Some(b'.') | Some(b'e') | Some(b'E') => radix == 10,
+            _ => false,
+        };
+        if fraction {
+            if self.eat(".") {
+                self.digits(10);
+            }
+            if self.eat("e") || self.eat("E") {
+                if !self.eat("+") {
+                    self.eat("-");
+                }
+                self.digits(10);
+            }
+            let text = str::from_utf8(&self.input[start..self.pos]).unwrap();
+            return match text.parse() {
+                Ok(value) => float(value, out),
+                Err(_) => {
+                    self.pos = start;
+                    Err(self.error("invalid number"))
+                }
+            };
+        }
+        let text = str::from_utf8(&self.input[digits..self.pos]).unwrap();
+        let value = match u128::from_str_radix(text, radix) {
+            Ok(value) => value,
+            Err(_) => {
+                self.pos = start;
+                return Err(self.error("number out of range"));
+            }
+        };
+        if !negative && self.eat("(") {
+            if value > u128::from(u64::max_value()) {
+                self.pos = start;
+                return Err(self.error("tag number out of range"));
+            }
+            head(6, value as u64, out);
+            self.item(out)?;
+            self.space()?;
+            return self.expect(")");
+        }
+        let (major, value) = match (negative, value) {
+            (false, value) | (true, value @ 0) => (0, value),
+            (true, value) => (1, value - 1),
+        };
+        if value <= u128::from(u64::max_value()) {
+            head(major, value as u64, out);
+        } else {
+            // Too large for a head, so encode it as a tag 2 or 3 bignum.
+            let bytes = value.to_be_bytes();
+            let skip = bytes.iter().take_while(|&&byte| byte == 0).count();
+            head(6, 2 + u64::from(major), out);
+            head(2, (bytes.len() - skip) as u64, out);
+            out.extend_from_slice(&bytes[skip..]);
+        }
+        Ok(())
+    }
+
+    // Skips digits of the given radix, returning the position of the first.
+    fn digits(&mut self, radix: u32) -> usize {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| (c as char).is_digit(radix)) {
+            self.pos += 1;
+        }
+        start
+    }
+
+    fn keyword(&mut self, out: &mut Vec<u8>) -> error::Result<()> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| (c as char).is_ascii_alphanumeric()) {
+            self.pos += 1;
+        }
+        match &self.input[start..self.pos] {
+            b"false" => out.push(0xf4),
+            b"true" => out.push(0xf5),
+            b"null" => out.push(0xf6),
+            b"undefined" => out.push(0xf7),
+            b"NaN" => return float(f64::NAN, out),
+            b"Infinity" => return float(f64::INFINITY, out),
+            b"simple" => {
+                self.space()?;
+                self.expect("(")?;
+                self.space()?;
+                let digits = self.digits(10);
+                let text = str::from_utf8(&self.input[digits..self.pos]).unwrap();
+                match text.parse::<u8>() {
+                    Ok(value @ 0...23) => out.push(0xe0 | value),
+                    Ok(value) if value >= 32 => out.extend_from_slice(&[0xf8, value]),
+                    _ => {
+                        self.pos = digits;
+                        return Err(self.error("invalid simple value"));
+                    }
+                }
+                self.space()?;
+                self.expect(")")?;
+            }
+            _ => {
+                self.pos = start;
+                return Err(self.error("unknown keyword"));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,25 +1,43 @@
 //! When serializing or deserializing CBOR goes wrong.
+use core::fmt;
+use core::result;
 use serde::de;
 use serde::ser;
+#[cfg(feature = "std")]
 use std::error;
-use std::fmt;
+#[cfg(feature = "std")]
 use std::io;
-use std::result;
 
 /// This type represents all possible errors that can occur when serializing or deserializing CBOR
 /// data.
+#[cfg(feature = "std")]
 pub struct Error(Box<ErrorImpl>);
 
+/// This type represents all possible errors that can occur when serializing or deserializing CBOR
+/// data.
+#[cfg(not(feature = "std"))]
+pub struct Error(ErrorImpl);
+
 /// Alias for a `Result` with the error type `serde_cbor::Error`.
 pub type Result<T> = result::Result<T, Error>;
 
 /// Categorizes the cause of a `serde_cbor::Error`.
+///
+/// The categories follow the terminology of RFC 8949: input that is not *well-formed* cannot be
+/// parsed as CBOR at all and is reported as `Syntax` (or `Eof` if it is merely truncated), while
+/// well-formed input that is not *valid*, breaks the rules of a profile such as deterministic
+/// encoding, or does not match the expected type is reported as `Data`.
 pub enum Category {
-    /// The error was caused by a failure to read or write bytes on an IO stream.
+    /// The error was caused by a failure to read or write bytes on an IO stream, or by running
+    /// out of room in the slice written to.
     Io,
-    /// The error was caused by input that was not syntactically valid CBOR.
+    /// The error was caused by input that was not well-formed CBOR, or by text that was not
+    /// valid diagnostic notation, CDDL or JSON.
     Syntax,
-    /// The error was caused by input data that was semantically incorrect.
+    /// The error was caused by well-formed input that was invalid or semantically incorrect,
+    /// for example a text string containing invalid UTF-8, a duplicate map key, a value of an
+    /// unexpected type, or an encoding the deserializer was told to reject, such as a
+    /// non-minimal argument.
     Data,
     /// The error was causeed by prematurely reaching the end of the input data.
     Eof,
@@ -31,34 +49,115 @@ impl Error {
         self.0.offset
     }
 
-    pub(crate) fn syntax(code: ErrorCode, offset: u64) -> Error {
-        Error(Box::new(ErrorImpl { code, offset }))
+    /// The location of the data item in which the error occurred, starting at the root of the
+    /// document.
+    ///
+    /// The path is empty for errors in the top-level item or errors that did not happen while
+    /// deserializing.
+    ///
+    /// ```
+    /// # use serde_cbor::{from_slice, Value};
+    /// # use serde_cbor::error::PathSegment;
+    /// // {"a": [1, <invalid UTF-8>]}
+    /// let error = from_slice::<Value>(b"\xa1\x61a\x82\x01\x61\xff").unwrap_err();
+    /// assert_eq!(error.path(), &[PathSegment::Key("\"a\"".to_string()), PathSegment::Index(1)]);
+    /// assert_eq!(error.to_string(), "invalid UTF-8 at offset 6 in [\"a\"][1]");
+    /// ```
+    ///
+    /// This method is available with the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn path(&self) -> &[PathSegment] {
+        &self.0.path
     }
 
-    pub(crate) fn io(error: io::Error) -> Error {
+    #[cfg(feature = "std")]
+    pub(crate) fn at(mut self, segment: PathSegment) -> Error {
+        self.0.path.insert(0, segment);
+        self
+    }
+
+    // Moves the offset of an error found in a part of the input to the whole of it.
+    #[cfg(feature = "std")]
+    pub(crate) fn shifted(mut self, by: u64) -> Error {
+        if !self.is_io() {
+            self.0.offset += by;
+        }
+        self
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn syntax(code: ErrorCode, offset: u64) -> Error {
         Error(Box::new(ErrorImpl {
-            code: ErrorCode::Io(error),
-            offset: 0,
+            code,
+            offset,
+            path: Vec::new(),
         }))
     }
 
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn syntax(code: ErrorCode, offset: u64) -> Error {
+        Error(ErrorImpl { code, offset })
+    }
+
+    /// Creates an error for input that ends at `offset` in the middle of a data item.
+    ///
+    /// This is for implementations of `de::Read` outside of this crate, and is available with the
+    /// `unsealed-read` feature.
+    #[cfg(feature = "unsealed-read")]
+    pub fn eof(offset: u64) -> Error {
+        Error::syntax(ErrorCode::EofWhileParsingValue, offset)
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn io(error: io::Error) -> Error {
+        Error::syntax(ErrorCode::Io(error), 0)
+    }
+
+    // The slice being written to has no room left. Like IO errors, this has no input offset;
+    // `SliceWrite::bytes_written` tells how far the output got.
+    pub(crate) fn buffer_full() -> Error {
+        Error::syntax(ErrorCode::BufferFull, 0)
+    }
+
     /// Categorizes the cause of this error.
     pub fn classify(&self) -> Category {
         match self.0.code {
-            ErrorCode::Message(_) => Category::Data,
+            #[cfg(feature = "std")]
             ErrorCode::Io(_) => Category::Io,
+            ErrorCode::BufferFull => Category::Io,
             ErrorCode::EofWhileParsingValue |
             ErrorCode::EofWhileParsingArray |
             ErrorCode::EofWhileParsingMap => Category::Eof,
+            ErrorCode::UnassignedCode | ErrorCode::UnexpectedCode => Category::Syntax,
+            #[cfg(feature = "std")]
+            ErrorCode::InvalidDiagnostic(_) => Category::Syntax,
+            #[cfg(feature = "cddl")]
+            ErrorCode::InvalidSchema(_) => Category::Syntax,
+            #[cfg(feature = "json")]
+            ErrorCode::InvalidJson(_) => Category::Syntax,
+            ErrorCode::NonMinimalArgument(_) |
+            ErrorCode::IndefiniteLength |
+            ErrorCode::TagNotAllowed => Category::Data,
+            #[cfg(feature = "float")]
+            ErrorCode::NonPreferredFloat => Category::Data,
+            #[cfg(feature = "std")]
+            ErrorCode::UnsortedKey(_) => Category::Data,
+            #[cfg(feature = "dcbor")]
+            ErrorCode::SimpleValueNotAllowed | ErrorCode::NonNfcText => Category::Data,
+            #[cfg(feature = "std")]
+            ErrorCode::DuplicateKey(_) | ErrorCode::InvalidStringRef => Category::Data,
+            #[cfg(not(feature = "std"))]
+            ErrorCode::IndefiniteString => Category::Data,
+            #[cfg(not(feature = "float"))]
+            ErrorCode::Float => Category::Data,
+            ErrorCode::Message(_) |
             ErrorCode::NumberOutOfRange |
             ErrorCode::LengthOutOfRange |
             ErrorCode::InvalidUtf8 |
-            ErrorCode::UnassignedCode |
-            ErrorCode::UnexpectedCode |
             ErrorCode::TrailingData |
             ErrorCode::ArrayTooShort |
             ErrorCode::ArrayTooLong |
-            ErrorCode::RecursionLimitExceeded => Category::Syntax,
+            ErrorCode::RecursionLimitExceeded => Category::Data,
         }
     }
 
@@ -70,7 +169,7 @@ impl Error {
         }
     }
 
-    /// Returns true if this error was caused by input that was not syntactically valid CBOR.
+    /// Returns true if this error was caused by input that was not well-formed CBOR.
     pub fn is_syntax(&self) -> bool {
         match self.classify() {
             Category::Syntax => true,
@@ -78,7 +177,8 @@ impl Error {
         }
     }
 
-    /// Returns true if this error was caused by data that was semantically incorrect.
+    /// Returns true if this error was caused by well-formed data that was invalid or semantically
+    /// incorrect.
     pub fn is_data(&self) -> bool {
         match self.classify() {
             Category::Data => true,
@@ -93,8 +193,28 @@ impl Error {
             _ => false,
         }
     }
+
+    /// Returns true if the input read so far is a valid prefix of CBOR data and providing more
+    /// bytes could make decoding succeed.
+    ///
+    /// Incremental consumers can use this to decide between waiting for more data and rejecting
+    /// the message. Besides `Eof` errors this includes IO errors of kind `UnexpectedEof`.
+    ///
+    /// ```
+    /// # use serde_cbor::{from_slice, Value};
+    /// assert!(from_slice::<Value>(b"\x82\x01").unwrap_err().needs_more_data());
+    /// assert!(!from_slice::<Value>(b"\x82\x01\xff").unwrap_err().needs_more_data());
+    /// ```
+    pub fn needs_more_data(&self) -> bool {
+        match self.0.code {
+            #[cfg(feature = "std")]
+            ErrorCode::Io(ref err) => err.kind() == io::ErrorKind::UnexpectedEof,
+            _ => self.is_eof(),
+        }
+    }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for Error {
     fn description(&self) -> &str {
         match self.0.code {
@@ -111,31 +231,127 @@ impl error::Error for Error {
     }
 }
 
+#[cfg(not(feature = "std"))]
+impl de::StdError for Error {}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    /// Wraps an `io::Error`.
+    ///
+    /// If the `io::Error` was itself created from a `serde_cbor::Error`, the original error is
+    /// recovered including its offset and category.
+    fn from(error: io::Error) -> Error {
+        let is_cbor = error.get_ref().is_some_and(|inner| inner.is::<Error>());
+        if !is_cbor {
+            return Error::io(error);
+        }
+        match error.into_inner().map(|inner| inner.downcast::<Error>()) {
+            Some(Ok(error)) => *error,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for io::Error {
+    /// Converts a `serde_cbor::Error` into an `io::Error`.
+    ///
+    /// IO errors are unwrapped. All other errors are boxed into an `io::Error` of kind
+    /// `UnexpectedEof` or `InvalidData` so that they can be converted back without loss.
+    fn from(error: Error) -> io::Error {
+        let kind = match error.classify() {
+            Category::Io => match error.0.code {
+                ErrorCode::Io(err) => return err,
+                _ => io::ErrorKind::WriteZero,
+            },
+            Category::Eof => io::ErrorKind::UnexpectedEof,
+            Category::Syntax | Category::Data => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+/// Rich diagnostics for CLI tools, available with the `miette` feature.
+///
+/// The label points at the byte offset of the error. Attach the input the error was produced
+/// from with `miette::Report::with_source_code` to have it rendered.
+#[cfg(all(feature = "std", feature = "miette"))]
+impl miette::Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let code = match self.classify() {
+            Category::Io => "serde_cbor::io",
+            Category::Syntax => "serde_cbor::syntax",
+            Category::Data => "serde_cbor::data",
+            Category::Eof => "serde_cbor::eof",
+        };
+        Some(Box::new(code))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let help = match self.0.code {
+            ErrorCode::TrailingData => "the input contains more than one data item",
+            ErrorCode::InvalidUtf8 => "use a byte string for data that is not UTF-8 text",
+            ErrorCode::RecursionLimitExceeded => "the input is nested too deeply",
+            _ if self.is_eof() => "the input is truncated",
+            _ => return None,
+        };
+        Some(Box::new(help))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        if self.is_io() {
+            return None;
+        }
+        let label = if self.0.path.is_empty() {
+            String::from("here")
+        } else {
+            let mut label = String::from("in ");
+            for segment in &self.0.path {
+                label.push_str(&segment.to_string());
+            }
+            label
+        };
+        let span = miette::LabeledSpan::at_offset(self.0.offset as usize, label);
+        Some(Box::new(Some(span).into_iter()))
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.0.offset == 0 {
-            fmt::Display::fmt(&self.0.code, f)
+            fmt::Display::fmt(&self.0.code, f)?;
         } else {
-            write!(f, "{} at offset {}", self.0.code, self.0.offset)
+            write!(f, "{} at offset {}", self.0.code, self.0.offset)?;
         }
+        #[cfg(feature = "std")]
+        if !self.0.path.is_empty() {
+            f.write_str(" in ")?;
+            for segment in &self.0.path {
+                fmt::Display::fmt(segment, f)?;
+            }
+        }
+        Ok(())
     }
 }
 
 impl fmt::Debug for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Debug::fmt(&*self.0, fmt)
+        fmt::Debug::fmt(&self.0, fmt)
     }
 }
 
+#[cfg(feature = "std")]
 impl de::Error for Error {
     fn custom<T>(msg: T) -> Error
     where
         T: fmt::Display,
     {
-        Error(Box::new(ErrorImpl {
-            code: ErrorCode::Message(msg.to_string()),
-            offset: 0,
-        }))
+        Error::syntax(ErrorCode::Message(msg.to_string()), 0)
+    }
+
+    fn duplicate_field(field: &'static str) -> Error {
+        let key = ::ser::to_vec(&field).expect("serializing a string cannot fail");
+        Error::syntax(ErrorCode::DuplicateKey(::diag::to_string(&key)), 0)
     }
 
     fn invalid_type(unexp: de::Unexpected, exp: &de::Expected) -> Error {
@@ -147,15 +363,141 @@ impl de::Error for Error {
     }
 }
 
+// Without an allocator, messages are replaced by a fixed description of their kind.
+#[cfg(not(feature = "std"))]
+impl de::Error for Error {
+    fn custom<T>(_: T) -> Error
+    where
+        T: fmt::Display,
+    {
+        Error::syntax(ErrorCode::Message("custom error"), 0)
+    }
+
+    fn invalid_type(_: de::Unexpected, _: &de::Expected) -> Error {
+        Error::syntax(ErrorCode::Message("invalid type"), 0)
+    }
+
+    fn invalid_value(_: de::Unexpected, _: &de::Expected) -> Error {
+        Error::syntax(ErrorCode::Message("invalid value"), 0)
+    }
+
+    fn invalid_length(_: usize, _: &de::Expected) -> Error {
+        Error::syntax(ErrorCode::Message("invalid length"), 0)
+    }
+
+    fn unknown_variant(_: &str, _: &'static [&'static str]) -> Error {
+        Error::syntax(ErrorCode::Message("unknown variant"), 0)
+    }
+
+    fn unknown_field(_: &str, _: &'static [&'static str]) -> Error {
+        Error::syntax(ErrorCode::Message("unknown field"), 0)
+    }
+
+    fn missing_field(_: &'static str) -> Error {
+        Error::syntax(ErrorCode::Message("missing field"), 0)
+    }
+
+    fn duplicate_field(_: &'static str) -> Error {
+        Error::syntax(ErrorCode::Message("duplicate field"), 0)
+    }
+}
+
 impl ser::Error for Error {
+    #[cfg(feature = "std")]
     fn custom<T>(msg: T) -> Error
     where
         T: fmt::Display,
     {
-        Error(Box::new(ErrorImpl {
-            code: ErrorCode::Message(msg.to_string()),
-            offset: 0,
-        }))
+        Error::syntax(ErrorCode::Message(msg.to_string()), 0)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn custom<T>(_: T) -> Error
+    where
+        T: fmt::Display,
+    {
+        Error::syntax(ErrorCode::Message("custom error"), 0)
+    }
+}
+
+/// A non-fatal issue noticed while deserializing.
+///
+/// Warnings are only recorded if the deserializer was asked to collect them, see
+/// `Deserializer::collect_warnings`. They describe input that was accepted but is not in the
+/// form a strict or deterministic encoder would have produced.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    kind: WarningKind,
+    offset: u64,
+    key: Option<String>,
+}
+
+/// Categorizes the cause of a `Warning`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningKind {
+    /// An integer, length or tag argument was not encoded in its shortest form.
+    NonMinimalArgument,
+    /// A map key was encountered that sorts before the preceding key of the same map.
+    UnsortedMapKey,
+    /// A map key was encountered that is identical to a preceding key of the same map.
+    DuplicateMapKey,
+    /// An indefinite-length string, array or map was encountered.
+    IndefiniteLength,
+}
+
+#[cfg(feature = "std")]
+impl Warning {
+    pub(crate) fn new(kind: WarningKind, offset: u64) -> Warning {
+        Warning {
+            kind,
+            offset,
+            key: None,
+        }
+    }
+
+    pub(crate) fn with_key(kind: WarningKind, offset: u64, key: String) -> Warning {
+        Warning {
+            kind,
+            offset,
+            key: Some(key),
+        }
+    }
+
+    /// Categorizes the cause of this warning.
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+
+    /// The byte offset of the data item that caused the warning.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The offending map key in diagnostic notation, for warnings about map keys.
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_ref().map(|key| &key[..])
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.key {
+            Some(ref key) => write!(f, "{} {} at offset {}", self.kind, key, self.offset),
+            None => write!(f, "{} at offset {}", self.kind, self.offset),
+        }
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WarningKind::NonMinimalArgument => f.write_str("non-minimal argument encoding"),
+            WarningKind::UnsortedMapKey => f.write_str("unsorted map key"),
+            WarningKind::DuplicateMapKey => f.write_str("duplicate map key"),
+            WarningKind::IndefiniteLength => f.write_str("indefinite-length item"),
+        }
     }
 }
 
@@ -163,20 +505,91 @@ impl ser::Error for Error {
 struct ErrorImpl {
     code: ErrorCode,
     offset: u64,
+    #[cfg(feature = "std")]
+    path: Vec<PathSegment>,
+}
+
+/// One step on the way from the root of a CBOR document to a nested data item.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// An element of an array, identified by its zero-based index.
+    Index(usize),
+    /// The value of a map entry, identified by its key in diagnostic notation.
+    Key(String),
+    /// The value of a map entry, identified by its zero-based position in the map.
+    ///
+    /// This takes the place of `Key` when deserializing from a reader, which would have to copy
+    /// every key to name it, unless the keys are checked anyway.
+    Entry(usize),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+            PathSegment::Key(ref key) => write!(f, "[{}]", key),
+            PathSegment::Entry(index) => write!(f, "[entry {}]", index),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub(crate) enum ErrorCode {
-    Message(String),
+    #[cfg(feature = "std")]
     Io(io::Error),
+    // The slice written to is too small.
+    BufferFull,
+
+    // The input is truncated.
     EofWhileParsingValue,
     EofWhileParsingArray,
     EofWhileParsingMap,
+
+    // The input is not well-formed.
+    UnassignedCode,
+    UnexpectedCode,
+    // The text is not valid diagnostic notation, CDDL or JSON.
+    #[cfg(feature = "std")]
+    InvalidDiagnostic(String),
+    #[cfg(feature = "cddl")]
+    InvalidSchema(String),
+    #[cfg(feature = "json")]
+    InvalidJson(String),
+
+    // The input is well-formed, but not in the form the deserializer checks for.
+    // The number of bytes the argument takes beyond the fewest it needs.
+    NonMinimalArgument(u8),
+    IndefiniteLength,
+    TagNotAllowed,
+    #[cfg(feature = "float")]
+    NonPreferredFloat,
+    #[cfg(feature = "std")]
+    UnsortedKey(String),
+    #[cfg(feature = "dcbor")]
+    SimpleValueNotAllowed,
+    #[cfg(feature = "dcbor")]
+    NonNfcText,
+
+    // The input is well-formed, but not valid or not what was expected.
+    #[cfg(feature = "std")]
+    Message(String),
+    #[cfg(not(feature = "std"))]
+    Message(&'static str),
+    #[cfg(feature = "std")]
+    DuplicateKey(String),
+    #[cfg(feature = "std")]
+    InvalidStringRef,
+    // Without an allocator, the chunks of indefinite-length strings cannot be joined.
+    #[cfg(not(feature = "std"))]
+    IndefiniteString,
+    // Floats are compiled out.
+    #[cfg(not(feature = "float"))]
+    Float,
     NumberOutOfRange,
     LengthOutOfRange,
     InvalidUtf8,
-    UnassignedCode,
-    UnexpectedCode,
     TrailingData,
     ArrayTooShort,
     ArrayTooLong,
@@ -186,8 +599,21 @@ pub(crate) enum ErrorCode {
 impl fmt::Display for ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            #[cfg(feature = "std")]
             ErrorCode::Message(ref msg) => f.write_str(msg),
+            #[cfg(not(feature = "std"))]
+            ErrorCode::Message(msg) => f.write_str(msg),
+            #[cfg(feature = "std")]
+            ErrorCode::DuplicateKey(ref key) => write!(f, "duplicate map key {}", key),
+            #[cfg(feature = "std")]
+            ErrorCode::InvalidStringRef => f.write_str("invalid string reference"),
+            #[cfg(not(feature = "std"))]
+            ErrorCode::IndefiniteString => f.write_str("indefinite-length string"),
+            #[cfg(not(feature = "float"))]
+            ErrorCode::Float => f.write_str("floating-point number without the float feature"),
+            #[cfg(feature = "std")]
             ErrorCode::Io(ref err) => fmt::Display::fmt(err, f),
+            ErrorCode::BufferFull => f.write_str("buffer full"),
             ErrorCode::EofWhileParsingValue => f.write_str("EOF while parsing a value"),
             ErrorCode::EofWhileParsingArray => f.write_str("EOF while parsing an array"),
             ErrorCode::EofWhileParsingMap => f.write_str("EOF while parsing a map"),
@@ -196,6 +622,30 @@ impl fmt::Display for ErrorCode {
             ErrorCode::InvalidUtf8 => f.write_str("invalid UTF-8"),
             ErrorCode::UnassignedCode => f.write_str("unassigned type"),
             ErrorCode::UnexpectedCode => f.write_str("unexpected code"),
+            ErrorCode::NonMinimalArgument(1) => {
+                f.write_str("argument not in its shortest form, wasting 1 byte")
+            }
+            ErrorCode::NonMinimalArgument(wasted) => {
+                write!(f, "argument not in its shortest form, wasting {} bytes", wasted)
+            }
+            ErrorCode::IndefiniteLength => f.write_str("indefinite-length item"),
+            ErrorCode::TagNotAllowed => f.write_str("tag not allowed"),
+            #[cfg(feature = "float")]
+            ErrorCode::NonPreferredFloat => f.write_str("float not in its shortest form"),
+            #[cfg(feature = "std")]
+            ErrorCode::UnsortedKey(ref key) => write!(f, "map key {} out of order", key),
+            #[cfg(feature = "dcbor")]
+            ErrorCode::SimpleValueNotAllowed => f.write_str("simple value not allowed"),
+            #[cfg(feature = "dcbor")]
+            ErrorCode::NonNfcText => f.write_str("text not in Unicode Normalization Form C"),
+            #[cfg(feature = "std")]
+            ErrorCode::InvalidDiagnostic(ref msg) => {
+                write!(f, "invalid diagnostic notation: {}", msg)
+            }
+            #[cfg(feature = "cddl")]
+            ErrorCode::InvalidSchema(ref msg) => write!(f, "invalid CDDL: {}", msg),
+            #[cfg(feature = "json")]
+            ErrorCode::InvalidJson(ref msg) => write!(f, "invalid JSON: {}", msg),
             ErrorCode::TrailingData => f.write_str("trailing data"),
             ErrorCode::ArrayTooShort => f.write_str("array too short"),
             ErrorCode::ArrayTooLong => f.write_str("array too long"),
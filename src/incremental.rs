@@ -0,0 +1,289 @@
+// Decoding CBOR from input that arrives bit by bit.
+//
+// A resumable scan of the structure of the buffered input finds where a data item ends, without
+// decoding it. Once all of it is there, it is deserialized from the buffer in one go.
+use serde::de::Deserialize;
+
+use de::from_slice;
+use error::{Error, ErrorCode, Result};
+
+// How deeply data items may be nested in each other, as in `Deserializer`.
+const MAX_DEPTH: usize = 128;
+
+/// The outcome of `Incremental::next_value`.
+#[derive(Debug, PartialEq)]
+pub enum Status<T> {
+    /// A data item has been deserialized.
+    Value(T),
+    /// The next data item is not complete yet, and more input has to be fed.
+    NeedMoreData,
+}
+
+/// Parser that is fed input as it arrives, in the style of a protocol implementation that does
+/// no I/O itself.
+///
+/// Chunks of input of any size are handed to `feed`, and `next_value` returns each data item
+/// once all of it is there. Data items may borrow from the buffered input. A data item that
+/// cannot be deserialized as the type asked for is skipped, so that the next call continues with
+/// the data item after it.
+///
+/// ```
+/// use serde_cbor::de::{Incremental, Status};
+///
+/// let mut parser = Incremental::new();
+/// parser.feed(b"\x82\x01");
+/// assert_eq!(parser.next_value::<Vec<u8>>().unwrap(), Status::NeedMoreData);
+/// parser.feed(b"\x02\x63a");
+/// assert_eq!(parser.next_value::<Vec<u8>>().unwrap(), Status::Value(vec![1, 2]));
+/// assert_eq!(parser.next_value::<&str>().unwrap(), Status::NeedMoreData);
+/// parser.feed(b"bc");
+/// assert_eq!(parser.next_value::<&str>().unwrap(), Status::Value("abc"));
+/// parser.end().unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct Incremental {
+    // Input fed so far, of which `buf[start..]` is yet to be deserialized.
+    buf: Vec<u8>,
+    start: usize,
+    offset: u64,
+    scanner: Scanner,
+}
+
+impl Incremental {
+    /// Creates a parser without any input.
+    pub fn new() -> Incremental {
+        Incremental::default()
+    }
+
+    /// Adds a chunk of input.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.spare(bytes.len()).copy_from_slice(bytes);
+    }
+
+    /// Deserializes the next data item if all of it has been fed.
+    ///
+    /// Malformed input fails every time until the parser is dropped, since there is no telling
+    /// where the next data item starts.
+    pub fn next_value<'a, T>(&'a mut self) -> Result<Status<T>>
+    where
+        T: Deserialize<'a>,
+    {
+        match self.item()? {
+            Some(len) => self.take(len).map(Status::Value),
+            None => Ok(Status::NeedMoreData),
+        }
+    }
+
+    /// Checks that no part of a data item is left over, which is an error at the end of the
+    /// input.
+    pub fn end(&self) -> Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            let offset = self.offset + self.buffered() as u64;
+            Err(Error::syntax(ErrorCode::EofWhileParsingValue, offset))
+        }
+    }
+
+    /// Returns the number of bytes of the input that have been deserialized so far.
+    pub fn byte_offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the number of bytes that have been fed but not deserialized yet.
+    pub fn buffered(&self) -> usize {
+        self.buf.len() - self.start
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.start == self.buf.len()
+    }
+
+    // Returns the length of the next data item once all of it is in the buffer.
+    pub(crate) fn item(&mut self) -> Result<Option<usize>> {
+        let offset = self.offset;
+        self.scanner
+            .scan(&self.buf[self.start..])
+            .map_err(|e| e.shifted(offset))
+    }
+
+    // Deserializes the data item of `len` bytes at the start of the buffer and moves past it.
+    pub(crate) fn take<'a, T>(&'a mut self, len: usize) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        let start = self.start;
+        let offset = self.offset;
+        self.start += len;
+        self.offset += len as u64;
+        self.scanner = Scanner::default();
+        from_slice(&self.buf[start..start + len]).map_err(|e| e.shifted(offset))
+    }
+
+    // Makes room for `len` more bytes of input at the end of the buffer, which `filled` then
+    // trims to what was actually put there.
+    pub(crate) fn spare(&mut self, len: usize) -> &mut [u8] {
+        if self.is_empty() {
+            self.buf.clear();
+            self.start = 0;
+        } else if self.start > self.buf.len() / 2 {
+            self.buf.drain(..self.start);
+            self.start = 0;
+        }
+        let end = self.buf.len();
+        self.buf.resize(end + len, 0);
+        &mut self.buf[end..]
+    }
+
+    #[cfg(any(feature = "futures-io", feature = "tokio"))]
+    pub(crate) fn filled(&mut self, len: usize, count: usize) {
+        let end = self.buf.len() - len + count;
+        self.buf.truncate(end);
+    }
+}
+
+// Finds where the first data item in a buffer ends, while the buffer is filled bit by bit. The
+// scan picks up where it stopped for lack of input when more arrives.
+#[derive(Debug, Default)]
+struct Scanner {
+    pos: usize,
+    stack: Vec<Pending>,
+}
+
+// An array, map or indefinite-length string that the scan is inside of.
+#[derive(Debug)]
+enum Pending {
+    // The number of data items left, counting keys and values separately.
+    Items(u64),
+    // An array or map of indefinite length by its major type, and whether a key (or an array
+    // element) or the break comes next.
+    Indefinite(u8, bool),
+    // The chunks of an indefinite-length string by its major type.
+    Chunks(u8),
+}
+
+impl Scanner {
+    fn error(&self, code: ErrorCode) -> Error {
+        Error::syntax(code, self.pos as u64 + 1)
+    }
+
+    // Returns the length of the data item once all of it is in `buf`.
+    fn scan(&mut self, buf: &[u8]) -> Result<Option<usize>> {
+        loop {
+            let byte = match buf.get(self.pos) {
+                Some(&byte) => byte,
+                None => return Ok(None),
+            };
+            match self.stack.last_mut() {
+                Some(&mut Pending::Indefinite(_, true)) | Some(&mut Pending::Chunks(_))
+                    if byte == 0xff =>
+                {
+                    self.pos += 1;
+                    self.stack.pop();
+                    if self.complete() {
+                        return Ok(Some(self.pos));
+                    }
+                    continue;
+                }
+                Some(&mut Pending::Chunks(major)) if byte >> 5 != major || byte & 0x1f > 0x1b => {
+                    return Err(self.error(ErrorCode::UnexpectedCode));
+                }
+                _ => {}
+            }
+            let (len, arg) = match self.head(buf, byte)? {
+                Some(head) => head,
+                None => return Ok(None),
+            };
+            let pending = match (byte >> 5, arg) {
+                (6, _) => {
+                    self.pos += len;
+                    continue;
+                }
+                (2, Some(arg)) | (3, Some(arg)) => {
+                    let end = match ((self.pos + len) as u64).checked_add(arg) {
+                        Some(end) => end,
+                        None => return Err(self.error(ErrorCode::LengthOutOfRange)),
+                    };
+                    if end > buf.len() as u64 {
+                        return Ok(None);
+                    }
+                    self.pos = end as usize;
+                    None
+                }
+                (major @ 2, None) | (major @ 3, None) => Some(Pending::Chunks(major)),
+                (4, Some(0)) | (5, Some(0)) => None,
+                (4, Some(arg)) => Some(Pending::Items(arg)),
+                (5, Some(arg)) => match arg.checked_mul(2) {
+                    Some(items) => Some(Pending::Items(items)),
+                    None => return Err(self.error(ErrorCode::LengthOutOfRange)),
+                },
+                (major, None) => Some(Pending::Indefinite(major, true)),
+                _ => None,
+            };
+            if let Some(pending) = pending {
+                if self.stack.len() + 1 >= MAX_DEPTH {
+                    return Err(self.error(ErrorCode::RecursionLimitExceeded));
+                }
+                self.pos += len;
+                self.stack.push(pending);
+            } else {
+                if byte >> 5 != 2 && byte >> 5 != 3 {
+                    self.pos += len;
+                }
+                if self.complete() {
+                    return Ok(Some(self.pos));
+                }
+            }
+        }
+    }
+
+    // Returns the length of the head at the scan position and its argument, which is `None` for
+    // indefinite lengths, or `None` if the head is not all in `buf` yet.
+    fn head(&self, buf: &[u8], byte: u8) -> Result<Option<(usize, Option<u64>)>> {
+        let len = match byte {
+            0xe0...0xf7 => return Ok(Some((1, Some(0)))),
+            0xf8 => 2,
+            0xf9 => 3,
+            0xfa => 5,
+            0xfb => 9,
+            0xff => return Err(self.error(ErrorCode::UnexpectedCode)),
+            0xfc...0xfe => return Err(self.error(ErrorCode::UnassignedCode)),
+            _ => match byte & 0x1f {
+                0x00...0x17 => return Ok(Some((1, Some(u64::from(byte & 0x1f))))),
+                0x18 => 2,
+                0x19 => 3,
+                0x1a => 5,
+                0x1b => 9,
+                0x1f if byte >> 5 >= 2 && byte >> 5 != 6 => return Ok(Some((1, None))),
+                _ => return Err(self.error(ErrorCode::UnassignedCode)),
+            },
+        };
+        let bytes = match buf.get(self.pos + 1..self.pos + len) {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        let arg = bytes.iter().fold(0, |arg, &b| arg << 8 | u64::from(b));
+        Ok(Some((len, Some(arg))))
+    }
+
+    // Counts a data item as done, and returns whether that completes the outermost one.
+    fn complete(&mut self) -> bool {
+        loop {
+            match self.stack.last_mut() {
+                None => return true,
+                Some(&mut Pending::Items(ref mut left)) => {
+                    *left -= 1;
+                    if *left != 0 {
+                        return false;
+                    }
+                }
+                Some(&mut Pending::Indefinite(major, ref mut at_key)) => {
+                    *at_key = major == 4 || !*at_key;
+                    return false;
+                }
+                Some(&mut Pending::Chunks(_)) => return false,
+            }
+            self.stack.pop();
+        }
+    }
+}
@@ -79,16 +79,70 @@
 //!
 //! # Packed Encoding
 //! When serializing structs or enums in CBOR the keys or enum variant names will be serialized
-//! as string keys to a map. Esspecially in embedded environments this can increase the file
-//! size too much. In packed encoding the keys and variants will be serialized as variable sized
-//! integers. The first 24 entries in any struct consume only a single byte!
-//! To serialize a document in packed encoding use `ser::to_(vec|writer)_packed`, deserialization
-//! works without any changes.
+//! as string keys to a map. Especially in embedded environments, or for messages sent over
+//! constrained radio links, this can increase the size too much. In packed encoding the keys and
+//! variants will be serialized as variable sized integers, numbered by the order in which they
+//! are declared. The first 24 entries in any struct consume only a single byte!
+//! To serialize a document in packed encoding use `ser::to_(vec|writer)_packed`, or the
+//! `packed_format` option of a `Serializer`, which combines with the others such as the
+//! canonical encoding. A single field can be packed with `with::packed`. Deserialization works
+//! without any changes, as the deserializer accepts both encodings.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Position {
+//!     latitude: i32,
+//!     longitude: i32,
+//! }
+//!
+//! # fn main() {
+//! let position = Position { latitude: 52_520_008, longitude: 13_404_954 };
+//! let packed = serde_cbor::ser::to_vec_packed(&position).unwrap();
+//! assert_eq!(packed.len(), 13);
+//! assert_eq!(serde_cbor::to_vec(&position).unwrap().len(), 30);
+//! let decoded: Position = serde_cbor::from_slice(&packed).unwrap();
+//! assert_eq!(decoded, position);
+//! # }
+//! ```
+//!
+//! Packed documents are also faster to decode: each key selects its field by index instead of
+//! being compared against the field names one by one, which pays off for structs with many
+//! fields.
 //!
 //! # Self describing documents
 //! In some contexts different formats are used but there is no way to declare the format used
 //! out of band. For this reason CBOR has a magic number that may be added before any document.
 //! The *`_sd` (for *s*elf*d*escribe) append the magic number before documents.
+//! `Serializer::self_described` does the same for a serializer with other options. The
+//! deserializer skips the magic number at the start of its input.
+//!
+//! # Without `std`
+//! With the default `std` feature turned off, the crate is `#![no_std]` and never allocates, so
+//! that it fits microcontrollers without a heap:
+//!
+//! ```toml
+//! [dependencies]
+//! serde_cbor = { version = "0.9", default-features = false }
+//! ```
+//!
+//! Values are serialized into a slice with `ser::to_slice` and deserialized from one with
+//! `de::from_slice`, which borrows strings from the input. Errors carry a fixed description in
+//! place of a message and no path. Everything that needs to allocate is left out, including
+//! `Value`, parsing diagnostic notation, warnings and the deterministic encoding, and
+//! indefinite-length strings are rejected as their chunks would have to be joined. Received
+//! payloads can still be logged in diagnostic notation with `diag::display`.
+//!
+//! Floating-point numbers are then left out as well, unless the `float` feature is turned on,
+//! so that targets without a floating-point unit need no soft-float routines. Serializing a float
+//! fails, and so does deserializing one, while floats in ignored parts of the input are skipped
+//! over as usual:
+//!
+//! ```toml
+//! [dependencies]
+//! serde_cbor = { version = "0.9", default-features = false, features = ["float"] }
+//! ```
 //!
 //! # Examples
 //! Read a CBOR value that is known to be a map of string keys to string values and print it.
@@ -109,7 +163,7 @@
 //!
 //! let slice = b"\x82\x01\xa1aaab";
 //! let value: Value = from_slice(slice).unwrap();
-//! println!("{:?}", value); // Array([U64(1), Object({String("a"): String("b")})])
+//! println!("{:?}", value); // Array([Integer(1), Object({String("a"): String("b")})])
 //! ```
 //!
 //! Serialize an object.
@@ -146,22 +200,126 @@
 //! ```
 
 #![deny(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "bytemuck")]
+extern crate bytemuck;
 extern crate byteorder;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "digest")]
+extern crate digest;
+#[cfg(feature = "futures-io")]
+extern crate futures_io;
+#[cfg(feature = "float")]
 extern crate half;
+#[cfg(feature = "indexmap")]
+extern crate indexmap;
+#[cfg(feature = "ipnet")]
+extern crate ipnet;
+#[cfg(feature = "wasm")]
+extern crate js_sys;
+#[cfg(feature = "memmap2")]
+extern crate memmap2;
+#[cfg(feature = "miette")]
+extern crate miette;
+#[cfg(feature = "mime")]
+extern crate mime;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+#[cfg(feature = "num-bigint")]
+extern crate num_bigint;
+#[cfg(feature = "num-rational")]
+extern crate num_rational;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "regex")]
+extern crate regex;
+#[cfg(feature = "rust_decimal")]
+extern crate rust_decimal;
+#[cfg(feature = "simdutf8")]
+extern crate simdutf8;
+#[cfg(feature = "smallvec")]
+extern crate smallvec;
+#[cfg(feature = "time")]
+extern crate time;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "unicode-normalization")]
+extern crate unicode_normalization;
+#[cfg(feature = "url")]
+extern crate url;
+#[cfg(feature = "uuid")]
+extern crate uuid;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
 
 #[macro_use]
 extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "serde-reflection")]
+extern crate serde_reflection;
 
+#[cfg(all(feature = "std", any(feature = "futures-io", feature = "tokio")))]
+mod async_read;
+#[cfg(all(feature = "std", any(feature = "futures-io", feature = "tokio")))]
+mod async_write;
+#[cfg(feature = "cddl")]
+pub mod cddl;
+#[cfg(feature = "std")]
+mod cursor;
+pub mod diag;
+#[cfg(feature = "std")]
+mod incremental;
+#[cfg(all(feature = "std", feature = "memmap2"))]
+mod mmap;
+#[cfg(feature = "std")]
+pub mod raw;
 mod read;
+#[cfg(feature = "json")]
+pub mod convert;
 pub mod de;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod number;
+#[cfg(all(feature = "std", feature = "serde-reflection"))]
+pub mod reflection;
 pub mod ser;
+#[cfg(feature = "std")]
+pub mod shared;
+#[cfg(all(feature = "std", feature = "proptest"))]
+pub mod strategy;
+#[cfg(feature = "std")]
+pub mod stringref;
+pub mod tags;
+#[cfg(feature = "std")]
+pub mod text;
+#[cfg(feature = "std")]
+pub mod typed_array;
+#[cfg(feature = "std")]
 pub mod value;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod with;
 
 #[doc(inline)]
-pub use de::{from_slice, from_reader, Deserializer, StreamDeserializer};
+pub use de::{from_slice, Deserializer, StreamDeserializer};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use de::from_reader;
+#[doc(inline)]
+pub use ser::{to_slice, Serializer};
+#[cfg(feature = "std")]
 #[doc(inline)]
-pub use ser::{to_writer, to_vec, Serializer};
+pub use ser::{to_writer, to_vec};
+#[cfg(feature = "std")]
 #[doc(inline)]
 pub use value::{Value, ObjectKey, to_value, from_value};
@@ -0,0 +1,82 @@
+// Reading CBOR from files mapped into memory.
+use memmap2::Mmap;
+use serde::de::Deserialize;
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+
+use de::{from_slice, Deserializer};
+use error::{Error, Result};
+use read::SliceRead;
+
+/// A file mapped into memory to deserialize from.
+///
+/// The file is read like a slice, so strings and byte strings can be borrowed from the mapping
+/// rather than copied, and only the parts of the file that are looked at are loaded into
+/// memory.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// # extern crate serde_cbor;
+/// use serde_cbor::de::MappedFile;
+///
+/// #[derive(Deserialize)]
+/// struct Entry<'a> {
+///     name: &'a str,
+/// }
+///
+/// # fn main() {
+/// # let path = std::env::temp_dir().join("serde_cbor_doc_mmap");
+/// # std::fs::write(&path, b"\xa1\x64name\x63abc").unwrap();
+/// let file = unsafe { MappedFile::open(&path).unwrap() };
+/// let entry: Entry = file.deserialize().unwrap();
+/// assert_eq!(entry.name, "abc");
+/// # }
+/// ```
+///
+/// This type is available with the `memmap2` feature.
+#[derive(Debug)]
+pub struct MappedFile {
+    map: Mmap,
+}
+
+impl MappedFile {
+    /// Maps the file at `path` into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be changed or truncated while it is mapped, by this or any other
+    /// process. Either is undefined behavior, as the mapping is read like a slice that does not
+    /// change.
+    pub unsafe fn open<P>(path: P) -> Result<MappedFile>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path).map_err(Error::io)?;
+        let map = Mmap::map(&file).map_err(Error::io)?;
+        Ok(MappedFile { map })
+    }
+
+    /// Decodes a value from the file, which must end after it.
+    pub fn deserialize<'a, T>(&'a self) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        from_slice(&self.map)
+    }
+
+    /// Constructs a `Deserializer` which reads from the file, such as to iterate over the data
+    /// items of a CBOR sequence.
+    pub fn deserializer<'a>(&'a self) -> Deserializer<SliceRead<'a>> {
+        Deserializer::from_slice(&self.map)
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
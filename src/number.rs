@@ -0,0 +1,297 @@
+//! Numbers that CBOR encodes with semantic tags.
+//!
+//! `DecimalFraction` is a number with a decimal exponent, written as an array of the exponent
+//! and the mantissa with tag 4. It keeps amounts such as prices exact, where a float would round
+//! them. `Bigfloat` is a number with a binary exponent, written the same way with tag 5, and
+//! `Rational` is a fraction, written as an array of its numerator and denominator with tag 30.
+//!
+//! ```
+//! use serde_cbor::number::DecimalFraction;
+//!
+//! // 273.15
+//! let number = DecimalFraction::new(-2, 27315);
+//! let bytes = serde_cbor::to_vec(&number).unwrap();
+//! assert_eq!(bytes, b"\xc4\x82\x21\x19\x6a\xb3");
+//! assert_eq!(serde_cbor::from_slice::<DecimalFraction>(&bytes).unwrap(), number);
+//! ```
+//!
+//! Mantissas and the parts of fractions are 128-bit integers, written as bignums where they need
+//! more than 64 bits. As
+//! with other tagged values, data items without a tag are accepted when deserializing, and other
+//! formats see just the array.
+//!
+//! This module is available with the `std` feature.
+#[cfg(feature = "num-rational")]
+use num_rational::Ratio;
+#[cfg(feature = "rust_decimal")]
+use rust_decimal::Decimal;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{self, Serialize, Serializer};
+#[cfg(any(feature = "num-rational", feature = "rust_decimal"))]
+use std::convert::TryFrom;
+
+use tags::{deserialize_tagged, Tagged};
+
+/// A decimal fraction, which is `mantissa * 10^exponent`, with tag 4.
+///
+/// Numbers are kept as they are given rather than normalized, so `DecimalFraction::new(-1, 10)`
+/// and `DecimalFraction::new(0, 1)` are not equal, even though both are 1.
+///
+/// With the `rust_decimal` feature, it converts from `rust_decimal::Decimal` and back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DecimalFraction {
+    /// The power of ten the mantissa is multiplied by.
+    pub exponent: i64,
+    /// The digits of the number.
+    pub mantissa: i128,
+}
+
+impl DecimalFraction {
+    /// Creates a decimal fraction of `mantissa * 10^exponent`.
+    pub fn new(exponent: i64, mantissa: i128) -> DecimalFraction {
+        DecimalFraction { exponent, mantissa }
+    }
+
+    /// Converts the number to a `Decimal`, if it can be represented as one exactly.
+    ///
+    /// This method is available with the `rust_decimal` feature.
+    #[cfg(feature = "rust_decimal")]
+    pub fn to_decimal(&self) -> Option<Decimal> {
+        if self.exponent > 0 {
+            let factor = 10i128.checked_pow(u32::try_from(self.exponent).ok()?)?;
+            let mantissa = self.mantissa.checked_mul(factor)?;
+            Decimal::try_from_i128_with_scale(mantissa, 0).ok()
+        } else {
+            let scale = u32::try_from(self.exponent.checked_neg()?).ok()?;
+            Decimal::try_from_i128_with_scale(self.mantissa, scale).ok()
+        }
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl From<Decimal> for DecimalFraction {
+    fn from(decimal: Decimal) -> DecimalFraction {
+        DecimalFraction {
+            exponent: -i64::from(decimal.scale()),
+            mantissa: decimal.mantissa(),
+        }
+    }
+}
+
+impl Serialize for DecimalFraction {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 4,
+            value: (self.exponent, self.mantissa),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DecimalFraction {
+    fn deserialize<D>(deserializer: D) -> Result<DecimalFraction, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (exponent, mantissa) = deserialize_tagged(deserializer, 4)?;
+        Ok(DecimalFraction { exponent, mantissa })
+    }
+}
+
+/// A bigfloat, which is `mantissa * 2^exponent`, with tag 5.
+///
+/// It converts to and from `f64` where that does not change the value. As with
+/// `DecimalFraction`, numbers are kept as they are given rather than normalized.
+///
+/// ```
+/// use serde_cbor::number::Bigfloat;
+///
+/// let number = Bigfloat::from_f64(1.5).unwrap();
+/// assert_eq!(number, Bigfloat::new(-1, 3));
+/// assert_eq!(serde_cbor::to_vec(&number).unwrap(), b"\xc5\x82\x20\x03");
+/// assert_eq!(number.to_f64(), Some(1.5));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bigfloat {
+    /// The power of two the mantissa is multiplied by.
+    pub exponent: i64,
+    /// The bits of the number.
+    pub mantissa: i128,
+}
+
+impl Bigfloat {
+    /// Creates a bigfloat of `mantissa * 2^exponent`.
+    pub fn new(exponent: i64, mantissa: i128) -> Bigfloat {
+        Bigfloat { exponent, mantissa }
+    }
+
+    /// Converts a float to a bigfloat with an odd mantissa, or zero. Returns `None` for
+    /// infinities, NaNs and negative zero, which a bigfloat cannot represent.
+    pub fn from_f64(value: f64) -> Option<Bigfloat> {
+        if !value.is_finite() || (value == 0.0 && value.is_sign_negative()) {
+            return None;
+        }
+        if value == 0.0 {
+            return Some(Bigfloat::new(0, 0));
+        }
+        let bits = value.to_bits();
+        let biased = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = bits & ((1 << 52) - 1);
+        let (mut mantissa, mut exponent) = match biased {
+            // Subnormal numbers have no implicit leading bit.
+            0 => (fraction, -1074),
+            _ => (fraction | 1 << 52, biased - 1075),
+        };
+        let zeros = mantissa.trailing_zeros();
+        mantissa >>= zeros;
+        exponent += i64::from(zeros);
+        let mantissa = i128::from(mantissa);
+        Some(Bigfloat {
+            exponent,
+            mantissa: if value < 0.0 { -mantissa } else { mantissa },
+        })
+    }
+
+    /// Converts the number to a float, if it can be represented as one exactly.
+    pub fn to_f64(&self) -> Option<f64> {
+        if self.mantissa == 0 {
+            return Some(0.0);
+        }
+        let zeros = self.mantissa.trailing_zeros();
+        let mantissa = self.mantissa >> zeros;
+        let exponent = self.exponent.checked_add(i64::from(zeros))?;
+        let magnitude = mantissa.unsigned_abs();
+        let width = i64::from(128 - magnitude.leading_zeros());
+        // The lowest bit of the number is the smallest subnormal or above, and its highest
+        // bit is the highest bit of the largest finite float or below.
+        if width > 53 || exponent < -1074 || exponent > 1024 - width {
+            return None;
+        }
+        // Both factors are normal powers of two, so each product is exact.
+        let power_of_two = |exponent: i64| f64::from_bits(((exponent + 1023) as u64) << 52);
+        let value = mantissa as f64;
+        Some(if exponent < -1022 {
+            value * power_of_two(exponent + 1022) * power_of_two(-1022)
+        } else {
+            value * power_of_two(exponent)
+        })
+    }
+}
+
+impl Serialize for Bigfloat {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 5,
+            value: (self.exponent, self.mantissa),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bigfloat {
+    fn deserialize<D>(deserializer: D) -> Result<Bigfloat, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (exponent, mantissa) = deserialize_tagged(deserializer, 5)?;
+        Ok(Bigfloat { exponent, mantissa })
+    }
+}
+
+/// A rational number, which is `numerator / denominator`, with tag 30.
+///
+/// The denominator must not be zero: such numbers fail to serialize, and are rejected when
+/// deserializing. Fractions are kept as they are given rather than reduced.
+///
+/// With the `num-rational` feature, it converts from `num_rational::Ratio<i64>` and back.
+///
+/// ```
+/// use serde_cbor::number::Rational;
+///
+/// let number = Rational::new(-1, 3);
+/// let bytes = serde_cbor::to_vec(&number).unwrap();
+/// assert_eq!(bytes, b"\xd8\x1e\x82\x20\x03");
+/// assert_eq!(serde_cbor::from_slice::<Rational>(&bytes).unwrap(), number);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    /// The number above the fraction bar, which carries the sign.
+    pub numerator: i128,
+    /// The number below the fraction bar.
+    pub denominator: u128,
+}
+
+impl Rational {
+    /// Creates a rational number of `numerator / denominator`.
+    pub fn new(numerator: i128, denominator: u128) -> Rational {
+        Rational {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// Converts the number to a `Ratio<i64>`, reduced to lowest terms, if both its numerator and
+    /// its denominator fit in an `i64`.
+    ///
+    /// This method is available with the `num-rational` feature.
+    #[cfg(feature = "num-rational")]
+    pub fn to_ratio(&self) -> Option<Ratio<i64>> {
+        let numerator = i64::try_from(self.numerator).ok()?;
+        let denominator = i64::try_from(self.denominator).ok()?;
+        if denominator == 0 {
+            return None;
+        }
+        Some(Ratio::new(numerator, denominator))
+    }
+}
+
+#[cfg(feature = "num-rational")]
+impl From<Ratio<i64>> for Rational {
+    fn from(ratio: Ratio<i64>) -> Rational {
+        let (numerator, denominator) = (i128::from(*ratio.numer()), i128::from(*ratio.denom()));
+        // The denominator is positive unless the ratio was made with `Ratio::new_raw`.
+        if denominator < 0 {
+            Rational::new(-numerator, -denominator as u128)
+        } else {
+            Rational::new(numerator, denominator as u128)
+        }
+    }
+}
+
+impl Serialize for Rational {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if self.denominator == 0 {
+            return Err(ser::Error::custom("denominator of a rational number is zero"));
+        }
+        Tagged {
+            tag: 30,
+            value: (self.numerator, self.denominator),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rational {
+    fn deserialize<D>(deserializer: D) -> Result<Rational, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (numerator, denominator) = deserialize_tagged(deserializer, 30)?;
+        if denominator == 0 {
+            return Err(de::Error::custom("denominator of a rational number is zero"));
+        }
+        Ok(Rational {
+            numerator,
+            denominator,
+        })
+    }
+}
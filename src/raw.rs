@@ -0,0 +1,238 @@
+//! Data items that are passed through in their encoding.
+//!
+//! A `RawValue` keeps the exact bytes of a data item where it is deserialized, and writes them
+//! again as they are where it is serialized. A proxy can hand on a signed payload without
+//! changing a byte of it, and a large part of a document can be left encoded until it is needed
+//! and then decoded with `RawValue::decode`. A `RawValueRef` borrows the bytes from the input
+//! instead of copying them.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! use serde_cbor::raw::RawValue;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Envelope {
+//!     id: u32,
+//!     payload: RawValue,
+//! }
+//!
+//! # fn main() {
+//! // {"id": 7, "payload": [_ 1, 2]}
+//! let bytes = b"\xa2\x62id\x07\x67payload\x9f\x01\x02\xff";
+//! let envelope: Envelope = serde_cbor::from_slice(bytes).unwrap();
+//! assert_eq!(envelope.payload.as_bytes(), b"\x9f\x01\x02\xff");
+//! assert_eq!(envelope.payload.decode::<Vec<u8>>().unwrap(), vec![1, 2]);
+//! assert_eq!(serde_cbor::to_vec(&envelope).unwrap(), &bytes[..]);
+//! # }
+//! ```
+//!
+//! Deserializing a raw value checks that it is well-formed, but not that its text strings are
+//! valid UTF-8. Serializing one writes it without the checks and changes the serializer is set up
+//! for, so a raw value in a canonical encoding has to be canonical itself already. As the strings
+//! in it would be numbered differently when reading it, it cannot be written with string
+//! references. Other data formats see a byte string.
+//!
+//! This module is available with the `std` feature.
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, IgnoredAny};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+use de::from_slice;
+use diag;
+use error::Result;
+use ser::to_vec;
+use tags::RAW_NEWTYPE_NAME;
+use value::Value;
+use with::Bytes;
+
+/// The encoding of a data item.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RawValue {
+    bytes: Vec<u8>,
+}
+
+impl RawValue {
+    /// Takes the bytes of a single well-formed data item.
+    pub fn from_vec(bytes: Vec<u8>) -> Result<RawValue> {
+        from_slice::<IgnoredAny>(&bytes)?;
+        Ok(RawValue { bytes })
+    }
+
+    /// Copies the bytes of a single well-formed data item.
+    pub fn from_slice(bytes: &[u8]) -> Result<RawValue> {
+        RawValueRef::from_slice(bytes).map(RawValueRef::to_raw_value)
+    }
+
+    /// Serializes a value to a raw value.
+    pub fn from_serialize<T>(value: &T) -> Result<RawValue>
+    where
+        T: Serialize,
+    {
+        Ok(RawValue {
+            bytes: to_vec(value)?,
+        })
+    }
+
+    /// Returns the encoding of the data item.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the encoding of the data item.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Deserializes the data item.
+    pub fn decode<'a, T>(&'a self) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        from_slice(&self.bytes)
+    }
+}
+
+impl fmt::Debug for RawValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RawValue({})", diag::display(&self.bytes))
+    }
+}
+
+impl Serialize for RawValue {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_NEWTYPE_NAME, &Bytes(&self.bytes))
+    }
+}
+
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<RawValue, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawValueVisitor;
+
+        impl<'de> de::Visitor<'de> for RawValueVisitor {
+            type Value = RawValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a data item")
+            }
+
+            fn visit_bytes<E>(self, bytes: &[u8]) -> ::std::result::Result<RawValue, E>
+            where
+                E: de::Error,
+            {
+                Ok(RawValue {
+                    bytes: bytes.to_vec(),
+                })
+            }
+
+            // Deserializers of other formats hand out the content, which is encoded again.
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> ::std::result::Result<RawValue, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                let value = Value::deserialize(deserializer)?;
+                RawValue::from_serialize(&value).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RAW_NEWTYPE_NAME, RawValueVisitor)
+    }
+}
+
+/// The encoding of a data item, borrowed from the input.
+///
+/// It can only be deserialized from a slice, as input from a reader is not kept around to
+/// borrow from.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawValueRef<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> RawValueRef<'a> {
+    /// Takes the bytes of a single well-formed data item.
+    pub fn from_slice(bytes: &'a [u8]) -> Result<RawValueRef<'a>> {
+        from_slice::<IgnoredAny>(bytes)?;
+        Ok(RawValueRef { bytes })
+    }
+
+    /// Returns the encoding of the data item.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Copies the encoding into a `RawValue`.
+    pub fn to_raw_value(self) -> RawValue {
+        RawValue {
+            bytes: self.bytes.to_vec(),
+        }
+    }
+
+    /// Deserializes the data item, which can borrow from the input as well.
+    pub fn decode<T>(&self) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        from_slice(self.bytes)
+    }
+
+    /// Deserializes the data item into a type that does not borrow.
+    pub fn decode_owned<T>(&self) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        from_slice(self.bytes)
+    }
+}
+
+impl<'a> fmt::Debug for RawValueRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RawValueRef({})", diag::display(self.bytes))
+    }
+}
+
+impl<'a> Serialize for RawValueRef<'a> {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_NEWTYPE_NAME, &Bytes(self.bytes))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawValueRef<'a> {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<RawValueRef<'a>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawValueRefVisitor;
+
+        impl<'de> de::Visitor<'de> for RawValueRefVisitor {
+            type Value = RawValueRef<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a data item borrowed from the input")
+            }
+
+            fn visit_borrowed_bytes<E>(
+                self,
+                bytes: &'de [u8],
+            ) -> ::std::result::Result<RawValueRef<'de>, E>
+            where
+                E: de::Error,
+            {
+                Ok(RawValueRef { bytes })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RAW_NEWTYPE_NAME, RawValueRefVisitor)
+    }
+}
@@ -1,133 +1,339 @@
-use std::cmp;
-use std::io::{self, Read as StdRead};
+#[cfg(feature = "std")]
+use core::cmp;
+#[cfg(not(feature = "std"))]
+use core::ops::Deref;
+use core::ops::DerefMut;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(all(feature = "std", feature = "bytes"))]
+use bytes::Buf;
+
+#[cfg(all(feature = "std", feature = "smallvec"))]
+use smallvec::SmallVec;
 
 use error::{Result, Error, ErrorCode};
 
 /// Trait used by the deserializer for iterating over input.
 ///
-/// This trait is sealed and cannot be implemented for types outside of `serde_cbor`.
+/// This trait is sealed and cannot be implemented for types outside of `serde_cbor`, unless the
+/// `unsealed-read` feature is turned on to plug in other input sources. Its methods are tied to
+/// how the deserializer works and may change in minor releases, so that feature is for
+/// applications rather than libraries.
 pub trait Read<'de>: private::Sealed {
-    #[doc(hidden)]
-    fn next(&mut self) -> io::Result<Option<u8>>;
-    #[doc(hidden)]
-    fn peek(&mut self) -> io::Result<Option<u8>>;
-
-    #[doc(hidden)]
-    fn read(
+    /// Consumes and returns the next byte, or `None` at the end of the input.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
+    fn next(&mut self) -> Result<Option<u8>>;
+    /// Returns the next byte without consuming it, or `None` at the end of the input.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
+    fn peek(&mut self) -> Result<Option<u8>>;
+
+    /// Consumes `n` bytes, which are either borrowed from the input or copied into `scratch`
+    /// from `scratch_offset` on, making it just long enough to hold them.
+    ///
+    /// As `n` comes from the input and may be far more than follows it, `scratch` must not grow
+    /// more than `limit` bytes ahead of the input actually copied into it, unless the input is
+    /// known to hold `n` more bytes.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
+    fn read<S>(
         &mut self,
         n: usize,
-        scratch: &mut Vec<u8>,
+        scratch: &mut S,
         scratch_offset: usize,
-    ) -> Result<Reference<'de>>;
+        limit: usize,
+    ) -> Result<Reference<'de>>
+    where
+        S: Scratch;
 
-    #[doc(hidden)]
+    /// Consumes as many bytes as fit into `buf`, failing if the input ends before.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
     fn read_into(&mut self, buf: &mut [u8]) -> Result<()>;
 
-    #[doc(hidden)]
+    /// Consumes `n` bytes without looking at them, failing if the input ends before.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
+    fn skip(&mut self, n: usize) -> Result<()>;
+
+    /// Consumes the byte last returned by `peek`.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
     fn discard(&mut self);
 
-    #[doc(hidden)]
+    /// Returns the number of bytes consumed so far.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
     fn offset(&self) -> u64;
+
+    /// Starts recording the bytes consumed from now on, and returns a position to pass to
+    /// `end_raw`. Recordings may be nested.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
+    fn begin_raw(&mut self) -> usize;
+
+    /// Stops the recording started at `start`, and returns the bytes consumed since, either
+    /// borrowed from the input or copied into `scratch` in place of its contents.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
+    fn end_raw<S>(&mut self, start: usize, scratch: &mut S) -> Reference<'de>
+    where
+        S: Scratch;
+
+    /// Returns whether `end_raw` borrows the bytes it returns, so that recording costs nothing.
+    #[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
+    fn borrows_raw(&self) -> bool {
+        false
+    }
 }
 
+/// Where the bytes consumed by `Read::read` and `Read::end_raw` are found.
 pub enum Reference<'b> {
+    /// The bytes were borrowed from the input.
     Borrowed(&'b [u8]),
+    /// The bytes were copied into the scratch buffer.
     Copied,
 }
 
 mod private {
     pub trait Sealed {}
+
+    #[cfg(feature = "unsealed-read")]
+    impl<T: ?Sized> Sealed for T {}
+}
+
+/// A growable buffer input is copied into when it cannot be borrowed.
+///
+/// Without `std` the buffer cannot grow, and input has to be borrowed.
+#[cfg_attr(not(feature = "unsealed-read"), doc(hidden))]
+pub trait Scratch: DerefMut<Target = [u8]> {
+    /// Empties the buffer.
+    fn clear(&mut self);
+    /// Grows or shrinks the buffer to `len` bytes, filling it up with zeros.
+    fn resize(&mut self, len: usize);
+    /// Appends `buf` to the buffer.
+    fn extend_from_slice(&mut self, buf: &[u8]);
+}
+
+#[cfg(feature = "std")]
+impl Scratch for Vec<u8> {
+    #[inline]
+    fn clear(&mut self) {
+        Vec::clear(self)
+    }
+
+    #[inline]
+    fn resize(&mut self, len: usize) {
+        Vec::resize(self, len, 0)
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, buf: &[u8]) {
+        Vec::extend_from_slice(self, buf)
+    }
+}
+
+#[cfg(all(feature = "std", feature = "smallvec"))]
+impl<A> Scratch for SmallVec<A>
+where
+    A: ::smallvec::Array<Item = u8>,
+{
+    #[inline]
+    fn clear(&mut self) {
+        SmallVec::clear(self)
+    }
+
+    #[inline]
+    fn resize(&mut self, len: usize) {
+        SmallVec::resize(self, len, 0)
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, buf: &[u8]) {
+        SmallVec::extend_from_slice(self, buf)
+    }
+}
+
+/// Stands in for a scratch buffer without `std`, where input is only ever borrowed from slices.
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[derive(Default)]
+pub struct NoScratch;
+
+#[cfg(not(feature = "std"))]
+impl NoScratch {
+    pub fn new() -> NoScratch {
+        NoScratch
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Deref for NoScratch {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &[]
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl DerefMut for NoScratch {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Scratch for NoScratch {
+    fn clear(&mut self) {}
+
+    fn resize(&mut self, _: usize) {
+        unreachable!("input from slices is never copied")
+    }
+
+    fn extend_from_slice(&mut self, _: &[u8]) {
+        unreachable!("input from slices is never copied")
+    }
 }
 
 /// CBOR input source that reads from a std::io input stream.
+///
+/// Input is read in chunks into a buffer of 8 KiB, so that the many small reads of parsing do not
+/// each reach the reader, which is slow for unbuffered files and sockets. This means that bytes
+/// after the end of a data item may be taken from the reader and lost with the `IoRead`. Where
+/// whatever follows a data item is still to be read from the reader, `with_capacity` with a
+/// buffer of a single byte reads no further than needed.
+#[cfg(feature = "std")]
 pub struct IoRead<R>
 where
     R: io::Read,
 {
-    reader: OffsetReader<R>,
-    ch: Option<u8>,
+    reader: R,
+    // Input read ahead of time, of which `buf[pos..len]` is yet to be consumed.
+    buf: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    offset: u64,
+    raw: Vec<u8>,
+    raw_depth: usize,
 }
 
+#[cfg(feature = "std")]
 impl<R> IoRead<R>
 where
     R: io::Read,
 {
     /// Creates a new CBOR input source to read from a std::io input stream.
     pub fn new(reader: R) -> IoRead<R> {
+        IoRead::with_capacity(reader, 8 * 1024)
+    }
+
+    /// Creates a new CBOR input source to read from a std::io input stream with a buffer of
+    /// `capacity` bytes, or of a single byte if `capacity` is zero.
+    pub fn with_capacity(reader: R, capacity: usize) -> IoRead<R> {
         IoRead {
-            reader: OffsetReader {
-                reader,
-                offset: 0,
-            },
-            ch: None,
+            reader,
+            buf: vec![0; cmp::max(capacity, 1)].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            offset: 0,
+            raw: Vec::new(),
+            raw_depth: 0,
+        }
+    }
+
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        if self.raw_depth > 0 {
+            self.raw.extend_from_slice(&self.buf[self.pos..self.pos + n]);
         }
+        self.pos += n;
+        self.offset += n as u64;
     }
 
+    // Returns the number of buffered bytes, refilling the buffer if it is empty. Zero means
+    // the end of the input.
     #[inline]
-    fn next_inner(&mut self) -> io::Result<Option<u8>> {
-        let mut buf = [0; 1];
-        loop {
-            match self.reader.read(&mut buf) {
-                Ok(0) => return Ok(None),
-                Ok(_) => return Ok(Some(buf[0])),
+    fn fill(&mut self) -> Result<usize> {
+        if self.pos == self.len {
+            self.pos = 0;
+            self.len = 0;
+            loop {
+                match self.reader.read(&mut self.buf) {
+                    Ok(len) => {
+                        self.len = len;
+                        break;
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(Error::io(e)),
+                }
+            }
+        }
+        Ok(self.len - self.pos)
+    }
+
+    // Reads exactly enough to fill `buf` from the reader, bypassing the buffer.
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.reader.read(buf) {
+                Ok(0) => {
+                    return Err(Error::syntax(
+                        ErrorCode::EofWhileParsingValue,
+                        self.offset,
+                    ))
+                }
+                Ok(count) => {
+                    self.offset += count as u64;
+                    buf = &mut { buf }[count..]
+                }
                 Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
-                Err(e) => return Err(e),
+                Err(e) => return Err(Error::io(e)),
             }
         }
+        Ok(())
     }
 }
 
+#[cfg(all(feature = "std", not(feature = "unsealed-read")))]
 impl<R> private::Sealed for IoRead<R>
 where
     R: io::Read,
 {
 }
 
+#[cfg(feature = "std")]
 impl<'de, R> Read<'de> for IoRead<R>
 where
     R: io::Read,
 {
     #[inline]
-    fn next(&mut self) -> io::Result<Option<u8>> {
-        match self.ch.take() {
-            Some(ch) => Ok(Some(ch)),
-            None => self.next_inner(),
+    fn next(&mut self) -> Result<Option<u8>> {
+        if self.fill()? == 0 {
+            return Ok(None);
         }
+        let ch = self.buf[self.pos];
+        self.consume(1);
+        Ok(Some(ch))
     }
 
     #[inline]
-    fn peek(&mut self) -> io::Result<Option<u8>> {
-        match self.ch {
-            Some(ch) => Ok(Some(ch)),
-            None => {
-                self.ch = self.next_inner()?;
-                Ok(self.ch)
-            }
+    fn peek(&mut self) -> Result<Option<u8>> {
+        if self.fill()? == 0 {
+            return Ok(None);
         }
+        Ok(Some(self.buf[self.pos]))
     }
 
-    fn read(
+    fn read<S>(
         &mut self,
         mut n: usize,
-        scratch: &mut Vec<u8>,
+        scratch: &mut S,
         mut scratch_offset: usize,
-    ) -> Result<Reference<'de>> {
+        limit: usize,
+    ) -> Result<Reference<'de>>
+    where
+        S: Scratch,
+    {
         while n > 0 {
             // defend against malicious input pretending to be huge strings by limiting growth
-            let to_read = cmp::min(n, 16 * 1024);
+            let to_read = cmp::min(n, cmp::max(limit, 1));
             n -= to_read;
-
-            if to_read > scratch.len() - scratch_offset {
-                scratch.resize(scratch_offset + to_read, 0);
-            }
-
-            if let Some(ch) = self.ch.take() {
-                scratch[scratch_offset] = ch;
-                scratch_offset += 1;
-            }
-
+            scratch.resize(scratch_offset + to_read);
             self.read_into(&mut scratch[scratch_offset..])?;
-            scratch_offset = scratch.len();
+            scratch_offset += to_read;
         }
 
         Ok(Reference::Copied)
@@ -135,52 +341,208 @@ where
 
     fn read_into(&mut self, mut buf: &mut [u8]) -> Result<()> {
         while !buf.is_empty() {
-            match self.reader.read(buf) {
-                Ok(0) => {
-                    return Err(Error::syntax(
-                        ErrorCode::EofWhileParsingValue,
-                        self.offset(),
-                    ))
-                }
-                Ok(count) => {
-                    buf = &mut {
-                        buf
-                    }[count..]
+            // Large reads go straight to the reader once the buffer is used up.
+            if self.pos == self.len && buf.len() >= self.buf.len() {
+                self.read_exact(buf)?;
+                if self.raw_depth > 0 {
+                    self.raw.extend_from_slice(buf);
                 }
-                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
-                Err(e) => return Err(Error::io(e)),
+                return Ok(());
             }
+            let available = self.fill()?;
+            if available == 0 {
+                return Err(Error::syntax(
+                    ErrorCode::EofWhileParsingValue,
+                    self.offset,
+                ));
+            }
+            let count = cmp::min(available, buf.len());
+            buf[..count].copy_from_slice(&self.buf[self.pos..self.pos + count]);
+            self.consume(count);
+            buf = &mut { buf }[count..];
         }
+        Ok(())
+    }
 
+    fn skip(&mut self, mut n: usize) -> Result<()> {
+        while n > 0 {
+            let available = self.fill()?;
+            if available == 0 {
+                return Err(Error::syntax(
+                    ErrorCode::EofWhileParsingValue,
+                    self.offset,
+                ));
+            }
+            let count = cmp::min(available, n);
+            self.consume(count);
+            n -= count;
+        }
         Ok(())
     }
 
     #[inline]
     fn discard(&mut self) {
-        self.ch = None;
+        self.consume(1);
     }
 
     fn offset(&self) -> u64 {
-        self.reader.offset
+        self.offset
+    }
+
+    fn begin_raw(&mut self) -> usize {
+        self.raw_depth += 1;
+        self.raw.len()
+    }
+
+    fn end_raw<S>(&mut self, start: usize, scratch: &mut S) -> Reference<'de>
+    where
+        S: Scratch,
+    {
+        scratch.clear();
+        scratch.extend_from_slice(&self.raw[start..]);
+        self.raw.truncate(start);
+        self.raw_depth -= 1;
+        Reference::Copied
     }
 }
 
-struct OffsetReader<R> {
-    reader: R,
+/// CBOR input source that reads from a `bytes::Buf`.
+///
+/// The buffer may consist of several chunks, such as a chain of `Bytes`, which are read one
+/// after another without first being copied into one contiguous slice. Strings and byte strings
+/// are copied out of the buffer as they cannot be borrowed from it. Input is consumed from the
+/// buffer as it is read, so reading from `&mut buf` leaves what follows a data item in `buf`.
+///
+/// This type is available with the `bytes` feature.
+#[cfg(all(feature = "std", feature = "bytes"))]
+pub struct BytesRead<B> {
+    buf: B,
     offset: u64,
+    raw: Vec<u8>,
+    raw_depth: usize,
 }
 
-impl<R> io::Read for OffsetReader<R>
+#[cfg(all(feature = "std", feature = "bytes"))]
+impl<B> BytesRead<B>
 where
-    R: io::Read,
+    B: Buf,
 {
+    /// Creates a CBOR input source to read from a `bytes::Buf`.
+    pub fn new(buf: B) -> BytesRead<B> {
+        BytesRead {
+            buf,
+            offset: 0,
+            raw: Vec::new(),
+            raw_depth: 0,
+        }
+    }
+
+    // Consumes `n` bytes, which must not be more than the current chunk holds.
     #[inline]
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let r = self.reader.read(buf);
-        if let Ok(count) = r {
-            self.offset += count as u64;
+    fn consume(&mut self, n: usize) {
+        if self.raw_depth > 0 {
+            self.raw.extend_from_slice(&self.buf.chunk()[..n]);
         }
-        r
+        self.buf.advance(n);
+        self.offset += n as u64;
+    }
+
+    // Fails unless there are at least `n` bytes left.
+    fn check_remaining(&self, n: usize) -> Result<()> {
+        let remaining = self.buf.remaining();
+        if n > remaining {
+            let offset = self.offset + remaining as u64;
+            return Err(Error::syntax(ErrorCode::EofWhileParsingValue, offset));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "std", feature = "bytes", not(feature = "unsealed-read")))]
+impl<B> private::Sealed for BytesRead<B> where B: Buf {}
+
+#[cfg(all(feature = "std", feature = "bytes"))]
+impl<'de, B> Read<'de> for BytesRead<B>
+where
+    B: Buf,
+{
+    #[inline]
+    fn next(&mut self) -> Result<Option<u8>> {
+        let ch = match self.buf.chunk().first() {
+            Some(&ch) => ch,
+            None => return Ok(None),
+        };
+        self.consume(1);
+        Ok(Some(ch))
+    }
+
+    #[inline]
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.buf.chunk().first().cloned())
+    }
+
+    fn read<S>(
+        &mut self,
+        n: usize,
+        scratch: &mut S,
+        scratch_offset: usize,
+        _: usize,
+    ) -> Result<Reference<'de>>
+    where
+        S: Scratch,
+    {
+        // The length of the buffer is known, so a string longer than the input is rejected
+        // before any memory is set aside for it.
+        self.check_remaining(n)?;
+        scratch.resize(scratch_offset + n);
+        self.read_into(&mut scratch[scratch_offset..])?;
+        Ok(Reference::Copied)
+    }
+
+    fn read_into(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        self.check_remaining(buf.len())?;
+        while !buf.is_empty() {
+            let count = cmp::min(self.buf.chunk().len(), buf.len());
+            buf[..count].copy_from_slice(&self.buf.chunk()[..count]);
+            self.consume(count);
+            buf = &mut { buf }[count..];
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, mut n: usize) -> Result<()> {
+        self.check_remaining(n)?;
+        while n > 0 {
+            let count = cmp::min(self.buf.chunk().len(), n);
+            self.consume(count);
+            n -= count;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn discard(&mut self) {
+        self.consume(1);
+    }
+
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn begin_raw(&mut self) -> usize {
+        self.raw_depth += 1;
+        self.raw.len()
+    }
+
+    fn end_raw<S>(&mut self, start: usize, scratch: &mut S) -> Reference<'de>
+    where
+        S: Scratch,
+    {
+        scratch.clear();
+        scratch.extend_from_slice(&self.raw[start..]);
+        self.raw.truncate(start);
+        self.raw_depth -= 1;
+        Reference::Copied
     }
 }
 
@@ -212,11 +574,12 @@ impl<'a> SliceRead<'a> {
     }
 }
 
+#[cfg(not(feature = "unsealed-read"))]
 impl<'a> private::Sealed for SliceRead<'a> {}
 
 impl<'a> Read<'a> for SliceRead<'a> {
     #[inline]
-    fn next(&mut self) -> io::Result<Option<u8>> {
+    fn next(&mut self) -> Result<Option<u8>> {
         Ok(if self.index < self.slice.len() {
             let ch = self.slice[self.index];
             self.index += 1;
@@ -227,7 +590,7 @@ impl<'a> Read<'a> for SliceRead<'a> {
     }
 
     #[inline]
-    fn peek(&mut self) -> io::Result<Option<u8>> {
+    fn peek(&mut self) -> Result<Option<u8>> {
         Ok(if self.index < self.slice.len() {
             Some(self.slice[self.index])
         } else {
@@ -236,7 +599,10 @@ impl<'a> Read<'a> for SliceRead<'a> {
     }
 
     #[inline]
-    fn read(&mut self, n: usize, _: &mut Vec<u8>, _: usize) -> Result<Reference<'a>> {
+    fn read<S>(&mut self, n: usize, _: &mut S, _: usize, _: usize) -> Result<Reference<'a>>
+    where
+        S: Scratch,
+    {
         let end = self.end(n)?;
         let slice = &self.slice[self.index..end];
         self.index = end;
@@ -251,6 +617,12 @@ impl<'a> Read<'a> for SliceRead<'a> {
         Ok(())
     }
 
+    #[inline]
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.index = self.end(n)?;
+        Ok(())
+    }
+
     #[inline]
     fn discard(&mut self) {
         self.index += 1;
@@ -259,4 +631,21 @@ impl<'a> Read<'a> for SliceRead<'a> {
     fn offset(&self) -> u64 {
         self.index as u64
     }
+
+    #[inline]
+    fn begin_raw(&mut self) -> usize {
+        self.index
+    }
+
+    #[inline]
+    fn end_raw<S>(&mut self, start: usize, _: &mut S) -> Reference<'a>
+    where
+        S: Scratch,
+    {
+        Reference::Borrowed(&self.slice[start..self.index])
+    }
+
+    fn borrows_raw(&self) -> bool {
+        true
+    }
 }
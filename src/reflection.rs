@@ -0,0 +1,250 @@
+//! Describing the CBOR encoding of types for schema generation in other languages.
+//!
+//! A [`Tracer`] collects the formats of a set of types with
+//! [serde-reflection](https://docs.rs/serde-reflection), configured the way this crate serializes
+//! them: not human-readable, so types like `serde_bytes::ByteBuf` are recorded as byte strings.
+//! The resulting registry can be processed with the serde-reflection ecosystem or written down as
+//! a CDDL schema (RFC 8610) with `to_cddl` and `to_cddl_packed`.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! use serde_cbor::reflection::{self, Tracer};
+//!
+//! #[derive(Deserialize)]
+//! struct Point {
+//!     x: i64,
+//!     y: i64,
+//! }
+//!
+//! # fn main() {
+//! let mut tracer = Tracer::new();
+//! tracer.trace_type::<Point>().unwrap();
+//! let registry = tracer.registry().unwrap();
+//! let cddl = reflection::to_cddl(&registry).unwrap();
+//! assert_eq!(cddl, "Point = {\n  \"x\": int,\n  \"y\": int,\n}\n");
+//! # }
+//! ```
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_reflection::{self, ContainerFormat, Format, Named, Registry, Samples, TracerConfig,
+                       VariantFormat};
+use std::fmt::{self, Write};
+use std::slice;
+
+use error::{Error, ErrorCode, Result};
+
+/// Collects the formats of types as they are encoded by this crate.
+pub struct Tracer {
+    tracer: serde_reflection::Tracer,
+    samples: Samples,
+}
+
+impl Tracer {
+    /// Creates a new tracer without any types.
+    pub fn new() -> Tracer {
+        Tracer {
+            tracer: serde_reflection::Tracer::new(TracerConfig::default().is_human_readable(false)),
+            samples: Samples::new(),
+        }
+    }
+
+    /// Traces a type by deserializing it, covering all variants of enums.
+    ///
+    /// Only the variants of `T` itself are covered, so enums nested in `T` need to be traced on
+    /// their own before `registry` is called. Types that validate their input while deserializing
+    /// may need a sample value recorded with `trace_value` first.
+    pub fn trace_type<T>(&mut self) -> Result<&mut Tracer>
+    where
+        T: DeserializeOwned,
+    {
+        self.tracer
+            .trace_type::<T>(&self.samples)
+            .map_err(error)?;
+        Ok(self)
+    }
+
+    /// Traces the type of a value by serializing it, and keeps the value as a sample for tracing
+    /// types that contain it.
+    pub fn trace_value<T>(&mut self, value: &T) -> Result<&mut Tracer>
+    where
+        T: Serialize,
+    {
+        self.tracer
+            .trace_value(&mut self.samples, value)
+            .map_err(error)?;
+        Ok(self)
+    }
+
+    /// Returns the formats of all types seen so far.
+    ///
+    /// Fails if the format of some type could not be determined completely.
+    pub fn registry(self) -> Result<Registry> {
+        self.tracer.registry().map_err(error)
+    }
+}
+
+impl Default for Tracer {
+    fn default() -> Tracer {
+        Tracer::new()
+    }
+}
+
+fn error<T: fmt::Display>(msg: T) -> Error {
+    Error::syntax(ErrorCode::Message(msg.to_string()), 0)
+}
+
+/// Writes a CDDL schema for the types in `registry` as encoded by `to_vec`, with one rule per
+/// type.
+pub fn to_cddl(registry: &Registry) -> Result<String> {
+    Cddl { packed: false }.registry(registry)
+}
+
+/// Writes a CDDL schema for the types in `registry` as encoded by `to_vec_packed`, where struct
+/// fields and enum variants are identified by their index.
+pub fn to_cddl_packed(registry: &Registry) -> Result<String> {
+    Cddl { packed: true }.registry(registry)
+}
+
+struct Cddl {
+    packed: bool,
+}
+
+impl Cddl {
+    fn registry(&self, registry: &Registry) -> Result<String> {
+        let mut out = String::new();
+        for (name, container) in registry {
+            out.push_str(name);
+            out.push_str(" = ");
+            match *container {
+                ContainerFormat::UnitStruct => out.push_str("null"),
+                ContainerFormat::NewTypeStruct(ref format) => self.format(format, &mut out)?,
+                ContainerFormat::TupleStruct(ref formats) => {
+                    self.tuple(None, formats, &mut out)?
+                }
+                ContainerFormat::Struct(ref fields) => self.fields(fields, &mut out)?,
+                ContainerFormat::Enum(ref variants) => {
+                    if variants.is_empty() {
+                        return Err(error(format_args!("{} has no variants", name)));
+                    }
+                    for (i, (index, variant)) in variants.iter().enumerate() {
+                        if i != 0 {
+                            out.push_str("\n  / ");
+                        }
+                        self.variant(*index, variant, &mut out)?;
+                    }
+                }
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    // Writes the encoding of an enum variant, which is its identifier for unit variants and an
+    // array starting with it otherwise.
+    fn variant(&self, index: u32, variant: &Named<VariantFormat>, out: &mut String) -> Result<()> {
+        let key = self.key(index, &variant.name);
+        match variant.value {
+            VariantFormat::Unit => out.push_str(&key),
+            VariantFormat::NewType(ref format) => {
+                self.tuple(Some(&key), slice::from_ref(&**format), out)?
+            }
+            VariantFormat::Tuple(ref formats) => self.tuple(Some(&key), formats, out)?,
+            VariantFormat::Struct(ref fields) => {
+                write!(out, "[{}, ", key).unwrap();
+                self.fields(fields, out)?;
+                out.push(']');
+            }
+            VariantFormat::Variable(_) => {
+                return Err(error(format_args!("unknown format of {}", variant.name)))
+            }
+        }
+        Ok(())
+    }
+
+    fn tuple(&self, first: Option<&str>, formats: &[Format], out: &mut String) -> Result<()> {
+        out.push('[');
+        let mut separator = "";
+        if let Some(first) = first {
+            out.push_str(first);
+            separator = ", ";
+        }
+        for format in formats {
+            out.push_str(separator);
+            self.format(format, out)?;
+            separator = ", ";
+        }
+        out.push(']');
+        Ok(())
+    }
+
+    fn fields(&self, fields: &[Named<Format>], out: &mut String) -> Result<()> {
+        out.push('{');
+        for (index, field) in fields.iter().enumerate() {
+            write!(out, "\n  {}: ", self.key(index as u32, &field.name)).unwrap();
+            self.format(&field.value, out)?;
+            out.push(',');
+        }
+        if !fields.is_empty() {
+            out.push('\n');
+        }
+        out.push('}');
+        Ok(())
+    }
+
+    fn key(&self, index: u32, name: &str) -> String {
+        if self.packed {
+            index.to_string()
+        } else {
+            format!("{:?}", name)
+        }
+    }
+
+    fn format(&self, format: &Format, out: &mut String) -> Result<()> {
+        let cddl = match *format {
+            Format::TypeName(ref name) => name,
+            Format::Unit => "null",
+            Format::Bool => "bool",
+            Format::I8 => "-128..127",
+            Format::I16 => "-32768..32767",
+            Format::I32 => "-2147483648..2147483647",
+            Format::I64 | Format::I128 => "int",
+            Format::U8 => "uint .size 1",
+            Format::U16 => "uint .size 2",
+            Format::U32 => "uint .size 4",
+            Format::U64 | Format::U128 => "uint",
+            Format::F32 | Format::F64 => "float",
+            Format::Char | Format::Str => "tstr",
+            Format::Bytes => "bstr",
+            Format::Option(ref format) => {
+                self.format(format, out)?;
+                out.push_str(" / null");
+                return Ok(());
+            }
+            Format::Seq(ref format) => {
+                out.push_str("[* ");
+                self.format(format, out)?;
+                out.push(']');
+                return Ok(());
+            }
+            Format::Map { ref key, ref value } => {
+                out.push_str("{* ");
+                self.format(key, out)?;
+                out.push_str(" => ");
+                self.format(value, out)?;
+                out.push('}');
+                return Ok(());
+            }
+            Format::Tuple(ref formats) => return self.tuple(None, formats, out),
+            Format::TupleArray { ref content, size } => {
+                write!(out, "[{}*{} ", size, size).unwrap();
+                self.format(content, out)?;
+                out.push(']');
+                return Ok(());
+            }
+            Format::Variable(_) => return Err(error("unknown format")),
+        };
+        out.push_str(cddl);
+        Ok(())
+    }
+}
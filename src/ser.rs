@@ -1,12 +1,136 @@
 //! Serialize a Rust data structure to CBOR data.
 use byteorder::{ByteOrder, BigEndian};
+#[cfg(feature = "std")]
+use core::cmp::Ordering;
+use core::mem;
+#[cfg(all(feature = "std", feature = "digest"))]
+use digest::{Digest, Output};
+#[cfg(feature = "float")]
 use half::f16;
 use serde::ser::{self, Serialize};
+#[cfg(feature = "dcbor")]
+use unicode_normalization::is_nfc;
+#[cfg(feature = "std")]
 use std::io;
 
+#[cfg(not(feature = "float"))]
+use error::ErrorCode;
 use error::{Error, Result};
+#[cfg(feature = "std")]
+use stringref::StringTable;
+use tags;
+#[cfg(all(feature = "std", any(feature = "futures-io", feature = "tokio")))]
+pub use async_write::{to_async_writer, AsyncIoWrite, AsyncSink, Flush, ToAsyncWriter, WriteValue};
+#[cfg(all(feature = "std", feature = "tokio"))]
+pub use async_read::TokioIo;
+
+/// A sink for encoded CBOR data.
+///
+/// This is implemented for every `std::io::Write`, and for `SliceWrite` which also works without
+/// `std`.
+pub trait Write {
+    /// Writes all of `buf`, or fails.
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+}
+
+#[cfg(feature = "std")]
+impl<W> Write for W
+where
+    W: io::Write,
+{
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        io::Write::write_all(self, buf).map_err(Error::io)
+    }
+}
+
+/// Writes encoded CBOR data into a slice.
+///
+/// Writing more than fits into the slice fails with an error in the `Io` category.
+pub struct SliceWrite<'a> {
+    slice: &'a mut [u8],
+    index: usize,
+}
+
+impl<'a> SliceWrite<'a> {
+    /// Creates a sink that writes into `slice` from its start.
+    pub fn new(slice: &'a mut [u8]) -> SliceWrite<'a> {
+        SliceWrite { slice, index: 0 }
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn bytes_written(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the slice that was written into.
+    pub fn into_inner(self) -> &'a mut [u8] {
+        self.slice
+    }
+}
+
+impl<'a> Write for SliceWrite<'a> {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        if buf.len() > self.slice.len() - self.index {
+            return Err(Error::buffer_full());
+        }
+        self.slice[self.index..self.index + buf.len()].copy_from_slice(buf);
+        self.index += buf.len();
+        Ok(())
+    }
+}
+
+/// Serializes a value into a slice and returns the number of bytes written.
+///
+/// This works without `std` and does not allocate.
+///
+/// ```
+/// let mut buf = [0; 16];
+/// let len = serde_cbor::ser::to_slice(&mut buf, &("hi", 100)).unwrap();
+/// assert_eq!(&buf[..len], b"\x82\x62hi\x18\x64");
+/// assert!(serde_cbor::ser::to_slice(&mut buf[..4], &("hi", 100)).is_err());
+/// ```
+pub fn to_slice<T>(slice: &mut [u8], value: &T) -> Result<usize>
+where
+    T: ser::Serialize,
+{
+    let mut ser = Serializer::new(SliceWrite::new(slice));
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner().bytes_written())
+}
+
+/// Returns the number of bytes a value is serialized in, without keeping them.
+///
+/// This works without `std` and does not allocate, so buffers can be sized and size limits
+/// checked before serializing for real.
+///
+/// ```
+/// let len = serde_cbor::ser::serialized_size(&("hi", 100)).unwrap();
+/// assert_eq!(len, 6);
+/// ```
+pub fn serialized_size<T>(value: &T) -> Result<usize>
+where
+    T: ser::Serialize,
+{
+    let mut ser = Serializer::new(SizeCounter(0));
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner().0)
+}
+
+// Counts the bytes written to it.
+struct SizeCounter(usize);
+
+impl Write for SizeCounter {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.0 += buf.len();
+        Ok(())
+    }
+}
 
 /// Serializes a value to a writer.
+#[cfg(feature = "std")]
 pub fn to_writer<W, T>(mut writer: &mut W, value: &T) -> Result<()>
 where
     W: io::Write,
@@ -15,7 +139,21 @@ where
     value.serialize(&mut Serializer::new(&mut writer))
 }
 
+/// Serializes a value to a writer behind a trait object.
+///
+/// Unlike `to_writer`, this instantiates the serializer only once for all kinds of writers, which
+/// keeps binaries small where code size matters more than speed, such as in firmware or
+/// WebAssembly.
+#[cfg(feature = "std")]
+pub fn to_dyn_writer<T>(mut writer: &mut io::Write, value: &T) -> Result<()>
+where
+    T: ser::Serialize,
+{
+    value.serialize(&mut Serializer::new(&mut writer))
+}
+
 /// Serializes a value to a writer and adds a CBOR self-describe tag.
+#[cfg(feature = "std")]
 pub fn to_writer_sd<W, T>(mut writer: &mut W, value: &T) -> Result<()>
 where
     W: io::Write,
@@ -30,6 +168,7 @@ where
 ///
 /// Struct fields and enum variants are identified by their numeric indices rather than names to
 /// save space.
+#[cfg(feature = "std")]
 pub fn to_writer_packed<W, T>(mut writer: &mut W, value: &T) -> Result<()>
 where
     W: io::Write,
@@ -42,6 +181,7 @@ where
 ///
 /// Struct fields and enum variants are identified by their numeric indices rather than names to
 /// save space.
+#[cfg(feature = "std")]
 pub fn to_writer_packed_sd<W, T>(mut writer: &mut W, value: &T) -> Result<()>
 where
     W: io::Write,
@@ -53,6 +193,7 @@ where
 }
 
 /// Serializes a value to a vector.
+#[cfg(feature = "std")]
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
 where
     T: ser::Serialize,
@@ -63,6 +204,7 @@ where
 }
 
 /// Serializes a value to a vector and adds a CBOR self-describe tag.
+#[cfg(feature = "std")]
 pub fn to_vec_sd<T>(value: &T) -> Result<Vec<u8>>
 where
     T: ser::Serialize,
@@ -76,6 +218,7 @@ where
 ///
 /// Struct fields and enum variants are identified by their numeric indices rather than names to
 /// save space.
+#[cfg(feature = "std")]
 pub fn to_vec_packed<T>(value: &T) -> Result<Vec<u8>>
 where
     T: ser::Serialize,
@@ -88,6 +231,7 @@ where
 ///
 /// Struct fields and enum variants are identified by their numeric indices rather than names to
 /// save space.
+#[cfg(feature = "std")]
 pub fn to_vec_packed_sd<T>(value: &T) -> Result<Vec<u8>>
 where
     T: ser::Serialize,
@@ -97,15 +241,185 @@ where
     Ok(vec)
 }
 
+/// Serializes a value to a writer in the deterministic encoding of RFC 8949, section 4.2.1.
+///
+/// See `Serializer::canonical` for what the encoding requires.
+#[cfg(feature = "std")]
+pub fn to_writer_canonical<W, T>(mut writer: &mut W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ser::Serialize,
+{
+    value.serialize(&mut Serializer::canonical(&mut writer))
+}
+
+/// Serializes a value to a vector in the deterministic encoding of RFC 8949, section 4.2.1.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// let mut map = HashMap::new();
+/// map.insert("aa", 1.5);
+/// map.insert("b", 100000.0);
+/// // {"b": 100000.0, "aa": 1.5}
+/// let bytes = serde_cbor::ser::to_vec_canonical(&map).unwrap();
+/// assert_eq!(bytes, b"\xa2\x61b\xfa\x47\xc3\x50\x00\x62aa\xf9\x3e\x00");
+/// ```
+#[cfg(feature = "std")]
+pub fn to_vec_canonical<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ser::Serialize,
+{
+    let mut vec = Vec::new();
+    to_writer_canonical(&mut vec, value)?;
+    Ok(vec)
+}
+
+/// Serializes a value to a vector in the canonical encoding of CTAP2.
+///
+/// See `Serializer::ctap2` for what the encoding requires.
+#[cfg(feature = "std")]
+pub fn to_vec_ctap2<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ser::Serialize,
+{
+    let mut vec = Vec::new();
+    value.serialize(&mut Serializer::ctap2(&mut vec))?;
+    Ok(vec)
+}
+
+/// Computes the digest of the deterministic encoding of a value.
+///
+/// The value is encoded as required by section 4.2.1 of RFC 8949, with definite lengths and map
+/// entries sorted by their encoded keys, so that equal values have equal digests regardless of
+/// the order their maps were filled in. This makes the digest suitable for addressing content
+/// or as a cache key. The encoding is fed to the hasher as it is produced, and only the content
+/// of maps and of sequences of unknown length is buffered.
+///
+/// ```
+/// # extern crate serde_cbor;
+/// # extern crate sha2;
+/// use sha2::{Digest, Sha256};
+/// use std::collections::HashMap;
+///
+/// # fn main() {
+/// let mut map = HashMap::new();
+/// map.insert("b", 2);
+/// map.insert("a", 1);
+/// let hash = serde_cbor::ser::canonical_hash::<Sha256, _>(&map).unwrap();
+/// assert_eq!(hash, Sha256::digest(b"\xa2\x61a\x01\x61b\x02"));
+/// # }
+/// ```
+///
+/// This function is available with the `digest` feature.
+#[cfg(all(feature = "std", feature = "digest"))]
+pub fn canonical_hash<D, T>(value: &T) -> Result<Output<D>>
+where
+    D: Digest,
+    T: ser::Serialize,
+{
+    let mut ser = Serializer::canonical(DigestWriter(D::new()));
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner().0.finalize())
+}
+
+#[cfg(all(feature = "std", feature = "digest"))]
+struct DigestWriter<D>(D);
+
+#[cfg(all(feature = "std", feature = "digest"))]
+impl<D> io::Write for DigestWriter<D>
+where
+    D: Digest,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Items up to this size are assembled on the stack and handed to the writer in one piece.
+const INLINE_LEN: usize = 64;
+
+// Encodes the shortest head for `value` into `buf`, returning the number of bytes used.
+#[inline]
+pub(crate) fn encode_head(major: u8, value: u64, buf: &mut [u8]) -> usize {
+    if value <= 0x17 {
+        buf[0] = major << 5 | value as u8;
+        1
+    } else if value <= u64::from(u8::max_value()) {
+        buf[0] = major << 5 | 24;
+        buf[1] = value as u8;
+        2
+    } else if value <= u64::from(u16::max_value()) {
+        buf[0] = major << 5 | 25;
+        BigEndian::write_u16(&mut buf[1..3], value as u16);
+        3
+    } else if value <= u64::from(u32::max_value()) {
+        buf[0] = major << 5 | 26;
+        BigEndian::write_u32(&mut buf[1..5], value as u32);
+        5
+    } else {
+        buf[0] = major << 5 | 27;
+        BigEndian::write_u64(&mut buf[1..9], value);
+        9
+    }
+}
+
+/// The order in which a canonical serializer writes map entries, by the encoding of their keys.
+///
+/// This type is available with the `std` feature.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyOrder {
+    /// Bytewise lexicographic order, as in the deterministic encoding of RFC 8949. This is the
+    /// default.
+    Bytewise,
+    /// Shorter keys first and bytewise among keys of equal length, as in the canonical encoding
+    /// of RFC 7049.
+    LengthFirst,
+    /// Keys of lower major types first, and among those as in `LengthFirst`, as in the canonical
+    /// encoding of CTAP2. Serializers with this order also refuse to write tags, which CTAP2
+    /// rules out.
+    Ctap2,
+}
+
+#[cfg(feature = "std")]
+impl KeyOrder {
+    // Compares the encodings of two keys.
+    pub(crate) fn compare(self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            KeyOrder::Bytewise => a.cmp(b),
+            KeyOrder::LengthFirst => (a.len(), a).cmp(&(b.len(), b)),
+            KeyOrder::Ctap2 => (a[0] >> 5, a.len(), a).cmp(&(b[0] >> 5, b.len(), b)),
+        }
+    }
+}
+
 /// A structure for serializing Rust values to CBOR.
 pub struct Serializer<W> {
     writer: W,
     packed: bool,
+    // The order of map keys, if the encoding is canonical.
+    #[cfg(feature = "std")]
+    canonical: Option<KeyOrder>,
+    #[cfg(feature = "dcbor")]
+    dcbor: bool,
+    // Whether a self-describe tag is still to be written before the first data item.
+    self_describe: bool,
+    // The strings numbered so far, if repeated strings are written as references.
+    #[cfg(feature = "std")]
+    string_refs: Option<StringTable>,
+    // Whether the next byte string is the encoding of a data item to be written as it is.
+    raw: bool,
 }
 
 impl<W> Serializer<W>
 where
-    W: io::Write,
+    W: Write,
 {
     /// Creates a new CBOR serializer.
     #[inline]
@@ -113,6 +427,14 @@ where
         Serializer {
             writer,
             packed: false,
+            #[cfg(feature = "std")]
+            canonical: None,
+            #[cfg(feature = "dcbor")]
+            dcbor: false,
+            self_describe: false,
+            #[cfg(feature = "std")]
+            string_refs: None,
+            raw: false,
         }
     }
 
@@ -120,23 +442,243 @@ where
     ///
     /// Struct fields and enum variants are identified by their numeric indices rather than names
     /// to save space.
+    ///
+    /// This is the same as `Serializer::new(writer).packed_format()`.
     #[inline]
     pub fn packed(writer: W) -> Serializer<W> {
+        Serializer::new(writer).packed_format()
+    }
+
+    /// Creates a new CBOR serializer for the deterministic encoding of RFC 8949, section 4.2.1.
+    ///
+    /// Integers, lengths and floats take the fewest bytes that keep their value, collections
+    /// have definite lengths, and map entries are sorted by the encoding of their keys. Equal
+    /// values thus always encode to the same bytes, so the output can be signed or compared
+    /// byte for byte. Maps and sequences of unknown length are buffered until they end, and a
+    /// map that repeats a key fails to encode.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn canonical(writer: W) -> Serializer<W> {
         Serializer {
             writer,
-            packed: true,
+            packed: false,
+            canonical: Some(KeyOrder::Bytewise),
+            #[cfg(feature = "dcbor")]
+            dcbor: false,
+            self_describe: false,
+            #[cfg(feature = "std")]
+            string_refs: None,
+            raw: false,
         }
     }
 
+    /// Creates a new CBOR serializer for dCBOR, the deterministic profile of CBOR used by
+    /// Blockchain Commons.
+    ///
+    /// On top of the canonical encoding, floats that are integers in the range of `i64` or
+    /// `u64` are written as integers, and text strings that are not in Unicode Normalization
+    /// Form C fail to encode. NaN is always written as `f9 7e00`, as by all serializers.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Serialize;
+    /// use serde_cbor::Serializer;
+    ///
+    /// # fn main() {
+    /// let mut ser = Serializer::dcbor(Vec::new());
+    /// (2.0, -0.0, 2.5).serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.into_inner(), b"\x83\x02\x00\xf9\x41\x00");
+    /// # }
+    /// ```
+    ///
+    /// This method is available with the `dcbor` feature.
+    #[cfg(feature = "dcbor")]
+    #[inline]
+    pub fn dcbor(writer: W) -> Serializer<W> {
+        let mut ser = Serializer::canonical(writer);
+        ser.dcbor = true;
+        ser
+    }
+
+    /// Sets the order in which a canonical serializer writes map entries.
+    ///
+    /// The deterministic encoding of RFC 8949 sorts map entries bytewise by the encoding of
+    /// their keys. Protocols that follow the canonical encoding of RFC 7049, section 3.9, such as
+    /// CTAP2 and older COSE implementations, instead need shorter keys first. Serializers that
+    /// are not canonical write map entries as they come, and are unaffected.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Serialize;
+    /// use serde_cbor::ser::{KeyOrder, Serializer};
+    /// use std::collections::BTreeMap;
+    ///
+    /// # fn main() {
+    /// let mut map = BTreeMap::new();
+    /// map.insert(10, "a");
+    /// map.insert(-1, "b");
+    /// map.insert(100, "c");
+    ///
+    /// let mut ser = Serializer::canonical(Vec::new());
+    /// map.serialize(&mut ser).unwrap();
+    /// // {10: "a", 100: "c", -1: "b"}
+    /// assert_eq!(ser.into_inner(), b"\xa3\x0a\x61a\x18\x64\x61c\x20\x61b");
+    ///
+    /// let mut ser = Serializer::canonical(Vec::new()).key_order(KeyOrder::LengthFirst);
+    /// map.serialize(&mut ser).unwrap();
+    /// // {10: "a", -1: "b", 100: "c"}
+    /// assert_eq!(ser.into_inner(), b"\xa3\x0a\x61a\x20\x61b\x18\x64\x61c");
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn key_order(mut self, order: KeyOrder) -> Serializer<W> {
+        if self.canonical.is_some() {
+            self.canonical = Some(order);
+        }
+        self
+    }
+
+    /// Creates a new CBOR serializer for the canonical encoding of CTAP2.
+    ///
+    /// This is the canonical encoding with the keys of each map ordered as by
+    /// `KeyOrder::Ctap2`, and values that are serialized with a tag fail to encode.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn ctap2(writer: W) -> Serializer<W> {
+        Serializer::canonical(writer).key_order(KeyOrder::Ctap2)
+    }
+
+    /// Makes the serializer write a CBOR self-describe tag before the first data item.
+    ///
+    /// This is the same as calling `self_describe` right away, but fits in a chain of options.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Serialize;
+    /// use serde_cbor::Serializer;
+    ///
+    /// # fn main() {
+    /// let mut ser = Serializer::canonical(Vec::new()).self_described();
+    /// [1, 2].serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.into_inner(), b"\xd9\xd9\xf7\x82\x01\x02");
+    /// # }
+    /// ```
+    #[inline]
+    pub fn self_described(mut self) -> Serializer<W> {
+        self.self_describe = true;
+        self
+    }
+
+    /// Makes the serializer write the packed encoding, in which struct fields and enum variants
+    /// are identified by their index rather than their name.
+    ///
+    /// Fields are numbered from 0 in the order they are declared in, and variants likewise.
+    /// Fields that serde skips altogether take no number, while those left out with
+    /// `skip_serializing_if` keep theirs, so the keys written are those the deserializer expects
+    /// either way. The deserializer of this crate reads both encodings without being told which
+    /// one to expect, as serde looks fields and variants up by their index as well as by their
+    /// name.
+    ///
+    /// A packed document is only understood by code that declares the fields in the same order,
+    /// so fields can be added at the end of a struct, but not reordered or removed.
+    ///
+    /// ```
+    /// # #[macro_use] extern crate serde_derive;
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Serialize;
+    /// use serde_cbor::Serializer;
+    ///
+    /// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    /// struct Reading {
+    ///     sensor: u8,
+    ///     #[serde(skip_serializing_if = "Option::is_none")]
+    ///     celsius: Option<f32>,
+    ///     battery: u8,
+    /// }
+    ///
+    /// # fn main() {
+    /// let reading = Reading { sensor: 3, celsius: None, battery: 90 };
+    /// let mut ser = Serializer::canonical(Vec::new()).packed_format();
+    /// reading.serialize(&mut ser).unwrap();
+    /// let bytes = ser.into_inner();
+    /// // {0: 3, 2: 90}
+    /// assert_eq!(bytes, b"\xa2\x00\x03\x02\x18\x5a");
+    /// let decoded: Reading = serde_cbor::from_slice(&bytes).unwrap();
+    /// assert_eq!(decoded, reading);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn packed_format(mut self) -> Serializer<W> {
+        self.packed = true;
+        self
+    }
+
+    /// Makes the serializer write repeated strings as references to their first occurrence, as
+    /// in the stringref extension.
+    ///
+    /// Everything the serializer writes is in one namespace, so it should write a single data
+    /// item. Serializers for a deterministic encoding write strings in full, and are unaffected.
+    /// See the `stringref` module for more.
+    ///
+    /// ```
+    /// # extern crate serde;
+    /// # extern crate serde_cbor;
+    /// use serde::Serialize;
+    /// use serde_cbor::Serializer;
+    ///
+    /// # fn main() {
+    /// let mut ser = Serializer::new(Vec::new()).string_refs();
+    /// ["abc", "abc", "ab", "ab"].serialize(&mut ser).unwrap();
+    /// // 256(["abc", 25(0), "ab", "ab"])
+    /// assert_eq!(ser.into_inner(), b"\xd9\x01\x00\x84\x63abc\xd8\x19\x00\x62ab\x62ab");
+    /// # }
+    /// ```
+    ///
+    /// This method is available with the `std` feature.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn string_refs(mut self) -> Serializer<W> {
+        if self.canonical.is_none() {
+            self.string_refs = Some(StringTable::new());
+        }
+        self
+    }
+
     /// Writes a CBOR self-describe tag to the stream.
     ///
     /// Tagging allows a decoder to distinguish different file formats based on their content
-    /// without further information.
+    /// without further information. The deserializer of this crate skips the tag at the start
+    /// of its input.
     #[inline]
     pub fn self_describe(&mut self) -> Result<()> {
+        self.self_describe = false;
         let mut buf = [6 << 5 | 25, 0, 0];
         BigEndian::write_u16(&mut buf[1..], 55799);
-        self.writer.write_all(&buf).map_err(Error::io)
+        self.writer.write_all(&buf)
+    }
+
+    // Writes to the output, after a self-describe tag and the tag of a stringref namespace that
+    // are still to be written.
+    #[inline]
+    fn write_all(&mut self, bytes: &[u8]) -> Result<()> {
+        if self.self_describe {
+            self.self_describe()?;
+        }
+        #[cfg(feature = "std")]
+        {
+            if let Some(ref mut table) = self.string_refs {
+                if table.pending {
+                    table.pending = false;
+                    self.writer.write_all(&[0xd9, 0x01, 0x00])?;
+                }
+            }
+        }
+        self.writer.write_all(bytes)
     }
 
     /// Unwrap the `Writer` from the `Serializer`.
@@ -147,60 +689,175 @@ where
 
     #[inline]
     fn write_u8(&mut self, major: u8, value: u8) -> Result<()> {
-        if value <= 0x17 {
-            self.writer.write_all(&[major << 5 | value])
-        } else {
-            let buf = [major << 5 | 24, value];
-            self.writer.write_all(&buf)
-        }.map_err(Error::io)
+        self.write_u64(major, u64::from(value))
     }
 
     #[inline]
     fn write_u16(&mut self, major: u8, value: u16) -> Result<()> {
-        if value <= u16::from(u8::max_value()) {
-            self.write_u8(major, value as u8)
-        } else {
-            let mut buf = [major << 5 | 25, 0, 0];
-            BigEndian::write_u16(&mut buf[1..], value);
-            self.writer.write_all(&buf).map_err(Error::io)
-        }
+        self.write_u64(major, u64::from(value))
     }
 
     #[inline]
     fn write_u32(&mut self, major: u8, value: u32) -> Result<()> {
-        if value <= u32::from(u16::max_value()) {
-            self.write_u16(major, value as u16)
-        } else {
-            let mut buf = [major << 5 | 26, 0, 0, 0, 0];
-            BigEndian::write_u32(&mut buf[1..], value);
-            self.writer.write_all(&buf).map_err(Error::io)
-        }
+        self.write_u64(major, u64::from(value))
     }
 
     #[inline]
     fn write_u64(&mut self, major: u8, value: u64) -> Result<()> {
-        if value <= u64::from(u32::max_value()) {
-            self.write_u32(major, value as u32)
+        let mut buf = [0; 9];
+        let len = encode_head(major, value, &mut buf);
+        self.write_all(&buf[..len])
+    }
+
+    // Writes an integer of major type 0 or 1, as a bignum with tag 2 or 3 if it needs more than
+    // 64 bits.
+    fn write_u128(&mut self, major: u8, value: u128) -> Result<()> {
+        if value <= u128::from(u64::max_value()) {
+            return self.write_u64(major, value as u64);
+        }
+        let mut buf = [0; 16];
+        BigEndian::write_u128(&mut buf, value);
+        let start = buf.iter().position(|byte| *byte != 0).unwrap_or(buf.len());
+        self.write_tag(2 + u64::from(major))?;
+        self.write_string(2, &buf[start..])
+    }
+
+    fn write_tag(&mut self, tag: u64) -> Result<()> {
+        #[cfg(feature = "std")]
+        {
+            if self.canonical == Some(KeyOrder::Ctap2) {
+                return Err(ser::Error::custom("tags are not allowed in CTAP2"));
+            }
+        }
+        self.write_u64(6, tag)
+    }
+
+    // Writes a simple value. Those from 24 to 31 cannot be written, as their encodings are not
+    // well-formed.
+    fn write_simple(&mut self, value: u8) -> Result<()> {
+        if (24..32).contains(&value) {
+            return Err(ser::Error::custom("invalid simple value"));
+        }
+        #[cfg(feature = "dcbor")]
+        {
+            if self.dcbor && !(20..23).contains(&value) {
+                return Err(ser::Error::custom("simple values are not allowed in dCBOR"));
+            }
+        }
+        self.write_u64(7, u64::from(value))
+    }
+
+    // Writes a byte or text string. Short strings are written together with their head, and
+    // strings that were written before may be written as references.
+    #[inline]
+    fn write_string(&mut self, major: u8, value: &[u8]) -> Result<()> {
+        #[cfg(feature = "std")]
+        {
+            let index = match self.string_refs {
+                Some(ref mut table) => table.index(major, value),
+                None => None,
+            };
+            if let Some(index) = index {
+                self.write_tag(25)?;
+                return self.write_u64(0, index);
+            }
+        }
+        if value.len() > INLINE_LEN - 9 {
+            self.write_u64(major, value.len() as u64)?;
+            return self.write_all(value);
+        }
+        let mut buf = [0; INLINE_LEN];
+        let len = encode_head(major, value.len() as u64, &mut buf);
+        buf[len..len + value.len()].copy_from_slice(value);
+        self.write_all(&buf[..len + value.len()])
+    }
+
+    // Whether strings have to go through `write_string` to be numbered.
+    #[cfg(feature = "std")]
+    #[inline]
+    fn writes_string_refs(&self) -> bool {
+        self.string_refs.is_some()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    fn writes_string_refs(&self) -> bool {
+        false
+    }
+
+    // Writes the head of the array an enum variant with data is encoded in, followed by the
+    // variant identifier.
+    #[inline]
+    fn write_variant(&mut self, len: usize, variant_index: u32, variant: &str) -> Result<()> {
+        let mut buf = [0; INLINE_LEN];
+        let mut pos = encode_head(4, len as u64, &mut buf);
+        if self.packed {
+            pos += encode_head(0, u64::from(variant_index), &mut buf[pos..]);
+        } else if variant.len() <= INLINE_LEN - 18 && !self.writes_string_refs() {
+            pos += encode_head(3, variant.len() as u64, &mut buf[pos..]);
+            buf[pos..pos + variant.len()].copy_from_slice(variant.as_bytes());
+            pos += variant.len();
+        } else {
+            self.write_all(&buf[..pos])?;
+            return self.write_string(3, variant.as_bytes());
+        }
+        self.write_all(&buf[..pos])
+    }
+
+    // Writes a float that is an integer in the range of `i64` or `u64` as that integer, and
+    // returns whether it was.
+    #[cfg(feature = "dcbor")]
+    fn write_reduced(&mut self, value: f64) -> Result<bool> {
+        if value.trunc() != value {
+            return Ok(false);
+        }
+        // Both bounds are powers of two, and so exact.
+        if (-9_223_372_036_854_775_808.0..0.0).contains(&value) {
+            self.write_u64(1, -(value as i64 + 1) as u64)?;
+        } else if (0.0..18_446_744_073_709_551_616.0).contains(&value) {
+            self.write_u64(0, value as u64)?;
         } else {
-            let mut buf = [major << 5 | 27, 0, 0, 0, 0, 0, 0, 0, 0];
-            BigEndian::write_u64(&mut buf[1..], value);
-            self.writer.write_all(&buf).map_err(Error::io)
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    // Starts buffering the items of a collection if they cannot be written as they come.
+    #[cfg(feature = "std")]
+    #[inline]
+    fn buffer(&self, major: u8, len: Option<usize>) -> Option<Buffer> {
+        match self.canonical {
+            Some(order) if major == 5 || len.is_none() => Some(Buffer::new(major, self, order)),
+            _ => None,
         }
     }
 
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    fn buffer(&self, _: u8, _: Option<usize>) -> Option<Buffer> {
+        None
+    }
+
     #[inline]
     fn serialize_collection<'a>(
         &'a mut self,
         major: u8,
         len: Option<usize>,
     ) -> Result<CollectionSerializer<'a, W>> {
+        if let Some(buffer) = self.buffer(major, len) {
+            return Ok(CollectionSerializer {
+                ser: self,
+                needs_eof: false,
+                buffer: Some(buffer),
+            });
+        }
         let needs_eof = match len {
             Some(len) => {
                 self.write_u64(major, len as u64)?;
                 false
             }
             None => {
-                self.writer.write_all(&[major << 5 | 31]).map_err(Error::io)?;
+                self.write_all(&[major << 5 | 31])?;
                 true
             }
         };
@@ -208,13 +865,14 @@ where
         Ok(CollectionSerializer {
             ser: self,
             needs_eof,
+            buffer: None,
         })
     }
 }
 
 impl<'a, W> ser::Serializer for &'a mut Serializer<W>
 where
-    W: io::Write,
+    W: Write,
 {
     type Ok = ();
     type Error = Error;
@@ -230,7 +888,7 @@ where
     #[inline]
     fn serialize_bool(self, value: bool) -> Result<()> {
         let value = if value { 0xf5 } else { 0xf4 };
-        self.writer.write_all(&[value]).map_err(Error::io)
+        self.write_all(&[value])
     }
 
     #[inline]
@@ -269,6 +927,16 @@ where
         }
     }
 
+    // Integers that need more than 64 bits are written as bignums.
+    #[inline]
+    fn serialize_i128(self, value: i128) -> Result<()> {
+        if value < 0 {
+            self.write_u128(1, !value as u128)
+        } else {
+            self.write_u128(0, value as u128)
+        }
+    }
+
     #[inline]
     fn serialize_u8(self, value: u8) -> Result<()> {
         self.write_u8(0, value)
@@ -289,38 +957,69 @@ where
         self.write_u64(0, value)
     }
 
+    #[inline]
+    fn serialize_u128(self, value: u128) -> Result<()> {
+        self.write_u128(0, value)
+    }
+
+    #[cfg(feature = "float")]
     #[inline]
     fn serialize_f32(self, value: f32) -> Result<()> {
+        #[cfg(feature = "dcbor")]
+        {
+            if self.dcbor && self.write_reduced(f64::from(value))? {
+                return Ok(());
+            }
+        }
         if value.is_infinite() {
             if value.is_sign_positive() {
-                self.writer.write_all(&[0xf9, 0x7c, 0x00])
+                self.write_all(&[0xf9, 0x7c, 0x00])
             } else {
-                self.writer.write_all(&[0xf9, 0xfc, 0x00])
+                self.write_all(&[0xf9, 0xfc, 0x00])
             }
         } else if value.is_nan() {
-            self.writer.write_all(&[0xf9, 0x7e, 0x00])
+            self.write_all(&[0xf9, 0x7e, 0x00])
         } else if f32::from(f16::from_f32(value)) == value {
             let mut buf = [0xf9, 0, 0];
             BigEndian::write_u16(&mut buf[1..], f16::from_f32(value).to_bits());
-            self.writer.write_all(&buf)
+            self.write_all(&buf)
         } else {
             let mut buf = [0xfa, 0, 0, 0, 0];
             BigEndian::write_f32(&mut buf[1..], value);
-            self.writer.write_all(&buf)
-        }.map_err(Error::io)
+            self.write_all(&buf)
+        }
     }
 
+    #[cfg(feature = "float")]
     #[inline]
     fn serialize_f64(self, value: f64) -> Result<()> {
+        #[cfg(feature = "dcbor")]
+        {
+            if self.dcbor && self.write_reduced(value)? {
+                return Ok(());
+            }
+        }
         if !value.is_finite() || value as f32 as f64 == value {
             self.serialize_f32(value as f32)
         } else {
             let mut buf = [0xfb, 0, 0, 0, 0, 0, 0, 0, 0];
             BigEndian::write_f64(&mut buf[1..], value);
-            self.writer.write_all(&buf).map_err(Error::io)
+            self.write_all(&buf)
         }
     }
 
+    #[cfg(not(feature = "float"))]
+    #[inline]
+    fn serialize_f32(self, _: f32) -> Result<()> {
+        Err(Error::syntax(ErrorCode::Float, 0))
+    }
+
+    #[cfg(not(feature = "float"))]
+    #[inline]
+    fn serialize_f64(self, _: f64) -> Result<()> {
+        Err(Error::syntax(ErrorCode::Float, 0))
+    }
+
     #[inline]
     fn serialize_char(self, value: char) -> Result<()> {
         // A char encoded as UTF-8 takes 4 bytes at most.
@@ -330,14 +1029,21 @@ where
 
     #[inline]
     fn serialize_str(self, value: &str) -> Result<()> {
-        self.write_u64(3, value.len() as u64)?;
-        self.writer.write_all(value.as_bytes()).map_err(Error::io)
+        #[cfg(feature = "dcbor")]
+        {
+            if self.dcbor && !is_nfc(value) {
+                return Err(ser::Error::custom("text not in Unicode Normalization Form C"));
+            }
+        }
+        self.write_string(3, value.as_bytes())
     }
 
     #[inline]
     fn serialize_bytes(self, value: &[u8]) -> Result<()> {
-        self.write_u64(2, value.len() as u64)?;
-        self.writer.write_all(value).map_err(Error::io)
+        if mem::replace(&mut self.raw, false) {
+            return self.write_all(value);
+        }
+        self.write_string(2, value)
     }
 
     #[inline]
@@ -355,7 +1061,7 @@ where
 
     #[inline]
     fn serialize_none(self) -> Result<()> {
-        self.writer.write_all(&[0xf6]).map_err(Error::io)
+        self.write_all(&[0xf6])
     }
 
     #[inline]
@@ -378,17 +1084,44 @@ where
     }
 
     #[inline]
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + ser::Serialize,
     {
+        if name == tags::CBOR_NEWTYPE_NAME {
+            if let Some(tag) = tags::take_tag() {
+                self.write_tag(tag)?;
+            }
+        } else if name == tags::SIMPLE_NEWTYPE_NAME {
+            if let Some(simple) = tags::take_simple() {
+                return self.write_simple(simple);
+            }
+        } else if name == tags::PACKED_NEWTYPE_NAME {
+            let packed = mem::replace(&mut self.packed, true);
+            let result = value.serialize(&mut *self);
+            self.packed = packed;
+            return result;
+        } else if name == tags::RAW_NEWTYPE_NAME {
+            // Strings in a raw data item would be numbered when reading it but not here.
+            #[cfg(feature = "std")]
+            {
+                if self.string_refs.is_some() {
+                    let msg = "raw values cannot be written with string references";
+                    return Err(ser::Error::custom(msg));
+                }
+            }
+            self.raw = true;
+            let result = value.serialize(&mut *self);
+            self.raw = false;
+            return result;
+        }
         value.serialize(self)
     }
 
     #[inline]
     fn serialize_newtype_variant<T>(
         self,
-        name: &'static str,
+        _name: &'static str,
         variant_index: u32,
         variant: &'static str,
         value: &T,
@@ -396,8 +1129,7 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        self.writer.write_all(&[4 << 5 | 2]).map_err(Error::io)?;
-        self.serialize_unit_variant(name, variant_index, variant)?;
+        self.write_variant(2, variant_index, variant)?;
         value.serialize(self)
     }
 
@@ -424,13 +1156,12 @@ where
     #[inline]
     fn serialize_tuple_variant(
         self,
-        name: &'static str,
+        _name: &'static str,
         variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> Result<&'a mut Serializer<W>> {
-        self.write_u64(4, (len + 1) as u64)?;
-        self.serialize_unit_variant(name, variant_index, variant)?;
+        self.write_variant(len + 1, variant_index, variant)?;
         Ok(self)
     }
 
@@ -441,8 +1172,15 @@ where
 
     #[inline]
     fn serialize_struct(self, _name: &'static str, len: usize) -> Result<StructSerializer<'a, W>> {
-        self.write_u64(5, len as u64)?;
-        Ok(StructSerializer { ser: self, idx: 0 })
+        let buffer = self.buffer(5, Some(len));
+        if buffer.is_none() {
+            self.write_u64(5, len as u64)?;
+        }
+        Ok(StructSerializer {
+            ser: self,
+            idx: 0,
+            buffer,
+        })
     }
 
     #[inline]
@@ -453,8 +1191,7 @@ where
         variant: &'static str,
         len: usize,
     ) -> Result<StructSerializer<'a, W>> {
-        self.writer.write_all(&[4 << 5 | 2]).map_err(Error::io)?;
-        self.serialize_unit_variant(name, variant_index, variant)?;
+        self.write_variant(2, variant_index, variant)?;
         self.serialize_struct(name, len)
     }
 
@@ -466,7 +1203,7 @@ where
 
 impl<'a, W> ser::SerializeTuple for &'a mut Serializer<W>
 where
-    W: io::Write,
+    W: Write,
 {
     type Ok = ();
     type Error = Error;
@@ -487,7 +1224,7 @@ where
 
 impl<'a, W> ser::SerializeTupleStruct for &'a mut Serializer<W>
 where
-    W: io::Write,
+    W: Write,
 {
     type Ok = ();
     type Error = Error;
@@ -508,7 +1245,7 @@ where
 
 impl<'a, W> ser::SerializeTupleVariant for &'a mut Serializer<W>
 where
-    W: io::Write,
+    W: Write,
 {
     type Ok = ();
     type Error = Error;
@@ -531,17 +1268,27 @@ where
 pub struct StructSerializer<'a, W: 'a> {
     ser: &'a mut Serializer<W>,
     idx: u32,
+    buffer: Option<Buffer>,
 }
 
 impl<'a, W> StructSerializer<'a, W>
 where
-    W: io::Write,
+    W: Write,
 {
     #[inline]
     fn serialize_field_inner<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + ser::Serialize,
     {
+        if let Some(ref mut buffer) = self.buffer {
+            if self.ser.packed {
+                buffer.push(&self.idx)?;
+            } else {
+                buffer.push(key)?;
+            }
+            self.idx += 1;
+            return buffer.append(value);
+        }
         if self.ser.packed {
             self.idx.serialize(&mut *self.ser)?;
         } else {
@@ -556,11 +1303,19 @@ where
         self.idx += 1;
         Ok(())
     }
+
+    #[inline]
+    fn end_inner(self) -> Result<()> {
+        match self.buffer {
+            Some(buffer) => buffer.write_to(self.ser),
+            None => Ok(()),
+        }
+    }
 }
 
 impl<'a, W> ser::SerializeStruct for StructSerializer<'a, W>
 where
-    W: io::Write,
+    W: Write,
 {
     type Ok = ();
     type Error = Error;
@@ -580,13 +1335,13 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_inner()
     }
 }
 
 impl<'a, W> ser::SerializeStructVariant for StructSerializer<'a, W>
 where
-    W: io::Write,
+    W: Write,
 {
     type Ok = ();
     type Error = Error;
@@ -606,7 +1361,7 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_inner()
     }
 }
 
@@ -614,16 +1369,19 @@ where
 pub struct CollectionSerializer<'a, W: 'a> {
     ser: &'a mut Serializer<W>,
     needs_eof: bool,
+    buffer: Option<Buffer>,
 }
 
 impl<'a, W> CollectionSerializer<'a, W>
 where
-    W: io::Write,
+    W: Write,
 {
     #[inline]
     fn end_inner(self) -> Result<()> {
-        if self.needs_eof {
-            self.ser.writer.write_all(&[0xff]).map_err(Error::io)
+        if let Some(buffer) = self.buffer {
+            buffer.write_to(self.ser)
+        } else if self.needs_eof {
+            self.ser.write_all(&[0xff])
         } else {
             Ok(())
         }
@@ -632,7 +1390,7 @@ where
 
 impl<'a, W> ser::SerializeSeq for CollectionSerializer<'a, W>
 where
-    W: io::Write,
+    W: Write,
 {
     type Ok = ();
     type Error = Error;
@@ -642,7 +1400,10 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        value.serialize(&mut *self.ser)
+        match self.buffer {
+            Some(ref mut buffer) => buffer.push(value),
+            None => value.serialize(&mut *self.ser),
+        }
     }
 
     #[inline]
@@ -653,7 +1414,7 @@ where
 
 impl<'a, W> ser::SerializeMap for CollectionSerializer<'a, W>
 where
-    W: io::Write,
+    W: Write,
 {
     type Ok = ();
     type Error = Error;
@@ -663,7 +1424,10 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        key.serialize(&mut *self.ser)
+        match self.buffer {
+            Some(ref mut buffer) => buffer.push(key),
+            None => key.serialize(&mut *self.ser),
+        }
     }
 
     #[inline]
@@ -671,7 +1435,10 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        value.serialize(&mut *self.ser)
+        match self.buffer {
+            Some(ref mut buffer) => buffer.append(value),
+            None => value.serialize(&mut *self.ser),
+        }
     }
 
     #[inline]
@@ -679,3 +1446,116 @@ where
         self.end_inner()
     }
 }
+
+// The items of a collection in deterministic encoding whose head cannot be written before them,
+// because their number is not known in advance or map entries have to be sorted first.
+#[cfg(feature = "std")]
+struct Buffer {
+    major: u8,
+    packed: bool,
+    order: KeyOrder,
+    #[cfg(feature = "dcbor")]
+    dcbor: bool,
+    bytes: Vec<u8>,
+    // Where each item starts and, for map entries, where its key ends.
+    items: Vec<(usize, usize)>,
+}
+
+#[cfg(feature = "std")]
+impl Buffer {
+    // Starts a collection that is serialized like `ser` would serialize it.
+    fn new<W>(major: u8, ser: &Serializer<W>, order: KeyOrder) -> Buffer {
+        Buffer {
+            major,
+            packed: ser.packed,
+            order,
+            #[cfg(feature = "dcbor")]
+            dcbor: ser.dcbor,
+            bytes: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+
+    // Starts an item with an array element or a map key.
+    fn push<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let start = self.bytes.len();
+        self.append(value)?;
+        self.items.push((start, self.bytes.len()));
+        Ok(())
+    }
+
+    // Adds a map value to the last item.
+    fn append<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(&mut Serializer {
+            writer: &mut self.bytes,
+            packed: self.packed,
+            canonical: Some(self.order),
+            #[cfg(feature = "dcbor")]
+            dcbor: self.dcbor,
+            self_describe: false,
+            #[cfg(feature = "std")]
+            string_refs: None,
+            raw: false,
+        })
+    }
+
+    fn write_to<W>(self, ser: &mut Serializer<W>) -> Result<()>
+    where
+        W: Write,
+    {
+        let bytes = &self.bytes;
+        let ends = self.items.iter().skip(1).map(|item| item.0);
+        let mut items = self
+            .items
+            .iter()
+            .zip(ends.chain(Some(bytes.len())))
+            .map(|(&(start, key_end), end)| (&bytes[start..key_end], &bytes[start..end]))
+            .collect::<Vec<_>>();
+        if self.major == 5 {
+            let order = self.order;
+            items.sort_by(|a, b| order.compare(a.0, b.0));
+            if items.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+                return Err(ser::Error::custom("duplicate map key"));
+            }
+        }
+        ser.write_u64(self.major, items.len() as u64)?;
+        for (_, item) in items {
+            ser.write_all(item)?;
+        }
+        Ok(())
+    }
+}
+
+// Without `std` there is no deterministic encoding, so nothing is ever buffered.
+#[cfg(not(feature = "std"))]
+enum Buffer {}
+
+#[cfg(not(feature = "std"))]
+impl Buffer {
+    fn push<T>(&mut self, _: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        match *self {}
+    }
+
+    fn append<T>(&mut self, _: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        match *self {}
+    }
+
+    fn write_to<W>(self, _: &mut Serializer<W>) -> Result<()>
+    where
+        W: Write,
+    {
+        match self {}
+    }
+}
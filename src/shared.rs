@@ -0,0 +1,343 @@
+//! Shared values, which are written once with tag 28 and referred to with tag 29 after that.
+//!
+//! Values behind an `Rc` or an `Arc` may appear more than once in a structure, and serde writes
+//! each appearance in full, so that data shaped like a directed acyclic graph can grow
+//! exponentially in size. The `with::shared` adapters mark such fields. Serialized with `to_vec`,
+//! the first appearance of a value is written with tag 28, which makes it shareable, and every
+//! further one as tag 29 with the number of the shareable value, counted from zero in the order
+//! they appear. Deserialized with `from_slice`, the references are turned back into clones of the
+//! same `Rc` or `Arc`. This is the extension specified at <http://cbor.schmorp.de/value-sharing>.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! use serde_cbor::shared;
+//! use std::rc::Rc;
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Edge {
+//!     #[serde(with = "serde_cbor::with::shared")]
+//!     from: Rc<String>,
+//!     #[serde(with = "serde_cbor::with::shared")]
+//!     to: Rc<String>,
+//! }
+//!
+//! # fn main() {
+//! let node = Rc::new("node".to_string());
+//! let edges = vec![
+//!     Edge { from: node.clone(), to: Rc::new("a".to_string()) },
+//!     Edge { from: node.clone(), to: node },
+//! ];
+//! let bytes = shared::to_vec(&edges).unwrap();
+//! let diag = serde_cbor::diag::display(&bytes).to_string();
+//! assert_eq!(
+//!     diag,
+//!     r#"[{"from": 28("node"), "to": 28("a")}, {"from": 29(0), "to": 29(0)}]"#
+//! );
+//!
+//! let decoded: Vec<Edge> = shared::from_slice(&bytes).unwrap();
+//! assert_eq!(decoded, edges);
+//! assert!(Rc::ptr_eq(&decoded[0].from, &decoded[1].to));
+//! # }
+//! ```
+//!
+//! Outside of `to_vec` and `from_slice`, the adapters write just the value, and read the value
+//! with tag 28 or none. Shareable values are numbered as the adapters see them, so all of them
+//! in the input have to be deserialized with the adapters. Other values, such as a `Value`, can
+//! be deserialized after `resolve` replaces the references with copies of what they refer to.
+//! A value that refers to itself, which an `Rc` cannot, fails to deserialize and to resolve.
+//!
+//! This module is available with the `std` feature.
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use cursor::Cursor;
+use de as cbor_de;
+use error::{ErrorCode, Result};
+use ser as cbor_ser;
+use tags::{take_tag, Tagged, CBOR_NEWTYPE_NAME};
+
+// The numbers given to the addresses of the shareable values serialized so far, while `to_vec`
+// runs.
+thread_local!(
+    static SERIALIZED: RefCell<Option<HashMap<usize, u64>>> = const { RefCell::new(None) }
+);
+
+// The shareable values deserialized so far, while `from_slice` runs. A value that is still being
+// deserialized has no entry yet.
+type Values = Vec<Option<Box<dyn Any>>>;
+
+thread_local!(static DESERIALIZED: RefCell<Option<Values>> = const { RefCell::new(None) });
+
+/// Serializes a value to a vector, with the values that the `with::shared` adapters see more
+/// than once written only the first time.
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    let outer = SERIALIZED.with(|cell| cell.replace(Some(HashMap::new())));
+    let result = cbor_ser::to_vec(value);
+    SERIALIZED.with(|cell| cell.replace(outer));
+    result
+}
+
+/// Deserializes a value from a slice, with the references to shared values that the
+/// `with::shared` adapters see turned back into shared values.
+pub fn from_slice<T>(slice: &[u8]) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let outer = DESERIALIZED.with(|cell| cell.replace(Some(Vec::new())));
+    let result = cbor_de::from_slice(slice);
+    DESERIALIZED.with(|cell| cell.replace(outer));
+    result
+}
+
+/// Replaces the references to shared values in a sequence of data items with copies of the
+/// values they refer to, and removes the tags that make values shareable.
+///
+/// As a few bytes can refer to the same value over and over, this fails if the result would be
+/// longer than `limit` bytes.
+///
+/// ```
+/// use serde_cbor::{shared, Value};
+///
+/// // [28("node"), 29(0)]
+/// let bytes = b"\x82\xd8\x1c\x64node\xd8\x1d\x00";
+/// let resolved = shared::resolve(bytes, 1024).unwrap();
+/// let value: Value = serde_cbor::from_slice(&resolved).unwrap();
+/// let node = Value::String("node".to_string());
+/// assert_eq!(value, Value::Array(vec![node.clone(), node]));
+/// ```
+pub fn resolve(bytes: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut resolver = Resolver {
+        cursor: Cursor::new(bytes),
+        out: Vec::with_capacity(bytes.len()),
+        values: Vec::new(),
+        limit,
+    };
+    while !resolver.cursor.at_end() {
+        resolver.item(0)?;
+    }
+    Ok(resolver.out)
+}
+
+// Serializes a value that the adapters find at `address`.
+pub(crate) fn serialize_shared<T, S>(
+    address: usize,
+    value: &T,
+    serializer: S,
+) -> ::std::result::Result<S::Ok, S::Error>
+where
+    T: ?Sized + Serialize,
+    S: Serializer,
+{
+    let seen = SERIALIZED.with(|cell| {
+        cell.borrow_mut().as_mut().map(|seen| {
+            let count = seen.len() as u64;
+            match seen.entry(address) {
+                Entry::Occupied(entry) => Some(*entry.get()),
+                Entry::Vacant(entry) => {
+                    entry.insert(count);
+                    None
+                }
+            }
+        })
+    });
+    match seen {
+        None => value.serialize(serializer),
+        Some(None) => Tagged { tag: 28, value }.serialize(serializer),
+        Some(Some(index)) => Tagged {
+            tag: 29,
+            value: index,
+        }
+        .serialize(serializer),
+    }
+}
+
+// Deserializes a value for the adapters, which `wrap` puts in a pointer `P` that can be shared.
+pub(crate) fn deserialize_shared<'de, D, T, P>(
+    deserializer: D,
+    wrap: fn(T) -> P,
+) -> ::std::result::Result<P, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+    P: Clone + 'static,
+{
+    struct SharedVisitor<T, P> {
+        wrap: fn(T) -> P,
+        marker: PhantomData<T>,
+    }
+
+    impl<'de, T, P> de::Visitor<'de> for SharedVisitor<T, P>
+    where
+        T: DeserializeOwned,
+        P: Clone + 'static,
+    {
+        type Value = P;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a shared value")
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> ::std::result::Result<P, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            match take_tag() {
+                None => T::deserialize(deserializer).map(self.wrap),
+                Some(28) => {
+                    let index = DESERIALIZED.with(|cell| {
+                        cell.borrow_mut().as_mut().map(|values| {
+                            values.push(None);
+                            values.len() - 1
+                        })
+                    });
+                    let value = (self.wrap)(T::deserialize(deserializer)?);
+                    if let Some(index) = index {
+                        let shared: Box<dyn Any> = Box::new(value.clone());
+                        DESERIALIZED.with(|cell| {
+                            if let Some(ref mut values) = *cell.borrow_mut() {
+                                values[index] = Some(shared);
+                            }
+                        });
+                    }
+                    Ok(value)
+                }
+                Some(29) => {
+                    let index = u64::deserialize(deserializer)?;
+                    let value = DESERIALIZED.with(|cell| match *cell.borrow() {
+                        Some(ref values) => values
+                            .get(index as usize)
+                            .and_then(|value| value.as_ref())
+                            .map(|value| value.downcast_ref::<P>().cloned()),
+                        None => None,
+                    });
+                    match value {
+                        Some(Some(value)) => Ok(value),
+                        Some(None) => Err(de::Error::custom(format_args!(
+                            "shared value {} is of another type",
+                            index
+                        ))),
+                        None => Err(de::Error::custom(format_args!(
+                            "no shared value {}",
+                            index
+                        ))),
+                    }
+                }
+                Some(tag) => Err(de::Error::custom(format_args!(
+                    "expected tag 28 or 29, found tag {}",
+                    tag
+                ))),
+            }
+        }
+    }
+
+    deserializer.deserialize_newtype_struct(
+        CBOR_NEWTYPE_NAME,
+        SharedVisitor {
+            wrap,
+            marker: PhantomData,
+        },
+    )
+}
+
+struct Resolver<'a> {
+    cursor: Cursor<'a>,
+    out: Vec<u8>,
+    // The resolved encodings of the shareable values, or `None` for those still being resolved.
+    values: Vec<Option<Vec<u8>>>,
+    limit: usize,
+}
+
+impl<'a> Resolver<'a> {
+    fn item(&mut self, depth: usize) -> Result<()> {
+        if depth == cbor_de::MAX_DEPTH {
+            return Err(self.cursor.error(ErrorCode::RecursionLimitExceeded));
+        }
+        let start = self.cursor.offset;
+        let (major, arg) = self.cursor.head()?;
+        match (major, arg) {
+            (0, _) | (1, _) | (7, Some(_)) => self.copy_from(start)?,
+            (2, _) | (3, _) => {
+                self.cursor.skip_string(major, arg)?;
+                self.copy_from(start)?;
+            }
+            (4, _) | (5, _) => {
+                self.copy_from(start)?;
+                let per_entry = if major == 4 { 1 } else { 2 };
+                match arg {
+                    Some(len) => {
+                        for _ in 0..len {
+                            for _ in 0..per_entry {
+                                self.item(depth + 1)?;
+                            }
+                        }
+                    }
+                    None => {
+                        while !self.cursor.at_break() {
+                            for _ in 0..per_entry {
+                                self.item(depth + 1)?;
+                            }
+                        }
+                        self.cursor.take(1)?;
+                        self.out.push(0xff);
+                    }
+                }
+            }
+            (6, Some(28)) => {
+                let index = self.values.len();
+                self.values.push(None);
+                let value_start = self.out.len();
+                self.item(depth + 1)?;
+                self.values[index] = Some(self.out[value_start..].to_vec());
+            }
+            (6, Some(29)) => {
+                let reference = self.cursor.offset;
+                let value = match self.cursor.head()? {
+                    (0, Some(index)) if index < self.values.len() as u64 => {
+                        self.values[index as usize].clone()
+                    }
+                    _ => None,
+                };
+                match value {
+                    Some(value) => self.append(&value)?,
+                    None => {
+                        self.cursor.offset = reference;
+                        let code = ErrorCode::Message("invalid shared value reference".into());
+                        return Err(self.cursor.error(code));
+                    }
+                }
+            }
+            (6, _) => {
+                self.copy_from(start)?;
+                self.item(depth + 1)?;
+            }
+            _ => {
+                self.cursor.offset = start;
+                return Err(self.cursor.error(ErrorCode::UnexpectedCode));
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_from(&mut self, start: usize) -> Result<()> {
+        let bytes = self.cursor.since(start);
+        self.append(bytes)
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> Result<()> {
+        if self.limit - self.out.len() < bytes.len() {
+            return Err(self.cursor.error(ErrorCode::LengthOutOfRange));
+        }
+        self.out.extend_from_slice(bytes);
+        Ok(())
+    }
+}
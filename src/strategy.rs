@@ -0,0 +1,484 @@
+//! Proptest strategies for generating CBOR values and their encodings.
+//!
+//! `value` generates `Value` trees for round-trip tests of serialization, and `encoded`
+//! generates a value together with an encoding of it for testing decoders and other
+//! implementations. Both shrink towards smaller values, shallower trees and, for encodings, the
+//! canonical form, so a failing case is reported with the simplest input that still fails.
+//!
+//! NaN is left out because every NaN is written as `f9 7e00`, which loses its sign and payload.
+//!
+//! `Options` sets how deep and large the generated trees get, and whether encodings may use
+//! the non-canonical forms CBOR allows.
+//!
+//! This module is available with the `proptest` feature.
+//!
+//! ```
+//! # #[macro_use] extern crate proptest;
+//! # extern crate serde_cbor;
+//! use serde_cbor::strategy;
+//!
+//! proptest! {
+//!     # #![proptest_config(proptest::test_runner::Config::with_cases(16))]
+//!     fn decodes(encoded in strategy::Options::new().non_canonical().encoded()) {
+//!         let value: serde_cbor::Value = serde_cbor::from_slice(&encoded.bytes).unwrap();
+//!         prop_assert_eq!(value, encoded.value);
+//!     }
+//! }
+//! # fn main() { decodes(); }
+//! ```
+use byteorder::{BigEndian, ByteOrder};
+use half::f16;
+use proptest::collection::vec;
+use proptest::num;
+use proptest::option;
+use proptest::prelude::*;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+
+use ser::encode_head;
+use value::{ObjectKey, Value};
+
+/// Generates values with the default options.
+pub fn value() -> BoxedStrategy<Value> {
+    Options::new().value()
+}
+
+/// Generates values and their canonical encodings with the default options.
+pub fn encoded() -> BoxedStrategy<Encoded> {
+    Options::new().encoded()
+}
+
+/// A value together with an encoding of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Encoded {
+    /// The value.
+    pub value: Value,
+    /// A well-formed encoding that decodes to `value`.
+    pub bytes: Vec<u8>,
+}
+
+/// Knobs for the generated values and encodings.
+#[derive(Clone, Debug)]
+pub struct Options {
+    depth: u32,
+    size: u32,
+    items: u32,
+    canonical: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options::new()
+    }
+}
+
+impl Options {
+    /// Creates options for trees of up to 4 levels, around 64 values and 8 items per array, map
+    /// or string, with canonical encodings.
+    pub fn new() -> Options {
+        Options {
+            depth: 4,
+            size: 64,
+            items: 8,
+            canonical: true,
+        }
+    }
+
+    /// Sets how many levels of arrays and maps may be nested, where 0 only allows scalars.
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Sets the number of values a tree should contain in total. Nesting stops early once a
+    /// tree gets larger.
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets the largest number of items in an array or map, and of characters or bytes in a
+    /// string.
+    pub fn items(mut self, items: u32) -> Self {
+        self.items = items;
+        self
+    }
+
+    /// Also generates encodings that are well-formed but not canonical: integers, lengths and
+    /// floats that are longer than needed, indefinite-length strings, arrays and maps, and map
+    /// keys in any order.
+    ///
+    /// Canonical encodings follow the core deterministic encoding of RFC 8949, which sorts map
+    /// keys by their encoding. This differs from `to_vec`, which writes `Value::Object` in the
+    /// order of its `Map`.
+    pub fn non_canonical(mut self) -> Self {
+        self.canonical = false;
+        self
+    }
+
+    /// Returns a strategy for values.
+    pub fn value(&self) -> BoxedStrategy<Value> {
+        let mut options = self.clone();
+        options.canonical = true;
+        options.encoded().prop_map(|encoded| encoded.value).boxed()
+    }
+
+    /// Returns a strategy for values that can be map keys.
+    pub fn key(&self) -> BoxedStrategy<ObjectKey> {
+        let mut options = self.clone();
+        options.canonical = true;
+        options
+            .key_node()
+            .prop_map(|node| ObjectKey::try_from(node.encode(&mut Vec::new())).unwrap())
+            .boxed()
+    }
+
+    /// Returns a strategy for values together with an encoding of them.
+    pub fn encoded(&self) -> BoxedStrategy<Encoded> {
+        let items = self.items as usize;
+        let widen = self.widen();
+        let indefinite = self.indefinite();
+        let unsorted = self.unsorted();
+        self.scalar_node()
+            .prop_recursive(self.depth, self.size, self.items / 2 + 1, move |inner| {
+                prop_oneof![
+                    (
+                        vec(inner.clone(), 0..=items),
+                        indefinite.clone(),
+                        widen.clone()
+                    )
+                        .prop_map(|(items, indefinite, widen)| {
+                            Node::Array(items, indefinite, widen)
+                        }),
+                    (
+                        vec((inner.clone(), inner), 0..=items),
+                        indefinite.clone(),
+                        widen.clone(),
+                        unsorted.clone(),
+                    )
+                        .prop_map(|(entries, indefinite, widen, unsorted)| {
+                            Node::Map(entries, indefinite, widen, unsorted)
+                        }),
+                ]
+            })
+            .prop_map(|node| {
+                let mut bytes = Vec::new();
+                let value = node.encode(&mut bytes);
+                Encoded { value, bytes }
+            })
+            .boxed()
+    }
+
+    fn widen(&self) -> BoxedStrategy<u8> {
+        if self.canonical {
+            Just(0).boxed()
+        } else {
+            prop_oneof![3 => Just(0), 1 => 1..4u8].boxed()
+        }
+    }
+
+    fn indefinite(&self) -> BoxedStrategy<bool> {
+        if self.canonical {
+            Just(false).boxed()
+        } else {
+            prop::bool::weighted(0.25).boxed()
+        }
+    }
+
+    fn unsorted(&self) -> BoxedStrategy<bool> {
+        if self.canonical {
+            Just(false).boxed()
+        } else {
+            any::<bool>().boxed()
+        }
+    }
+
+    fn chunks(&self) -> BoxedStrategy<Option<Vec<usize>>> {
+        if self.canonical {
+            Just(None).boxed()
+        } else {
+            option::weighted(0.25, vec(any::<usize>(), 0..4)).boxed()
+        }
+    }
+
+    fn key_node(&self) -> BoxedStrategy<Node> {
+        let items = self.items as usize;
+        prop_oneof![
+            (integer(), self.widen()).prop_map(|(n, widen)| if n < 0 {
+                Node::Negative((-1 - n) as u64, widen)
+            } else {
+                Node::Unsigned(n as u64, widen)
+            }),
+            (vec(any::<u8>(), 0..=items), self.widen(), self.chunks())
+                .prop_map(|(bytes, widen, chunks)| Node::Bytes(bytes, widen, chunks)),
+            (vec(any::<char>(), 0..=items), self.widen(), self.chunks()).prop_map(
+                |(chars, widen, chunks)| Node::Text(chars.into_iter().collect(), widen, chunks)
+            ),
+            any::<bool>().prop_map(Node::Bool),
+            Just(Node::Null),
+        ]
+        .boxed()
+    }
+
+    fn scalar_node(&self) -> BoxedStrategy<Node> {
+        prop_oneof![
+            4 => self.key_node(),
+            1 => (large(), self.widen()).prop_map(|(n, widen)| Node::Unsigned(n, widen)),
+            1 => (large(), self.widen()).prop_map(|(n, widen)| Node::Negative(n, widen)),
+            2 => (float(), self.widen()).prop_map(|(n, widen)| Node::Float(n, widen)),
+            1 => simple().prop_map(Node::Simple),
+        ]
+        .boxed()
+    }
+}
+
+// Integers in the range of `i64`, favouring the boundaries between the lengths of their
+// encoding.
+fn integer() -> BoxedStrategy<i64> {
+    prop_oneof![
+        -24..24i64,
+        any::<i8>().prop_map(i64::from),
+        any::<i16>().prop_map(i64::from),
+        any::<i32>().prop_map(i64::from),
+        any::<i64>(),
+    ]
+    .boxed()
+}
+
+// Simple values other than false, true and null, which have no type in serde's data model.
+fn simple() -> BoxedStrategy<u8> {
+    prop_oneof![0..20u8, Just(23u8), 32..=255u8].boxed()
+}
+
+// Non-negative integers beyond the range of `i64`, and the arguments of negative ones beyond it.
+fn large() -> BoxedStrategy<u64> {
+    (1u64 << 63..=u64::max_value()).boxed()
+}
+
+// Floats other than NaN, favouring those that can be encoded in half or single precision.
+fn float() -> BoxedStrategy<f64> {
+    let double = num::f64::POSITIVE
+        | num::f64::NEGATIVE
+        | num::f64::NORMAL
+        | num::f64::SUBNORMAL
+        | num::f64::ZERO
+        | num::f64::INFINITE;
+    let single = num::f32::POSITIVE
+        | num::f32::NEGATIVE
+        | num::f32::NORMAL
+        | num::f32::SUBNORMAL
+        | num::f32::ZERO
+        | num::f32::INFINITE;
+    prop_oneof![
+        double,
+        single.prop_map(f64::from),
+        any::<u16>()
+            .prop_map(|bits| f64::from(f16::from_bits(bits)))
+            .prop_filter("NaN", |n| !n.is_nan()),
+    ]
+    .boxed()
+}
+
+// A data item and the choices made for encoding it. The choices default to the canonical
+// encoding, which is where they shrink to: `widen` is the number of steps by which a head or
+// float is longer than needed, chunks are the split points of an indefinite-length string, the
+// first `bool` of arrays and maps marks them as indefinite-length and the second one keeps map
+// entries in the order they were generated in.
+#[derive(Clone, Debug)]
+enum Node {
+    Unsigned(u64, u8),
+    // The argument of a negative integer, which is -1 minus the integer.
+    Negative(u64, u8),
+    Bytes(Vec<u8>, u8, Option<Vec<usize>>),
+    Text(String, u8, Option<Vec<usize>>),
+    Float(f64, u8),
+    Bool(bool),
+    Null,
+    Simple(u8),
+    Array(Vec<Node>, bool, u8),
+    Map(Vec<(Node, Node)>, bool, u8, bool),
+}
+
+impl Node {
+    // Appends the encoding of the item to `out` and returns its value.
+    fn encode(self, out: &mut Vec<u8>) -> Value {
+        match self {
+            Node::Unsigned(n, widen) => {
+                head(0, n, widen, out);
+                Value::Integer(i128::from(n))
+            }
+            Node::Negative(n, widen) => {
+                head(1, n, widen, out);
+                Value::Integer(-1 - i128::from(n))
+            }
+            Node::Bytes(bytes, widen, chunks) => {
+                string(
+                    2,
+                    &bytes,
+                    widen,
+                    chunks.map(|chunks| splits(&chunks, bytes.len())),
+                    out,
+                );
+                Value::Bytes(bytes)
+            }
+            Node::Text(text, widen, chunks) => {
+                let chunks = chunks.map(|chunks| {
+                    let mut splits = splits(&chunks, text.len());
+                    for split in &mut splits {
+                        while !text.is_char_boundary(*split) {
+                            *split -= 1;
+                        }
+                    }
+                    splits
+                });
+                string(3, text.as_bytes(), widen, chunks, out);
+                Value::String(text)
+            }
+            Node::Float(n, widen) => {
+                float_bytes(n, widen, out);
+                Value::F64(n)
+            }
+            Node::Bool(false) => {
+                out.push(0xf4);
+                Value::Bool(false)
+            }
+            Node::Bool(true) => {
+                out.push(0xf5);
+                Value::Bool(true)
+            }
+            Node::Null => {
+                out.push(0xf6);
+                Value::Null
+            }
+            Node::Simple(n) => {
+                head(7, u64::from(n), 0, out);
+                match n {
+                    23 => Value::Undefined,
+                    _ => Value::Simple(n),
+                }
+            }
+            Node::Array(items, indefinite, widen) => {
+                if indefinite {
+                    out.push(0x9f);
+                } else {
+                    head(4, items.len() as u64, widen, out);
+                }
+                let values = items.into_iter().map(|item| item.encode(out)).collect();
+                if indefinite {
+                    out.push(0xff);
+                }
+                Value::Array(values)
+            }
+            Node::Map(entries, indefinite, widen, unsorted) => {
+                // Keep the first of entries with the same key.
+                let mut seen = BTreeSet::new();
+                let mut encoded = Vec::new();
+                for (key, value) in entries {
+                    let mut bytes = Vec::new();
+                    let key = key.encode(&mut bytes);
+                    if seen.insert(key.clone()) {
+                        let start = bytes.len();
+                        let value = value.encode(&mut bytes);
+                        encoded.push((start, bytes, key, value));
+                    }
+                }
+                if !unsorted {
+                    encoded.sort_by(|a, b| a.1[..a.0].cmp(&b.1[..b.0]));
+                }
+                if indefinite {
+                    out.push(0xbf);
+                } else {
+                    head(5, encoded.len() as u64, widen, out);
+                }
+                let map = encoded
+                    .into_iter()
+                    .map(|(_, bytes, key, value)| {
+                        out.extend(bytes);
+                        (key, value)
+                    })
+                    .collect();
+                if indefinite {
+                    out.push(0xff);
+                }
+                Value::Object(map)
+            }
+        }
+    }
+}
+
+// Writes the head of an item, `widen` steps longer than the shortest one.
+fn head(major: u8, value: u64, widen: u8, out: &mut Vec<u8>) {
+    let mut buf = [0; 9];
+    let len = encode_head(major, value, &mut buf);
+    // The lengths of a head are 1, 2, 3, 5 and 9 bytes.
+    let shortest = match len {
+        1 => 0,
+        2 => 1,
+        3 => 2,
+        5 => 3,
+        _ => 4,
+    };
+    let step = (shortest + widen).min(4);
+    if step == shortest {
+        out.extend_from_slice(&buf[..len]);
+        return;
+    }
+    out.push(major << 5 | (23 + step));
+    let width = 1 << (step - 1);
+    for i in (0..width).rev() {
+        out.push((value >> (8 * i)) as u8);
+    }
+}
+
+fn string(major: u8, bytes: &[u8], widen: u8, chunks: Option<Vec<usize>>, out: &mut Vec<u8>) {
+    let chunks = match chunks {
+        Some(chunks) => chunks,
+        None => {
+            head(major, bytes.len() as u64, widen, out);
+            out.extend_from_slice(bytes);
+            return;
+        }
+    };
+    out.push(major << 5 | 31);
+    let mut start = 0;
+    for end in chunks.into_iter().chain(Some(bytes.len())) {
+        head(major, (end - start) as u64, widen, out);
+        out.extend_from_slice(&bytes[start..end]);
+        start = end;
+    }
+    out.push(0xff);
+}
+
+// Turns arbitrary numbers into sorted split points of a string of `len` bytes.
+fn splits(chunks: &[usize], len: usize) -> Vec<usize> {
+    let mut splits: Vec<usize> = chunks.iter().map(|n| n % (len + 1)).collect();
+    splits.sort();
+    splits
+}
+
+fn float_bytes(n: f64, widen: u8, out: &mut Vec<u8>) {
+    let shortest = if f64::from(f16::from_f64(n)) == n {
+        0
+    } else if f64::from(n as f32) == n {
+        1
+    } else {
+        2
+    };
+    match (shortest + widen).min(2) {
+        0 => {
+            let mut buf = [0xf9, 0, 0];
+            BigEndian::write_u16(&mut buf[1..], f16::from_f64(n).to_bits());
+            out.extend_from_slice(&buf);
+        }
+        1 => {
+            let mut buf = [0xfa, 0, 0, 0, 0];
+            BigEndian::write_f32(&mut buf[1..], n as f32);
+            out.extend_from_slice(&buf);
+        }
+        _ => {
+            let mut buf = [0xfb, 0, 0, 0, 0, 0, 0, 0, 0];
+            BigEndian::write_f64(&mut buf[1..], n);
+            out.extend_from_slice(&buf);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! The stringref extension, which writes repeated strings as references to their first
+//! occurrence.
+//!
+//! Within a namespace, a data item with tag 256, the strings that are long enough to gain from
+//! it are numbered in the order they appear, and later copies of them are written as tag 25 with
+//! their number. Text and byte strings are numbered together, and a nested namespace starts
+//! numbering afresh. Payloads that repeat map keys or enum variant names, such as records with
+//! the same struct in them over and over, get considerably smaller this way. The extension is
+//! specified at <http://cbor.schmorp.de/stringref>.
+//!
+//! `Serializer::string_refs` makes a serializer write a namespace, and `to_vec` serializes a
+//! value with it. `resolve` turns input that uses references back into plain CBOR, which
+//! `from_slice` then deserializes.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! use serde_cbor::stringref;
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Reading {
+//!     sensor: String,
+//!     value: u32,
+//! }
+//!
+//! # fn main() {
+//! let readings = vec![
+//!     Reading { sensor: "kitchen".to_string(), value: 21 },
+//!     Reading { sensor: "kitchen".to_string(), value: 22 },
+//! ];
+//! let bytes = stringref::to_vec(&readings).unwrap();
+//! assert!(bytes.len() < serde_cbor::to_vec(&readings).unwrap().len());
+//! let diag = serde_cbor::diag::display(&bytes).to_string();
+//! assert_eq!(
+//!     diag,
+//!     r#"256([{"sensor": "kitchen", "value": 21}, {25(0): 25(1), 25(2): 22}])"#
+//! );
+//!
+//! let decoded: Vec<Reading> = stringref::from_slice(&bytes).unwrap();
+//! assert_eq!(decoded, readings);
+//! # }
+//! ```
+//!
+//! This module is available with the `std` feature.
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use cursor::Cursor;
+use de;
+use error::{ErrorCode, Result};
+use ser::{encode_head, Serializer};
+
+/// Serializes a value to a vector, with repeated strings written as references.
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut ser = Serializer::new(Vec::new()).string_refs();
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+// The most bytes `from_slice` resolves its input to.
+const DEFAULT_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Deserializes a value from a slice that may use string references.
+///
+/// The input is resolved to plain CBOR first, so the value cannot borrow from it. Errors in the
+/// resolved input are reported at their offset in that. The resolved input may be at most 64 MiB
+/// long; larger payloads can be resolved with `resolve` and a limit of their own.
+pub fn from_slice<T>(slice: &[u8]) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    de::from_slice(&resolve(slice, DEFAULT_LIMIT)?)
+}
+
+/// Replaces the string references in a sequence of data items with the strings they refer to,
+/// and removes the tags of their namespaces.
+///
+/// Everything else is copied as it is. References outside of a namespace are left alone, as tag
+/// 25 has no meaning there.
+///
+/// As a few bytes can refer to a long string over and over, this fails if the result would be
+/// longer than `limit` bytes.
+///
+/// ```
+/// use serde_cbor::stringref;
+///
+/// // 256(["hello", 25(0)])
+/// let bytes = b"\xd9\x01\x00\x82\x65hello\xd8\x19\x00";
+/// assert_eq!(stringref::resolve(bytes, 1024).unwrap(), b"\x82\x65hello\x65hello");
+/// ```
+pub fn resolve(bytes: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut resolver = Resolver {
+        cursor: Cursor::new(bytes),
+        out: Vec::with_capacity(bytes.len().min(limit)),
+        namespaces: Vec::new(),
+        limit,
+    };
+    while !resolver.cursor.at_end() {
+        resolver.item(0)?;
+    }
+    Ok(resolver.out)
+}
+
+// The number of bytes a string needs for a reference to it to be shorter than the string, when
+// `count` strings have been numbered before it.
+fn min_len(count: usize) -> usize {
+    match count as u64 {
+        0...23 => 3,
+        24...255 => 4,
+        256...65535 => 5,
+        65536...4294967295 => 7,
+        _ => 11,
+    }
+}
+
+// The strings a serializer has numbered in its namespace, text and byte strings apart, and
+// whether the tag of the namespace is still to be written.
+pub(crate) struct StringTable {
+    texts: HashMap<Vec<u8>, u64>,
+    bytes: HashMap<Vec<u8>, u64>,
+    pub(crate) pending: bool,
+}
+
+impl StringTable {
+    pub(crate) fn new() -> StringTable {
+        StringTable {
+            texts: HashMap::new(),
+            bytes: HashMap::new(),
+            pending: true,
+        }
+    }
+
+    // Returns the number of a string of major type 2 or 3 that was numbered before, or else
+    // numbers it if it is long enough.
+    pub(crate) fn index(&mut self, major: u8, value: &[u8]) -> Option<u64> {
+        let count = self.texts.len() + self.bytes.len();
+        let strings = if major == 3 {
+            &mut self.texts
+        } else {
+            &mut self.bytes
+        };
+        if let Some(&index) = strings.get(value) {
+            return Some(index);
+        }
+        if value.len() >= min_len(count) {
+            strings.insert(value.to_vec(), count as u64);
+        }
+        None
+    }
+}
+
+struct Resolver<'a> {
+    cursor: Cursor<'a>,
+    out: Vec<u8>,
+    // The strings numbered in each enclosing namespace, by major type and place in the input.
+    namespaces: Vec<Vec<(u8, Range<usize>)>>,
+    limit: usize,
+}
+
+impl<'a> Resolver<'a> {
+    fn item(&mut self, depth: usize) -> Result<()> {
+        if depth == de::MAX_DEPTH {
+            return Err(self.cursor.error(ErrorCode::RecursionLimitExceeded));
+        }
+        let start = self.cursor.offset;
+        let (major, arg) = self.cursor.head()?;
+        match (major, arg) {
+            (0, _) | (1, _) | (7, Some(_)) => self.copy_from(start)?,
+            (2, _) | (3, _) => {
+                self.cursor.skip_string(major, arg)?;
+                self.copy_from(start)?;
+                // The chunks of an indefinite-length string are not numbered.
+                if let (Some(len), Some(strings)) = (arg, self.namespaces.last_mut()) {
+                    let len = len as usize;
+                    if len >= min_len(strings.len()) {
+                        let end = self.cursor.offset;
+                        strings.push((major, end - len..end));
+                    }
+                }
+            }
+            (4, _) | (5, _) => {
+                self.copy_from(start)?;
+                let per_entry = if major == 4 { 1 } else { 2 };
+                match arg {
+                    Some(len) => {
+                        for _ in 0..len {
+                            for _ in 0..per_entry {
+                                self.item(depth + 1)?;
+                            }
+                        }
+                    }
+                    None => {
+                        while !self.cursor.at_break() {
+                            for _ in 0..per_entry {
+                                self.item(depth + 1)?;
+                            }
+                        }
+                        self.cursor.take(1)?;
+                        self.append(&[0xff])?;
+                    }
+                }
+            }
+            (6, Some(256)) => {
+                self.namespaces.push(Vec::new());
+                let result = self.item(depth + 1);
+                self.namespaces.pop();
+                result?;
+            }
+            (6, Some(25)) if !self.namespaces.is_empty() => self.reference()?,
+            (6, _) => {
+                self.copy_from(start)?;
+                self.item(depth + 1)?;
+            }
+            _ => {
+                self.cursor.offset = start;
+                return Err(self.cursor.error(ErrorCode::UnexpectedCode));
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the string that the reference after tag 25 refers to.
+    fn reference(&mut self) -> Result<()> {
+        let start = self.cursor.offset;
+        let index = match self.cursor.head()? {
+            (0, Some(index)) => index,
+            _ => {
+                self.cursor.offset = start;
+                return Err(self.cursor.error(ErrorCode::InvalidStringRef));
+            }
+        };
+        let strings = self.namespaces.last().expect("references are only resolved in a namespace");
+        if index >= strings.len() as u64 {
+            self.cursor.offset = start;
+            return Err(self.cursor.error(ErrorCode::InvalidStringRef));
+        }
+        let (major, range) = strings[index as usize].clone();
+        let mut buf = [0; 9];
+        let len = encode_head(major, range.len() as u64, &mut buf);
+        self.append(&buf[..len])?;
+        let input = self.cursor.input;
+        self.append(&input[range])
+    }
+
+    fn copy_from(&mut self, start: usize) -> Result<()> {
+        let bytes = self.cursor.since(start);
+        self.append(bytes)
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> Result<()> {
+        if self.limit - self.out.len() < bytes.len() {
+            return Err(self.cursor.error(ErrorCode::LengthOutOfRange));
+        }
+        self.out.extend_from_slice(bytes);
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+//! Semantic tags, which give data items a meaning beyond their type.
+//!
+//! A tag is a number in front of a data item, such as 1 for a time in seconds since the epoch or
+//! 32 for a URI. `Tagged` writes a value with a tag, and reads a value along with its tag, which
+//! can then decide how to interpret the value. `CborEncoded` writes a value encoded as CBOR in a
+//! byte string with tag 24.
+//!
+//! ```
+//! use serde_cbor::tags::Tagged;
+//! use serde_cbor::Value;
+//!
+//! // [0("2013-03-21T20:04:00Z"), 1(1363896240)]
+//! let bytes = b"\x82\xc0\x74\x32\x30\x31\x33\x2d\x30\x33\x2d\x32\x31\x54\x32\x30\x3a\
+//!     \x30\x34\x3a\x30\x30\x5a\xc1\x1a\x51\x4b\x67\xb0";
+//! let items: Vec<Tagged<Value>> = serde_cbor::from_slice(bytes).unwrap();
+//! for item in items {
+//!     match (item.tag, item.value) {
+//!         (0, Value::String(text)) => assert_eq!(text, "2013-03-21T20:04:00Z"),
+//!         (1, Value::Integer(seconds)) => assert_eq!(seconds, 1363896240),
+//!         _ => panic!("unexpected item"),
+//!     }
+//! }
+//! ```
+//!
+//! This module is only useful with the `std` feature.
+
+// Passing semantic tags and encoding switches through serde's data model, which has no notion of
+// them.
+//
+// Both travel as newtype structs with reserved names. The serializer of this crate writes the tag
+// set with `set_tag` in front of the content of a `CBOR_NEWTYPE_NAME` newtype, and the
+// deserializer records the tag of the item it is about to visit as one with `set_tag`. Other
+// formats see an ordinary newtype struct and just its content.
+//
+// `Value` takes every data item, which serde's data model cannot describe either. It asks for
+// the content of a `VALUE_NEWTYPE_NAME` newtype, which the deserializer hands out with integers
+// beyond `i64` visited as `i128`, and with simple values other than booleans and null visited
+// as a unit, their number set with `set_simple`. A simple value is written the same way, as a
+// `SIMPLE_NEWTYPE_NAME` newtype.
+//
+// Without `std` there are no thread-local variables to pass tags in, so tags are neither written
+// nor reported.
+#[cfg(feature = "std")]
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer};
+#[cfg(feature = "std")]
+use serde::ser::{self, Serialize, Serializer};
+#[cfg(feature = "std")]
+use std::cell::Cell;
+#[cfg(feature = "std")]
+use std::fmt;
+#[cfg(feature = "std")]
+use std::marker::PhantomData;
+
+#[cfg(feature = "std")]
+use with::{deserialize_bytes, serialize_bytes};
+
+// The names of the newtype structs that are encoded specially all start with a NUL, which no
+// Rust identifier does.
+pub(crate) const CBOR_NEWTYPE_NAME: &str = "\0cbor_tag";
+
+// The content of a newtype struct with this name is encoded the way `to_vec_packed` encodes it.
+pub(crate) const PACKED_NEWTYPE_NAME: &str = "\0cbor_packed";
+
+pub(crate) const VALUE_NEWTYPE_NAME: &str = "\0cbor_value";
+
+pub(crate) const SIMPLE_NEWTYPE_NAME: &str = "\0cbor_simple";
+
+// The content of a newtype struct with this name is a byte string with the encoding of a data
+// item, which is written and read as it is.
+pub(crate) const RAW_NEWTYPE_NAME: &str = "\0cbor_raw";
+
+#[cfg(feature = "std")]
+thread_local!(static CBOR_TAG: Cell<Option<u64>> = const { Cell::new(None) });
+
+#[cfg(feature = "std")]
+pub(crate) fn set_tag(tag: Option<u64>) {
+    CBOR_TAG.with(|cell| cell.set(tag));
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn set_tag(_: Option<u64>) {}
+
+// Returns the current tag and clears it, so that it is not applied a second time.
+#[cfg(feature = "std")]
+pub(crate) fn take_tag() -> Option<u64> {
+    CBOR_TAG.with(|cell| cell.replace(None))
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn take_tag() -> Option<u64> {
+    None
+}
+
+#[cfg(feature = "std")]
+thread_local!(static CBOR_SIMPLE: Cell<Option<u8>> = const { Cell::new(None) });
+
+#[cfg(feature = "std")]
+pub(crate) fn set_simple(value: Option<u8>) {
+    CBOR_SIMPLE.with(|cell| cell.set(value));
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn set_simple(_: Option<u8>) {}
+
+// Returns the current simple value and clears it.
+#[cfg(feature = "std")]
+pub(crate) fn take_simple() -> Option<u8> {
+    CBOR_SIMPLE.with(|cell| cell.replace(None))
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn take_simple() -> Option<u8> {
+    None
+}
+
+// A simple value, which serializes as one with the serializer of this crate and as its number
+// with others.
+#[cfg(feature = "std")]
+pub(crate) struct Simple(pub u8);
+
+#[cfg(feature = "std")]
+impl Serialize for Simple {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        set_simple(Some(self.0));
+        let result = serializer.serialize_newtype_struct(SIMPLE_NEWTYPE_NAME, &self.0);
+        set_simple(None);
+        result
+    }
+}
+
+/// A value with a semantic tag.
+///
+/// It serializes as the value with the tag in front, and deserializes from a tagged data item,
+/// keeping its tag so that applications can tell values apart by it. Data items without a tag
+/// fail to deserialize.
+///
+/// ```
+/// use serde_cbor::tags::Tagged;
+///
+/// // 1(1363896240), a time in seconds since the epoch
+/// let bytes = serde_cbor::to_vec(&Tagged::new(1, 1363896240)).unwrap();
+/// assert_eq!(bytes, b"\xc1\x1a\x51\x4b\x67\xb0");
+///
+/// let tagged: Tagged<u32> = serde_cbor::from_slice(&bytes).unwrap();
+/// assert_eq!(tagged.tag, 1);
+/// assert_eq!(tagged.value, 1363896240);
+/// ```
+///
+/// The tag is passed outside of serde's data model, so other formats see just the value, and
+/// `Tagged` can only be deserialized by the deserializer of this crate.
+///
+/// This type is available with the `std` feature.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tagged<T> {
+    /// The tag.
+    pub tag: u64,
+    /// The value that is tagged.
+    pub value: T,
+}
+
+#[cfg(feature = "std")]
+impl<T> Tagged<T> {
+    /// Tags a value.
+    pub fn new(tag: u64, value: T) -> Tagged<T> {
+        Tagged { tag, value }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Serialize for Tagged<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        set_tag(Some(self.tag));
+        let result = serializer.serialize_newtype_struct(CBOR_NEWTYPE_NAME, &self.value);
+        set_tag(None);
+        result
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, T> Deserialize<'de> for Tagged<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match deserialize_with_tag(deserializer)? {
+            (Some(tag), value) => Ok(Tagged { tag, value }),
+            (None, _) => Err(de::Error::custom("expected a tagged value")),
+        }
+    }
+}
+
+/// A value encoded as CBOR and embedded in a byte string with tag 24.
+///
+/// This is the `bstr .cbor` pattern of CDDL, which COSE and CWT use for data that is signed or
+/// encrypted as bytes. The value is encoded and decoded in one step, with the default options
+/// of `to_vec` and `from_slice`.
+///
+/// ```
+/// use serde_cbor::tags::CborEncoded;
+///
+/// let bytes = serde_cbor::to_vec(&CborEncoded("IETF")).unwrap();
+/// assert_eq!(bytes, b"\xd8\x18\x45\x64\x49\x45\x54\x46");
+///
+/// let encoded: CborEncoded<String> = serde_cbor::from_slice(&bytes).unwrap();
+/// assert_eq!(encoded.0, "IETF");
+/// ```
+///
+/// Other formats see just the byte string. When deserializing, a byte string without a tag is
+/// accepted.
+///
+/// This type is available with the `std` feature.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CborEncoded<T>(pub T);
+
+#[cfg(feature = "std")]
+impl<T> Serialize for CborEncoded<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let bytes = ::ser::to_vec(&self.0).map_err(ser::Error::custom)?;
+        serialize_bytes(&bytes, 24, serializer)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, T> Deserialize<'de> for CborEncoded<T>
+where
+    T: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = deserialize_bytes(deserializer, 24)?;
+        ::de::from_slice(&bytes)
+            .map(CborEncoded)
+            .map_err(de::Error::custom)
+    }
+}
+
+// Deserializes a value that is either untagged or has the tag `expected`.
+#[cfg(feature = "std")]
+pub(crate) fn deserialize_tagged<'de, D, T>(deserializer: D, expected: u64) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    match deserialize_with_tag(deserializer)? {
+        (Some(tag), _) if tag != expected => Err(de::Error::custom(format_args!(
+            "expected tag {}, found tag {}",
+            expected, tag
+        ))),
+        (_, value) => Ok(value),
+    }
+}
+
+// Deserializes a value along with its tag, if it has one.
+#[cfg(feature = "std")]
+pub(crate) fn deserialize_with_tag<'de, D, T>(deserializer: D) -> Result<(Option<u64>, T), D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct TagVisitor<T>(PhantomData<T>);
+
+    impl<'de, T> de::Visitor<'de> for TagVisitor<T>
+    where
+        T: Deserialize<'de>,
+    {
+        type Value = (Option<u64>, T);
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a tagged value")
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let tag = take_tag();
+            Ok((tag, T::deserialize(deserializer)?))
+        }
+    }
+
+    deserializer.deserialize_newtype_struct(CBOR_NEWTYPE_NAME, TagVisitor(PhantomData))
+}
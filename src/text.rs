@@ -0,0 +1,373 @@
+//! Text strings that CBOR marks with semantic tags.
+//!
+//! `Base64Url` and `Base64` are text holding binary data in base64url with tag 33 and in base64
+//! with tag 34, as found where JSON based formats such as JOSE carry binary data. They keep the
+//! text as it is, so that it can be passed on unchanged, and decode it to bytes on request.
+//! `Regex` is a regular expression with tag 35, and `MimeMessage` a MIME message with its header
+//! with tag 36.
+//!
+//! ```
+//! use serde_cbor::text::Base64Url;
+//!
+//! let text = Base64Url::new("yv66vg".to_string()).unwrap();
+//! let bytes = serde_cbor::to_vec(&text).unwrap();
+//! assert_eq!(bytes, b"\xd8\x21\x66yv66vg");
+//! assert_eq!(text.decode(), [0xca, 0xfe, 0xba, 0xbe]);
+//! ```
+//!
+//! As with other tagged values, text without a tag is accepted when deserializing, and other
+//! formats see just the text. Text that is not valid for its tag is rejected.
+//!
+//! This module is available with the `std` feature.
+#[cfg(feature = "mime")]
+use mime::Mime;
+#[cfg(feature = "regex")]
+use regex;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use tags::{deserialize_tagged, Tagged};
+
+pub(crate) const BASE64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+pub(crate) const BASE64URL: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Binary data as base64url text without padding, with tag 33.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Base64Url(String);
+
+impl Base64Url {
+    /// Wraps text that is already in base64url, or returns `None` if it is not.
+    ///
+    /// Padding, characters outside the alphabet and nonzero bits after the last byte are not
+    /// allowed.
+    pub fn new(text: String) -> Option<Base64Url> {
+        decode_base64(&text, BASE64URL, false).map(|_| Base64Url(text))
+    }
+
+    /// Encodes bytes in base64url.
+    pub fn encode(bytes: &[u8]) -> Base64Url {
+        Base64Url(encode_base64(bytes, BASE64URL, false))
+    }
+
+    /// Decodes the text to the bytes it holds.
+    pub fn decode(&self) -> Vec<u8> {
+        decode_base64(&self.0, BASE64URL, false).expect("text was checked when created")
+    }
+
+    /// Returns the text.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the text, consuming the value.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Serialize for Base64Url {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 33,
+            value: self.as_str(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Base64Url {
+    fn deserialize<D>(deserializer: D) -> Result<Base64Url, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text: String = deserialize_tagged(deserializer, 33)?;
+        if decode_base64(&text, BASE64URL, false).is_none() {
+            return Err(de::Error::invalid_value(de::Unexpected::Str(&text), &"base64url text"));
+        }
+        Ok(Base64Url(text))
+    }
+}
+
+/// Binary data as base64 text with padding, with tag 34.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Base64(String);
+
+impl Base64 {
+    /// Wraps text that is already in base64, or returns `None` if it is not.
+    ///
+    /// Missing padding, characters outside the alphabet and nonzero bits after the last byte
+    /// are not allowed.
+    pub fn new(text: String) -> Option<Base64> {
+        decode_base64(&text, BASE64, true).map(|_| Base64(text))
+    }
+
+    /// Encodes bytes in base64.
+    pub fn encode(bytes: &[u8]) -> Base64 {
+        Base64(encode_base64(bytes, BASE64, true))
+    }
+
+    /// Decodes the text to the bytes it holds.
+    pub fn decode(&self) -> Vec<u8> {
+        decode_base64(&self.0, BASE64, true).expect("text was checked when created")
+    }
+
+    /// Returns the text.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the text, consuming the value.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Serialize for Base64 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 34,
+            value: self.as_str(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Base64 {
+    fn deserialize<D>(deserializer: D) -> Result<Base64, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text: String = deserialize_tagged(deserializer, 34)?;
+        if decode_base64(&text, BASE64, true).is_none() {
+            return Err(de::Error::invalid_value(de::Unexpected::Str(&text), &"base64 text"));
+        }
+        Ok(Base64(text))
+    }
+}
+
+/// A regular expression, with tag 35.
+///
+/// The tag stands for the syntax of PCRE and ECMAScript, which the pattern is passed on in
+/// without being checked. With the `regex` feature, it converts to a `regex::Regex` and back,
+/// and the `with::regex` adapter deserializes only patterns that the `regex` crate accepts.
+///
+/// ```
+/// use serde_cbor::text::Regex;
+///
+/// let regex = Regex::new(r"^[a-z]+\.example$".to_string());
+/// let diag = serde_cbor::diag::to_diag_string(&regex).unwrap();
+/// assert_eq!(diag, r#"35("^[a-z]+\\.example$")"#);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Regex(String);
+
+impl Regex {
+    /// Wraps a pattern.
+    pub fn new(pattern: String) -> Regex {
+        Regex(pattern)
+    }
+
+    /// Compiles the pattern with the `regex` crate.
+    ///
+    /// This method is available with the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn to_regex(&self) -> Result<regex::Regex, regex::Error> {
+        regex::Regex::new(&self.0)
+    }
+
+    /// Returns the pattern.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the pattern, consuming the value.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+#[cfg(feature = "regex")]
+impl From<regex::Regex> for Regex {
+    fn from(regex: regex::Regex) -> Regex {
+        Regex(regex.as_str().to_string())
+    }
+}
+
+impl Serialize for Regex {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 35,
+            value: self.as_str(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Regex {
+    fn deserialize<D>(deserializer: D) -> Result<Regex, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_tagged(deserializer, 35).map(Regex)
+    }
+}
+
+/// A MIME message, with its header fields and its body, with tag 36.
+///
+/// The message is kept as text and not checked. With the `mime` feature, its media type can be
+/// parsed from its `Content-Type` field.
+///
+/// ```
+/// use serde_cbor::text::MimeMessage;
+///
+/// let message = MimeMessage::new("Content-Type: text/plain\r\n\r\nHello".to_string());
+/// assert_eq!(message.header("content-type").unwrap(), "text/plain");
+/// let bytes = serde_cbor::to_vec(&message).unwrap();
+/// assert_eq!(&bytes[..2], b"\xd8\x24");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MimeMessage(String);
+
+impl MimeMessage {
+    /// Wraps a message.
+    pub fn new(message: String) -> MimeMessage {
+        MimeMessage(message)
+    }
+
+    /// Returns the value of the first header field with the given name, which is compared
+    /// without regard to case.
+    ///
+    /// Values that continue on further lines are joined, and white space around them is removed.
+    pub fn header(&self, name: &str) -> Option<String> {
+        let mut value: Option<String> = None;
+        for line in self.0.lines() {
+            // The header ends at the first empty line.
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with(' ') || line.starts_with('\t') {
+                if let Some(ref mut value) = value {
+                    value.push_str(line);
+                }
+                continue;
+            }
+            if value.is_some() {
+                break;
+            }
+            if let Some(colon) = line.find(':') {
+                if line[..colon].trim_end().eq_ignore_ascii_case(name) {
+                    value = Some(line[colon + 1..].to_string());
+                }
+            }
+        }
+        value.map(|value| value.trim().to_string())
+    }
+
+    /// Parses the media type of the message from its `Content-Type` field, which is
+    /// `text/plain; charset=us-ascii` if there is none. Returns `None` if the field is not a
+    /// valid media type.
+    ///
+    /// This method is available with the `mime` feature.
+    #[cfg(feature = "mime")]
+    pub fn content_type(&self) -> Option<Mime> {
+        match self.header("Content-Type") {
+            Some(value) => value.parse().ok(),
+            None => "text/plain; charset=us-ascii".parse().ok(),
+        }
+    }
+
+    /// Returns the message.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the message, consuming the value.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Serialize for MimeMessage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 36,
+            value: self.as_str(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MimeMessage {
+    fn deserialize<D>(deserializer: D) -> Result<MimeMessage, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_tagged(deserializer, 36).map(MimeMessage)
+    }
+}
+
+pub(crate) fn encode_base64(bytes: &[u8], alphabet: &[u8; 64], padded: bool) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, &byte)| bits | u32::from(byte) << (16 - 8 * i));
+        for i in 0..chunk.len() + 1 {
+            out.push(alphabet[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+        if padded {
+            for _ in chunk.len()..3 {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// Decodes text in the exact form `encode_base64` writes, so that each sequence of bytes has just
+// one encoding.
+fn decode_base64(text: &str, alphabet: &[u8; 64], padded: bool) -> Option<Vec<u8>> {
+    let mut text = text.as_bytes();
+    if padded {
+        if text.len() % 4 != 0 {
+            return None;
+        }
+        let padding = text.iter().rev().take(2).take_while(|&&c| c == b'=').count();
+        text = &text[..text.len() - padding];
+    }
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3 + 2);
+    let mut bits = 0u32;
+    let mut nbits = 0;
+    for &c in text {
+        let sextet = alphabet.iter().position(|&a| a == c)?;
+        bits = bits << 6 | sextet as u32;
+        nbits += 6;
+        if nbits >= 8 {
+            nbits -= 8;
+            bytes.push((bits >> nbits) as u8);
+            bits &= (1 << nbits) - 1;
+        }
+    }
+    if bits != 0 {
+        return None;
+    }
+    Some(bytes)
+}
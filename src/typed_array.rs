@@ -0,0 +1,378 @@
+//! Typed arrays of RFC 8746, which store arrays of numbers as byte strings.
+//!
+//! The tag of a typed array tells the type and byte order of its elements, for example tag 69
+//! for unsigned 16-bit integers in little-endian order. A `TypedArray` borrows the byte string
+//! from the input, so it can only be deserialized with `from_slice`. Its elements are read with
+//! `to_vec`, or with the `bytemuck` feature, viewed in place with `view` when the byte order is
+//! that of the target and the byte string is suitably aligned, which saves copying large
+//! numeric buffers. Byte strings without a tag are taken to hold elements in the byte order of
+//! the target. For fields that own their elements, the `with::typed_array` adapters encode a
+//! `Vec` of numbers as a typed array in either byte order.
+//!
+//! `NdArray` is a multi-dimensional array, written as its shape and its elements in a flat array
+//! with tag 40 for row-major order or tag 1040 for column-major order. With the `ndarray`
+//! feature, it converts to and from `ndarray::ArrayD`.
+//!
+//! ```
+//! use serde_cbor::typed_array::TypedArray;
+//!
+//! // 69(h'01000200'), two unsigned 16-bit integers in little-endian order
+//! let bytes = b"\xd8\x45\x44\x01\x00\x02\x00";
+//! let array: TypedArray = serde_cbor::from_slice(bytes).unwrap();
+//! assert_eq!(array.to_vec::<u16>(), Some(vec![1, 2]));
+//! assert_eq!(array.to_vec::<u32>(), None);
+//! ```
+#[cfg(feature = "bytemuck")]
+use bytemuck::{self, Pod};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+#[cfg(feature = "ndarray")]
+use ndarray::{ArrayD, IxDyn, ShapeBuilder};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::mem;
+
+use tags::{deserialize_with_tag, Tagged};
+
+/// A typed array or plain byte string borrowed from the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TypedArray<'a> {
+    tag: Option<u64>,
+    bytes: &'a [u8],
+}
+
+impl<'a> TypedArray<'a> {
+    pub(crate) fn new(tag: Option<u64>, bytes: &'a [u8]) -> TypedArray<'a> {
+        TypedArray { tag, bytes }
+    }
+
+    /// Creates a typed array of elements in the byte order of the target.
+    #[cfg(feature = "bytemuck")]
+    pub fn from_elements<T>(elements: &'a [T]) -> TypedArray<'a>
+    where
+        T: Element + Pod,
+    {
+        let tag = if cfg!(target_endian = "little") {
+            T::LITTLE_ENDIAN_TAG
+        } else {
+            T::BIG_ENDIAN_TAG
+        };
+        TypedArray {
+            tag: Some(tag),
+            bytes: bytemuck::cast_slice(elements),
+        }
+    }
+
+    /// Returns the tag of the typed array, or `None` for a plain byte string.
+    pub fn tag(&self) -> Option<u64> {
+        self.tag
+    }
+
+    /// Returns the content of the byte string.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Copies the elements out of the byte string, or returns `None` if the tag is for another
+    /// element type or the length is not a multiple of the element size.
+    pub fn to_vec<T>(&self) -> Option<Vec<T>>
+    where
+        T: Element,
+    {
+        let little_endian = self.little_endian::<T>()?;
+        let mut elements = vec![T::default(); self.bytes.len() / mem::size_of::<T>()];
+        T::read_into(self.bytes, &mut elements, little_endian);
+        Some(elements)
+    }
+
+    /// Returns the elements in place, or `None` if the tag is for another element type or byte
+    /// order, or the byte string is not aligned for or a multiple of the element size.
+    #[cfg(feature = "bytemuck")]
+    pub fn view<T>(&self) -> Option<&'a [T]>
+    where
+        T: Element + Pod,
+    {
+        if self.little_endian::<T>()? != cfg!(target_endian = "little") {
+            return None;
+        }
+        bytemuck::try_cast_slice(self.bytes).ok()
+    }
+
+    // Returns whether the elements are in little-endian order if they are of type `T`.
+    fn little_endian<T>(&self) -> Option<bool>
+    where
+        T: Element,
+    {
+        let little_endian = match self.tag {
+            None => cfg!(target_endian = "little"),
+            Some(tag) if tag == T::BIG_ENDIAN_TAG => false,
+            Some(tag) if tag == T::LITTLE_ENDIAN_TAG => true,
+            // Clamped arithmetic makes no difference to the elements themselves.
+            Some(68) if T::BIG_ENDIAN_TAG == 64 => false,
+            Some(_) => return None,
+        };
+        if self.bytes.len() % mem::size_of::<T>() != 0 {
+            return None;
+        }
+        Some(little_endian)
+    }
+}
+
+impl<'a> Serialize for TypedArray<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let bytes = Bytes(self.bytes);
+        match self.tag {
+            Some(tag) => Tagged { tag, value: &bytes }.serialize(serializer),
+            None => bytes.serialize(serializer),
+        }
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for TypedArray<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<TypedArray<'a>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match deserialize_with_tag(deserializer)? {
+            (Some(tag), _) if !(64..=87).contains(&tag) => Err(de::Error::custom(format_args!(
+                "expected a typed array, found tag {}",
+                tag
+            ))),
+            (tag, bytes) => Ok(TypedArray { tag, bytes }),
+        }
+    }
+}
+
+/// A multi-dimensional array, with tag 40 for row-major order or tag 1040 for column-major order.
+///
+/// The array is written as an array of its shape, the length along each dimension, and of its
+/// elements in one flat array. Arrays without a tag are taken to be in row-major order.
+///
+/// ```
+/// use serde_cbor::typed_array::NdArray;
+///
+/// // A 2 x 3 matrix.
+/// let matrix = NdArray::new(vec![2, 3], vec![1, 2, 3, 4, 5, 6]).unwrap();
+/// let diag = serde_cbor::diag::to_diag_string(&matrix).unwrap();
+/// assert_eq!(diag, "40([[2, 3], [1, 2, 3, 4, 5, 6]])");
+/// ```
+///
+/// With the `ndarray` feature, it converts from `ndarray::ArrayD` and back.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NdArray<T> {
+    shape: Vec<usize>,
+    data: Vec<T>,
+    column_major: bool,
+}
+
+impl<T> NdArray<T> {
+    /// Creates an array of elements in row-major order, where the last index changes fastest.
+    /// Returns `None` if the number of elements does not match the shape.
+    pub fn new(shape: Vec<usize>, data: Vec<T>) -> Option<NdArray<T>> {
+        NdArray::with_order(shape, data, false)
+    }
+
+    /// Creates an array of elements in column-major order, where the first index changes
+    /// fastest. Returns `None` if the number of elements does not match the shape.
+    pub fn new_column_major(shape: Vec<usize>, data: Vec<T>) -> Option<NdArray<T>> {
+        NdArray::with_order(shape, data, true)
+    }
+
+    fn with_order(shape: Vec<usize>, data: Vec<T>, column_major: bool) -> Option<NdArray<T>> {
+        let len = shape
+            .iter()
+            .try_fold(1usize, |len, &dimension| len.checked_mul(dimension))?;
+        if len != data.len() {
+            return None;
+        }
+        Some(NdArray {
+            shape,
+            data,
+            column_major,
+        })
+    }
+
+    /// Returns the length of the array along each dimension.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// Returns the elements in the order of the array.
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Returns whether the elements are in column-major order.
+    pub fn is_column_major(&self) -> bool {
+        self.column_major
+    }
+
+    /// Returns the elements in the order of the array, consuming it.
+    pub fn into_data(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Converts the array to an `ArrayD`, or returns `None` if its shape is too large for one.
+    ///
+    /// This method is available with the `ndarray` feature.
+    #[cfg(feature = "ndarray")]
+    pub fn to_ndarray(&self) -> Option<ArrayD<T>>
+    where
+        T: Clone,
+    {
+        let shape = IxDyn(&self.shape);
+        let data = self.data.clone();
+        if self.column_major {
+            ArrayD::from_shape_vec(shape.f(), data).ok()
+        } else {
+            ArrayD::from_shape_vec(shape, data).ok()
+        }
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<T> From<ArrayD<T>> for NdArray<T>
+where
+    T: Clone,
+{
+    fn from(array: ArrayD<T>) -> NdArray<T> {
+        NdArray {
+            shape: array.shape().to_vec(),
+            data: array.iter().cloned().collect(),
+            column_major: false,
+        }
+    }
+}
+
+impl<T> Serialize for NdArray<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: if self.column_major { 1040 } else { 40 },
+            value: (&self.shape, &self.data),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for NdArray<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<NdArray<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (tag, (shape, data)) = deserialize_with_tag(deserializer)?;
+        let column_major = match tag {
+            None | Some(40) => false,
+            Some(1040) => true,
+            Some(tag) => {
+                return Err(de::Error::custom(format_args!(
+                    "expected tag 40 or 1040, found tag {}",
+                    tag
+                )))
+            }
+        };
+        NdArray::with_order(shape, data, column_major)
+            .ok_or_else(|| de::Error::custom("number of elements does not match the shape"))
+    }
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Serialize for Bytes<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Numbers that can be elements of typed arrays.
+pub trait Element: Copy + Default {
+    /// The tag of typed arrays with elements of this type in big-endian order.
+    const BIG_ENDIAN_TAG: u64;
+    /// The tag of typed arrays with elements of this type in little-endian order.
+    const LITTLE_ENDIAN_TAG: u64;
+
+    /// Reads elements from bytes that are exactly as long as the elements.
+    fn read_into(bytes: &[u8], elements: &mut [Self], little_endian: bool);
+
+    /// Writes elements to bytes that are exactly as long as the elements.
+    fn write_into(elements: &[Self], bytes: &mut [u8], little_endian: bool);
+}
+
+impl Element for u8 {
+    const BIG_ENDIAN_TAG: u64 = 64;
+    const LITTLE_ENDIAN_TAG: u64 = 64;
+
+    fn read_into(bytes: &[u8], elements: &mut [u8], _: bool) {
+        elements.copy_from_slice(bytes);
+    }
+
+    fn write_into(elements: &[u8], bytes: &mut [u8], _: bool) {
+        bytes.copy_from_slice(elements);
+    }
+}
+
+impl Element for i8 {
+    const BIG_ENDIAN_TAG: u64 = 72;
+    const LITTLE_ENDIAN_TAG: u64 = 72;
+
+    fn read_into(bytes: &[u8], elements: &mut [i8], _: bool) {
+        for (element, byte) in elements.iter_mut().zip(bytes) {
+            *element = *byte as i8;
+        }
+    }
+
+    fn write_into(elements: &[i8], bytes: &mut [u8], _: bool) {
+        for (byte, element) in bytes.iter_mut().zip(elements) {
+            *byte = *element as u8;
+        }
+    }
+}
+
+// The elements are copied as a whole, and swapped in place where the byte order is not that of
+// the target.
+macro_rules! element {
+    ($ty:ident, $big_endian_tag:expr, $little_endian_tag:expr, $read:ident, $write:ident) => {
+        impl Element for $ty {
+            const BIG_ENDIAN_TAG: u64 = $big_endian_tag;
+            const LITTLE_ENDIAN_TAG: u64 = $little_endian_tag;
+
+            fn read_into(bytes: &[u8], elements: &mut [$ty], little_endian: bool) {
+                if little_endian {
+                    LittleEndian::$read(bytes, elements)
+                } else {
+                    BigEndian::$read(bytes, elements)
+                }
+            }
+
+            fn write_into(elements: &[$ty], bytes: &mut [u8], little_endian: bool) {
+                if little_endian {
+                    LittleEndian::$write(elements, bytes)
+                } else {
+                    BigEndian::$write(elements, bytes)
+                }
+            }
+        }
+    };
+}
+
+element!(u16, 65, 69, read_u16_into, write_u16_into);
+element!(u32, 66, 70, read_u32_into, write_u32_into);
+element!(u64, 67, 71, read_u64_into, write_u64_into);
+element!(i16, 73, 77, read_i16_into, write_i16_into);
+element!(i32, 74, 78, read_i32_into, write_i32_into);
+element!(i64, 75, 79, read_i64_into, write_i64_into);
+element!(f32, 81, 85, read_f32_into, write_f32_into);
+element!(f64, 82, 86, read_f64_into, write_f64_into);
@@ -0,0 +1,372 @@
+//! Values that borrow their strings from the input.
+
+use serde::de;
+use serde::ser;
+use std::borrow::Cow;
+use std::fmt;
+
+use tags::{take_simple, take_tag, Simple, Tagged, VALUE_NEWTYPE_NAME};
+
+use super::{Map, Value};
+
+/// A CBOR value whose text and byte strings borrow from the slice it was deserialized from.
+///
+/// Deserializing a `Value` copies every string into a new allocation, which is wasted on code
+/// that only looks at a document. A `ValueRef` deserialized with `from_slice` points into the
+/// input instead. Only strings of indefinite length, whose chunks have to be joined, are
+/// copied, which is why they are `Cow`s. Map entries are kept in a vector in the order they
+/// were read, duplicates included, as the keys need not be compared to build it.
+///
+/// ```
+/// use serde_cbor::value::ValueRef;
+/// use std::borrow::Cow;
+///
+/// // {"name": "sensor", "data": h'0102'}
+/// let bytes = b"\xa2\x64name\x66sensor\x64data\x42\x01\x02";
+/// let value: ValueRef = serde_cbor::from_slice(bytes).unwrap();
+/// assert_eq!(value.get("name").and_then(|v| v.as_string()), Some("sensor"));
+/// match value.get("data") {
+///     Some(&ValueRef::Bytes(Cow::Borrowed(data))) => assert_eq!(data, &bytes[19..]),
+///     _ => panic!("the byte string is borrowed"),
+/// }
+/// ```
+///
+/// A `ValueRef` converts into a `Value`, and serializes the same way as the `Value` would.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueRef<'a> {
+    /// An integer, between -2^64 and 2^64 - 1 unless it was converted from a `Value`.
+    Integer(i128),
+    /// A byte string.
+    Bytes(Cow<'a, [u8]>),
+    /// A text string.
+    String(Cow<'a, str>),
+    /// An array.
+    Array(Vec<ValueRef<'a>>),
+    /// A map, as its entries in order.
+    Object(Vec<(ValueRef<'a>, ValueRef<'a>)>),
+    /// A float.
+    F64(f64),
+    /// A boolean.
+    Bool(bool),
+    /// Null.
+    Null,
+    /// Undefined.
+    Undefined,
+    /// A simple value other than false, true, null and undefined.
+    Simple(u8),
+    /// A value with a semantic tag.
+    Tag(u64, Box<ValueRef<'a>>),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Returns the value of the first entry whose key is the text `key`, or `None` if the value
+    /// is not a map or has no such entry.
+    pub fn get(&self, key: &str) -> Option<&ValueRef<'a>> {
+        self.as_object()?
+            .iter()
+            .find(|entry| entry.0.as_string() == Some(key))
+            .map(|entry| &entry.1)
+    }
+
+    /// Returns the integer, or `None` if the value is something else.
+    pub fn as_integer(&self) -> Option<i128> {
+        match *self {
+            ValueRef::Integer(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the float, or `None` if the value is something else.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            ValueRef::F64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the boolean, or `None` if the value is something else.
+    pub fn as_boolean(&self) -> Option<bool> {
+        match *self {
+            ValueRef::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string, or `None` if the value is something else.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            ValueRef::Bytes(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the text string, or `None` if the value is something else.
+    pub fn as_string(&self) -> Option<&str> {
+        match *self {
+            ValueRef::String(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of the array, or `None` if the value is something else.
+    pub fn as_array(&self) -> Option<&[ValueRef<'a>]> {
+        match *self {
+            ValueRef::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries of the map, or `None` if the value is something else.
+    pub fn as_object(&self) -> Option<&[(ValueRef<'a>, ValueRef<'a>)]> {
+        match *self {
+            ValueRef::Object(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the tag and the value that is tagged, or `None` if the value has no tag.
+    pub fn as_tag(&self) -> Option<(u64, &ValueRef<'a>)> {
+        match *self {
+            ValueRef::Tag(tag, ref v) => Some((tag, v)),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<ValueRef<'a>> for Value {
+    fn from(value: ValueRef<'a>) -> Value {
+        match value {
+            ValueRef::Integer(v) => Value::Integer(v),
+            ValueRef::Bytes(v) => Value::Bytes(v.into_owned()),
+            ValueRef::String(v) => Value::String(v.into_owned()),
+            ValueRef::Array(v) => Value::Array(v.into_iter().map(Value::from).collect()),
+            ValueRef::Object(v) => {
+                let map: Map = v
+                    .into_iter()
+                    .map(|(key, value)| (Value::from(key), Value::from(value)))
+                    .collect();
+                Value::Object(map)
+            }
+            ValueRef::F64(v) => Value::F64(v),
+            ValueRef::Bool(v) => Value::Bool(v),
+            ValueRef::Null => Value::Null,
+            ValueRef::Undefined => Value::Undefined,
+            ValueRef::Simple(v) => Value::Simple(v),
+            ValueRef::Tag(tag, v) => Value::Tag(tag, Box::new(Value::from(*v))),
+        }
+    }
+}
+
+impl<'a> From<&'a Value> for ValueRef<'a> {
+    fn from(value: &'a Value) -> ValueRef<'a> {
+        match *value {
+            Value::Integer(v) => ValueRef::Integer(v),
+            Value::Bytes(ref v) => ValueRef::Bytes(Cow::Borrowed(v)),
+            Value::String(ref v) => ValueRef::String(Cow::Borrowed(v)),
+            Value::Array(ref v) => ValueRef::Array(v.iter().map(ValueRef::from).collect()),
+            Value::Object(ref v) => ValueRef::Object(
+                v.iter()
+                    .map(|(key, value)| (ValueRef::from(key), ValueRef::from(value)))
+                    .collect(),
+            ),
+            Value::F64(v) => ValueRef::F64(v),
+            Value::Bool(v) => ValueRef::Bool(v),
+            Value::Null => ValueRef::Null,
+            Value::Undefined => ValueRef::Undefined,
+            Value::Simple(v) => ValueRef::Simple(v),
+            Value::Tag(tag, ref v) => ValueRef::Tag(tag, Box::new(ValueRef::from(&**v))),
+        }
+    }
+}
+
+impl<'de: 'a, 'a> de::Deserialize<'de> for ValueRef<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<ValueRef<'a>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct ValueRefVisitor;
+
+        impl<'de> de::Visitor<'de> for ValueRefVisitor {
+            type Value = ValueRef<'de>;
+
+            fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("any valid CBOR value")
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::String(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::String(Cow::Owned(v.to_owned())))
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::String(Cow::Owned(v)))
+            }
+
+            fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::Bytes(Cow::Borrowed(v)))
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::Bytes(Cow::Owned(v.to_owned())))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::Bytes(Cow::Owned(v)))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::Integer(i128::from(v)))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::Integer(i128::from(v)))
+            }
+
+            fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::Integer(v))
+            }
+
+            fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                if v > i128::max_value() as u128 {
+                    return Err(E::custom("integer out of range for ValueRef"));
+                }
+                Ok(ValueRef::Integer(v as i128))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::F64(v))
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(ValueRef::Bool(v))
+            }
+
+            fn visit_none<E>(self) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                self.visit_unit()
+            }
+
+            // The deserializer of this crate reports simple values other than false, true and
+            // null here, as it does for `Value`.
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                match take_simple() {
+                    None => Ok(ValueRef::Null),
+                    Some(23) => Ok(ValueRef::Undefined),
+                    Some(v) => Ok(ValueRef::Simple(v)),
+                }
+            }
+
+            fn visit_seq<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+            where
+                V: de::SeqAccess<'de>,
+            {
+                // the hint may come from an untrusted length header
+                let mut vec = Vec::with_capacity(visitor.size_hint().unwrap_or(0).min(4096));
+                while let Some(elem) = visitor.next_element()? {
+                    vec.push(elem);
+                }
+                Ok(ValueRef::Array(vec))
+            }
+
+            fn visit_map<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+            where
+                V: de::MapAccess<'de>,
+            {
+                let mut entries = Vec::with_capacity(visitor.size_hint().unwrap_or(0).min(4096));
+                while let Some(entry) = visitor.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(ValueRef::Object(entries))
+            }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                match take_tag() {
+                    Some(tag) => {
+                        let value = de::Deserialize::deserialize(deserializer)?;
+                        Ok(ValueRef::Tag(tag, Box::new(value)))
+                    }
+                    None => deserializer.deserialize_any(ValueRefVisitor),
+                }
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(VALUE_NEWTYPE_NAME, ValueRefVisitor)
+    }
+}
+
+impl<'a> ser::Serialize for ValueRef<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match *self {
+            // Other formats may not take 128-bit integers.
+            ValueRef::Integer(v) if v >= 0 && v <= i128::from(u64::max_value()) => {
+                serializer.serialize_u64(v as u64)
+            }
+            ValueRef::Integer(v) if v < 0 && v >= i128::from(i64::min_value()) => {
+                serializer.serialize_i64(v as i64)
+            }
+            ValueRef::Integer(v) => serializer.serialize_i128(v),
+            ValueRef::Bytes(ref v) => serializer.serialize_bytes(v),
+            ValueRef::String(ref v) => serializer.serialize_str(v),
+            ValueRef::Array(ref v) => serializer.collect_seq(v),
+            ValueRef::Object(ref v) => {
+                serializer.collect_map(v.iter().map(|entry| (&entry.0, &entry.1)))
+            }
+            ValueRef::F64(v) => serializer.serialize_f64(v),
+            ValueRef::Bool(v) => serializer.serialize_bool(v),
+            ValueRef::Null => serializer.serialize_unit(),
+            ValueRef::Undefined => Simple(23).serialize(serializer),
+            ValueRef::Simple(v) => Simple(v).serialize(serializer),
+            ValueRef::Tag(tag, ref v) => Tagged { tag, value: &**v }.serialize(serializer),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Encoding and decoding values head by head, without going through serde.
+
+use byteorder::{BigEndian, ByteOrder};
+use half::f16;
+use serde::ser;
+
+use cursor::Cursor;
+use de::MAX_DEPTH;
+use error::{Error, ErrorCode, Result};
+use ser::encode_head;
+
+use super::{Map, Value};
+
+impl Value {
+    /// Decodes a value from a slice that holds exactly one data item.
+    ///
+    /// The value is the same as that of `serde_cbor::from_slice::<Value>` with the default
+    /// options, but the data items are read straight into the value rather than being handed
+    /// through serde's visitors, which makes this quite a bit faster for code that only works
+    /// with `Value`s. Input that fails to decode fails with an error of the same category, but
+    /// its offset and message may differ and it has no path.
+    ///
+    /// ```
+    /// use serde_cbor::Value;
+    ///
+    /// let value = Value::from_slice(b"\x82\x01\xc1\x61a").unwrap();
+    /// let tagged = Value::Tag(1, Box::new(Value::from("a")));
+    /// assert_eq!(value, Value::Array(vec![Value::Integer(1), tagged]));
+    /// ```
+    pub fn from_slice(slice: &[u8]) -> Result<Value> {
+        let mut cursor = Cursor::new(slice);
+        // The self-described CBOR tag is skipped at the start, as the deserializer does.
+        if slice.starts_with(b"\xd9\xd9\xf7") {
+            cursor.offset = 3;
+        }
+        let value = decode(&mut cursor, 0)?;
+        if !cursor.at_end() {
+            return Err(cursor.error(ErrorCode::TrailingData));
+        }
+        Ok(value)
+    }
+
+    /// Encodes the value to a vector, without going through serde.
+    ///
+    /// The result is the same as that of `serde_cbor::to_vec` with the default options: heads
+    /// and floats take the fewest bytes that keep their value, and integers beyond 64 bits are
+    /// written as bignums.
+    ///
+    /// ```
+    /// use serde_cbor::Value;
+    ///
+    /// let value = Value::Array(vec![Value::Integer(1), Value::F64(1.5)]);
+    /// assert_eq!(value.to_vec().unwrap(), b"\x82\x01\xf9\x3e\x00");
+    /// assert_eq!(value.to_vec().unwrap(), serde_cbor::to_vec(&value).unwrap());
+    /// ```
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(self.encoded_size());
+        encode(self, &mut out)?;
+        Ok(out)
+    }
+}
+
+fn decode(cursor: &mut Cursor, depth: usize) -> Result<Value> {
+    if depth == MAX_DEPTH {
+        return Err(cursor.error(ErrorCode::RecursionLimitExceeded));
+    }
+    let start = cursor.offset;
+    let (major, arg) = cursor.head()?;
+    let value = match (major, arg) {
+        (0, Some(arg)) => Value::Integer(i128::from(arg)),
+        (1, Some(arg)) => Value::Integer(-1 - i128::from(arg)),
+        (2, _) => Value::Bytes(string(cursor, 2, arg)?),
+        (3, _) => {
+            let bytes = string(cursor, 3, arg)?;
+            match String::from_utf8(bytes) {
+                Ok(text) => Value::String(text),
+                Err(e) => {
+                    // Chunks of indefinite length text are joined, so the offset is only exact
+                    // for definite-length text.
+                    let shift = e.as_bytes().len() - e.utf8_error().valid_up_to();
+                    let offset = (cursor.offset - shift) as u64;
+                    return Err(Error::syntax(ErrorCode::InvalidUtf8, offset));
+                }
+            }
+        }
+        (4, Some(len)) => {
+            let len = cursor.len(len)?;
+            // The length may come from untrusted input.
+            let mut array = Vec::with_capacity(len.min(4096));
+            for _ in 0..len {
+                array.push(decode(cursor, depth + 1)?);
+            }
+            Value::Array(array)
+        }
+        (4, None) => {
+            let mut array = Vec::new();
+            while !cursor.at_break() {
+                array.push(decode(cursor, depth + 1)?);
+            }
+            cursor.take(1)?;
+            Value::Array(array)
+        }
+        (5, Some(len)) => {
+            let mut map = Map::new();
+            for _ in 0..len {
+                let key = decode(cursor, depth + 1)?;
+                map.insert(key, decode(cursor, depth + 1)?);
+            }
+            Value::Object(map)
+        }
+        (5, None) => {
+            let mut map = Map::new();
+            while !cursor.at_break() {
+                let key = decode(cursor, depth + 1)?;
+                map.insert(key, decode(cursor, depth + 1)?);
+            }
+            cursor.take(1)?;
+            Value::Object(map)
+        }
+        (6, Some(tag)) => Value::Tag(tag, Box::new(decode(cursor, depth + 1)?)),
+        (7, Some(arg)) => match cursor.input[start] & 0x1f {
+            20 => Value::Bool(false),
+            21 => Value::Bool(true),
+            22 => Value::Null,
+            23 => Value::Undefined,
+            0...19 => Value::Simple(arg as u8),
+            // The simple values below 32 only have the short form.
+            24 if arg >= 32 => Value::Simple(arg as u8),
+            25 => Value::F64(f64::from(f16::from_bits(arg as u16))),
+            26 => Value::F64(f64::from(f32::from_bits(arg as u32))),
+            27 => Value::F64(f64::from_bits(arg)),
+            _ => {
+                cursor.offset = start;
+                return Err(cursor.error(ErrorCode::UnexpectedCode));
+            }
+        },
+        _ => {
+            cursor.offset = start;
+            return Err(cursor.error(ErrorCode::UnexpectedCode));
+        }
+    };
+    Ok(value)
+}
+
+// Reads the content of a string whose head was just read, joining the chunks of one of
+// indefinite length.
+fn string(cursor: &mut Cursor, major: u8, arg: Option<u64>) -> Result<Vec<u8>> {
+    if let Some(len) = arg {
+        let len = cursor.len(len)?;
+        return Ok(cursor.take(len)?.to_vec());
+    }
+    let mut bytes = Vec::new();
+    while !cursor.at_break() {
+        let chunk = cursor.offset;
+        match cursor.head()? {
+            (m, Some(len)) if m == major => {
+                let len = cursor.len(len)?;
+                bytes.extend_from_slice(cursor.take(len)?);
+            }
+            _ => {
+                cursor.offset = chunk;
+                return Err(cursor.error(ErrorCode::UnexpectedCode));
+            }
+        }
+    }
+    cursor.take(1)?;
+    Ok(bytes)
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let mut buf = [0; 9];
+    let len = encode_head(major, arg, &mut buf);
+    out.extend_from_slice(&buf[..len]);
+}
+
+fn encode(value: &Value, out: &mut Vec<u8>) -> Result<()> {
+    match *value {
+        Value::Integer(v) => {
+            let (major, arg) = if v < 0 { (1, !v as u128) } else { (0, v as u128) };
+            if arg <= u128::from(u64::max_value()) {
+                write_head(out, major, arg as u64);
+            } else {
+                // A bignum, with the magnitude without leading zeros.
+                let bytes = arg.to_be_bytes();
+                let start = arg.leading_zeros() as usize / 8;
+                write_head(out, 6, 2 + u64::from(major));
+                write_head(out, 2, (bytes.len() - start) as u64);
+                out.extend_from_slice(&bytes[start..]);
+            }
+        }
+        Value::Bytes(ref v) => {
+            write_head(out, 2, v.len() as u64);
+            out.extend_from_slice(v);
+        }
+        Value::String(ref v) => {
+            write_head(out, 3, v.len() as u64);
+            out.extend_from_slice(v.as_bytes());
+        }
+        Value::Array(ref v) => {
+            write_head(out, 4, v.len() as u64);
+            for value in v {
+                encode(value, out)?;
+            }
+        }
+        Value::Object(ref map) => {
+            write_head(out, 5, map.len() as u64);
+            for (key, value) in map {
+                encode(key, out)?;
+                encode(value, out)?;
+            }
+        }
+        Value::Tag(tag, ref v) => {
+            write_head(out, 6, tag);
+            encode(v, out)?;
+        }
+        // The shortest float that keeps the value, as the serializer picks it.
+        Value::F64(v) => {
+            if v.is_nan() {
+                out.extend_from_slice(&[0xf9, 0x7e, 0x00]);
+            } else if !v.is_finite() || f64::from(f16::from_f64(v)) == v {
+                out.push(0xf9);
+                let mut buf = [0; 2];
+                BigEndian::write_u16(&mut buf, f16::from_f64(v).to_bits());
+                out.extend_from_slice(&buf);
+            } else if f64::from(v as f32) == v {
+                out.push(0xfa);
+                let mut buf = [0; 4];
+                BigEndian::write_f32(&mut buf, v as f32);
+                out.extend_from_slice(&buf);
+            } else {
+                out.push(0xfb);
+                let mut buf = [0; 8];
+                BigEndian::write_f64(&mut buf, v);
+                out.extend_from_slice(&buf);
+            }
+        }
+        Value::Bool(v) => out.push(if v { 0xf5 } else { 0xf4 }),
+        Value::Null => out.push(0xf6),
+        Value::Undefined => out.push(0xf7),
+        Value::Simple(v) => {
+            if (24..32).contains(&v) {
+                return Err(ser::Error::custom("invalid simple value"));
+            }
+            write_head(out, 7, u64::from(v));
+        }
+    }
+    Ok(())
+}
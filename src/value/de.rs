@@ -0,0 +1,457 @@
+//! Value deserialization routines
+
+use serde::de::{self, IntoDeserializer, Unexpected};
+use std::marker::PhantomData;
+use std::vec;
+
+use diag::to_diag_string;
+use error::{Error, PathSegment};
+use tags::{set_simple, set_tag, CBOR_NEWTYPE_NAME, RAW_NEWTYPE_NAME, VALUE_NEWTYPE_NAME};
+
+use value::{Map, Value};
+
+#[cfg(not(feature = "indexmap"))]
+type MapIter = ::std::collections::btree_map::IntoIter<Value, Value>;
+
+#[cfg(feature = "indexmap")]
+type MapIter = ::indexmap::map::IntoIter<Value, Value>;
+
+impl Value {
+    // Hands the value to the visitor the way the deserializer of this crate hands out the data
+    // item it is encoded as, with the tags already skipped. With `full`, the visitor is that of
+    // `Value`, which also takes the simple values that have no type in serde's data model.
+    fn deserialize_item<'de, V>(self, full: bool, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Integer(v) if v >= 0 && v <= i128::from(u64::max_value()) => {
+                visitor.visit_u64(v as u64)
+            }
+            Value::Integer(v) if v >= i128::from(i64::min_value()) && v < 0 => {
+                visitor.visit_i64(v as i64)
+            }
+            Value::Integer(v) if v >= 0 => visitor.visit_u128(v as u128),
+            Value::Integer(v) => visitor.visit_i128(v),
+            Value::Bytes(v) => visitor.visit_byte_buf(v),
+            Value::String(v) => visitor.visit_string(v),
+            Value::Array(v) => visit_array(v, visitor),
+            Value::Object(v) => visit_object(v, visitor),
+            Value::F64(v) => visitor.visit_f64(v),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Null => visitor.visit_unit(),
+            Value::Undefined if full => visit_simple(23, visitor),
+            Value::Undefined => visitor.visit_unit(),
+            Value::Simple(v) if full => visit_simple(v, visitor),
+            Value::Simple(_) => Err(de::Error::invalid_type(
+                Unexpected::Other("simple value"),
+                &visitor,
+            )),
+            Value::Tag(_, v) => v.deserialize_item(full, visitor),
+        }
+    }
+}
+
+// Hands a simple value to the visitor of `Value` as a unit.
+fn visit_simple<'de, V>(value: u8, visitor: V) -> Result<V::Value, Error>
+where
+    V: de::Visitor<'de>,
+{
+    set_simple(Some(value));
+    let result = visitor.visit_unit();
+    set_simple(None);
+    result
+}
+
+// Hands the content of a tag to the visitor of a newtype struct, with the tag set for it.
+fn visit_tagged<'de, V>(tag: Option<u64>, value: Value, visitor: V) -> Result<V::Value, Error>
+where
+    V: de::Visitor<'de>,
+{
+    set_tag(tag);
+    let result = visitor.visit_newtype_struct(value);
+    set_tag(None);
+    result
+}
+
+fn visit_array<'de, V>(array: Vec<Value>, visitor: V) -> Result<V::Value, Error>
+where
+    V: de::Visitor<'de>,
+{
+    let mut seq = SeqDeserializer::new(array);
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+fn visit_object<'de, V>(object: Map, visitor: V) -> Result<V::Value, Error>
+where
+    V: de::Visitor<'de>,
+{
+    let len = object.len();
+    let mut map = MapDeserializer {
+        iter: object.into_iter(),
+        entry: None,
+    };
+    let value = visitor.visit_map(&mut map)?;
+    match map.iter.len() {
+        0 => Ok(value),
+        remaining => Err(de::Error::invalid_length(len - remaining, &"fewer entries in map")),
+    }
+}
+
+// Values are deserialized the way the deserializer of this crate deserializes their encoding, so
+// that `from_value` reads what `to_value` writes, tags, byte strings and simple values included.
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_item(false, visitor)
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if name == CBOR_NEWTYPE_NAME {
+            return match self {
+                Value::Tag(tag, value) => visit_tagged(Some(tag), *value, visitor),
+                value => visit_tagged(None, value, visitor),
+            };
+        }
+        if name == VALUE_NEWTYPE_NAME {
+            return match self {
+                Value::Tag(tag, value) => visit_tagged(Some(tag), *value, visitor),
+                value => value.deserialize_item(true, visitor),
+            };
+        }
+        if name == RAW_NEWTYPE_NAME {
+            return visitor.visit_byte_buf(::to_vec(&self)?);
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Unit variants are just the variant identifier, and other variants an array of the
+    // identifier followed by the fields or the struct, as the serializer of this crate writes
+    // them.
+    fn deserialize_enum<V>(
+        self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Array(array) => {
+                let mut seq = SeqDeserializer::new(array);
+                let value = visitor.visit_enum(VariantDeserializer { seq: &mut seq })?;
+                seq.end()?;
+                Ok(value)
+            }
+            value => visitor.visit_enum(UnitVariantDeserializer { value }),
+        }
+    }
+
+    // Bignums are accepted for 128-bit integers, as long as their value fits.
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Integer(v) if v >= 0 => visitor.visit_u128(v as u128),
+            Value::Integer(v) => visitor.visit_i128(v),
+            Value::Tag(tag @ 2...3, value) => match *value {
+                Value::Bytes(bytes) => {
+                    // Leading zeros are allowed, however many there are.
+                    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+                    if bytes.len() - start > 16 {
+                        return Err(de::Error::invalid_value(
+                            Unexpected::Bytes(&bytes),
+                            &"a bignum of at most 16 bytes",
+                        ));
+                    }
+                    let value = bytes[start..]
+                        .iter()
+                        .fold(0, |value, byte| value << 8 | u128::from(*byte));
+                    if tag == 2 {
+                        visitor.visit_u128(value)
+                    } else if value <= i128::max_value() as u128 {
+                        visitor.visit_i128(-1 - value as i128)
+                    } else {
+                        Err(de::Error::invalid_value(
+                            Unexpected::Bytes(&bytes),
+                            &"a negative bignum that fits an i128",
+                        ))
+                    }
+                }
+                value => value.deserialize_any(visitor),
+            },
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    #[inline]
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+struct SeqDeserializer {
+    iter: vec::IntoIter<Value>,
+    index: usize,
+}
+
+impl SeqDeserializer {
+    fn new(array: Vec<Value>) -> SeqDeserializer {
+        SeqDeserializer {
+            iter: array.into_iter(),
+            index: 0,
+        }
+    }
+
+    // Fails if the visitor left elements of the array over.
+    fn end(self) -> Result<(), Error> {
+        match self.iter.len() {
+            0 => Ok(()),
+            _ => Err(de::Error::invalid_length(self.index, &"fewer elements in array")),
+        }
+    }
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for &'a mut SeqDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let value = match self.iter.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(value)
+            .map(Some)
+            .map_err(|e| e.at(PathSegment::Index(index)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer {
+    iter: MapIter,
+    // The entry whose key was handed out last, and whose value is next.
+    entry: Option<(Value, Value)>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for &'a mut MapDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let (key, value) = match self.iter.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let result = seed.deserialize(key.clone()).map(Some);
+        self.entry = Some((key, value));
+        result
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        // Panic because this indicates a bug in the visitor rather than an expected failure.
+        let (key, value) = self.entry.take().expect("next_value called before next_key");
+        seed.deserialize(value).map_err(|e| {
+            let key = to_diag_string(&key).unwrap_or_default();
+            e.at(PathSegment::Key(key))
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct UnitVariantDeserializer {
+    value: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for UnitVariantDeserializer {
+    type Error = Error;
+    type Variant = UnitVariantDeserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, UnitVariantDeserializer), Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.value)?;
+        Ok((variant, UnitVariantDeserializer { value: Value::Null }))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for UnitVariantDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        Err(de::Error::invalid_type(Unexpected::UnitVariant, &"newtype variant"))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(Unexpected::UnitVariant, &"tuple variant"))
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(Unexpected::UnitVariant, &"struct variant"))
+    }
+}
+
+struct VariantDeserializer<'a> {
+    seq: &'a mut SeqDeserializer,
+}
+
+impl<'a> VariantDeserializer<'a> {
+    fn next<'de, T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match de::SeqAccess::next_element_seed(&mut self.seq, seed)? {
+            Some(value) => Ok(value),
+            None => Err(de::Error::invalid_length(self.seq.index, &"more elements in array")),
+        }
+    }
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for VariantDeserializer<'a> {
+    type Error = Error;
+    type Variant = VariantDeserializer<'a>;
+
+    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, VariantDeserializer<'a>), Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = self.next(seed)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for VariantDeserializer<'a> {
+    type Error = Error;
+
+    fn unit_variant(mut self) -> Result<(), Error> {
+        self.next(PhantomData::<()>)
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        self.next(seed)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(self.seq)
+    }
+
+    fn struct_variant<V>(
+        mut self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.next(StructVariantSeed { visitor })
+    }
+}
+
+struct StructVariantSeed<V> {
+    visitor: V,
+}
+
+impl<'de, V> de::DeserializeSeed<'de> for StructVariantSeed<V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self.visitor)
+    }
+}
@@ -0,0 +1,109 @@
+/// Builds a `Value` from CBOR-like syntax.
+///
+/// Arrays are written in brackets and maps in braces, with `=>` between each key and its value.
+/// Keys can be anything a value can. `null` and `undefined` stand for those values, an integer
+/// literal followed by a value in parentheses is that value with a tag, and `h"..."` is a byte
+/// string written in hex. Anything else is an expression, which is converted with `Value::from`.
+///
+/// ```
+/// #[macro_use]
+/// extern crate serde_cbor;
+///
+/// use serde_cbor::value::Map;
+/// use serde_cbor::Value;
+///
+/// # fn main() {
+/// let id = 7;
+/// let value = cbor!({
+///     "key" => 1,
+///     2 => [true, null, undefined],
+///     "id" => id,
+///     [-1, 1.5] => 99(h"deadbeef"),
+/// });
+///
+/// let mut map = Map::new();
+/// map.insert(Value::from("key"), Value::Integer(1));
+/// map.insert(
+///     Value::Integer(2),
+///     Value::Array(vec![Value::Bool(true), Value::Null, Value::Undefined]),
+/// );
+/// map.insert(Value::from("id"), Value::Integer(7));
+/// map.insert(
+///     Value::Array(vec![Value::Integer(-1), Value::F64(1.5)]),
+///     Value::Tag(99, Box::new(Value::Bytes(vec![0xde, 0xad, 0xbe, 0xef]))),
+/// );
+/// assert_eq!(value, Value::Object(map));
+/// # }
+/// ```
+///
+/// Rust 2021 and later reserve prefixes like `h` before string literals, so code in those
+/// editions has to write a space after the `h` of a byte string.
+///
+/// # Panics
+///
+/// Panics if a byte string has a character that is not a hex digit, or an odd number of them.
+#[macro_export(local_inner_macros)]
+macro_rules! cbor {
+    // The tokens of the next element are collected up to a comma at the top level, and then
+    // built into a value on their own.
+    (@array $array:ident ()) => ();
+    (@array $array:ident ($($element:tt)+)) => ($array.push(cbor!($($element)+)););
+    (@array $array:ident ($($element:tt)+) , $($rest:tt)*) => (
+        $array.push(cbor!($($element)+));
+        cbor!(@array $array () $($rest)*);
+    );
+    (@array $array:ident ($($element:tt)*) $next:tt $($rest:tt)*) => (
+        cbor!(@array $array ($($element)* $next) $($rest)*);
+    );
+
+    // Likewise for the keys, which end at `=>`, and the values of maps.
+    (@map $map:ident) => ();
+    (@map $map:ident $($rest:tt)+) => (cbor!(@key $map () $($rest)+););
+    (@key $map:ident ($($key:tt)+) => $($rest:tt)*) => (
+        cbor!(@value $map ($($key)+) () $($rest)*);
+    );
+    (@key $map:ident ($($key:tt)*) $next:tt $($rest:tt)*) => (
+        cbor!(@key $map ($($key)* $next) $($rest)*);
+    );
+    (@value $map:ident ($($key:tt)+) ($($value:tt)+)) => (
+        $map.insert(cbor!($($key)+), cbor!($($value)+));
+    );
+    (@value $map:ident ($($key:tt)+) ($($value:tt)+) , $($rest:tt)*) => (
+        $map.insert(cbor!($($key)+), cbor!($($value)+));
+        cbor!(@map $map $($rest)*);
+    );
+    (@value $map:ident ($($key:tt)+) ($($value:tt)*) $next:tt $($rest:tt)*) => (
+        cbor!(@value $map ($($key)+) ($($value)* $next) $($rest)*);
+    );
+
+    (null) => ($crate::Value::Null);
+    (undefined) => ($crate::Value::Undefined);
+    (h $hex:literal) => ($crate::value::from_hex($hex));
+    ($tag:literal ($($value:tt)+)) => (
+        $crate::Value::Tag($tag, ::std::boxed::Box::new(cbor!($($value)+)))
+    );
+    ([$($elements:tt)*]) => ({
+        let mut array = ::std::vec::Vec::new();
+        cbor!(@array array () $($elements)*);
+        $crate::Value::Array(array)
+    });
+    ({$($entries:tt)*}) => ({
+        let mut map = $crate::value::Map::new();
+        cbor!(@map map $($entries)*);
+        $crate::Value::Object(map)
+    });
+    ($other:expr) => ($crate::Value::from($other));
+}
+
+use super::Value;
+
+/// Makes a byte string from hex digits, for `cbor!`.
+#[doc(hidden)]
+pub fn from_hex(hex: &str) -> Value {
+    let digits = hex
+        .chars()
+        .map(|c| c.to_digit(16).expect("invalid hex digit in byte string") as u8)
+        .collect::<Vec<_>>();
+    assert!(digits.len() % 2 == 0, "odd number of hex digits in byte string");
+    Value::Bytes(digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect())
+}
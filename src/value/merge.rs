@@ -0,0 +1,102 @@
+//! Merging values into each other.
+
+use super::{Map, Value};
+
+/// How `Value::merge` combines two values.
+///
+/// Maps are always merged entry by entry, with the values of keys that are in both merged in
+/// turn. Everything else is replaced by what is merged into it, unless set up otherwise here.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MergeStrategy {
+    concat_arrays: bool,
+    null_deletes: bool,
+}
+
+impl MergeStrategy {
+    /// Creates the default strategy, which replaces arrays and keeps null as a value.
+    pub fn new() -> MergeStrategy {
+        MergeStrategy::default()
+    }
+
+    /// Appends the elements of an array to those of the array it is merged into, rather than
+    /// replacing them.
+    pub fn concat_arrays(mut self) -> Self {
+        self.concat_arrays = true;
+        self
+    }
+
+    /// Removes the entries of a map whose keys have null as their value in the map merged into
+    /// it, as a JSON Merge Patch does.
+    pub fn null_deletes(mut self) -> Self {
+        self.null_deletes = true;
+        self
+    }
+}
+
+impl Value {
+    /// Merges `other` into the value, as `strategy` says.
+    ///
+    /// Values with the same tag are merged by their content. Layers of configuration can be
+    /// applied over each other this way.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate serde_cbor;
+    ///
+    /// use serde_cbor::value::MergeStrategy;
+    ///
+    /// # fn main() {
+    /// let mut config = cbor!({
+    ///     "port" => 80,
+    ///     "hosts" => ["a"],
+    ///     "tls" => {"cert" => "a.pem", "key" => "a.key"},
+    /// });
+    /// let local = cbor!({
+    ///     "hosts" => ["b"],
+    ///     "tls" => {"key" => null},
+    /// });
+    /// config.merge(local, MergeStrategy::new().concat_arrays().null_deletes());
+    /// let expected = cbor!({
+    ///     "port" => 80,
+    ///     "hosts" => ["a", "b"],
+    ///     "tls" => {"cert" => "a.pem"},
+    /// });
+    /// assert_eq!(config, expected);
+    /// # }
+    /// ```
+    pub fn merge(&mut self, other: Value, strategy: MergeStrategy) {
+        match (self, other) {
+            (&mut Value::Object(ref mut map), Value::Object(other)) => {
+                for (key, value) in other {
+                    if strategy.null_deletes && value == Value::Null {
+                        remove(map, &key);
+                    } else if let Some(existing) = map.get_mut(&key) {
+                        existing.merge(value, strategy);
+                    } else {
+                        map.insert(key, value);
+                    }
+                }
+            }
+            (&mut Value::Array(ref mut array), Value::Array(other)) if strategy.concat_arrays => {
+                array.extend(other);
+            }
+            (&mut Value::Tag(tag, ref mut content), Value::Tag(other_tag, other))
+                if tag == other_tag =>
+            {
+                content.merge(*other, strategy);
+            }
+            (this, other) => *this = other,
+        }
+    }
+}
+
+#[cfg(not(feature = "indexmap"))]
+fn remove(map: &mut Map, key: &Value) {
+    map.remove(key);
+}
+
+// Keeps the order of the other entries.
+#[cfg(feature = "indexmap")]
+fn remove(map: &mut Map, key: &Value) {
+    map.shift_remove(key);
+}
@@ -1,7 +1,16 @@
 //! CBOR values, keys and serialization routines.
 
+mod borrowed;
+mod codec;
+mod de;
+mod macros;
+mod merge;
 pub mod value;
 pub mod ser;
 
-pub use self::value::{ObjectKey, Value, from_value};
+pub use self::value::{CanonicalValue, Map, ObjectKey, Value, from_value};
+pub use self::borrowed::ValueRef;
+pub use self::merge::MergeStrategy;
 pub use self::ser::to_value;
+#[doc(hidden)]
+pub use self::macros::from_hex;
@@ -8,16 +8,30 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::collections::BTreeMap;
-
 use serde::{self, Serialize};
 use error::Error;
+use tags::{
+    take_simple, take_tag, CBOR_NEWTYPE_NAME, PACKED_NEWTYPE_NAME, RAW_NEWTYPE_NAME,
+    SIMPLE_NEWTYPE_NAME,
+};
 
-use value::Value;
-use value::ObjectKey;
+use value::{Map, Value};
 
 struct Serializer;
 
+// The value of an integer of major type 0 or 1, or a bignum with tag 2 or 3 if it needs more than
+// 64 bits, as the serializer of this crate writes it.
+fn integer(major: u8, value: u128) -> Value {
+    if value <= u128::from(u64::max_value()) {
+        let value = value as i128;
+        return Value::Integer(if major == 0 { value } else { -1 - value });
+    }
+    let bytes = value.to_be_bytes();
+    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+    let magnitude = Value::Bytes(bytes[start..].to_vec());
+    Value::Tag(2 + u64::from(major), Box::new(magnitude))
+}
+
 impl serde::Serializer for Serializer {
     type Ok = Value;
     type Error = Error;
@@ -25,7 +39,7 @@ impl serde::Serializer for Serializer {
     type SerializeSeq = SerializeVec;
     type SerializeTuple = SerializeVec;
     type SerializeTupleStruct = SerializeVec;
-    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeTupleVariant = SerializeVec;
     type SerializeMap = SerializeMap;
     type SerializeStruct = SerializeMap;
     type SerializeStructVariant = SerializeStructVariant;
@@ -51,7 +65,15 @@ impl serde::Serializer for Serializer {
     }
 
     fn serialize_i64(self, value: i64) -> Result<Value, Error> {
-        Ok(Value::I64(value))
+        Ok(Value::Integer(i128::from(value)))
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<Value, Error> {
+        if value < 0 {
+            Ok(integer(1, !value as u128))
+        } else {
+            Ok(integer(0, value as u128))
+        }
     }
 
     #[inline]
@@ -71,7 +93,11 @@ impl serde::Serializer for Serializer {
 
     #[inline]
     fn serialize_u64(self, value: u64) -> Result<Value, Error> {
-        Ok(Value::U64(value))
+        Ok(Value::Integer(i128::from(value)))
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<Value, Error> {
+        Ok(integer(0, value))
     }
 
     #[inline]
@@ -123,15 +149,39 @@ impl serde::Serializer for Serializer {
     #[inline]
     fn serialize_newtype_struct<T: ?Sized>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<Value, Error>
     where
         T: Serialize,
     {
+        if name == CBOR_NEWTYPE_NAME {
+            if let Some(tag) = take_tag() {
+                return Ok(Value::Tag(tag, Box::new(value.serialize(self)?)));
+            }
+        } else if name == SIMPLE_NEWTYPE_NAME {
+            if let Some(simple) = take_simple() {
+                return Ok(match simple {
+                    20 => Value::Bool(false),
+                    21 => Value::Bool(true),
+                    22 => Value::Null,
+                    23 => Value::Undefined,
+                    _ => Value::Simple(simple),
+                });
+            }
+        } else if name == PACKED_NEWTYPE_NAME {
+            return ::from_slice(&::ser::to_vec_packed(&value)?);
+        } else if name == RAW_NEWTYPE_NAME {
+            return match value.serialize(self)? {
+                Value::Bytes(bytes) => ::from_slice(&bytes),
+                other => Ok(other),
+            };
+        }
         value.serialize(self)
     }
 
+    // Variants other than unit variants are arrays of the variant identifier followed by the
+    // fields or the struct, as the serializer of this crate writes them.
     fn serialize_newtype_variant<T: ?Sized>(
         self,
         _name: &'static str,
@@ -142,9 +192,7 @@ impl serde::Serializer for Serializer {
     where
         T: Serialize,
     {
-        let mut values = BTreeMap::new();
-        values.insert(ObjectKey::from(variant.to_owned()), try!(to_value(&value)));
-        Ok(Value::Object(values))
+        Ok(Value::Array(vec![Value::from(variant), try!(to_value(&value))]))
     }
 
     #[inline]
@@ -183,15 +231,14 @@ impl serde::Serializer for Serializer {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Error> {
-        Ok(SerializeTupleVariant {
-            name: String::from(variant),
-            vec: Vec::with_capacity(len),
-        })
+        let mut vec = Vec::with_capacity(len + 1);
+        vec.push(Value::from(variant));
+        Ok(SerializeVec { vec })
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
         Ok(SerializeMap {
-            map: BTreeMap::new(),
+            map: Map::new(),
             next_key: None,
         })
     }
@@ -213,7 +260,7 @@ impl serde::Serializer for Serializer {
     ) -> Result<Self::SerializeStructVariant, Error> {
         Ok(SerializeStructVariant {
             name: String::from(variant),
-            map: BTreeMap::new(),
+            map: Map::new(),
         })
     }
 }
@@ -223,22 +270,16 @@ pub struct SerializeVec {
     vec: Vec<Value>,
 }
 
-#[doc(hidden)]
-pub struct SerializeTupleVariant {
-    name: String,
-    vec: Vec<Value>,
-}
-
 #[doc(hidden)]
 pub struct SerializeMap {
-    map: BTreeMap<ObjectKey, Value>,
-    next_key: Option<ObjectKey>,
+    map: Map,
+    next_key: Option<Value>,
 }
 
 #[doc(hidden)]
 pub struct SerializeStructVariant {
     name: String,
-    map: BTreeMap<ObjectKey, Value>,
+    map: Map,
 }
 
 impl serde::ser::SerializeSeq for SerializeVec {
@@ -290,7 +331,7 @@ impl serde::ser::SerializeTupleStruct for SerializeVec {
     }
 }
 
-impl serde::ser::SerializeTupleVariant for SerializeTupleVariant {
+impl serde::ser::SerializeTupleVariant for SerializeVec {
     type Ok = Value;
     type Error = Error;
 
@@ -298,16 +339,11 @@ impl serde::ser::SerializeTupleVariant for SerializeTupleVariant {
     where
         T: Serialize,
     {
-        self.vec.push(try!(to_value(&value)));
-        Ok(())
+        serde::ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Value, Error> {
-        let mut object = BTreeMap::new();
-
-        object.insert(ObjectKey::from(self.name), Value::Array(self.vec));
-
-        Ok(Value::Object(object))
+        serde::ser::SerializeSeq::end(self)
     }
 }
 
@@ -319,7 +355,7 @@ impl serde::ser::SerializeMap for SerializeMap {
     where
         T: Serialize,
     {
-        self.next_key = Some(ObjectKey::from(try!(to_value(&key))));
+        self.next_key = Some(try!(to_value(&key)));
         Ok(())
     }
 
@@ -366,18 +402,14 @@ impl serde::ser::SerializeStructVariant for SerializeStructVariant {
         T: Serialize,
     {
         self.map.insert(
-            ObjectKey::from(String::from(key)),
+            Value::from(String::from(key)),
             try!(to_value(&value)),
         );
         Ok(())
     }
 
     fn end(self) -> Result<Value, Error> {
-        let mut object = BTreeMap::new();
-
-        object.insert(ObjectKey::from(self.name), Value::Object(self.map));
-
-        Ok(Value::Object(object))
+        Ok(Value::Array(vec![Value::from(self.name), Value::Object(self.map)]))
     }
 }
 
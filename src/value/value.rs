@@ -1,18 +1,61 @@
 //! CBOR values and keys.
 
+use std::cmp::{self, Ordering};
+#[cfg(not(feature = "indexmap"))]
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops;
 
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
+
+use half::f16;
 use serde::de;
 use serde::ser;
 
+use error::{Error, ErrorCode};
+use ser::to_vec_canonical;
+use tags::{take_simple, take_tag, Simple, Tagged, VALUE_NEWTYPE_NAME};
+
+/// The map of a `Value::Object`.
+///
+/// It is a `BTreeMap`, which keeps the entries sorted by key, unless the `indexmap` feature is
+/// turned on. Then it is an `IndexMap`, which keeps the entries in the order they were inserted,
+/// so that a map is serialized with its entries in the order they were deserialized in.
+#[cfg(not(feature = "indexmap"))]
+pub type Map = BTreeMap<Value, Value>;
+
+/// The map of a `Value::Object`.
+///
+/// It is a `BTreeMap`, which keeps the entries sorted by key, unless the `indexmap` feature is
+/// turned on. Then it is an `IndexMap`, which keeps the entries in the order they were inserted,
+/// so that a map is serialized with its entries in the order they were deserialized in.
+#[cfg(feature = "indexmap")]
+pub type Map = IndexMap<Value, Value>;
+
 /// An enum over all possible CBOR types.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// Every well-formed data item deserializes into a `Value` that serializes to the same item
+/// again, up to the lengths of the heads, which are written in the shortest form, the width of
+/// floats and the order of map entries.
+///
+/// Values are ordered by their type first and then by their content, so that any value can be
+/// a map key. Floats are compared by `f64::total_cmp`, under which NaN equals itself and -0.0
+/// is less than 0.0. Maps are compared entry by entry, in the order of their keys.
+///
+/// Text and byte strings own their contents, each in a heap allocation of its exact length,
+/// however short it is. That includes map keys: a key that repeats across many maps is
+/// allocated once for each, so such documents take less memory decoded into structs, whose
+/// field names are not allocated at all.
+#[derive(Clone, Debug)]
 pub enum Value {
-    /// Represents an unsigned integer.
-    U64(u64),
-    /// Represents a signed integer.
-    I64(i64),
+    /// Represents an integer.
+    ///
+    /// Data items of major types 0 and 1 range from -2^64 to 2^64 - 1. Integers beyond that
+    /// are written as bignums with tag 2 or 3, which deserialize as tagged byte strings.
+    Integer(i128),
     /// Represents a byte string.
     Bytes(Vec<u8>),
     /// Represents an UTF-8 string.
@@ -20,13 +63,21 @@ pub enum Value {
     /// Represents a list.
     Array(Vec<Value>),
     /// Represents a map.
-    Object(BTreeMap<ObjectKey, Value>),
+    Object(Map),
     /// Represents a floating point value.
     F64(f64),
     /// Represents a boolean value.
     Bool(bool),
-    /// Represents the absence of a value or the value undefined.
+    /// Represents the absence of a value.
     Null,
+    /// Represents the value undefined.
+    Undefined,
+    /// Represents a simple value other than false, true, null and undefined.
+    ///
+    /// Simple values from 24 to 31 have no encoding and fail to serialize.
+    Simple(u8),
+    /// Represents a value with a semantic tag.
+    Tag(u64, Box<Value>),
 }
 
 impl Value {
@@ -35,8 +86,8 @@ impl Value {
         self.as_object().is_some()
     }
 
-    /// If the value is an object, returns the associated BTreeMap. Returns None otherwise.
-    pub fn as_object(&self) -> Option<&BTreeMap<ObjectKey, Value>> {
+    /// If the value is an object, returns the associated map. Returns None otherwise.
+    pub fn as_object(&self) -> Option<&Map> {
         if let Value::Object(ref v) = *self {
             Some(v)
         } else {
@@ -45,8 +96,8 @@ impl Value {
     }
 
 
-    /// If the value is an object, returns the associated mutable BTreeMap. Returns None otherwise.
-    pub fn as_object_mut(&mut self) -> Option<&mut BTreeMap<ObjectKey, Value>> {
+    /// If the value is an object, returns the associated mutable map. Returns None otherwise.
+    pub fn as_object_mut(&mut self) -> Option<&mut Map> {
         if let Value::Object(ref mut v) = *self {
             Some(v)
         } else {
@@ -126,25 +177,24 @@ impl Value {
     /// Retrns true if the value is a number.
     pub fn is_number(&self) -> bool {
         match *self {
-            Value::U64(_) | Value::I64(_) | Value::F64(_) => true,
+            Value::Integer(_) | Value::F64(_) => true,
             _ => false,
         }
     }
 
-    /// Returns true if the `Value` is a i64. Returns false otherwise.
+    /// Returns true if the `Value` is an integer. Returns false otherwise.
+    pub fn is_integer(&self) -> bool {
+        self.as_integer().is_some()
+    }
+
+    /// Returns true if the `Value` is an integer that fits in a i64. Returns false otherwise.
     pub fn is_i64(&self) -> bool {
-        match *self {
-            Value::I64(_) => true,
-            _ => false,
-        }
+        self.as_i64().is_some()
     }
 
-    /// Returns true if the `Value` is a u64. Returns false otherwise.
+    /// Returns true if the `Value` is an integer that fits in a u64. Returns false otherwise.
     pub fn is_u64(&self) -> bool {
-        match *self {
-            Value::U64(_) => true,
-            _ => false,
-        }
+        self.as_u64().is_some()
     }
 
     /// Returns true if the `Value` is a f64. Returns false otherwise.
@@ -155,20 +205,26 @@ impl Value {
         }
     }
 
-    /// If the `Value` is a number, return or cast it to a i64. Returns None otherwise.
+    /// If the `Value` is an integer, returns it. Returns None otherwise.
+    pub fn as_integer(&self) -> Option<i128> {
+        match *self {
+            Value::Integer(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// If the `Value` is an integer that fits in a i64, returns it. Returns None otherwise.
     pub fn as_i64(&self) -> Option<i64> {
         match *self {
-            Value::I64(n) => Some(n),
-            Value::U64(n) => Some(n as i64),
+            Value::Integer(n) if i128::from(n as i64) == n => Some(n as i64),
             _ => None,
         }
     }
 
-    /// If the `Value` is a number, return or cast it to a u64. Returns None otherwise.
+    /// If the `Value` is an integer that fits in a u64, returns it. Returns None otherwise.
     pub fn as_u64(&self) -> Option<u64> {
         match *self {
-            Value::I64(n) => Some(n as u64),
-            Value::U64(n) => Some(n),
+            Value::Integer(n) if i128::from(n as u64) == n => Some(n as u64),
             _ => None,
         }
     }
@@ -176,8 +232,7 @@ impl Value {
     /// If the `Value` is a number, return or cast it to a f64. Returns None otherwise.
     pub fn as_f64(&self) -> Option<f64> {
         match *self {
-            Value::I64(n) => Some(n as f64),
-            Value::U64(n) => Some(n as f64),
+            Value::Integer(n) => Some(n as f64),
             Value::F64(n) => Some(n),
             _ => None,
         }
@@ -210,6 +265,471 @@ impl Value {
             None
         }
     }
+
+    /// Returns true if the value is undefined. Returns false otherwise.
+    pub fn is_undefined(&self) -> bool {
+        *self == Value::Undefined
+    }
+
+    /// If the value is a simple value other than false, true, null and undefined, returns its
+    /// number. Returns None otherwise.
+    pub fn as_simple(&self) -> Option<u8> {
+        if let Value::Simple(v) = *self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the value has a tag.
+    pub fn is_tag(&self) -> bool {
+        self.as_tag().is_some()
+    }
+
+    /// If the value has a tag, returns the tag and the value that is tagged. Returns None
+    /// otherwise.
+    pub fn as_tag(&self) -> Option<(u64, &Value)> {
+        if let Value::Tag(tag, ref v) = *self {
+            Some((tag, v))
+        } else {
+            None
+        }
+    }
+
+    /// If the value has a tag, returns the tag and the mutable value that is tagged. Returns None
+    /// otherwise.
+    pub fn as_tag_mut(&mut self) -> Option<(u64, &mut Value)> {
+        if let Value::Tag(tag, ref mut v) = *self {
+            Some((tag, v))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of bytes the value is serialized in, without serializing it.
+    ///
+    /// ```
+    /// use serde_cbor::Value;
+    ///
+    /// let value = Value::Array(vec![Value::Integer(1000), Value::F64(1.5)]);
+    /// assert_eq!(value.encoded_size(), 7);
+    /// assert_eq!(value.encoded_size(), serde_cbor::to_vec(&value).unwrap().len());
+    /// ```
+    pub fn encoded_size(&self) -> usize {
+        match *self {
+            Value::Integer(v) => {
+                let arg = if v < 0 { !v as u128 } else { v as u128 };
+                if arg <= u128::from(u64::max_value()) {
+                    head_size(arg as u64)
+                } else {
+                    // A bignum, with a one-byte tag and the magnitude without leading zeros.
+                    let len = (128 - arg.leading_zeros() as usize + 7) / 8;
+                    1 + head_size(len as u64) + len
+                }
+            }
+            Value::Bytes(ref v) => head_size(v.len() as u64) + v.len(),
+            Value::String(ref v) => head_size(v.len() as u64) + v.len(),
+            Value::Array(ref v) => {
+                head_size(v.len() as u64) + v.iter().map(Value::encoded_size).sum::<usize>()
+            }
+            Value::Object(ref map) => {
+                let entries = map.iter().map(|(k, v)| k.encoded_size() + v.encoded_size());
+                head_size(map.len() as u64) + entries.sum::<usize>()
+            }
+            Value::Tag(tag, ref v) => head_size(tag) + v.encoded_size(),
+            // The shortest float that keeps the value, as the serializer picks it.
+            Value::F64(v) => {
+                if !v.is_finite() || f64::from(f16::from_f64(v)) == v {
+                    3
+                } else if f64::from(v as f32) == v {
+                    5
+                } else {
+                    9
+                }
+            }
+            Value::Bool(_) | Value::Null | Value::Undefined => 1,
+            Value::Simple(v) => head_size(u64::from(v)),
+        }
+    }
+
+    /// Compares two values by their deterministic encodings, bytewise, which is the order in
+    /// which a canonical serializer writes the keys of a map.
+    ///
+    /// Unlike the `Ord` of values, this puts shorter text strings before longer ones, and
+    /// integers before negative integers.
+    ///
+    /// ```
+    /// use serde_cbor::Value;
+    /// use std::cmp::Ordering;
+    ///
+    /// let a = Value::from("aa");
+    /// let b = Value::from("b");
+    /// assert_eq!(a.cmp(&b), Ordering::Less);
+    /// assert_eq!(a.canonical_cmp(&b), Ordering::Greater);
+    /// assert_eq!(Value::Integer(10).canonical_cmp(&Value::Integer(-1)), Ordering::Less);
+    /// ```
+    pub fn canonical_cmp(&self, other: &Value) -> Ordering {
+        canonical_encoding(self).cmp(&canonical_encoding(other))
+    }
+
+    /// Parses a data item written in diagnostic notation, as `diag::parse` does.
+    ///
+    /// ```
+    /// use serde_cbor::Value;
+    ///
+    /// let value = Value::from_diagnostic("1(1363896240) # seconds").unwrap();
+    /// assert_eq!(value, Value::Tag(1, Box::new(Value::Integer(1363896240))));
+    /// ```
+    pub fn from_diagnostic(diag: &str) -> Result<Value, ::error::Error> {
+        ::diag::parse(diag)
+    }
+
+    /// Looks up a value by a JSON Pointer, such as `"/config/ports/0"`.
+    ///
+    /// Each reference token after a `/`, with `~1` standing for `/` and `~0` for `~`, selects an
+    /// element of an array by its index, or the value of a map entry by its key. A key is a text
+    /// string, or else an integer if the token is one. The empty pointer selects the value
+    /// itself. Returns None if the pointer does not start with `/` or selects nothing.
+    ///
+    /// ```
+    /// use serde_cbor::Value;
+    ///
+    /// // {"ports": [80, 443], 1: "one"}
+    /// let bytes = b"\xa2\x65ports\x82\x18\x50\x19\x01\xbb\x01\x63one";
+    /// let value: Value = serde_cbor::from_slice(bytes).unwrap();
+    /// assert_eq!(value.pointer("/ports/1"), Some(&Value::Integer(443)));
+    /// assert_eq!(value.pointer("/1"), Some(&Value::String("one".to_string())));
+    /// assert_eq!(value.pointer("/ports/2"), None);
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        let tokens = pointer_tokens(pointer)?;
+        tokens.iter().try_fold(self, |value, token| match *value {
+            Value::Array(ref array) => array_index(token).and_then(|index| array.get(index)),
+            Value::Object(ref map) => map.get(&map_key(map, token)),
+            _ => None,
+        })
+    }
+
+    /// Looks up a mutable value by a JSON Pointer, as `pointer` does.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        let tokens = pointer_tokens(pointer)?;
+        tokens.iter().try_fold(self, |value, token| match *value {
+            Value::Array(ref mut array) => {
+                array_index(token).and_then(move |index| array.get_mut(index))
+            }
+            Value::Object(ref mut map) => {
+                let key = map_key(map, token);
+                map.get_mut(&key)
+            }
+            _ => None,
+        })
+    }
+}
+
+impl Value {
+    // The position of the type of the value in the order of values.
+    fn rank(&self) -> u8 {
+        match *self {
+            Value::Integer(_) => 0,
+            Value::Bytes(_) => 1,
+            Value::String(_) => 2,
+            Value::Array(_) => 3,
+            Value::Object(_) => 4,
+            Value::Tag(..) => 5,
+            Value::Bool(_) => 6,
+            Value::Null => 7,
+            Value::Undefined => 8,
+            Value::Simple(_) => 9,
+            Value::F64(_) => 10,
+        }
+    }
+}
+
+// The deterministic encoding of a value. A map with keys that encode the same, such as an
+// integer and the bignum with its value, cannot be encoded deterministically, and is encoded in
+// the order it is in instead.
+fn canonical_encoding(value: &Value) -> Vec<u8> {
+    to_vec_canonical(value)
+        .or_else(|_| ::to_vec(value))
+        .expect("values always serialize")
+}
+
+/// A value that is compared, ordered and hashed by its deterministic encoding, like
+/// `Value::canonical_cmp` compares values.
+///
+/// Map keys sorted as `CanonicalValue`s, or kept in a `BTreeMap` as such, are in the order in
+/// which a canonical serializer writes them, so that a canonical map can be searched for a key
+/// with a binary search. The encoding is kept along with the value, rather than serialized anew
+/// for each comparison.
+///
+/// ```
+/// use serde_cbor::value::CanonicalValue;
+/// use serde_cbor::Value;
+///
+/// let mut keys: Vec<_> = vec![Value::from("aa"), Value::Integer(-1), Value::from("b")]
+///     .into_iter()
+///     .map(CanonicalValue::from)
+///     .collect();
+/// keys.sort();
+/// let sorted: Vec<_> = keys.iter().map(CanonicalValue::value).collect();
+/// assert_eq!(sorted, [&Value::Integer(-1), &Value::from("b"), &Value::from("aa")]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct CanonicalValue {
+    value: Value,
+    encoding: Vec<u8>,
+}
+
+impl CanonicalValue {
+    /// Returns the value.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Returns the deterministic encoding of the value.
+    pub fn encoding(&self) -> &[u8] {
+        &self.encoding
+    }
+
+    /// Returns the value.
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+}
+
+impl From<Value> for CanonicalValue {
+    fn from(value: Value) -> CanonicalValue {
+        let encoding = canonical_encoding(&value);
+        CanonicalValue { value, encoding }
+    }
+}
+
+impl PartialEq for CanonicalValue {
+    fn eq(&self, other: &CanonicalValue) -> bool {
+        self.encoding == other.encoding
+    }
+}
+
+impl Eq for CanonicalValue {}
+
+impl PartialOrd for CanonicalValue {
+    fn partial_cmp(&self, other: &CanonicalValue) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CanonicalValue {
+    fn cmp(&self, other: &CanonicalValue) -> Ordering {
+        self.encoding.cmp(&other.encoding)
+    }
+}
+
+impl Hash for CanonicalValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.encoding.hash(state)
+    }
+}
+
+impl ser::Serialize for CanonicalValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        ser::Serialize::serialize(&self.value, serializer)
+    }
+}
+
+// The number of bytes in the shortest head with the argument `arg`.
+fn head_size(arg: u64) -> usize {
+    match arg {
+        0...23 => 1,
+        24...0xff => 2,
+        0x100...0xffff => 3,
+        0x1_0000...0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+// The unescaped reference tokens of a JSON Pointer.
+fn pointer_tokens(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return None;
+    }
+    let tokens = pointer[1..].split('/');
+    Some(tokens.map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+// The index of an array element that a reference token selects, which is written in decimal
+// without leading zeros.
+fn array_index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.starts_with('0') && token.len() > 1) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+// The key of the map entry that a reference token selects: the text string, unless only the
+// integer is a key.
+fn map_key(map: &Map, token: &str) -> Value {
+    let string = Value::String(token.to_string());
+    if !map.contains_key(&string) {
+        if let Ok(integer) = token.parse() {
+            return Value::Integer(integer);
+        }
+    }
+    string
+}
+
+// The entries of a map in the order of their keys, which a `BTreeMap` keeps them in already.
+#[cfg(not(feature = "indexmap"))]
+fn sorted<'a>(map: &'a Map) -> impl Iterator<Item = (&'a Value, &'a Value)> {
+    map.iter()
+}
+
+#[cfg(feature = "indexmap")]
+fn sorted<'a>(map: &'a Map) -> impl Iterator<Item = (&'a Value, &'a Value)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    entries.into_iter()
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Array(a), Value::Array(b)) => a.cmp(b),
+            (Value::Object(a), Value::Object(b)) => sorted(a).cmp(sorted(b)),
+            (Value::Tag(a, v), Value::Tag(b, w)) => (a, v).cmp(&(b, w)),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Simple(a), Value::Simple(b)) => a.cmp(b),
+            (Value::F64(a), Value::F64(b)) => a.total_cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match *self {
+            Value::Integer(v) => v.hash(state),
+            Value::Bytes(ref v) => v.hash(state),
+            Value::String(ref v) => v.hash(state),
+            Value::Array(ref v) => v.hash(state),
+            Value::Object(ref v) => {
+                v.len().hash(state);
+                for entry in sorted(v) {
+                    entry.hash(state);
+                }
+            }
+            Value::Tag(tag, ref v) => (tag, v).hash(state),
+            Value::Bool(v) => v.hash(state),
+            Value::Simple(v) => v.hash(state),
+            Value::F64(v) => v.to_bits().hash(state),
+            Value::Null | Value::Undefined => {}
+        }
+    }
+}
+
+static NULL: Value = Value::Null;
+
+/// Indexes into an array. Returns `Value::Null` if the value is not an array or the index is out
+/// of bounds.
+impl ops::Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        match *self {
+            Value::Array(ref array) => array.get(index).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+/// Indexes into a map by a text string key. Returns `Value::Null` if the value is not a map or
+/// has no such key.
+impl<'a> ops::Index<&'a str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &'a str) -> &Value {
+        &self[&Value::String(key.to_string())]
+    }
+}
+
+/// Indexes into a map by any key. Returns `Value::Null` if the value is not a map or has no
+/// such key.
+impl<'a> ops::Index<&'a Value> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &'a Value) -> &Value {
+        match *self {
+            Value::Object(ref map) => map.get(key).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+/// Mutably indexes into an array.
+///
+/// # Panics
+///
+/// Panics if the value is not an array or the index is out of bounds.
+impl ops::IndexMut<usize> for Value {
+    fn index_mut(&mut self, index: usize) -> &mut Value {
+        match *self {
+            Value::Array(ref mut array) => {
+                let len = array.len();
+                array.get_mut(index).unwrap_or_else(|| {
+                    panic!("cannot index array of length {} with {}", len, index)
+                })
+            }
+            ref other => panic!("cannot index {:?} with {}", other, index),
+        }
+    }
+}
+
+/// Mutably indexes into a map by a text string key, as indexing by a `Value` does.
+impl<'a> ops::IndexMut<&'a str> for Value {
+    fn index_mut(&mut self, key: &'a str) -> &mut Value {
+        &mut self[&Value::String(key.to_string())]
+    }
+}
+
+/// Mutably indexes into a map by any key, inserting `Value::Null` if there is no such key. Null
+/// is turned into an empty map first.
+///
+/// # Panics
+///
+/// Panics if the value is neither a map nor null.
+impl<'a> ops::IndexMut<&'a Value> for Value {
+    fn index_mut(&mut self, key: &'a Value) -> &mut Value {
+        if *self == Value::Null {
+            *self = Value::Object(Map::new());
+        }
+        match *self {
+            Value::Object(ref mut map) => map.entry(key.clone()).or_insert(Value::Null),
+            ref other => panic!("cannot index {:?} with {:?}", other, key),
+        }
+    }
 }
 
 impl<'de> de::Deserialize<'de> for Value {
@@ -263,7 +783,7 @@ impl<'de> de::Deserialize<'de> for Value {
             where
                 E: de::Error,
             {
-                Ok(Value::U64(v))
+                Ok(Value::Integer(i128::from(v)))
             }
 
             #[inline]
@@ -271,7 +791,26 @@ impl<'de> de::Deserialize<'de> for Value {
             where
                 E: de::Error,
             {
-                Ok(Value::I64(v))
+                Ok(Value::Integer(i128::from(v)))
+            }
+
+            #[inline]
+            fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Value::Integer(v))
+            }
+
+            #[inline]
+            fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                if v > i128::max_value() as u128 {
+                    return Err(E::custom("integer out of range for Value"));
+                }
+                Ok(Value::Integer(v as i128))
             }
 
             #[inline]
@@ -290,12 +829,18 @@ impl<'de> de::Deserialize<'de> for Value {
                 self.visit_unit()
             }
 
+            // The deserializer of this crate reports simple values other than false, true and
+            // null here.
             #[inline]
             fn visit_unit<E>(self) -> Result<Self::Value, E>
             where
                 E: de::Error,
             {
-                Ok(Value::Null)
+                match take_simple() {
+                    None => Ok(Value::Null),
+                    Some(23) => Ok(Value::Undefined),
+                    Some(v) => Ok(Value::Simple(v)),
+                }
             }
 
             #[inline]
@@ -303,7 +848,8 @@ impl<'de> de::Deserialize<'de> for Value {
             where
                 V: de::SeqAccess<'de>,
             {
-                let mut vec = Vec::new();
+                // the hint may come from an untrusted length header
+                let mut vec = Vec::with_capacity(cmp::min(visitor.size_hint().unwrap_or(0), 4096));
 
                 while let Some(elem) = visitor.next_element()? {
                     vec.push(elem);
@@ -317,7 +863,7 @@ impl<'de> de::Deserialize<'de> for Value {
             where
                 V: de::MapAccess<'de>,
             {
-                let mut values = BTreeMap::new();
+                let mut values = Map::new();
 
                 while let Some((key, value)) = visitor.next_entry()? {
                     values.insert(key, value);
@@ -333,9 +879,25 @@ impl<'de> de::Deserialize<'de> for Value {
             {
                 Ok(Value::F64(v))
             }
+
+            // The deserializer of this crate reports the tag of the item here, and each tag of
+            // the item in turn when the content is deserialized as a value again.
+            #[inline]
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                match take_tag() {
+                    Some(tag) => {
+                        let value = de::Deserialize::deserialize(deserializer)?;
+                        Ok(Value::Tag(tag, Box::new(value)))
+                    }
+                    None => deserializer.deserialize_any(ValueVisitor),
+                }
+            }
         }
 
-        deserializer.deserialize_any(ValueVisitor)
+        deserializer.deserialize_newtype_struct(VALUE_NEWTYPE_NAME, ValueVisitor)
     }
 }
 
@@ -346,8 +908,14 @@ impl ser::Serialize for Value {
         S: ser::Serializer,
     {
         match *self {
-            Value::U64(v) => serializer.serialize_u64(v),
-            Value::I64(v) => serializer.serialize_i64(v),
+            // Other formats may not take 128-bit integers.
+            Value::Integer(v) if v >= 0 && v <= i128::from(u64::max_value()) => {
+                serializer.serialize_u64(v as u64)
+            }
+            Value::Integer(v) if v < 0 && v >= i128::from(i64::min_value()) => {
+                serializer.serialize_i64(v as i64)
+            }
+            Value::Integer(v) => serializer.serialize_i128(v),
             Value::Bytes(ref v) => serializer.serialize_bytes(&v),
             Value::String(ref v) => serializer.serialize_str(&v),
             Value::Array(ref v) => v.serialize(serializer),
@@ -355,6 +923,9 @@ impl ser::Serialize for Value {
             Value::F64(v) => serializer.serialize_f64(v),
             Value::Bool(v) => serializer.serialize_bool(v),
             Value::Null => serializer.serialize_unit(),
+            Value::Undefined => Simple(23).serialize(serializer),
+            Value::Simple(v) => Simple(v).serialize(serializer),
+            Value::Tag(tag, ref v) => Tagged { tag, value: &**v }.serialize(serializer),
         }
     }
 }
@@ -584,7 +1155,7 @@ impl ser::Serialize for ObjectKey {
 impl From<ObjectKey> for Value {
     fn from(key: ObjectKey) -> Value {
         match key {
-            ObjectKey::Integer(v) => Value::I64(v),
+            ObjectKey::Integer(v) => Value::Integer(i128::from(v)),
             ObjectKey::Bytes(v) => Value::Bytes(v),
             ObjectKey::String(v) => Value::String(v),
             ObjectKey::Bool(v) => Value::Bool(v),
@@ -593,17 +1164,30 @@ impl From<ObjectKey> for Value {
     }
 }
 
-impl From<Value> for ObjectKey {
-    fn from(value: Value) -> ObjectKey {
-        match value {
-            Value::U64(v) => ObjectKey::Integer(v as i64),
-            Value::I64(v) => ObjectKey::Integer(v),
+impl TryFrom<Value> for ObjectKey {
+    type Error = Error;
+
+    /// Converts a `Value` to a key. Floats, arrays, maps, tags, simple values and integers
+    /// outside the range of `i64` have no key to convert to.
+    fn try_from(value: Value) -> Result<ObjectKey, Error> {
+        let key = match value {
+            Value::Integer(v) => match i64::try_from(v) {
+                Ok(v) => ObjectKey::Integer(v),
+                Err(_) => {
+                    let msg = format!("key {} out of range", v);
+                    return Err(Error::syntax(ErrorCode::Message(msg), 0));
+                }
+            },
             Value::Bytes(v) => ObjectKey::Bytes(v),
             Value::String(v) => ObjectKey::String(v),
             Value::Bool(v) => ObjectKey::Bool(v),
             Value::Null => ObjectKey::Null,
-            _ => panic!("invalid value type for key"),
-        }
+            _ => {
+                let msg = "invalid value type for key".to_string();
+                return Err(Error::syntax(ErrorCode::Message(msg), 0));
+            }
+        };
+        Ok(key)
     }
 }
 
@@ -623,13 +1207,34 @@ impl_from!(ObjectKey, Bytes, Vec<u8>);
 impl_from!(ObjectKey, String, String);
 impl_from!(ObjectKey, Bool, bool);
 
-// All except &'a str and Cow<'a, str>
-impl_from!(Value, U64, u64);
-impl_from!(Value, I64, i64);
+macro_rules! impl_from_integer {
+    ($($for_type:ty),*) => ($(
+        impl From<$for_type> for Value {
+            fn from (v: $for_type) -> Value {
+                Value::Integer(i128::from(v))
+            }
+        }
+    )*)
+}
+
+impl_from_integer!(u8, u16, u32, u64, i8, i16, i32, i64, i128);
+
+impl<'a> From<&'a str> for Value {
+    fn from(v: &'a str) -> Value {
+        Value::String(v.to_string())
+    }
+}
+
+impl<'a> From<&'a [u8]> for Value {
+    fn from(v: &'a [u8]) -> Value {
+        Value::Bytes(v.to_vec())
+    }
+}
+
 impl_from!(Value, Bytes, Vec<u8>);
 impl_from!(Value, String, String);
 impl_from!(Value, Array, Vec<Value>);
-impl_from!(Value, Object, BTreeMap<ObjectKey, Value>);
+impl_from!(Value, Object, Map);
 impl_from!(Value, F64, f64);
 impl_from!(Value, Bool, bool);
 
@@ -638,10 +1243,5 @@ pub fn from_value<T>(value: Value) -> Result<T, ::error::Error>
 where
     T: de::DeserializeOwned,
 {
-    // TODO implement in a way that doesn't require
-    // roundtrip through buffer (i.e. by implementing
-    // `serde::de::Deserializer` for `Value` and then doing
-    // `T::deserialize(value)`).
-    let buf = ::to_vec(&value)?;
-    ::from_slice(buf.as_slice())
+    T::deserialize(value)
 }
@@ -0,0 +1,189 @@
+//! Conversion between CBOR values and JavaScript values in WebAssembly.
+//!
+//! `Value` converts to a `JsValue` with `From`, following the data model of JavaScript:
+//!
+//! * Integers become numbers if they are safe integers in JavaScript, and `BigInt`s otherwise.
+//! * Byte strings become `Uint8Array`s.
+//! * Maps become `Map`s, which keep keys that are not strings as they are.
+//! * Null becomes `null`, and undefined and other simple values become `undefined`.
+//! * Tagged values become the value without the tag.
+//!
+//! Going the other way with `from_jsvalue`, numbers with an integral value become integers,
+//! both `Map`s and plain objects become maps, and both `Uint8Array`s and `ArrayBuffer`s become
+//! byte strings. `undefined` becomes null. Other values, such as functions, are rejected.
+//!
+//! `to_jsvalue` and `from_jsvalue` go through `Value` to convert any serializable type, so that
+//! decoded CBOR can be handed to JavaScript without walking it by hand:
+//!
+//! ```no_run
+//! # extern crate serde_cbor;
+//! # extern crate wasm_bindgen;
+//! use serde_cbor::wasm::to_jsvalue;
+//! use serde_cbor::Value;
+//! use wasm_bindgen::JsValue;
+//!
+//! fn decode(bytes: &[u8]) -> Result<JsValue, serde_cbor::error::Error> {
+//!     let value: Value = serde_cbor::from_slice(bytes)?;
+//!     to_jsvalue(&value)
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! This module is available with the `wasm` feature.
+use js_sys::{Array, ArrayBuffer, Map, Object, Uint8Array};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use std::convert::TryFrom;
+use std::fmt;
+use wasm_bindgen::{JsCast, JsValue};
+
+use error::{Error, ErrorCode, Result};
+use value::{from_value, to_value, Map as ValueMap, ObjectKey, Value};
+
+// `Number.MAX_SAFE_INTEGER`, up to which JavaScript numbers hold every integer exactly.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+// Nesting limit for values from JavaScript, which may contain themselves.
+const MAX_DEPTH: usize = 128;
+
+/// Serializes a value into a `JsValue`.
+pub fn to_jsvalue<T>(value: &T) -> Result<JsValue>
+where
+    T: Serialize,
+{
+    Ok(JsValue::from(&to_value(value)?))
+}
+
+/// Deserializes a value from a `JsValue`.
+pub fn from_jsvalue<T>(value: &JsValue) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    from_value(convert(value, 0)?)
+}
+
+impl<'a> From<&'a Value> for JsValue {
+    fn from(value: &'a Value) -> JsValue {
+        match *value {
+            Value::Integer(n) if n.abs() <= i128::from(MAX_SAFE_INTEGER) => {
+                JsValue::from_f64(n as f64)
+            }
+            Value::Integer(n) => JsValue::from(n),
+            Value::Bytes(ref bytes) => Uint8Array::from(&bytes[..]).into(),
+            Value::String(ref text) => JsValue::from_str(text),
+            Value::Array(ref items) => items.iter().map(JsValue::from).collect::<Array>().into(),
+            Value::Object(ref entries) => {
+                let map = Map::new();
+                for (key, value) in entries {
+                    map.set(&JsValue::from(key), &JsValue::from(value));
+                }
+                map.into()
+            }
+            Value::F64(n) => JsValue::from_f64(n),
+            Value::Bool(b) => JsValue::from_bool(b),
+            Value::Null => JsValue::NULL,
+            Value::Undefined | Value::Simple(_) => JsValue::UNDEFINED,
+            Value::Tag(_, ref value) => JsValue::from(&**value),
+        }
+    }
+}
+
+impl From<Value> for JsValue {
+    fn from(value: Value) -> JsValue {
+        JsValue::from(&value)
+    }
+}
+
+impl<'a> From<&'a ObjectKey> for JsValue {
+    fn from(key: &'a ObjectKey) -> JsValue {
+        match *key {
+            ObjectKey::Integer(n) => JsValue::from(&Value::Integer(i128::from(n))),
+            ObjectKey::Bytes(ref bytes) => Uint8Array::from(&bytes[..]).into(),
+            ObjectKey::String(ref text) => JsValue::from_str(text),
+            ObjectKey::Bool(b) => JsValue::from_bool(b),
+            ObjectKey::Null => JsValue::NULL,
+        }
+    }
+}
+
+fn error<T: fmt::Display>(msg: T) -> Error {
+    Error::syntax(ErrorCode::Message(msg.to_string()), 0)
+}
+
+fn convert(value: &JsValue, depth: usize) -> Result<Value> {
+    if depth == MAX_DEPTH {
+        return Err(Error::syntax(ErrorCode::RecursionLimitExceeded, 0));
+    }
+    if value.is_null_or_undefined() {
+        return Ok(Value::Null);
+    }
+    if let Some(b) = value.as_bool() {
+        return Ok(Value::Bool(b));
+    }
+    if let Some(n) = value.as_f64() {
+        return Ok(number(n));
+    }
+    if let Some(text) = value.as_string() {
+        return Ok(Value::String(text));
+    }
+    if value.is_bigint() {
+        return i128::try_from(value.clone())
+            .map(Value::Integer)
+            .map_err(|_| error("BigInt out of range"));
+    }
+    if let Some(bytes) = value.dyn_ref::<Uint8Array>() {
+        return Ok(Value::Bytes(bytes.to_vec()));
+    }
+    if let Some(buffer) = value.dyn_ref::<ArrayBuffer>() {
+        return Ok(Value::Bytes(Uint8Array::new(buffer).to_vec()));
+    }
+    if Array::is_array(value) {
+        let items = value.unchecked_ref::<Array>().iter();
+        return items
+            .map(|item| convert(&item, depth + 1))
+            .collect::<Result<_>>()
+            .map(Value::Array);
+    }
+    if let Some(map) = value.dyn_ref::<Map>() {
+        let mut entries = ValueMap::new();
+        let mut result = Ok(());
+        map.for_each(&mut |value, key| {
+            if result.is_ok() {
+                result = entry(&key, &value, depth, &mut entries);
+            }
+        });
+        return result.map(|()| Value::Object(entries));
+    }
+    if value.is_object() && !value.is_function() {
+        let mut entries = ValueMap::new();
+        for pair in Object::entries(value.unchecked_ref::<Object>()).iter() {
+            let pair = pair.unchecked_into::<Array>();
+            entry(&pair.get(0), &pair.get(1), depth, &mut entries)?;
+        }
+        return Ok(Value::Object(entries));
+    }
+    Err(error(format_args!(
+        "unsupported JavaScript value of type {}",
+        value.js_typeof().as_string().unwrap_or_default()
+    )))
+}
+
+fn entry(
+    key: &JsValue,
+    value: &JsValue,
+    depth: usize,
+    entries: &mut ValueMap,
+) -> Result<()> {
+    entries.insert(convert(key, depth + 1)?, convert(value, depth + 1)?);
+    Ok(())
+}
+
+// Integral numbers become integers, except for -0 which only a float can hold.
+fn number(n: f64) -> Value {
+    let negative_zero = n == 0.0 && n.is_sign_negative();
+    if n.fract() != 0.0 || n.abs() > MAX_SAFE_INTEGER as f64 || negative_zero {
+        Value::F64(n)
+    } else {
+        Value::Integer(n as i128)
+    }
+}
@@ -0,0 +1,164 @@
+//! `num_bigint::BigInt` as an integer where it fits in 64 bits and as a bignum otherwise.
+//!
+//! Bignums are byte strings holding a number in big-endian order, written without leading zero
+//! bytes. A bignum with tag 2 is the number itself and one with tag 3 is -1 minus the number.
+//! Leading zeros are accepted when deserializing, as is a byte string without a tag, which is
+//! taken to be a bignum with tag 2.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate num_bigint;
+//! # extern crate serde_cbor;
+//! use num_bigint::BigInt;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Balance {
+//!     #[serde(with = "serde_cbor::with::bigint")]
+//!     amount: BigInt,
+//! }
+//!
+//! # fn main() {
+//! let balance = Balance {
+//!     amount: -(BigInt::from(u64::max_value()) + 1u8) - 1,
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&balance).unwrap();
+//! assert_eq!(diag, r#"{"amount": 3(h'010000000000000000')}"#);
+//! # }
+//! ```
+//!
+//! This module is available with the `num-bigint` feature.
+use num_bigint::{BigInt, BigUint, Sign};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::Bytes;
+use tags::{deserialize_with_tag, Tagged};
+
+/// Serializes a `BigInt`.
+pub fn serialize<S>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if let Ok(value) = u64::try_from(value) {
+        return serializer.serialize_u64(value);
+    }
+    if let Ok(value) = i64::try_from(value) {
+        return serializer.serialize_i64(value);
+    }
+    match value.sign() {
+        // A negative integer holds down to -2^64, below what fits in an `i64`.
+        Sign::Minus => match i128::try_from(value) {
+            Ok(value) if value >= -(1 << 64) => serializer.serialize_i128(value),
+            _ => serialize_bignum(3, (-value - 1u32).magnitude(), serializer),
+        },
+        _ => serialize_bignum(2, value.magnitude(), serializer),
+    }
+}
+
+/// Deserializes a `BigInt` from an integer or a bignum with tag 2 or 3.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<BigInt, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match deserialize_with_tag::<D, Integer>(deserializer)? {
+        (None, Integer::Int(value)) => Ok(value),
+        (None, Integer::Bytes(bytes)) | (Some(2), Integer::Bytes(bytes)) => {
+            Ok(BigInt::from(BigUint::from_bytes_be(&bytes)))
+        }
+        (Some(3), Integer::Bytes(bytes)) => {
+            Ok(-BigInt::from(BigUint::from_bytes_be(&bytes)) - 1)
+        }
+        (Some(tag), Integer::Int(_)) if tag == 2 || tag == 3 => Err(de::Error::custom(
+            format_args!("expected a byte string with tag {}", tag),
+        )),
+        (Some(tag), _) => Err(de::Error::custom(format_args!(
+            "expected tag 2 or 3, found tag {}",
+            tag
+        ))),
+    }
+}
+
+pub(super) fn serialize_bignum<S>(
+    tag: u64,
+    magnitude: &BigUint,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Tagged {
+        tag,
+        value: &Bytes(&magnitude.to_bytes_be()),
+    }
+    .serialize(serializer)
+}
+
+// An integer, or the content of a bignum before its tag is looked at.
+enum Integer {
+    Int(BigInt),
+    Bytes(Vec<u8>),
+}
+
+impl<'de> Deserialize<'de> for Integer {
+    fn deserialize<D>(deserializer: D) -> Result<Integer, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct IntegerVisitor;
+
+        impl<'de> de::Visitor<'de> for IntegerVisitor {
+            type Value = Integer;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an integer or a bignum")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Integer, E>
+            where
+                E: de::Error,
+            {
+                Ok(Integer::Int(BigInt::from(value)))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Integer, E>
+            where
+                E: de::Error,
+            {
+                Ok(Integer::Int(BigInt::from(value)))
+            }
+
+            fn visit_i128<E>(self, value: i128) -> Result<Integer, E>
+            where
+                E: de::Error,
+            {
+                Ok(Integer::Int(BigInt::from(value)))
+            }
+
+            fn visit_u128<E>(self, value: u128) -> Result<Integer, E>
+            where
+                E: de::Error,
+            {
+                Ok(Integer::Int(BigInt::from(value)))
+            }
+
+            fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Integer, E>
+            where
+                E: de::Error,
+            {
+                Ok(Integer::Bytes(bytes.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(self, bytes: Vec<u8>) -> Result<Integer, E>
+            where
+                E: de::Error,
+            {
+                Ok(Integer::Bytes(bytes))
+            }
+        }
+
+        // This reaches the negative integers below what fits in an `i64`.
+        deserializer.deserialize_i128(IntegerVisitor)
+    }
+}
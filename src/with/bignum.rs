@@ -0,0 +1,94 @@
+//! `u128` as an integer where it fits in 64 bits and as a bignum with tag 2 otherwise.
+//!
+//! Bignums are byte strings holding the value in big-endian order, written without leading
+//! zero bytes. Leading zeros are accepted when deserializing.
+use byteorder::{BigEndian, ByteOrder};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+use super::Bytes;
+use tags::{deserialize_tagged, Tagged};
+
+/// Serializes a `u128`.
+pub fn serialize<S>(value: &u128, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if *value <= u128::from(u64::max_value()) {
+        return serializer.serialize_u64(*value as u64);
+    }
+    let mut buf = [0; 16];
+    BigEndian::write_u128(&mut buf, *value);
+    let start = buf.iter().position(|byte| *byte != 0).unwrap_or(buf.len());
+    Tagged {
+        tag: 2,
+        value: &Bytes(&buf[start..]),
+    }
+    .serialize(serializer)
+}
+
+/// Deserializes a `u128` from an unsigned integer or a bignum with tag 2.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<u128, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_tagged::<D, Bignum>(deserializer, 2).map(|bignum| bignum.0)
+}
+
+struct Bignum(u128);
+
+impl<'de> Deserialize<'de> for Bignum {
+    fn deserialize<D>(deserializer: D) -> Result<Bignum, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BignumVisitor;
+
+        impl<'de> de::Visitor<'de> for BignumVisitor {
+            type Value = Bignum;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an unsigned integer or a bignum")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Bignum, E>
+            where
+                E: de::Error,
+            {
+                Ok(Bignum(u128::from(value)))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Bignum, E>
+            where
+                E: de::Error,
+            {
+                if value < 0 {
+                    return Err(E::invalid_value(de::Unexpected::Signed(value), &self));
+                }
+                Ok(Bignum(value as u128))
+            }
+
+            fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Bignum, E>
+            where
+                E: de::Error,
+            {
+                let start = bytes
+                    .iter()
+                    .position(|byte| *byte != 0)
+                    .unwrap_or(bytes.len());
+                let bytes = &bytes[start..];
+                if bytes.len() > 16 {
+                    return Err(E::custom("bignum out of range for u128"));
+                }
+                Ok(Bignum(
+                    bytes
+                        .iter()
+                        .fold(0, |value, byte| value << 8 | u128::from(*byte)),
+                ))
+            }
+        }
+
+        deserializer.deserialize_any(BignumVisitor)
+    }
+}
@@ -0,0 +1,33 @@
+//! `num_bigint::BigUint` as an integer where it fits in 64 bits and as a bignum with tag 2
+//! otherwise.
+//!
+//! Deserializing accepts what `bigint` does, as long as the number is not negative.
+//!
+//! This module is available with the `num-bigint` feature.
+use num_bigint::BigUint;
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use std::convert::TryFrom;
+
+use super::bigint::{self, serialize_bignum};
+
+/// Serializes a `BigUint`.
+pub fn serialize<S>(value: &BigUint, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match u64::try_from(value) {
+        Ok(value) => serializer.serialize_u64(value),
+        Err(_) => serialize_bignum(2, value, serializer),
+    }
+}
+
+/// Deserializes a `BigUint` from an unsigned integer or a bignum with tag 2.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<BigUint, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    bigint::deserialize(deserializer)?
+        .to_biguint()
+        .ok_or_else(|| de::Error::custom("expected an integer that is not negative"))
+}
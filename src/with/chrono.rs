@@ -0,0 +1,150 @@
+//! `chrono::DateTime<Utc>` as a date and time with tag 0 or tag 1.
+//!
+//! The submodule picks the form that is written: `rfc3339` writes an RFC 3339 string with tag 0
+//! and `epoch` writes the number of seconds since 1970-01-01T00:00Z with tag 1. Both accept
+//! either form when deserializing, so the form can be changed without breaking existing data.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate chrono;
+//! # extern crate serde_cbor;
+//! use chrono::{DateTime, TimeZone, Utc};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Event {
+//!     #[serde(with = "serde_cbor::with::chrono::rfc3339")]
+//!     start: DateTime<Utc>,
+//!     #[serde(with = "serde_cbor::with::chrono::epoch")]
+//!     end: DateTime<Utc>,
+//! }
+//!
+//! # fn main() {
+//! let event = Event {
+//!     start: Utc.timestamp_opt(1363896240, 0).unwrap(),
+//!     end: Utc.timestamp_opt(1363896300, 500_000_000).unwrap(),
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&event).unwrap();
+//! assert_eq!(diag, r#"{"start": 0("2013-03-21T20:04:00Z"), "end": 1(1363896300.5)}"#);
+//! # }
+//! ```
+//!
+//! This module is available with the `chrono` feature.
+use chrono::{DateTime, Datelike, SecondsFormat, TimeZone, Utc};
+use serde::de::{self, Deserializer};
+use serde::ser::{self, Serialize, Serializer};
+
+use super::{deserialize_timestamp, Timestamp};
+
+/// Date and times written as an RFC 3339 string with tag 0.
+///
+/// Times are written in UTC, with fractional seconds in groups of three digits as needed. Only
+/// the years 0000 to 9999 can be represented.
+pub mod rfc3339 {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserializer, Serialize, Serializer};
+
+    use super::Rfc3339;
+    use tags::Tagged;
+
+    /// Serializes a date and time as text with tag 0.
+    pub fn serialize<S>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 0,
+            value: &Rfc3339(time),
+        }
+        .serialize(serializer)
+    }
+
+    /// Deserializes a date and time from text with tag 0 or a number with tag 1, or either
+    /// without a tag.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+/// Date and times written as seconds since the epoch with tag 1.
+///
+/// Whole seconds are written as an integer and other times as a float, which keeps a precision
+/// of about a microsecond for current dates.
+pub mod epoch {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserializer, Serialize, Serializer};
+
+    use super::Epoch;
+    use tags::Tagged;
+
+    /// Serializes a date and time as a number with tag 1.
+    pub fn serialize<S>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 1,
+            value: &Epoch(time),
+        }
+        .serialize(serializer)
+    }
+
+    /// Deserializes a date and time from text with tag 0 or a number with tag 1, or either
+    /// without a tag.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+struct Rfc3339<'a>(&'a DateTime<Utc>);
+
+impl<'a> Serialize for Rfc3339<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if !(0..=9999).contains(&self.0.year()) {
+            return Err(ser::Error::custom("time out of range for RFC 3339"));
+        }
+        serializer.serialize_str(&self.0.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+
+struct Epoch<'a>(&'a DateTime<Utc>);
+
+impl<'a> Serialize for Epoch<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let secs = self.0.timestamp();
+        match self.0.timestamp_subsec_nanos() {
+            0 => serializer.serialize_i64(secs),
+            nanos => serializer.serialize_f64(secs as f64 + f64::from(nanos) / 1e9),
+        }
+    }
+}
+
+fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match deserialize_timestamp(deserializer, 0, 1)? {
+        Timestamp::Text(text) => match DateTime::parse_from_rfc3339(&text) {
+            Ok(time) => Ok(time.with_timezone(&Utc)),
+            Err(_) => Err(de::Error::invalid_value(
+                de::Unexpected::Str(&text),
+                &"an RFC 3339 date and time",
+            )),
+        },
+        Timestamp::Number(secs, nanos) => match Utc.timestamp_opt(secs, nanos).single() {
+            Some(time) => Ok(time),
+            None => Err(de::Error::custom("timestamp out of range")),
+        },
+    }
+}
@@ -0,0 +1,50 @@
+//! `rust_decimal::Decimal` as a decimal fraction with tag 4.
+//!
+//! The number is written as an array of its exponent, which is minus its scale, and its
+//! mantissa. Deserializing accepts any decimal fraction that a `Decimal` represents exactly.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate rust_decimal;
+//! # extern crate serde_cbor;
+//! use rust_decimal::Decimal;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Price {
+//!     #[serde(with = "serde_cbor::with::decimal")]
+//!     amount: Decimal,
+//! }
+//!
+//! # fn main() {
+//! let price = Price {
+//!     amount: Decimal::new(1999, 2),
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&price).unwrap();
+//! assert_eq!(diag, r#"{"amount": 4([-2, 1999])}"#);
+//! # }
+//! ```
+//!
+//! This module is available with the `rust_decimal` feature.
+use rust_decimal::Decimal;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use number::DecimalFraction;
+
+/// Serializes a `Decimal`.
+pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    DecimalFraction::from(*value).serialize(serializer)
+}
+
+/// Deserializes a `Decimal` from a decimal fraction that has tag 4 or none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    DecimalFraction::deserialize(deserializer)?
+        .to_decimal()
+        .ok_or_else(|| de::Error::custom("decimal fraction out of range for Decimal"))
+}
@@ -0,0 +1,121 @@
+//! `SystemTime` as the number of seconds since 1970-01-01T00:00Z with tag 1.
+//!
+//! Whole seconds are encoded as an integer and other times as a float, which keeps a precision
+//! of about a microsecond for current dates. Deserializing accepts either.
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tags::{deserialize_tagged, Tagged};
+
+/// Serializes a `SystemTime`.
+pub fn serialize<S>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Tagged {
+        tag: 1,
+        value: &Epoch(*time),
+    }
+    .serialize(serializer)
+}
+
+/// Deserializes a `SystemTime` from a number that has tag 1 or none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_tagged::<D, Epoch>(deserializer, 1).map(|epoch| epoch.0)
+}
+
+struct Epoch(SystemTime);
+
+impl Serialize for Epoch {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (duration, before) = match self.0.duration_since(UNIX_EPOCH) {
+            Ok(duration) => (duration, false),
+            Err(error) => (error.duration(), true),
+        };
+        let secs = duration.as_secs();
+        if duration.subsec_nanos() == 0 {
+            if !before {
+                return serializer.serialize_u64(secs);
+            }
+            if secs <= 1 << 63 {
+                return serializer.serialize_i64((secs as i64).wrapping_neg());
+            }
+        }
+        let value = secs as f64 + f64::from(duration.subsec_nanos()) / 1e9;
+        serializer.serialize_f64(if before { -value } else { value })
+    }
+}
+
+impl<'de> Deserialize<'de> for Epoch {
+    fn deserialize<D>(deserializer: D) -> Result<Epoch, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct EpochVisitor;
+
+        impl EpochVisitor {
+            fn time<E>(self, duration: Duration, before: bool) -> Result<Epoch, E>
+            where
+                E: de::Error,
+            {
+                let time = if before {
+                    UNIX_EPOCH.checked_sub(duration)
+                } else {
+                    UNIX_EPOCH.checked_add(duration)
+                };
+                time.map(Epoch)
+                    .ok_or_else(|| E::custom("timestamp out of range"))
+            }
+        }
+
+        impl<'de> de::Visitor<'de> for EpochVisitor {
+            type Value = Epoch;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("seconds since the epoch")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Epoch, E>
+            where
+                E: de::Error,
+            {
+                self.time(Duration::from_secs(value), false)
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Epoch, E>
+            where
+                E: de::Error,
+            {
+                if value < 0 {
+                    let secs = (value as u64).wrapping_neg();
+                    self.time(Duration::from_secs(secs), true)
+                } else {
+                    self.time(Duration::from_secs(value as u64), false)
+                }
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Epoch, E>
+            where
+                E: de::Error,
+            {
+                let secs = value.abs();
+                if secs.is_nan() || secs >= 18_446_744_073_709_551_616.0 {
+                    return Err(E::invalid_value(de::Unexpected::Float(value), &self));
+                }
+                let nanos = (secs.fract() * 1e9).round() as u32;
+                let duration = Duration::from_secs(secs as u64) + Duration::new(0, nanos);
+                self.time(duration, value < 0.0)
+            }
+        }
+
+        deserializer.deserialize_any(EpochVisitor)
+    }
+}
@@ -0,0 +1,136 @@
+//! IP addresses as network addresses with tag 260.
+//!
+//! An address is written as a byte string of its 4 or 16 bytes in network order. The top level
+//! functions are for an `IpAddr`, and `v4` and `v6` are for an `Ipv4Addr` and an `Ipv6Addr`.
+//! Deserializing also accepts the byte string without a tag.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! use std::net::{IpAddr, Ipv4Addr};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Peer {
+//!     #[serde(with = "serde_cbor::with::ip")]
+//!     addr: IpAddr,
+//!     #[serde(with = "serde_cbor::with::ip::v4")]
+//!     gateway: Ipv4Addr,
+//! }
+//!
+//! # fn main() {
+//! let peer = Peer {
+//!     addr: "2001:db8::1".parse().unwrap(),
+//!     gateway: Ipv4Addr::new(192, 0, 2, 1),
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&peer).unwrap();
+//! assert_eq!(
+//!     diag,
+//!     r#"{"addr": 260(h'20010db8000000000000000000000001'), "gateway": 260(h'c0000201')}"#
+//! );
+//! # }
+//! ```
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::deserialize_bytes;
+
+/// IPv4 addresses, written as 4 bytes with tag 260.
+pub mod v4 {
+    use serde::de::{self, Deserializer};
+    use serde::ser::Serializer;
+    use std::net::Ipv4Addr;
+
+    use super::super::{deserialize_bytes, serialize_bytes};
+
+    /// Serializes an `Ipv4Addr`.
+    pub fn serialize<S>(addr: &Ipv4Addr, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_bytes(&addr.octets(), 260, serializer)
+    }
+
+    /// Deserializes an `Ipv4Addr` from a 4-byte string that has tag 260 or none.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Ipv4Addr, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = deserialize_bytes(deserializer, 260)?;
+        super::ipv4_from_bytes(&bytes)
+            .ok_or_else(|| de::Error::invalid_length(bytes.len(), &"an IPv4 address of 4 bytes"))
+    }
+}
+
+/// IPv6 addresses, written as 16 bytes with tag 260.
+pub mod v6 {
+    use serde::de::{self, Deserializer};
+    use serde::ser::Serializer;
+    use std::net::Ipv6Addr;
+
+    use super::super::{deserialize_bytes, serialize_bytes};
+
+    /// Serializes an `Ipv6Addr`.
+    pub fn serialize<S>(addr: &Ipv6Addr, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_bytes(&addr.octets(), 260, serializer)
+    }
+
+    /// Deserializes an `Ipv6Addr` from a 16-byte string that has tag 260 or none.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Ipv6Addr, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = deserialize_bytes(deserializer, 260)?;
+        super::ipv6_from_bytes(&bytes)
+            .ok_or_else(|| de::Error::invalid_length(bytes.len(), &"an IPv6 address of 16 bytes"))
+    }
+}
+
+/// Serializes an `IpAddr`.
+pub fn serialize<S>(addr: &IpAddr, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match *addr {
+        IpAddr::V4(addr) => v4::serialize(&addr, serializer),
+        IpAddr::V6(addr) => v6::serialize(&addr, serializer),
+    }
+}
+
+/// Deserializes an `IpAddr` from a 4-byte or 16-byte string that has tag 260 or none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<IpAddr, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let bytes = deserialize_bytes(deserializer, 260)?;
+    ip_from_bytes(&bytes)
+        .ok_or_else(|| de::Error::invalid_length(bytes.len(), &"an IP address of 4 or 16 bytes"))
+}
+
+pub(super) fn ip_from_bytes(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => ipv4_from_bytes(bytes).map(IpAddr::V4),
+        _ => ipv6_from_bytes(bytes).map(IpAddr::V6),
+    }
+}
+
+pub(super) fn ipv4_from_bytes(bytes: &[u8]) -> Option<Ipv4Addr> {
+    let mut octets = [0; 4];
+    if bytes.len() != octets.len() {
+        return None;
+    }
+    octets.copy_from_slice(bytes);
+    Some(Ipv4Addr::from(octets))
+}
+
+pub(super) fn ipv6_from_bytes(bytes: &[u8]) -> Option<Ipv6Addr> {
+    let mut octets = [0; 16];
+    if bytes.len() != octets.len() {
+        return None;
+    }
+    octets.copy_from_slice(bytes);
+    Some(Ipv6Addr::from(octets))
+}
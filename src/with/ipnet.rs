@@ -0,0 +1,200 @@
+//! IP networks of the `ipnet` crate as network address prefixes with tag 261.
+//!
+//! A prefix is written as a map with a single entry, from the network address as a byte string of
+//! 4 or 16 bytes to the length of the prefix. The address is written with the bits after the
+//! prefix cleared. The top level functions are for an `IpNet`, and `v4` and `v6` are for an
+//! `Ipv4Net` and an `Ipv6Net`. Deserializing also accepts the map without a tag.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate ipnet;
+//! # extern crate serde_cbor;
+//! use ipnet::IpNet;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Route {
+//!     #[serde(with = "serde_cbor::with::ipnet")]
+//!     destination: IpNet,
+//! }
+//!
+//! # fn main() {
+//! let route = Route {
+//!     destination: "192.0.2.0/24".parse().unwrap(),
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&route).unwrap();
+//! assert_eq!(diag, r#"{"destination": 261({h'c0000200': 24})}"#);
+//! # }
+//! ```
+//!
+//! This module is available with the `ipnet` feature.
+use ipnet::IpNet;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+
+use super::ip::ip_from_bytes;
+use super::{ByteBuf, Bytes};
+use tags::{deserialize_tagged, Tagged};
+
+/// IPv4 networks, written with a 4-byte address.
+pub mod v4 {
+    use ipnet::Ipv4Net;
+    use serde::de::{self, Deserializer};
+    use serde::ser::Serializer;
+
+    use super::super::ip::ipv4_from_bytes;
+
+    /// Serializes an `Ipv4Net`.
+    pub fn serialize<S>(net: &Ipv4Net, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        super::serialize_prefix(&net.network().octets(), net.prefix_len(), serializer)
+    }
+
+    /// Deserializes an `Ipv4Net` from a prefix with a 4-byte address that has tag 261 or none.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Ipv4Net, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let prefix = super::deserialize_prefix(deserializer)?;
+        let addr = ipv4_from_bytes(&prefix.addr).ok_or_else(|| {
+            de::Error::invalid_length(prefix.addr.len(), &"an IPv4 address of 4 bytes")
+        })?;
+        Ipv4Net::new(addr, prefix.len).map_err(|_| super::invalid_len(prefix.len))
+    }
+}
+
+/// IPv6 networks, written with a 16-byte address.
+pub mod v6 {
+    use ipnet::Ipv6Net;
+    use serde::de::{self, Deserializer};
+    use serde::ser::Serializer;
+
+    use super::super::ip::ipv6_from_bytes;
+
+    /// Serializes an `Ipv6Net`.
+    pub fn serialize<S>(net: &Ipv6Net, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        super::serialize_prefix(&net.network().octets(), net.prefix_len(), serializer)
+    }
+
+    /// Deserializes an `Ipv6Net` from a prefix with a 16-byte address that has tag 261 or none.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Ipv6Net, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let prefix = super::deserialize_prefix(deserializer)?;
+        let addr = ipv6_from_bytes(&prefix.addr).ok_or_else(|| {
+            de::Error::invalid_length(prefix.addr.len(), &"an IPv6 address of 16 bytes")
+        })?;
+        Ipv6Net::new(addr, prefix.len).map_err(|_| super::invalid_len(prefix.len))
+    }
+}
+
+/// Serializes an `IpNet`.
+pub fn serialize<S>(net: &IpNet, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match *net {
+        IpNet::V4(ref net) => v4::serialize(net, serializer),
+        IpNet::V6(ref net) => v6::serialize(net, serializer),
+    }
+}
+
+/// Deserializes an `IpNet` from a prefix with a 4-byte or 16-byte address that has tag 261 or
+/// none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<IpNet, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let prefix = deserialize_prefix(deserializer)?;
+    let addr = ip_from_bytes(&prefix.addr).ok_or_else(|| {
+        de::Error::invalid_length(prefix.addr.len(), &"an IP address of 4 or 16 bytes")
+    })?;
+    IpNet::new(addr, prefix.len).map_err(|_| invalid_len(prefix.len))
+}
+
+fn serialize_prefix<S>(addr: &[u8], len: u8, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Tagged {
+        tag: 261,
+        value: &Prefix {
+            addr: addr.to_vec(),
+            len,
+        },
+    }
+    .serialize(serializer)
+}
+
+fn deserialize_prefix<'de, D>(deserializer: D) -> Result<Prefix, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_tagged(deserializer, 261)
+}
+
+fn invalid_len<E>(len: u8) -> E
+where
+    E: de::Error,
+{
+    E::invalid_value(
+        de::Unexpected::Unsigned(u64::from(len)),
+        &"a prefix length that fits the address",
+    )
+}
+
+// The content of a network address prefix.
+struct Prefix {
+    addr: Vec<u8>,
+    len: u8,
+}
+
+impl Serialize for Prefix {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&Bytes(&self.addr), &self.len)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Prefix {
+    fn deserialize<D>(deserializer: D) -> Result<Prefix, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PrefixVisitor;
+
+        impl<'de> de::Visitor<'de> for PrefixVisitor {
+            type Value = Prefix;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map from a network address to a prefix length")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Prefix, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let (addr, len) = match map.next_entry::<ByteBuf, u8>()? {
+                    Some((addr, len)) => (addr.0, len),
+                    None => return Err(de::Error::invalid_length(0, &self)),
+                };
+                if map.next_key::<de::IgnoredAny>()?.is_some() {
+                    return Err(de::Error::custom("expected a single network address prefix"));
+                }
+                Ok(Prefix { addr, len })
+            }
+        }
+
+        deserializer.deserialize_map(PrefixVisitor)
+    }
+}
@@ -0,0 +1,347 @@
+//! Adapters for CBOR-specific representations of fields, for use with `#[serde(with = "...")]`.
+//!
+//! * `epoch` encodes a `SystemTime` as seconds since the epoch with tag 1.
+//! * `rfc3339` encodes a `SystemTime` as an RFC 3339 date and time string with tag 0.
+//! * `seconds` encodes a `Duration` as a number of seconds, which is fractional where needed.
+//! * `chrono::rfc3339` and `chrono::epoch` encode a `chrono::DateTime<Utc>` with tag 0 or 1, and
+//!   accept either when deserializing. They are available with the `chrono` feature.
+//! * `time::rfc3339` and `time::epoch` do the same for a `time::OffsetDateTime`, and
+//!   `time::full_date` and `time::epoch_days` encode a `time::Date` with tag 1004 or 100. They are
+//!   available with the `time` feature.
+//! * `bignum` encodes a `u128` as an integer where it fits and as a bignum with tag 2 otherwise.
+//! * `bigint` and `biguint` encode a `num_bigint::BigInt` or `BigUint` as an integer where it
+//!   fits and as a bignum with tag 2 or 3 otherwise. They are available with the `num-bigint`
+//!   feature.
+//! * `decimal` encodes a `rust_decimal::Decimal` as a decimal fraction with tag 4. It is
+//!   available with the `rust_decimal` feature.
+//! * `ratio` encodes a `num_rational::Ratio<i64>` as a rational number with tag 30. It is
+//!   available with the `num-rational` feature.
+//! * `url` encodes a `url::Url` as text with tag 32. It is available with the `url` feature.
+//! * `regex` encodes a `regex::Regex` as a regular expression with tag 35. It is available with
+//!   the `regex` feature.
+//! * `uuid` encodes a `uuid::Uuid` as a 16-byte string with tag 37. It is available with the
+//!   `uuid` feature.
+//! * `ip` encodes an `IpAddr` as a network address with tag 260, and `ip::v4` and `ip::v6` do
+//!   the same for an `Ipv4Addr` and an `Ipv6Addr`.
+//! * `ipnet` encodes an `ipnet::IpNet` as a network address prefix with tag 261, and `ipnet::v4`
+//!   and `ipnet::v6` do the same for an `Ipv4Net` and an `Ipv6Net`. They are available with the
+//!   `ipnet` feature.
+//! * `typed_array` encodes a `Vec` of numbers as a typed array of RFC 8746 in the byte order of
+//!   the target, and `typed_array::big_endian` and `typed_array::little_endian` in a fixed one.
+//! * `base64url`, `base64` and `base16` encode bytes as a byte string with tag 21, 22 or 23,
+//!   which tells converters to JSON the text encoding to use for it.
+//! * `shared` encodes an `Rc` as a value that may be shared, with tag 28 the first time and as a
+//!   reference with tag 29 after that, and `shared::arc` does the same for an `Arc`.
+//! * `packed` encodes a value the way `to_vec_packed` does, with struct fields and enum variants
+//!   identified by their index. Structs become maps with integer keys, as used by protocols
+//!   like COSE and CWT.
+//!
+//! Tags are written by the serializer of this crate only; other formats get just the content.
+//! When deserializing, a value without a tag is accepted, but one with a different tag is not.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! use std::time::{Duration, UNIX_EPOCH, SystemTime};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Entry {
+//!     #[serde(with = "serde_cbor::with::epoch")]
+//!     time: SystemTime,
+//!     #[serde(with = "serde_cbor::with::base16")]
+//!     hash: Vec<u8>,
+//! }
+//!
+//! # fn main() {
+//! let entry = Entry {
+//!     time: UNIX_EPOCH + Duration::from_secs(1363896240),
+//!     hash: vec![0xca, 0xfe],
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&entry).unwrap();
+//! assert_eq!(diag, r#"{"time": 1(1363896240), "hash": 23(h'cafe')}"#);
+//! # }
+//! ```
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+use tags::deserialize_with_tag;
+use tags::{deserialize_tagged, Tagged, PACKED_NEWTYPE_NAME};
+
+#[cfg(feature = "num-bigint")]
+pub mod bigint;
+#[cfg(feature = "num-bigint")]
+pub mod biguint;
+pub mod bignum;
+#[cfg(feature = "chrono")]
+pub mod chrono;
+#[cfg(feature = "rust_decimal")]
+pub mod decimal;
+pub mod epoch;
+pub mod ip;
+#[cfg(feature = "ipnet")]
+pub mod ipnet;
+#[cfg(feature = "num-rational")]
+pub mod ratio;
+#[cfg(feature = "regex")]
+pub mod regex;
+pub mod rfc3339;
+pub mod seconds;
+pub mod shared;
+#[cfg(feature = "time")]
+pub mod time;
+pub mod typed_array;
+#[cfg(feature = "url")]
+pub mod url;
+#[cfg(feature = "uuid")]
+pub mod uuid;
+
+macro_rules! expected_encoding {
+    ($(#[$attr:meta])* mod $name:ident = $tag:expr) => {
+        $(#[$attr])*
+        pub mod $name {
+            use serde::{Deserializer, Serializer};
+
+            /// Serializes bytes as a byte string with the tag of this module.
+            pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                T: ?Sized + AsRef<[u8]>,
+                S: Serializer,
+            {
+                super::serialize_bytes(bytes.as_ref(), $tag, serializer)
+            }
+
+            /// Deserializes a byte string that has the tag of this module or none.
+            pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+            where
+                T: From<Vec<u8>>,
+                D: Deserializer<'de>,
+            {
+                super::deserialize_bytes(deserializer, $tag).map(T::from)
+            }
+        }
+    };
+}
+
+expected_encoding! {
+    /// Byte strings with tag 21, to be converted to base64url without padding.
+    mod base64url = 21
+}
+
+expected_encoding! {
+    /// Byte strings with tag 22, to be converted to base64 with padding.
+    mod base64 = 22
+}
+
+expected_encoding! {
+    /// Byte strings with tag 23, to be converted to lower case hexadecimal digits.
+    mod base16 = 23
+}
+
+/// Values encoded with struct fields and enum variants identified by their index.
+///
+/// Only the serializer of this crate switches to the packed encoding; other formats use their
+/// usual one. The deserializer of this crate accepts both encodings, so no adapter is needed for
+/// deserializing, but `deserialize` is provided to pair with `serialize`.
+pub mod packed {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::PACKED_NEWTYPE_NAME;
+
+    /// Serializes a value in the packed encoding.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(PACKED_NEWTYPE_NAME, value)
+    }
+
+    /// Deserializes a value in the packed or the usual encoding.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer)
+    }
+}
+
+pub(crate) fn serialize_bytes<S>(bytes: &[u8], tag: u64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Tagged {
+        tag,
+        value: &Bytes(bytes),
+    }
+    .serialize(serializer)
+}
+
+pub(crate) fn deserialize_bytes<'de, D>(deserializer: D, tag: u64) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_tagged::<D, ByteBuf>(deserializer, tag).map(|bytes| bytes.0)
+}
+
+pub(crate) struct Bytes<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Serialize for Bytes<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+// Deserializes a byte string, or a sequence of bytes in formats without byte strings.
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D>(deserializer: D) -> Result<ByteBuf, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ByteBufVisitor;
+
+        impl<'de> de::Visitor<'de> for ByteBufVisitor {
+            type Value = ByteBuf;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a byte string")
+            }
+
+            fn visit_bytes<E>(self, bytes: &[u8]) -> Result<ByteBuf, E>
+            where
+                E: de::Error,
+            {
+                Ok(ByteBuf(bytes.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(self, bytes: Vec<u8>) -> Result<ByteBuf, E>
+            where
+                E: de::Error,
+            {
+                Ok(ByteBuf(bytes))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<ByteBuf, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let mut bytes = Vec::new();
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(ByteBuf(bytes))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+// A date or time as found in the input, before it is converted to the type of the field.
+#[cfg(any(feature = "chrono", feature = "time"))]
+enum Timestamp {
+    Text(String),
+    // Whole seconds, or days, and nanoseconds.
+    Number(i64, u32),
+}
+
+// Deserializes text that has `text_tag` or none, or a number that has `number_tag` or none.
+#[cfg(any(feature = "chrono", feature = "time"))]
+fn deserialize_timestamp<'de, D>(
+    deserializer: D,
+    text_tag: u64,
+    number_tag: u64,
+) -> Result<Timestamp, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match deserialize_with_tag::<D, Timestamp>(deserializer)? {
+        (None, timestamp) => Ok(timestamp),
+        (Some(tag), timestamp @ Timestamp::Text(_)) if tag == text_tag => Ok(timestamp),
+        (Some(tag), timestamp @ Timestamp::Number(..)) if tag == number_tag => Ok(timestamp),
+        (Some(tag), Timestamp::Number(..)) if tag == text_tag => Err(de::Error::custom(
+            format_args!("expected text with tag {}", tag),
+        )),
+        (Some(tag), Timestamp::Text(_)) if tag == number_tag => Err(de::Error::custom(
+            format_args!("expected a number with tag {}", tag),
+        )),
+        (Some(tag), _) => Err(de::Error::custom(format_args!(
+            "expected tag {} or {}, found tag {}",
+            text_tag, number_tag, tag
+        ))),
+    }
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Timestamp, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TimestampVisitor;
+
+        impl<'de> de::Visitor<'de> for TimestampVisitor {
+            type Value = Timestamp;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a date and time as text or as a number")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Timestamp, E>
+            where
+                E: de::Error,
+            {
+                if value > i64::max_value() as u64 {
+                    return Err(E::custom("timestamp out of range"));
+                }
+                Ok(Timestamp::Number(value as i64, 0))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Timestamp, E>
+            where
+                E: de::Error,
+            {
+                Ok(Timestamp::Number(value, 0))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Timestamp, E>
+            where
+                E: de::Error,
+            {
+                let secs = value.floor();
+                if secs.is_nan() || secs.abs() >= 9_223_372_036_854_775_808.0 {
+                    return Err(E::invalid_value(de::Unexpected::Float(value), &self));
+                }
+                let nanos = ((value - secs) * 1e9).round() as u32;
+                if nanos == 1_000_000_000 {
+                    Ok(Timestamp::Number(secs as i64 + 1, 0))
+                } else {
+                    Ok(Timestamp::Number(secs as i64, nanos))
+                }
+            }
+
+            fn visit_str<E>(self, text: &str) -> Result<Timestamp, E>
+            where
+                E: de::Error,
+            {
+                Ok(Timestamp::Text(text.to_string()))
+            }
+
+            fn visit_string<E>(self, text: String) -> Result<Timestamp, E>
+            where
+                E: de::Error,
+            {
+                Ok(Timestamp::Text(text))
+            }
+        }
+
+        deserializer.deserialize_any(TimestampVisitor)
+    }
+}
@@ -0,0 +1,30 @@
+//! `num_rational::Ratio<i64>` as a rational number with tag 30.
+//!
+//! The ratio is written as an array of its numerator and denominator. Deserializing accepts any
+//! rational number whose numerator and denominator fit in an `i64`, and reduces it to lowest
+//! terms.
+//!
+//! This module is available with the `num-rational` feature.
+use num_rational::Ratio;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use number::Rational;
+
+/// Serializes a `Ratio<i64>`.
+pub fn serialize<S>(value: &Ratio<i64>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Rational::from(*value).serialize(serializer)
+}
+
+/// Deserializes a `Ratio<i64>` from a rational number that has tag 30 or none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Ratio<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Rational::deserialize(deserializer)?
+        .to_ratio()
+        .ok_or_else(|| de::Error::custom("rational number out of range for Ratio<i64>"))
+}
@@ -0,0 +1,56 @@
+//! `regex::Regex` as a regular expression with tag 35.
+//!
+//! Deserializing accepts text with tag 35 or none, and fails unless the `regex` crate compiles
+//! the pattern, so that an invalid pattern is reported where it is read rather than where it is
+//! first used.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate regex;
+//! # extern crate serde_cbor;
+//! use regex::Regex;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Route {
+//!     #[serde(with = "serde_cbor::with::regex")]
+//!     path: Regex,
+//! }
+//!
+//! # fn main() {
+//! let route = Route {
+//!     path: Regex::new("^/sensors/[0-9]+$").unwrap(),
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&route).unwrap();
+//! assert_eq!(diag, r#"{"path": 35("^/sensors/[0-9]+$")}"#);
+//! # }
+//! ```
+//!
+//! This module is available with the `regex` feature.
+use regex::Regex;
+use serde::de::{self, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use tags::{deserialize_tagged, Tagged};
+
+/// Serializes a `Regex`.
+pub fn serialize<S>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Tagged {
+        tag: 35,
+        value: regex.as_str(),
+    }
+    .serialize(serializer)
+}
+
+/// Deserializes a `Regex` from text that has tag 35 or none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pattern: String = deserialize_tagged(deserializer, 35)?;
+    Regex::new(&pattern).map_err(|_| {
+        de::Error::invalid_value(de::Unexpected::Str(&pattern), &"a regular expression")
+    })
+}
@@ -0,0 +1,240 @@
+//! `SystemTime` as an RFC 3339 date and time string with tag 0.
+//!
+//! Times are written in UTC, such as `2013-03-21T20:04:00Z`, with as many digits of fractional
+//! seconds as needed. Deserializing accepts any offset from UTC. Only the years 0000 to 9999 can
+//! be represented.
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{self, Serialize, Serializer};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tags::{deserialize_tagged, Tagged};
+
+/// Serializes a `SystemTime`.
+pub fn serialize<S>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Tagged {
+        tag: 0,
+        value: &Rfc3339(*time),
+    }
+    .serialize(serializer)
+}
+
+/// Deserializes a `SystemTime` from a text string that has tag 0 or none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_tagged::<D, Rfc3339>(deserializer, 0).map(|time| time.0)
+}
+
+// Seconds from the epoch to 0000-01-01T00:00:00Z and to 10000-01-01T00:00:00Z.
+const MIN_SECS: i64 = -62_167_219_200;
+const MAX_SECS: i64 = 253_402_300_800;
+
+struct Rfc3339(SystemTime);
+
+impl Serialize for Rfc3339 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match format(self.0) {
+            Some(text) => serializer.serialize_str(&text),
+            None => Err(ser::Error::custom("time out of range for RFC 3339")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Rfc3339 {
+    fn deserialize<D>(deserializer: D) -> Result<Rfc3339, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Rfc3339Visitor;
+
+        impl<'de> de::Visitor<'de> for Rfc3339Visitor {
+            type Value = Rfc3339;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an RFC 3339 date and time")
+            }
+
+            fn visit_str<E>(self, text: &str) -> Result<Rfc3339, E>
+            where
+                E: de::Error,
+            {
+                match parse(text) {
+                    Some(time) => Ok(Rfc3339(time)),
+                    None => Err(E::invalid_value(de::Unexpected::Str(text), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_str(Rfc3339Visitor)
+    }
+}
+
+fn format(time: SystemTime) -> Option<String> {
+    let (secs, nanos) = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => (duration.as_secs() as i64, duration.subsec_nanos()),
+        Err(error) => {
+            let duration = error.duration();
+            if duration.as_secs() > -MIN_SECS as u64 {
+                return None;
+            }
+            let secs = -(duration.as_secs() as i64);
+            match duration.subsec_nanos() {
+                0 => (secs, 0),
+                nanos => (secs - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+    if !(MIN_SECS..MAX_SECS).contains(&secs) {
+        return None;
+    }
+    let days = if secs >= 0 {
+        secs / 86400
+    } else {
+        (secs + 1) / 86400 - 1
+    };
+    let time = secs - days * 86400;
+    let (year, month, day) = civil_from_days(days);
+    let mut text = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+    if nanos != 0 {
+        let fraction = format!(".{:09}", nanos);
+        text.push_str(fraction.trim_end_matches('0'));
+    }
+    text.push('Z');
+    Some(text)
+}
+
+fn parse(text: &str) -> Option<SystemTime> {
+    let bytes = text.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || (bytes[10] != b'T' && bytes[10] != b't')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let year = digits(&bytes[0..4])?;
+    let month = digits(&bytes[5..7])?;
+    let day = digits(&bytes[8..10])?;
+    let hour = digits(&bytes[11..13])?;
+    let minute = digits(&bytes[14..16])?;
+    // A leap second is taken as the first second of the next minute.
+    let second = digits(&bytes[17..19])?;
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let mut rest = &bytes[19..];
+    let mut nanos = 0;
+    if rest[0] == b'.' {
+        let count = rest[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+        if count == 0 {
+            return None;
+        }
+        // Digits beyond nanoseconds are dropped.
+        for i in 0..9 {
+            let digit = rest
+                .get(1 + i)
+                .filter(|_| i < count)
+                .map_or(0, |c| c - b'0');
+            nanos = nanos * 10 + u32::from(digit);
+        }
+        rest = &rest[1 + count..];
+    }
+    let offset = match rest {
+        b"Z" | b"z" => 0,
+        [sign, h1, h2, b':', m1, m2] if *sign == b'+' || *sign == b'-' => {
+            let hours = digits(&[*h1, *h2])?;
+            let minutes = digits(&[*m1, *m2])?;
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+
+    let secs =
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+    let time = if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(-secs as u64))
+    };
+    time?.checked_add(Duration::new(0, nanos))
+}
+
+fn digits(bytes: &[u8]) -> Option<i64> {
+    bytes.iter().try_fold(0, |value, c| {
+        if c.is_ascii_digit() {
+            Some(value * 10 + i64::from(c - b'0'))
+        } else {
+            None
+        }
+    })
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Converts between days since the epoch and dates in the proleptic Gregorian calendar, after
+// http://howardhinnant.github.io/date_algorithms.html.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = (if days >= 0 { days } else { days - 146_096 }) / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
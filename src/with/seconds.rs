@@ -0,0 +1,88 @@
+//! `Duration` as a number of seconds.
+//!
+//! Whole seconds are encoded as an integer and other durations as a float, the way `epoch`
+//! encodes times, rather than as the struct of seconds and nanoseconds that serde uses. No tag
+//! is written, as tag 1 is for points in time. Deserializing accepts either number.
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+use std::time::Duration;
+
+/// Serializes a `Duration`.
+pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Seconds(*duration).serialize(serializer)
+}
+
+/// Deserializes a `Duration` from a number that is not negative.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Seconds::deserialize(deserializer).map(|seconds| seconds.0)
+}
+
+struct Seconds(Duration);
+
+impl Serialize for Seconds {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0.subsec_nanos() {
+            0 => serializer.serialize_u64(self.0.as_secs()),
+            nanos => serializer.serialize_f64(self.0.as_secs() as f64 + f64::from(nanos) / 1e9),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Seconds {
+    fn deserialize<D>(deserializer: D) -> Result<Seconds, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SecondsVisitor;
+
+        impl<'de> de::Visitor<'de> for SecondsVisitor {
+            type Value = Seconds;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number of seconds")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Seconds, E>
+            where
+                E: de::Error,
+            {
+                Ok(Seconds(Duration::from_secs(value)))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Seconds, E>
+            where
+                E: de::Error,
+            {
+                if value < 0 {
+                    return Err(E::invalid_value(de::Unexpected::Signed(value), &self));
+                }
+                Ok(Seconds(Duration::from_secs(value as u64)))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Seconds, E>
+            where
+                E: de::Error,
+            {
+                if !(0.0..18_446_744_073_709_551_616.0).contains(&value) {
+                    return Err(E::invalid_value(de::Unexpected::Float(value), &self));
+                }
+                let nanos = (value.fract() * 1e9).round() as u32;
+                Ok(Seconds(
+                    Duration::from_secs(value as u64) + Duration::new(0, nanos),
+                ))
+            }
+        }
+
+        deserializer.deserialize_any(SecondsVisitor)
+    }
+}
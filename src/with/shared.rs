@@ -0,0 +1,85 @@
+//! Values behind an `Rc` that may be shared, written once with tag 28 and referred to with tag 29
+//! after that. `arc` does the same for an `Arc`.
+//!
+//! Sharing only happens within `shared::to_vec` and `shared::from_slice`; see the `shared`
+//! module. Elsewhere, the value is written on its own, and read with tag 28 or none.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! use std::sync::Arc;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Pair {
+//!     #[serde(with = "serde_cbor::with::shared::arc")]
+//!     left: Arc<Vec<u32>>,
+//!     #[serde(with = "serde_cbor::with::shared::arc")]
+//!     right: Arc<Vec<u32>>,
+//! }
+//!
+//! # fn main() {
+//! let values = Arc::new(vec![1, 2, 3]);
+//! let pair = Pair {
+//!     left: values.clone(),
+//!     right: values,
+//! };
+//! let bytes = serde_cbor::shared::to_vec(&pair).unwrap();
+//! let diag = serde_cbor::diag::display(&bytes).to_string();
+//! assert_eq!(diag, r#"{"left": 28([1, 2, 3]), "right": 29(0)}"#);
+//!
+//! let pair: Pair = serde_cbor::shared::from_slice(&bytes).unwrap();
+//! assert!(Arc::ptr_eq(&pair.left, &pair.right));
+//! # }
+//! ```
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::rc::Rc;
+
+use shared::{deserialize_shared, serialize_shared};
+
+/// Values behind an `Arc`.
+pub mod arc {
+    use serde::de::{DeserializeOwned, Deserializer};
+    use serde::ser::{Serialize, Serializer};
+    use std::sync::Arc;
+
+    use shared::{deserialize_shared, serialize_shared};
+
+    /// Serializes an `Arc`, referring to its value if it was serialized before.
+    pub fn serialize<T, S>(value: &Arc<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+        S: Serializer,
+    {
+        let address = Arc::as_ptr(value) as *const u8 as usize;
+        serialize_shared(address, &**value, serializer)
+    }
+
+    /// Deserializes an `Arc` from a value with tag 28 or none, or from a reference with tag 29.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Arc<T>, D::Error>
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+        D: Deserializer<'de>,
+    {
+        deserialize_shared(deserializer, Arc::new)
+    }
+}
+
+/// Serializes an `Rc`, referring to its value if it was serialized before.
+pub fn serialize<T, S>(value: &Rc<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + Serialize,
+    S: Serializer,
+{
+    let address = Rc::as_ptr(value) as *const u8 as usize;
+    serialize_shared(address, &**value, serializer)
+}
+
+/// Deserializes an `Rc` from a value with tag 28 or none, or from a reference with tag 29.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Rc<T>, D::Error>
+where
+    T: DeserializeOwned + 'static,
+    D: Deserializer<'de>,
+{
+    deserialize_shared(deserializer, Rc::new)
+}
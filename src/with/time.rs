@@ -0,0 +1,271 @@
+//! Dates and times of the `time` crate with the tags for them.
+//!
+//! An `OffsetDateTime` is written with `rfc3339` as an RFC 3339 string with tag 0, keeping its
+//! offset from UTC, or with `epoch` as the number of seconds since 1970-01-01T00:00Z with tag 1.
+//! A `Date` is written with `full_date` as a `YYYY-MM-DD` string with tag 1004, or with
+//! `epoch_days` as the number of days since 1970-01-01 with tag 100, after RFC 8943. Either form
+//! is accepted when deserializing, so the form can be changed without breaking existing data.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! # extern crate time;
+//! use time::{Date, Month, OffsetDateTime};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Entry {
+//!     #[serde(with = "serde_cbor::with::time::epoch")]
+//!     created: OffsetDateTime,
+//!     #[serde(with = "serde_cbor::with::time::full_date")]
+//!     due: Date,
+//! }
+//!
+//! # fn main() {
+//! let entry = Entry {
+//!     created: OffsetDateTime::from_unix_timestamp(1363896240).unwrap(),
+//!     due: Date::from_calendar_date(2013, Month::March, 28).unwrap(),
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&entry).unwrap();
+//! assert_eq!(diag, r#"{"created": 1(1363896240), "due": 1004("2013-03-28")}"#);
+//! # }
+//! ```
+//!
+//! This module is available with the `time` feature.
+use serde::de::{self, Deserializer};
+use serde::ser::{self, Serialize, Serializer};
+use std::convert::TryFrom;
+use time::format_description::well_known::Rfc3339;
+use time::{Date, Month, OffsetDateTime};
+
+use super::{deserialize_timestamp, Timestamp};
+
+// The Julian day of 1970-01-01.
+const EPOCH_JULIAN_DAY: i64 = 2_440_588;
+
+/// Dates and times written as an RFC 3339 string with tag 0.
+///
+/// Fractional seconds are written with as many digits as needed. Only the years 0000 to 9999
+/// and offsets from UTC in whole minutes can be represented.
+pub mod rfc3339 {
+    use serde::{Deserializer, Serialize, Serializer};
+    use time::OffsetDateTime;
+
+    use super::Text;
+    use tags::Tagged;
+
+    /// Serializes a date and time as text with tag 0.
+    pub fn serialize<S>(time: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 0,
+            value: &Text(time),
+        }
+        .serialize(serializer)
+    }
+
+    /// Deserializes a date and time from text with tag 0 or a number with tag 1, or either
+    /// without a tag.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize_date_time(deserializer)
+    }
+}
+
+/// Dates and times written as seconds since the epoch with tag 1.
+///
+/// Whole seconds are written as an integer and other times as a float, which keeps a precision
+/// of about a microsecond for current dates. The offset from UTC is not kept, and times are
+/// deserialized in UTC.
+pub mod epoch {
+    use serde::{Deserializer, Serialize, Serializer};
+    use time::OffsetDateTime;
+
+    use super::Number;
+    use tags::Tagged;
+
+    /// Serializes a date and time as a number with tag 1.
+    pub fn serialize<S>(time: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 1,
+            value: &Number(time),
+        }
+        .serialize(serializer)
+    }
+
+    /// Deserializes a date and time from text with tag 0 or a number with tag 1, or either
+    /// without a tag.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize_date_time(deserializer)
+    }
+}
+
+/// Dates written as a `YYYY-MM-DD` string with tag 1004.
+///
+/// Only the years 0000 to 9999 can be represented.
+pub mod full_date {
+    use serde::{Deserializer, Serialize, Serializer};
+    use time::Date;
+
+    use super::DateText;
+    use tags::Tagged;
+
+    /// Serializes a date as text with tag 1004.
+    pub fn serialize<S>(date: &Date, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 1004,
+            value: &DateText(*date),
+        }
+        .serialize(serializer)
+    }
+
+    /// Deserializes a date from text with tag 1004 or a number with tag 100, or either without a
+    /// tag.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Date, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize_date(deserializer)
+    }
+}
+
+/// Dates written as the number of days since 1970-01-01 with tag 100.
+pub mod epoch_days {
+    use serde::{Deserializer, Serialize, Serializer};
+    use time::Date;
+
+    use super::EPOCH_JULIAN_DAY;
+    use tags::Tagged;
+
+    /// Serializes a date as a number with tag 100.
+    pub fn serialize<S>(date: &Date, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Tagged {
+            tag: 100,
+            value: &(i64::from(date.to_julian_day()) - EPOCH_JULIAN_DAY),
+        }
+        .serialize(serializer)
+    }
+
+    /// Deserializes a date from text with tag 1004 or a number with tag 100, or either without a
+    /// tag.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Date, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize_date(deserializer)
+    }
+}
+
+struct Text<'a>(&'a OffsetDateTime);
+
+impl<'a> Serialize for Text<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0.format(&Rfc3339) {
+            Ok(text) => serializer.serialize_str(&text),
+            Err(_) => Err(ser::Error::custom("time out of range for RFC 3339")),
+        }
+    }
+}
+
+struct Number<'a>(&'a OffsetDateTime);
+
+impl<'a> Serialize for Number<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let secs = self.0.unix_timestamp();
+        match self.0.nanosecond() {
+            0 => serializer.serialize_i64(secs),
+            nanos => serializer.serialize_f64(secs as f64 + f64::from(nanos) / 1e9),
+        }
+    }
+}
+
+struct DateText(Date);
+
+impl Serialize for DateText {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let date = self.0;
+        if !(0..=9999).contains(&date.year()) {
+            return Err(ser::Error::custom("date out of range for RFC 3339"));
+        }
+        let text = format!("{:04}-{:02}-{:02}", date.year(), date.month() as u8, date.day());
+        serializer.serialize_str(&text)
+    }
+}
+
+fn deserialize_date_time<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match deserialize_timestamp(deserializer, 0, 1)? {
+        Timestamp::Text(text) => OffsetDateTime::parse(&text, &Rfc3339).map_err(|_| {
+            de::Error::invalid_value(de::Unexpected::Str(&text), &"an RFC 3339 date and time")
+        }),
+        Timestamp::Number(secs, nanos) => {
+            let nanos = i128::from(secs) * 1_000_000_000 + i128::from(nanos);
+            OffsetDateTime::from_unix_timestamp_nanos(nanos)
+                .map_err(|_| de::Error::custom("timestamp out of range"))
+        }
+    }
+}
+
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Date, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match deserialize_timestamp(deserializer, 1004, 100)? {
+        Timestamp::Text(text) => parse_date(&text).ok_or_else(|| {
+            de::Error::invalid_value(de::Unexpected::Str(&text), &"a date as YYYY-MM-DD")
+        }),
+        Timestamp::Number(days, 0) => days
+            .checked_add(EPOCH_JULIAN_DAY)
+            .and_then(|day| i32::try_from(day).ok())
+            .and_then(|day| Date::from_julian_day(day).ok())
+            .ok_or_else(|| de::Error::custom("date out of range")),
+        Timestamp::Number(..) => Err(de::Error::custom("expected a whole number of days")),
+    }
+}
+
+fn parse_date(text: &str) -> Option<Date> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    let year = digits(&bytes[0..4])?;
+    let month = Month::try_from(digits(&bytes[5..7])? as u8).ok()?;
+    let day = digits(&bytes[8..10])?;
+    Date::from_calendar_date(year as i32, month, day as u8).ok()
+}
+
+fn digits(bytes: &[u8]) -> Option<u32> {
+    bytes.iter().try_fold(0, |value, c| {
+        if c.is_ascii_digit() {
+            Some(value * 10 + u32::from(c - b'0'))
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,137 @@
+//! A `Vec` of numbers as a typed array of RFC 8746.
+//!
+//! The elements are written as a byte string with the tag for their type and byte order, which
+//! takes less space than an array of numbers and is copied in and out as a whole. The top level
+//! functions write the elements in the byte order of the target, so that readers on the same
+//! kind of machine need not swap bytes, and `big_endian` and `little_endian` write a fixed byte
+//! order. Deserializing accepts a typed array of the element type in either byte order, or a
+//! byte string without a tag, which is taken to be in the byte order of the target.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! #[derive(Serialize, Deserialize)]
+//! struct Samples {
+//!     #[serde(with = "serde_cbor::with::typed_array::little_endian")]
+//!     values: Vec<u16>,
+//! }
+//!
+//! # fn main() {
+//! let samples = Samples {
+//!     values: vec![1, 2, 0x1234],
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&samples).unwrap();
+//! assert_eq!(diag, r#"{"values": 69(h'010002003412')}"#);
+//! # }
+//! ```
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use std::mem;
+
+use super::{serialize_bytes, ByteBuf};
+use tags::deserialize_with_tag;
+use typed_array::{Element, TypedArray};
+
+/// Typed arrays with elements in big-endian order.
+pub mod big_endian {
+    use serde::de::Deserializer;
+    use serde::ser::Serializer;
+
+    use typed_array::Element;
+
+    /// Serializes elements in big-endian order.
+    pub fn serialize<T, S>(elements: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Element,
+        S: Serializer,
+    {
+        super::serialize_elements(elements, false, serializer)
+    }
+
+    /// Deserializes elements from a typed array in either byte order, or a byte string without
+    /// a tag.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: Element,
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+/// Typed arrays with elements in little-endian order.
+pub mod little_endian {
+    use serde::de::Deserializer;
+    use serde::ser::Serializer;
+
+    use typed_array::Element;
+
+    /// Serializes elements in little-endian order.
+    pub fn serialize<T, S>(elements: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Element,
+        S: Serializer,
+    {
+        super::serialize_elements(elements, true, serializer)
+    }
+
+    /// Deserializes elements from a typed array in either byte order, or a byte string without
+    /// a tag.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: Element,
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+/// Serializes elements in the byte order of the target.
+pub fn serialize<T, S>(elements: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Element,
+    S: Serializer,
+{
+    serialize_elements(elements, cfg!(target_endian = "little"), serializer)
+}
+
+/// Deserializes elements from a typed array in either byte order, or a byte string without a
+/// tag.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    T: Element,
+    D: Deserializer<'de>,
+{
+    let (tag, bytes) = deserialize_with_tag::<D, ByteBuf>(deserializer)?;
+    match TypedArray::new(tag, &bytes.0).to_vec() {
+        Some(elements) => Ok(elements),
+        None if bytes.0.len() % mem::size_of::<T>() != 0 => {
+            Err(de::Error::invalid_length(bytes.0.len(), &"a whole number of elements"))
+        }
+        None => Err(de::Error::custom(format_args!(
+            "expected a typed array with tag {} or {}, found tag {}",
+            T::BIG_ENDIAN_TAG,
+            T::LITTLE_ENDIAN_TAG,
+            tag.unwrap_or_default()
+        ))),
+    }
+}
+
+fn serialize_elements<T, S>(
+    elements: &[T],
+    little_endian: bool,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: Element,
+    S: Serializer,
+{
+    let mut bytes = vec![0; mem::size_of_val(elements)];
+    T::write_into(elements, &mut bytes, little_endian);
+    let tag = if little_endian {
+        T::LITTLE_ENDIAN_TAG
+    } else {
+        T::BIG_ENDIAN_TAG
+    };
+    serialize_bytes(&bytes, tag, serializer)
+}
@@ -0,0 +1,54 @@
+//! `url::Url` as a URI with tag 32.
+//!
+//! Deserializing accepts text with tag 32 or none, and fails unless the text parses as an
+//! absolute URL.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! # extern crate url;
+//! use url::Url;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Link {
+//!     #[serde(with = "serde_cbor::with::url")]
+//!     href: Url,
+//! }
+//!
+//! # fn main() {
+//! let link = Link {
+//!     href: Url::parse("coap://example.com/sensors/temp").unwrap(),
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&link).unwrap();
+//! assert_eq!(diag, r#"{"href": 32("coap://example.com/sensors/temp")}"#);
+//! # }
+//! ```
+//!
+//! This module is available with the `url` feature.
+use serde::de::{self, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use url::Url;
+
+use tags::{deserialize_tagged, Tagged};
+
+/// Serializes a `Url`.
+pub fn serialize<S>(url: &Url, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Tagged {
+        tag: 32,
+        value: url.as_str(),
+    }
+    .serialize(serializer)
+}
+
+/// Deserializes a `Url` from text that has tag 32 or none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Url, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text: String = deserialize_tagged(deserializer, 32)?;
+    Url::parse(&text)
+        .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&text), &"an absolute URL"))
+}
@@ -0,0 +1,50 @@
+//! `uuid::Uuid` as a 16-byte string with tag 37.
+//!
+//! This replaces the 36-character text that `Uuid` serializes as by default. Deserializing
+//! accepts a 16-byte string with tag 37 or none.
+//!
+//! ```
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_cbor;
+//! # extern crate uuid;
+//! use uuid::Uuid;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Device {
+//!     #[serde(with = "serde_cbor::with::uuid")]
+//!     id: Uuid,
+//! }
+//!
+//! # fn main() {
+//! let device = Device {
+//!     id: Uuid::from_u128(0x8ee2_4c3a_52ad_4b34_9f2d_6a0c_1f7a_8e3b),
+//! };
+//! let diag = serde_cbor::diag::to_diag_string(&device).unwrap();
+//! assert_eq!(diag, r#"{"id": 37(h'8ee24c3a52ad4b349f2d6a0c1f7a8e3b')}"#);
+//! # }
+//! ```
+//!
+//! This module is available with the `uuid` feature.
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use uuid::Uuid;
+
+use super::{deserialize_bytes, serialize_bytes};
+
+/// Serializes a `Uuid`.
+pub fn serialize<S>(uuid: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serialize_bytes(uuid.as_bytes(), 37, serializer)
+}
+
+/// Deserializes a `Uuid` from a 16-byte string that has tag 37 or none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Uuid, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let bytes = deserialize_bytes(deserializer, 37)?;
+    Uuid::from_slice(&bytes)
+        .map_err(|_| de::Error::invalid_length(bytes.len(), &"a UUID of 16 bytes"))
+}
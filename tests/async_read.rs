@@ -0,0 +1,150 @@
+#![cfg(any(feature = "futures-io", feature = "tokio"))]
+
+extern crate futures_executor;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use futures_executor::block_on;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use serde_cbor::de::{from_async_reader, AsyncIoRead, AsyncSource};
+use serde_cbor::{to_vec, Value};
+use serde_cbor::value::Map;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Message {
+    id: u32,
+    body: String,
+    parts: Vec<Vec<u8>>,
+}
+
+// Hands out one byte at a time, and has none ready every other time it is asked.
+struct Trickle<'a> {
+    bytes: &'a [u8],
+    ready: bool,
+}
+
+impl<'a> AsyncSource for Trickle<'a> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.ready = !self.ready;
+        if !self.ready {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        match self.bytes.split_first() {
+            Some((&byte, rest)) => {
+                buf[0] = byte;
+                self.bytes = rest;
+                Poll::Ready(Ok(1))
+            }
+            None => Poll::Ready(Ok(0)),
+        }
+    }
+}
+
+fn trickle(bytes: &[u8]) -> Trickle {
+    Trickle {
+        bytes,
+        ready: false,
+    }
+}
+
+fn messages() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for id in 0..3 {
+        let message = Message {
+            id,
+            body: "x".repeat(id as usize * 10),
+            parts: vec![vec![1, 2], vec![]],
+        };
+        bytes.extend(to_vec(&message).unwrap());
+    }
+    // [_ {_ "a": (_ h'01', h'')}, 0(1)]
+    bytes.extend_from_slice(b"\x9f\xbf\x61a\x5f\x41\x01\x40\xff\xff\xc0\x01\xff");
+    bytes
+}
+
+#[test]
+fn test_next_value() {
+    let bytes = messages();
+    let mut read = AsyncIoRead::new(trickle(&bytes));
+    for id in 0..3 {
+        let message: Message = block_on(read.next_value()).unwrap().unwrap();
+        assert_eq!(message.id, id);
+        assert_eq!(message.body.len(), id as usize * 10);
+    }
+    let value: Value = block_on(read.next_value()).unwrap().unwrap();
+    let mut map = Map::new();
+    map.insert(Value::String("a".to_string()), Value::Bytes(vec![1]));
+    let tagged = Value::Tag(0, Box::new(Value::Integer(1)));
+    assert_eq!(value, Value::Array(vec![Value::Object(map), tagged]));
+    assert_eq!(read.byte_offset(), bytes.len() as u64);
+    assert!(block_on(read.next_value::<u8>()).unwrap().is_none());
+}
+
+#[test]
+fn test_errors() {
+    // A data item of the wrong type is skipped.
+    let mut read = AsyncIoRead::new(trickle(b"\x61a\x01"));
+    assert!(block_on(read.next_value::<u8>()).unwrap_err().is_data());
+    assert_eq!(block_on(read.next_value::<u8>()).unwrap(), Some(1));
+
+    let mut read = AsyncIoRead::new(trickle(b"\x01\x82\x01"));
+    assert_eq!(block_on(read.next_value::<u8>()).unwrap(), Some(1));
+    let error = block_on(read.next_value::<Vec<u8>>()).unwrap_err();
+    assert!(error.is_eof());
+    assert_eq!(error.offset(), 3);
+
+    let mut read = AsyncIoRead::new(trickle(b"\x01\x9f\x01\x1c"));
+    assert_eq!(block_on(read.next_value::<u8>()).unwrap(), Some(1));
+    let error = block_on(read.next_value::<Vec<u8>>()).unwrap_err();
+    assert!(error.is_syntax());
+    assert_eq!(error.offset(), 4);
+
+    let error =
+        block_on(AsyncIoRead::new(trickle(b"\x7f\x41a\xff")).next_value::<String>()).unwrap_err();
+    assert!(error.is_syntax());
+
+    let mut nested = vec![0x81; 128];
+    nested.push(0x01);
+    let error = block_on(AsyncIoRead::new(trickle(&nested)).next_value::<Value>());
+    assert!(error.unwrap_err().is_data());
+}
+
+#[test]
+fn test_from_async_reader() {
+    let value: Vec<u8> = block_on(from_async_reader(trickle(b"\x82\x01\x02"))).unwrap();
+    assert_eq!(value, [1, 2]);
+    let error = block_on(from_async_reader::<_, u8>(trickle(b"\x01\x02"))).unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.offset(), 2);
+    let error = block_on(from_async_reader::<_, u8>(trickle(b""))).unwrap_err();
+    assert!(error.is_eof());
+}
+
+#[cfg(feature = "futures-io")]
+#[test]
+fn test_futures_io() {
+    let bytes = messages();
+    let mut read = AsyncIoRead::new(&bytes[..]);
+    let message: Message = block_on(read.next_value()).unwrap().unwrap();
+    assert_eq!(message.id, 0);
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_tokio() {
+    use serde_cbor::de::TokioIo;
+
+    let bytes = messages();
+    let mut read = AsyncIoRead::new(TokioIo(&bytes[..]));
+    let message: Message = block_on(read.next_value()).unwrap().unwrap();
+    assert_eq!(message.id, 0);
+}
@@ -0,0 +1,130 @@
+#![cfg(any(feature = "futures-io", feature = "tokio"))]
+
+extern crate futures_executor;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use futures_executor::block_on;
+use serde::ser::{Error, Serialize, Serializer};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use serde_cbor::ser::{to_async_writer, AsyncIoWrite, AsyncSink};
+use serde_cbor::to_vec;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Message {
+    id: u32,
+    body: String,
+}
+
+struct Fail;
+
+impl Serialize for Fail {
+    fn serialize<S>(&self, _: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Err(S::Error::custom("cannot be encoded"))
+    }
+}
+
+// Takes one byte at a time, and takes none every other time it is asked.
+#[derive(Default)]
+struct Trickle {
+    bytes: Vec<u8>,
+    ready: bool,
+    flushed: usize,
+    limit: Option<usize>,
+}
+
+impl AsyncSink for Trickle {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.ready = !self.ready;
+        if !self.ready {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        if self.limit == Some(self.bytes.len()) {
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, "full")));
+        }
+        self.bytes.push(buf[0]);
+        Poll::Ready(Ok(1))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+        self.flushed = self.bytes.len();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[test]
+fn test_write_value() {
+    let mut write = AsyncIoWrite::new(Trickle::default());
+    let mut expected = Vec::new();
+    for id in 0..3 {
+        let message = Message {
+            id,
+            body: "x".repeat(id as usize * 10),
+        };
+        block_on(write.write_value(&message)).unwrap();
+        expected.extend(to_vec(&message).unwrap());
+    }
+    assert_eq!(write.bytes_written(), expected.len() as u64);
+    block_on(write.flush()).unwrap();
+    let trickle = write.into_inner();
+    assert_eq!(trickle.bytes, expected);
+    assert_eq!(trickle.flushed, expected.len());
+}
+
+#[test]
+fn test_errors() {
+    let mut write = AsyncIoWrite::new(Trickle::default());
+    block_on(write.write_value(&1)).unwrap();
+    // A value that fails to encode halfway writes nothing.
+    assert!(block_on(write.write_value(&(1, Fail))).is_err());
+    block_on(write.write_value(&2)).unwrap();
+    assert_eq!(write.into_inner().bytes, [1, 2]);
+
+    let trickle = Trickle {
+        limit: Some(2),
+        ..Trickle::default()
+    };
+    let error = block_on(to_async_writer(trickle, "abc")).unwrap_err();
+    assert!(error.is_io());
+}
+
+#[test]
+fn test_to_async_writer() {
+    let message = Message {
+        id: 7,
+        body: "hi".to_string(),
+    };
+    block_on(to_async_writer(Trickle::default(), &message)).unwrap();
+    assert!(block_on(to_async_writer(Trickle::default(), &Fail)).is_err());
+}
+
+#[cfg(feature = "futures-io")]
+#[test]
+fn test_futures_io() {
+    let mut bytes = Vec::new();
+    block_on(to_async_writer(&mut bytes, &[1, 2])).unwrap();
+    assert_eq!(bytes, b"\x82\x01\x02");
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_tokio() {
+    use serde_cbor::ser::TokioIo;
+
+    let mut bytes = Vec::new();
+    block_on(to_async_writer(TokioIo(&mut bytes), &[1, 2])).unwrap();
+    assert_eq!(bytes, b"\x82\x01\x02");
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "std")]
+
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -0,0 +1,63 @@
+#![cfg(all(feature = "std", feature = "bytes"))]
+
+extern crate bytes;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use bytes::{Buf, Bytes};
+
+use serde_cbor::de::from_buf;
+use serde_cbor::{to_vec, Deserializer, Value};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Frame {
+    id: u16,
+    text: String,
+    data: Vec<u8>,
+}
+
+// Splits the encoding into chunks of `size` bytes.
+fn chunked(bytes: &[u8], size: usize) -> Box<dyn Buf> {
+    let mut buf: Box<dyn Buf> = Box::new(Bytes::new());
+    for chunk in bytes.chunks(size) {
+        buf = Box::new(buf.chain(Bytes::copy_from_slice(chunk)));
+    }
+    buf
+}
+
+#[test]
+fn test_from_buf() {
+    let frame = Frame {
+        id: 7,
+        text: "a text that spans several chunks".to_string(),
+        data: vec![1; 40],
+    };
+    let bytes = to_vec(&frame).unwrap();
+    for size in 1..8 {
+        assert_eq!(from_buf::<Frame, _>(chunked(&bytes, size)).unwrap(), frame);
+    }
+
+    // (_ "a", "b") and a tagged value
+    let bytes = b"\x82\x7f\x61a\x61b\xff\xc1\x01";
+    let value: Value = from_buf(chunked(bytes, 3)).unwrap();
+    let tagged = Value::Tag(1, Box::new(Value::Integer(1)));
+    assert_eq!(value, Value::Array(vec![Value::String("ab".to_string()), tagged]));
+}
+
+#[test]
+fn test_remaining() {
+    let mut buf = chunked(b"\x01\x02\x03", 1);
+    let de = Deserializer::from_buf(&mut buf);
+    let value: Vec<u8> = de.into_iter().take(2).map(|x| x.unwrap()).collect();
+    assert_eq!(value, [1, 2]);
+    assert_eq!(buf.remaining(), 1);
+}
+
+#[test]
+fn test_eof() {
+    // A string that claims to be longer than the input fails right away.
+    let error = from_buf::<String, _>(chunked(b"\x7a\xff\xff\xff\xffabc", 2)).unwrap_err();
+    assert!(error.is_eof());
+    assert_eq!(error.offset(), 8);
+}
@@ -0,0 +1,258 @@
+#![cfg(feature = "std")]
+
+extern crate serde;
+extern crate serde_cbor;
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "digest")]
+extern crate sha2;
+
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde::Deserialize;
+#[cfg(feature = "digest")]
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+use serde_cbor::de::Profile;
+#[cfg(feature = "digest")]
+use serde_cbor::ser::canonical_hash;
+use serde_cbor::ser::{to_vec_canonical, to_vec_ctap2, to_writer_canonical, KeyOrder};
+use serde_cbor::{to_value, Deserializer, Value};
+use serde_cbor::value::Map;
+
+#[cfg(feature = "digest")]
+fn hash<T: Serialize>(value: &T) -> Vec<u8> {
+    canonical_hash::<Sha256, _>(value).unwrap().to_vec()
+}
+
+#[cfg(feature = "digest")]
+fn expected(bytes: &[u8]) -> Vec<u8> {
+    Sha256::digest(bytes).to_vec()
+}
+
+#[derive(Serialize)]
+struct Record {
+    name: &'static str,
+    id: u32,
+    tags: BTreeMap<String, bool>,
+}
+
+// A sequence of unknown length, or a map that repeats a key.
+#[derive(Serialize)]
+struct Big(#[serde(with = "serde_cbor::with::bignum")] u128);
+
+#[derive(Clone)]
+struct Unsized(Vec<u8>);
+struct Repeated;
+
+impl Serialize for Unsized {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        for item in &self.0 {
+            seq.serialize_element(item)?;
+        }
+        seq.end()
+    }
+}
+
+impl Serialize for Repeated {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("a", &1)?;
+        map.serialize_entry("a", &2)?;
+        map.end()
+    }
+}
+
+#[cfg(feature = "digest")]
+#[test]
+fn test_map_order() {
+    let mut forward = HashMap::new();
+    let mut backward = HashMap::new();
+    for i in 0..100u32 {
+        forward.insert(i, i);
+        backward.insert(99 - i, 99 - i);
+    }
+    assert_eq!(hash(&forward), hash(&backward));
+
+    // Keys are sorted by their encoding, where shorter strings come first.
+    let mut map = BTreeMap::new();
+    map.insert("aa", 1);
+    map.insert("b", -1);
+    assert_eq!(hash(&map), expected(b"\xa2\x61b\x20\x62aa\x01"));
+    assert_ne!(hash(&map), expected(&serde_cbor::to_vec(&map).unwrap()));
+}
+
+#[cfg(feature = "digest")]
+#[test]
+fn test_struct() {
+    let mut tags = BTreeMap::new();
+    tags.insert("z".to_string(), true);
+    tags.insert("y".to_string(), false);
+    let record = Record {
+        name: "x",
+        id: 500,
+        tags,
+    };
+    assert_eq!(
+        hash(&record),
+        expected(b"\xa3\x62id\x19\x01\xf4\x64name\x61x\x64tags\xa2\x61y\xf4\x61z\xf5")
+    );
+    let value = serde_cbor::to_value(&record).unwrap();
+    assert_eq!(hash(&value), hash(&record));
+}
+
+#[cfg(feature = "digest")]
+#[test]
+fn test_definite_lengths() {
+    assert_eq!(hash(&Unsized(vec![1, 2])), expected(b"\x82\x01\x02"));
+    assert_eq!(
+        hash(&vec![Unsized(vec![]), Unsized(vec![3])]),
+        expected(b"\x82\x80\x81\x03")
+    );
+    assert_eq!(hash(&Value::F64(1.5)), expected(b"\xf9\x3e\x00"));
+}
+
+#[cfg(feature = "digest")]
+#[test]
+fn test_duplicate_keys() {
+    assert!(canonical_hash::<Sha256, _>(&Repeated).is_err());
+    assert!(canonical_hash::<Sha256, _>(&vec![Repeated]).is_err());
+}
+
+#[test]
+fn test_to_vec_canonical() {
+    let mut map = HashMap::new();
+    for i in 0..100u32 {
+        map.insert(format!("{}", i * 37 % 101), vec![Unsized(vec![1]); (i % 3) as usize]);
+    }
+    let bytes = to_vec_canonical(&map).unwrap();
+    let mut writer = Vec::new();
+    to_writer_canonical(&mut writer, &map).unwrap();
+    assert_eq!(bytes, writer);
+
+    // Nothing in the output is out of order, indefinite or longer than it needs to be.
+    let mut de = Deserializer::from_slice(&bytes).collect_warnings();
+    let decoded = HashMap::<String, Vec<Vec<u8>>>::deserialize(&mut de).unwrap();
+    assert!(de.warnings().is_empty());
+    let mut de = Deserializer::from_slice(&bytes).profile(Profile::Rfc8949Strict);
+    Value::deserialize(&mut de).unwrap();
+    assert_eq!(decoded.len(), 100);
+    assert_eq!(decoded["36"], vec![vec![1]]);
+
+    let record = Record {
+        name: "x",
+        id: 500,
+        tags: BTreeMap::new(),
+    };
+    assert_eq!(
+        to_vec_canonical(&record).unwrap(),
+        b"\xa3\x62id\x19\x01\xf4\x64name\x61x\x64tags\xa0"
+    );
+    assert_eq!(to_vec_canonical(&100000.0f64).unwrap(), b"\xfa\x47\xc3\x50\x00");
+    assert!(to_vec_canonical(&Repeated).is_err());
+}
+
+#[test]
+fn test_length_first() {
+    let canonical = |value: &Value, order| {
+        let mut ser = serde_cbor::Serializer::canonical(Vec::new()).key_order(order);
+        value.serialize(&mut ser).unwrap();
+        ser.into_inner()
+    };
+    // {"b": 1, "aa": 2, 24: 3, h'ff': 4}, and the same with the map itself at -1
+    let mut map = Map::new();
+    map.insert(Value::String("b".to_string()), Value::Integer(1));
+    map.insert(Value::String("aa".to_string()), Value::Integer(2));
+    map.insert(Value::Integer(24), Value::Integer(3));
+    map.insert(Value::Bytes(vec![0xff]), Value::Integer(4));
+    let inner = Value::Object(map.clone());
+    map.insert(Value::Integer(-1), inner.clone());
+    let value = Value::Object(map);
+
+    let inner_bytes = b"\xa4\x18\x18\x03\x41\xff\x04\x61b\x01\x62aa\x02";
+    assert_eq!(canonical(&inner, KeyOrder::LengthFirst), &inner_bytes[..]);
+    let mut expected = b"\xa5\x20".to_vec();
+    expected.extend_from_slice(inner_bytes);
+    expected.extend_from_slice(&inner_bytes[1..]);
+    let bytes = canonical(&value, KeyOrder::LengthFirst);
+    assert_eq!(bytes, expected);
+    assert_eq!(serde_cbor::from_slice::<Value>(&bytes).unwrap(), value);
+
+    // Bytewise, -1 comes after 24, and CTAP2 puts it after all unsigned integers.
+    let bytes = canonical(&value, KeyOrder::Bytewise);
+    assert_eq!(&bytes[..5], b"\xa5\x18\x18\x03\x20");
+    let bytes = canonical(&value, KeyOrder::Ctap2);
+    assert_eq!(&bytes[..6], b"\xa5\x18\x18\x03\x20\xa4");
+    assert_eq!(&bytes[bytes.len() - 10..], &inner_bytes[4..]);
+
+    // Serializers that are not canonical keep the order keys come in.
+    let mut ser = serde_cbor::Serializer::new(Vec::new()).key_order(KeyOrder::LengthFirst);
+    Repeated.serialize(&mut ser).unwrap();
+    assert_eq!(ser.into_inner(), b"\xbf\x61a\x01\x61a\x02\xff");
+}
+
+#[test]
+fn test_ctap2() {
+    #[derive(Serialize)]
+    struct Options {
+        rk: bool,
+        up: bool,
+        plat: bool,
+        #[serde(rename = "clientPin")]
+        client_pin: bool,
+    }
+
+    // A response shaped like that of authenticatorGetInfo, whose members are identified by
+    // integers.
+    let mut info = BTreeMap::new();
+    info.insert(1, to_value(&["U2F_V2", "FIDO_2_0"]).unwrap());
+    info.insert(2, to_value(&["uvm", "hmac-secret"]).unwrap());
+    info.insert(3, Value::Bytes(vec![0xf8; 16]));
+    info.insert(
+        4,
+        to_value(&Options {
+            client_pin: false,
+            plat: false,
+            up: true,
+            rk: true,
+        })
+        .unwrap(),
+    );
+    info.insert(5, Value::Integer(1200));
+    info.insert(6, to_value(&[1]).unwrap());
+
+    let mut expected = b"\xa6\x01\x82\x66U2F_V2\x68FIDO_2_0\x02\x82\x63uvm\x6bhmac-secret\x03\x50"
+        .to_vec();
+    expected.extend_from_slice(&[0xf8; 16]);
+    expected.extend_from_slice(
+        b"\x04\xa4\x62rk\xf5\x62up\xf5\x64plat\xf4\x69clientPin\xf4\x05\x19\x04\xb0\x06\x81\x01",
+    );
+    let bytes = to_vec_ctap2(&info).unwrap();
+    assert_eq!(bytes, expected);
+    let mut de = Deserializer::from_slice(&bytes).profile(Profile::Ctap2);
+    Value::deserialize(&mut de).unwrap();
+
+    // {3: -7, -1: 1, 24: 0}: RFC 7049 puts 24 last, CTAP2 puts unsigned integers first.
+    let mut key = BTreeMap::new();
+    key.insert(3, -7);
+    key.insert(-1, 1);
+    key.insert(24, 0);
+    let bytes = to_vec_ctap2(&key).unwrap();
+    assert_eq!(bytes, b"\xa3\x03\x26\x18\x18\x00\x20\x01");
+    let mut de = Deserializer::from_slice(&bytes).profile(Profile::Ctap2);
+    Value::deserialize(&mut de).unwrap();
+    let mut ser = serde_cbor::Serializer::canonical(Vec::new()).key_order(KeyOrder::LengthFirst);
+    key.serialize(&mut ser).unwrap();
+    let bytes = ser.into_inner();
+    assert_eq!(bytes, b"\xa3\x03\x26\x20\x01\x18\x18\x00");
+    let mut de = Deserializer::from_slice(&bytes).profile(Profile::Ctap2);
+    let error = Value::deserialize(&mut de).unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.to_string(), "map key 24 out of order at offset 5");
+
+    // Tags are not written.
+    let big = Big(1 << 100);
+    assert!(to_vec_canonical(&big).is_ok());
+    assert!(to_vec_ctap2(&big).is_err());
+}
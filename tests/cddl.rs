@@ -0,0 +1,243 @@
+#![cfg(feature = "cddl")]
+
+extern crate serde_cbor;
+
+use std::collections::BTreeMap;
+
+use serde_cbor::cddl::Schema;
+use serde_cbor::{to_vec, Value};
+
+fn messages(schema: &Schema, bytes: &[u8]) -> Vec<String> {
+    schema
+        .validate(bytes)
+        .iter()
+        .map(|violation| violation.to_string())
+        .collect()
+}
+
+#[test]
+fn test_prelude() {
+    let schema = Schema::parse("value = [int, tstr, bstr, bool, null, float16, any]").unwrap();
+    // [-1, "a", h'00', true, null, 1.5, [1]]
+    let bytes = b"\x87\x20\x61a\x41\x00\xf5\xf6\xf9\x3e\x00\x81\x01";
+    assert!(schema.validate(bytes).is_empty());
+    // [-1, "a", h'00', true, null, 1.5 as a float32, [1]]
+    let bytes = b"\x87\x20\x61a\x41\x00\xf5\xf6\xfa\x3f\xc0\x00\x00\x81\x01";
+    assert_eq!(
+        messages(&schema, bytes),
+        vec!["expected float16, found 1.5 at offset 8"]
+    );
+}
+
+#[test]
+fn test_occurrences() {
+    let schema = Schema::parse("list = [uint, * tstr, ? bool]").unwrap();
+    assert!(schema.validate(b"\x81\x01").is_empty());
+    assert!(schema.validate(b"\x84\x01\x61a\x61b\xf4").is_empty());
+    assert_eq!(
+        messages(&schema, b"\x83\x01\x61a\x02"),
+        vec!["expected tstr, found 2 at offset 4"]
+    );
+    assert_eq!(
+        messages(&schema, b"\x80"),
+        vec!["missing array element uint at offset 0"]
+    );
+    assert_eq!(
+        messages(&schema, b"\x83\x01\xf4\xf4"),
+        vec!["unexpected array element at offset 3"]
+    );
+
+    let schema = Schema::parse("pairs = [2*3 (uint, tstr)]").unwrap();
+    assert!(schema.validate(b"\x84\x01\x61a\x02\x61b").is_empty());
+    assert!(!schema.validate(b"\x82\x01\x61a").is_empty());
+}
+
+#[test]
+fn test_maps() {
+    let schema = Schema::parse(
+        r#"
+        message = {
+            id: uint,
+            ? "tags": [* tstr],
+            * int => any,   ; extensions
+        }
+        "#,
+    ).unwrap();
+    let mut map = BTreeMap::new();
+    map.insert(Value::String("id".to_string()), Value::Integer(7));
+    map.insert(Value::Integer(-1), Value::Null);
+    assert!(schema.validate(&to_vec(&map).unwrap()).is_empty());
+
+    // {"tags": ["a", 1], "other": 2}
+    let bytes = b"\xa2\x64tags\x82\x61a\x01\x65other\x02";
+    assert_eq!(
+        messages(&schema, bytes),
+        vec![
+            "missing key \"id\" at offset 0",
+            "expected tstr, found 1 at offset 9",
+            "unexpected key \"other\" at offset 10",
+        ]
+    );
+}
+
+#[test]
+fn test_choices() {
+    let schema = Schema::parse(
+        r#"
+        shape = circle / rect
+        circle = ["circle", radius: float]
+        rect = ["rect", width: float, height: float]
+        "#,
+    ).unwrap();
+    assert!(schema.validate(b"\x82\x66circle\xf9\x3c\x00").is_empty());
+    assert_eq!(
+        messages(&schema, b"\x83\x64rect\xf9\x3c\x00\x01"),
+        vec!["expected float, found 1 at offset 9"]
+    );
+    assert_eq!(messages(&schema, b"\x01"), vec!["expected shape, found 1 at offset 0"]);
+}
+
+#[test]
+fn test_generated_schema() {
+    // The shape of schemas written by `reflection::to_cddl`.
+    let schema = Schema::parse(
+        r#"
+        Message = {
+          "code": uint .size 2,
+          "kind": Kind,
+          "extra": {* tstr => [* -128..127]} / null,
+        }
+        Kind = "Empty"
+          / ["Pair", bool, float]
+        "#,
+    ).unwrap();
+    // {"code": 1000, "kind": ["Pair", true, 0.5], "extra": {"a": [-1, 127]}}
+    let bytes = b"\xa3\x64code\x19\x03\xe8\x64kind\x83\x64Pair\xf5\xf9\x38\x00\
+                  \x65extra\xa1\x61a\x82\x20\x18\x7f";
+    assert!(schema.validate(bytes).is_empty());
+    // {"code": 100000, "kind": "Full", "extra": {"a": [128]}}
+    let bytes = b"\xa3\x64code\x1a\x00\x01\x86\xa0\x64kind\x64Full\x65extra\xa1\x61a\x81\x18\x80";
+    assert_eq!(
+        messages(&schema, bytes),
+        vec![
+            "expected uint .size 2, found 100000 at offset 6",
+            "expected Kind, found a text string at offset 16",
+            "expected -128..127, found 128 at offset 31",
+        ]
+    );
+}
+
+#[test]
+fn test_controls_and_tags() {
+    let schema = Schema::parse(
+        r#"
+        signed = #6.18([protected: bstr .cbor header, payload: bstr .size (1..4)])
+        header = {1: -7 / -8, ? 4: bstr}
+        "#,
+    ).unwrap();
+    // 18([<<{1: -7}>>, h'01'])
+    assert!(schema.validate(b"\xd2\x82\x43\xa1\x01\x26\x41\x01").is_empty());
+    // 18([<<{1: -9}>>, h''])
+    assert_eq!(
+        messages(&schema, b"\xd2\x82\x43\xa1\x01\x28\x40"),
+        vec![
+            "expected -7 / -8, found -9 at offset 5",
+            "expected bstr .size (1..4), found a byte string at offset 6",
+        ]
+    );
+    assert_eq!(
+        messages(&schema, b"\xc1\x80"),
+        vec!["expected signed, found tag 1 at offset 0"]
+    );
+
+    let schema = Schema::parse("small = uint .lt 10").unwrap();
+    assert!(schema.validate(b"\x09").is_empty());
+    assert!(!schema.validate(b"\x0a").is_empty());
+}
+
+#[test]
+fn test_extensions() {
+    let schema = Schema::parse(
+        r#"
+        value = int
+        value /= tstr
+        "#,
+    ).unwrap();
+    assert_eq!(schema.root(), "value");
+    assert!(schema.validate(b"\x61a").is_empty());
+    assert_eq!(schema.validate(b"\xf5").len(), 1);
+}
+
+#[test]
+fn test_group_rules() {
+    let schema = Schema::parse(
+        r#"
+        point = [coordinates]
+        coordinates = (x: int, y: int)
+        coordinates //= (x: int, y: int, z: int)
+        "#,
+    ).unwrap();
+    assert!(schema.validate(b"\x82\x01\x02").is_empty());
+    assert!(schema.validate(b"\x83\x01\x02\x03").is_empty());
+    assert!(!schema.validate(b"\x81\x01").is_empty());
+
+    let schema = Schema::parse(
+        r#"
+        record = {common, ? "note": tstr}
+        common = (id: uint, name: tstr)
+        "#,
+    ).unwrap();
+    // {"id": 1, "name": "x"}
+    assert!(schema.validate(b"\xa2\x62id\x01\x64name\x61x").is_empty());
+    // {"id": 1}
+    assert_eq!(
+        messages(&schema, b"\xa1\x62id\x01"),
+        vec!["missing key \"name\" at offset 0"]
+    );
+}
+
+#[test]
+fn test_recursive_rules() {
+    let schema = Schema::parse("tree = [* tree] / uint").unwrap();
+    assert!(schema.validate(b"\x82\x81\x01\x80").is_empty());
+    assert_eq!(
+        messages(&schema, b"\x82\x81\x61a\x80"),
+        vec!["expected tree, found a text string at offset 2"]
+    );
+}
+
+#[test]
+fn test_malformed_input() {
+    let schema = Schema::parse("value = any").unwrap();
+    assert_eq!(
+        messages(&schema, b"\x82\x01"),
+        vec!["unexpected end of input at offset 2"]
+    );
+    assert_eq!(messages(&schema, b"\x01\x02"), vec!["trailing data at offset 1"]);
+    assert_eq!(messages(&schema, b"\x62\xff\xfe"), vec!["invalid UTF-8 at offset 0"]);
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = Schema::parse("a = [b]").unwrap_err();
+    assert!(error.is_syntax());
+    assert_eq!(error.to_string(), "invalid CDDL: undefined rule `b` at offset 5");
+    assert_eq!(Schema::parse("a = b<int>\nb<T> = [T]").unwrap_err().offset(), 5);
+    assert_eq!(Schema::parse("a = tstr .regexp \"a+\"").unwrap_err().offset(), 16);
+    assert_eq!(Schema::parse("a = int\na = tstr").unwrap_err().offset(), 8);
+    assert_eq!(Schema::parse("a = [int").unwrap_err().offset(), 8);
+    assert!(Schema::parse("").is_err());
+}
+
+#[test]
+fn test_deserialize() {
+    let schema = Schema::parse("pair = [uint, tstr]").unwrap();
+    let value: (u32, String) = schema.from_slice(b"\x82\x01\x61a").unwrap();
+    assert_eq!(value, (1, "a".to_string()));
+    let error = schema.from_slice::<(u32, String)>(b"\x82\x01\x02").unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.offset(), 2);
+    let value: (u32, String) = schema.from_reader(&b"\x82\x01\x61a"[..]).unwrap();
+    assert_eq!(value.0, 1);
+    assert!(schema.validate_reader(&b"\x82\x01\x61a"[..]).unwrap().is_empty());
+}
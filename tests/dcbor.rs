@@ -0,0 +1,121 @@
+#![cfg(feature = "dcbor")]
+
+extern crate serde;
+extern crate serde_cbor;
+
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use serde_cbor::de::Profile;
+use serde_cbor::{Deserializer, Serializer, Value};
+
+#[derive(Serialize)]
+struct Reading {
+    value: f64,
+    unit: &'static str,
+}
+
+fn dcbor<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut ser = Serializer::dcbor(Vec::new());
+    value.serialize(&mut ser).unwrap();
+    ser.into_inner()
+}
+
+fn check(bytes: &[u8]) -> serde_cbor::error::Result<Value> {
+    let mut de = Deserializer::from_slice(bytes).profile(Profile::Dcbor);
+    let value = Value::deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+#[test]
+fn test_numeric_reduction() {
+    let cases: &[(f64, &[u8])] = &[
+        (0.0, b"\x00"),
+        (-0.0, b"\x00"),
+        (1.0, b"\x01"),
+        (-1.0, b"\x20"),
+        (1.5, b"\xf9\x3e\x00"),
+        (100000.0, b"\x1a\x00\x01\x86\xa0"),
+        (-9223372036854775808.0, b"\x3b\x7f\xff\xff\xff\xff\xff\xff\xff"),
+        (18446744073709549568.0, b"\x1b\xff\xff\xff\xff\xff\xff\xf8\x00"),
+        (18446744073709551616.0, b"\xfa\x5f\x80\x00\x00"),
+        (-9223372036854777856.0, b"\xfb\xc3\xe0\x00\x00\x00\x00\x00\x01"),
+        (1.1, b"\xfb\x3f\xf1\x99\x99\x99\x99\x99\x9a"),
+        (::std::f64::INFINITY, b"\xf9\x7c\x00"),
+        (::std::f64::NEG_INFINITY, b"\xf9\xfc\x00"),
+        (::std::f64::NAN, b"\xf9\x7e\x00"),
+    ];
+    for &(value, bytes) in cases {
+        assert_eq!(dcbor(&value), bytes, "{}", value);
+        assert!(check(bytes).is_ok(), "{}", value);
+    }
+    assert_eq!(dcbor(&2.0f32), b"\x02");
+    assert_eq!(dcbor(&2.5f32), b"\xf9\x41\x00");
+
+    let reading = Reading {
+        value: 20.0,
+        unit: "°C",
+    };
+    assert_eq!(dcbor(&reading), b"\xa2\x64unit\x63\xc2\xb0C\x65value\x14");
+}
+
+#[test]
+fn test_nfc() {
+    // "é" composed, and as "e" followed by a combining acute accent.
+    assert_eq!(dcbor(&"\u{e9}"), b"\x62\xc3\xa9");
+    let mut ser = Serializer::dcbor(Vec::new());
+    assert!("e\u{301}".serialize(&mut ser).is_err());
+    let mut map = BTreeMap::new();
+    map.insert("e\u{301}", 1);
+    let mut ser = Serializer::dcbor(Vec::new());
+    assert!(map.serialize(&mut ser).is_err());
+
+    assert!(check(b"\x62\xc3\xa9").is_ok());
+    let error = check(b"\x82\x01\x63e\xcc\x81").unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.offset(), 3);
+    assert!(error.to_string().starts_with("text not in Unicode Normalization Form C"));
+}
+
+#[test]
+fn test_rejected() {
+    let cases: &[(&[u8], u64, &str)] = &[
+        (b"\xf9\x3c\x00", 0, "float not in its shortest form"),
+        (b"\x82\x01\xfb\x40\x00\x00\x00\x00\x00\x00\x00", 2, "float not in its shortest form"),
+        (b"\xf9\x80\x00", 0, "float not in its shortest form"),
+        (b"\xf9\x7e\x01", 0, "float not in its shortest form"),
+        (b"\xf9\xfe\x00", 0, "float not in its shortest form"),
+        (b"\xfa\x7f\xc0\x00\x00", 0, "float not in its shortest form"),
+        (b"\xfa\x3f\xc0\x00\x00", 0, "float not in its shortest form"),
+        (b"\x82\x01\xf7", 2, "simple value not allowed"),
+        (b"\x18\x01", 0, "argument not in its shortest form"),
+        (b"\x9f\xff", 0, "indefinite-length item"),
+    ];
+    for &(bytes, offset, message) in cases {
+        assert!(serde_cbor::from_slice::<Value>(bytes).is_ok());
+        let error = check(bytes).unwrap_err();
+        assert_eq!(error.offset(), offset, "{:?}", bytes);
+        assert!(error.to_string().starts_with(message), "{}", error);
+
+        // Skipped items are checked as well.
+        let mut de = Deserializer::from_slice(bytes).profile(Profile::Dcbor);
+        assert_eq!(IgnoredAny::deserialize(&mut de).unwrap_err().offset(), offset);
+    }
+
+    let error = check(b"\xa2\x61b\x01\x61a\x02").unwrap_err();
+    assert_eq!(error.to_string(), "map key \"a\" out of order at offset 4");
+    let error = check(b"\xa2\x61a\x01\x61a\x02").unwrap_err();
+    assert_eq!(error.to_string(), "duplicate map key \"a\" at offset 4");
+}
+
+#[test]
+fn test_round_trip() {
+    let mut map = BTreeMap::new();
+    map.insert("b".to_string(), Value::F64(3.0));
+    map.insert("a".to_string(), Value::Array(vec![Value::F64(0.5), Value::Null]));
+    let bytes = dcbor(&map);
+    assert_eq!(bytes, b"\xa2\x61a\x82\xf9\x38\x00\xf6\x61b\x03");
+    check(&bytes).unwrap();
+}
@@ -1,10 +1,18 @@
+#![cfg(feature = "std")]
+
+extern crate serde;
 extern crate serde_cbor;
 extern crate serde_bytes;
 
+use serde::Deserialize;
+use serde::de::IgnoredAny;
 use serde_bytes::ByteBuf;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 
-use serde_cbor::{to_vec, Value, ObjectKey, error, de, Deserializer, from_reader};
+use serde_cbor::{to_vec, Value, error, de, Deserializer, from_reader};
+use serde_cbor::error::{PathSegment, WarningKind};
+use serde_cbor::value::Map;
 
 #[test]
 fn test_string1() {
@@ -35,19 +43,64 @@ fn test_byte_string() {
 #[test]
 fn test_numbers1() {
     let value: error::Result<Value> = de::from_slice(&[0x00]);
-    assert_eq!(value.unwrap(), Value::U64(0));
+    assert_eq!(value.unwrap(), Value::Integer(0));
 }
 
 #[test]
 fn test_numbers2() {
     let value: error::Result<Value> = de::from_slice(&[0x1a, 0x00, 0xbc, 0x61, 0x4e]);
-    assert_eq!(value.unwrap(), Value::U64(12345678));
+    assert_eq!(value.unwrap(), Value::Integer(12345678));
 }
 
 #[test]
 fn test_numbers3() {
     let value: error::Result<Value> = de::from_slice(&[0x39, 0x07, 0xde]);
-    assert_eq!(value.unwrap(), Value::I64(-2015));
+    assert_eq!(value.unwrap(), Value::Integer(-2015));
+}
+
+#[test]
+fn test_integer128() {
+    let value: u128 = de::from_slice(b"\x1b\xff\xff\xff\xff\xff\xff\xff\xff").unwrap();
+    assert_eq!(value, u128::from(u64::max_value()));
+    let value: i128 = de::from_slice(b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff").unwrap();
+    assert_eq!(value, -(1 << 64));
+    let value: i128 = de::from_slice(b"\x21").unwrap();
+    assert_eq!(value, -2);
+
+    // Bignums are accepted where they fit, leading zeros and all.
+    let value: u128 = de::from_slice(b"\xc2\x49\x01\0\0\0\0\0\0\0\0").unwrap();
+    assert_eq!(value, 1 << 64);
+    let value: i128 = de::from_slice(b"\xc3\x42\x00\x01").unwrap();
+    assert_eq!(value, -2);
+    let mut bytes = b"\xc3\x58\x20".to_vec();
+    bytes.extend_from_slice(&[0; 16]);
+    bytes.extend_from_slice(&(i128::max_value() as u128).to_be_bytes());
+    let value: i128 = de::from_slice(&bytes).unwrap();
+    assert_eq!(value, i128::min_value());
+    for value in &[0, 1, u128::max_value()] {
+        let bytes = to_vec(value).unwrap();
+        assert_eq!(de::from_slice::<u128>(&bytes).unwrap(), *value);
+    }
+    for value in &[0, -1, i128::min_value(), i128::max_value()] {
+        let bytes = to_vec(value).unwrap();
+        assert_eq!(de::from_slice::<i128>(&bytes).unwrap(), *value);
+    }
+
+    // Bignums that do not fit.
+    let mut bytes = b"\xc2\x51\x01".to_vec();
+    bytes.extend_from_slice(&[0; 16]);
+    let error = de::from_slice::<u128>(&bytes).unwrap_err();
+    assert!(error.to_string().starts_with("number out of range"));
+    assert_eq!(error.offset(), 1);
+    let mut bytes = b"\xc3\x50\x80".to_vec();
+    bytes.extend_from_slice(&[0; 15]);
+    assert!(de::from_slice::<i128>(&bytes).is_err());
+    let mut bytes = b"\xc2\x50".to_vec();
+    bytes.extend_from_slice(&[0xff; 16]);
+    assert!(de::from_slice::<i128>(&bytes).is_err());
+    assert!(de::from_slice::<u128>(b"\xc3\x41\x01").is_err());
+    assert!(de::from_slice::<u128>(b"\xc4\x41\x01").is_err());
+    assert!(de::from_slice::<u128>(b"\x61\x01").is_err());
 }
 
 #[test]
@@ -65,40 +118,40 @@ fn test_trailing_bytes() {
 #[test]
 fn test_list1() {
     let value: error::Result<Value> = de::from_slice(b"\x83\x01\x02\x03");
-    assert_eq!(value.unwrap(), Value::Array(vec![Value::U64(1), Value::U64(2), Value::U64(3)]));
+    assert_eq!(value.unwrap(), Value::Array(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)]));
 }
 
 #[test]
 fn test_list2() {
     let value: error::Result<Value> = de::from_slice(b"\x82\x01\x82\x02\x81\x03");
-    assert_eq!(value.unwrap(), Value::Array(vec![Value::U64(1), Value::Array(vec![Value::U64(2), Value::Array(vec![Value::U64(3)])])]));
+    assert_eq!(value.unwrap(), Value::Array(vec![Value::Integer(1), Value::Array(vec![Value::Integer(2), Value::Array(vec![Value::Integer(3)])])]));
 }
 
 #[test]
 fn test_object() {
     let value: error::Result<Value> = de::from_slice(b"\xa5aaaAabaBacaCadaDaeaE");
-    let mut object = BTreeMap::new();
-    object.insert(ObjectKey::String("a".to_owned()), Value::String("A".to_owned()));
-    object.insert(ObjectKey::String("b".to_owned()), Value::String("B".to_owned()));
-    object.insert(ObjectKey::String("c".to_owned()), Value::String("C".to_owned()));
-    object.insert(ObjectKey::String("d".to_owned()), Value::String("D".to_owned()));
-    object.insert(ObjectKey::String("e".to_owned()), Value::String("E".to_owned()));
+    let mut object = Map::new();
+    object.insert(Value::String("a".to_owned()), Value::String("A".to_owned()));
+    object.insert(Value::String("b".to_owned()), Value::String("B".to_owned()));
+    object.insert(Value::String("c".to_owned()), Value::String("C".to_owned()));
+    object.insert(Value::String("d".to_owned()), Value::String("D".to_owned()));
+    object.insert(Value::String("e".to_owned()), Value::String("E".to_owned()));
     assert_eq!(value.unwrap(), Value::Object(object));
 }
 
 #[test]
 fn test_indefinite_object() {
     let value: error::Result<Value> = de::from_slice(b"\xbfaa\x01ab\x9f\x02\x03\xff\xff");
-    let mut object = BTreeMap::new();
-    object.insert(ObjectKey::String("a".to_owned()), Value::U64(1));
-    object.insert(ObjectKey::String("b".to_owned()), Value::Array(vec![Value::U64(2), Value::U64(3)]));
+    let mut object = Map::new();
+    object.insert(Value::String("a".to_owned()), Value::Integer(1));
+    object.insert(Value::String("b".to_owned()), Value::Array(vec![Value::Integer(2), Value::Integer(3)]));
     assert_eq!(value.unwrap(), Value::Object(object));
 }
 
 #[test]
 fn test_indefinite_list() {
     let value: error::Result<Value> = de::from_slice(b"\x9f\x01\x02\x03\xff");
-    assert_eq!(value.unwrap(), Value::Array(vec![Value::U64(1), Value::U64(2), Value::U64(3)]));
+    assert_eq!(value.unwrap(), Value::Array(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)]));
 }
 
 #[test]
@@ -144,12 +197,12 @@ fn test_crazy_list() {
     let slice = b"\x88\x1b\x00\x00\x00\x1c\xbe\x99\x1d\xc7\x3b\x00\x7a\xcf\x51\xdc\x51\x70\xdb\x3a\x1b\x3a\x06\xdd\xf5\xf6\xf7\xfb\x41\x76\x5e\xb1\xf8\x00\x00\x00\xf9\x7c\x00";
     let value: Vec<Value> = de::from_slice(slice).unwrap();
     assert_eq!(value, vec![
-        Value::U64(123456789959),
-        Value::I64(-34567897654325468),
-        Value::I64(-456787678),
+        Value::Integer(123456789959),
+        Value::Integer(-34567897654325468),
+        Value::Integer(-456787678),
         Value::Bool(true),
         Value::Null,
-        Value::Null,
+        Value::Undefined,
         Value::F64(23456543.5),
         Value::F64(::std::f64::INFINITY)]);
 }
@@ -201,8 +254,8 @@ fn test_option_none_roundtrip() {
 fn test_variable_length_map() {
     let slice = b"\xbf\x67\x6d\x65\x73\x73\x61\x67\x65\x64\x70\x6f\x6e\x67\xff";
     let value: Value = de::from_slice(slice).unwrap();
-    let mut map = BTreeMap::new();
-    map.insert(ObjectKey::String("message".to_string()), Value::String("pong".to_string()));
+    let mut map = Map::new();
+    map.insert(Value::String("message".to_string()), Value::String("pong".to_string()));
     assert_eq!(value, Value::Object(map))
 }
 
@@ -220,7 +273,7 @@ fn test_object_determinism_roundtrip() {
 fn stream_deserializer() {
     let slice = b"\x01\x66foobar";
     let mut it = Deserializer::from_slice(slice).into_iter::<Value>();
-    assert_eq!(Value::U64(1), it.next().unwrap().unwrap());
+    assert_eq!(Value::Integer(1), it.next().unwrap().unwrap());
     assert_eq!(Value::String("foobar".to_string()), it.next().unwrap().unwrap());
     assert!(it.next().is_none());
 }
@@ -229,8 +282,56 @@ fn stream_deserializer() {
 fn stream_deserializer_eof() {
     let slice = b"\x01\x66foob";
     let mut it = Deserializer::from_slice(slice).into_iter::<Value>();
-    assert_eq!(Value::U64(1), it.next().unwrap().unwrap());
+    assert_eq!(Value::Integer(1), it.next().unwrap().unwrap());
     assert!(it.next().unwrap().unwrap_err().is_eof());
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn stream_deserializer_fused() {
+    // A record of the wrong type ends the sequence, as what follows it is unknown.
+    let slice = b"\x01\x61a\x02";
+    let mut it = Deserializer::from_reader(&slice[..]).into_iter::<u32>();
+    assert_eq!(it.next().unwrap().unwrap(), 1);
+    assert!(it.next().unwrap().unwrap_err().is_data());
+    assert!(it.next().is_none());
+    assert!(it.next().is_none());
+    assert_eq!(it.byte_offset(), 1);
+}
+
+#[test]
+fn array_iter() {
+    let slice = b"\x83\x01\x02\x03";
+    let it = Deserializer::from_reader(&slice[..]).into_array_iter::<u32>().unwrap();
+    assert_eq!(it.map(|x| x.unwrap()).collect::<Vec<_>>(), [1, 2, 3]);
+
+    // 0([_ "a", "b"])
+    let slice = b"\xc0\x9f\x61a\x61b\xff";
+    let it = Deserializer::from_slice(slice).into_array_iter::<&str>().unwrap();
+    assert_eq!(it.map(|x| x.unwrap()).collect::<Vec<_>>(), ["a", "b"]);
+
+    let error = Deserializer::from_slice(b"\xa0").into_array_iter::<u32>().err().unwrap();
+    assert!(error.is_syntax());
+}
+
+#[test]
+fn array_iter_errors() {
+    let mut it = Deserializer::from_slice(b"\x82\x01\x61a\x02").into_array_iter::<u32>().unwrap();
+    assert_eq!(it.next().unwrap().unwrap(), 1);
+    let error = it.next().unwrap().unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.path(), [PathSegment::Index(1)]);
+    assert!(it.next().is_none());
+
+    let mut it = Deserializer::from_slice(b"\x81\x01\x02").into_array_iter::<u32>().unwrap();
+    assert_eq!(it.next().unwrap().unwrap(), 1);
+    assert!(it.next().unwrap().unwrap_err().is_data());
+    assert!(it.next().is_none());
+
+    let mut it = Deserializer::from_slice(b"\x9f\x01").into_array_iter::<u32>().unwrap();
+    assert_eq!(it.next().unwrap().unwrap(), 1);
+    assert!(it.next().unwrap().unwrap_err().is_eof());
+    assert!(it.next().is_none());
 }
 
 #[test]
@@ -242,3 +343,638 @@ fn test_large_bytes() {
     let actual = from_reader(&v[..]).unwrap();
     assert_eq!(expected, actual);
 }
+
+#[test]
+fn test_byte_buf() {
+    let expected = ByteBuf::from(vec![0, 1, 2, 255]);
+    let v = to_vec(&expected).unwrap();
+    let actual: ByteBuf = de::from_slice(&v).unwrap();
+    assert_eq!(expected, actual);
+    let actual: ByteBuf = from_reader(&v[..]).unwrap();
+    assert_eq!(expected, actual);
+
+    // indefinite-length byte strings are still concatenated
+    let actual: ByteBuf = from_reader(&b"\x5f\x42\x00\x01\x42\x02\xff\xff"[..]).unwrap();
+    assert_eq!(expected, actual);
+
+    let err = from_reader::<ByteBuf, _>(&b"\x5a\xff\xff\xff\xff\x00"[..]).unwrap_err();
+    assert!(err.is_eof());
+}
+
+#[test]
+fn test_owned_string() {
+    let expected = "ü".repeat(100);
+    let v = to_vec(&expected).unwrap();
+    let actual: String = de::from_slice(&v).unwrap();
+    assert_eq!(expected, actual);
+    let actual: String = from_reader(&v[..]).unwrap();
+    assert_eq!(expected, actual);
+
+    let err = from_reader::<String, _>(&b"\x64ab\xffc"[..]).unwrap_err();
+    assert_eq!(err.offset(), 3);
+    let err = de::from_slice::<String>(b"\x64ab\xffc").unwrap_err();
+    assert_eq!(err.offset(), 3);
+}
+
+// Records the size hint a sequence or map is deserialized with.
+struct SizeHint(Option<usize>);
+
+impl<'de> Deserialize<'de> for SizeHint {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = SizeHint;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a sequence or map")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<SizeHint, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let hint = seq.size_hint();
+                while let Some(IgnoredAny) = seq.next_element()? {}
+                Ok(SizeHint(hint))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<SizeHint, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let hint = map.size_hint();
+                while let Some((IgnoredAny, IgnoredAny)) = map.next_entry()? {}
+                Ok(SizeHint(hint))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[test]
+fn test_preallocation_limit() {
+    let hint = |slice: &[u8], limit: Option<usize>| {
+        let mut de = Deserializer::from_slice(slice);
+        if let Some(limit) = limit {
+            de = de.preallocation_limit(limit);
+        }
+        SizeHint::deserialize(&mut de).unwrap().0
+    };
+    assert_eq!(hint(b"\x83\x01\x02\x03", None), Some(3));
+    assert_eq!(hint(b"\x83\x01\x02\x03", Some(2)), Some(2));
+    assert_eq!(hint(b"\xa1\x01\x02", Some(0)), Some(0));
+    let mut array = b"\x99\x13\x88".to_vec();
+    array.resize(3 + 5000, 0);
+    assert_eq!(hint(&array, None), Some(4096));
+    let mut map = b"\xb9\x13\x88".to_vec();
+    map.resize(3 + 10000, 0);
+    assert_eq!(hint(&map, None), Some(4096));
+
+    // a lying header only costs the capped allocation
+    let err = de::from_slice::<Value>(b"\x9b\x00\x00\x00\x01\x00\x00\x00\x00").unwrap_err();
+    assert!(err.is_eof());
+}
+
+#[test]
+fn test_long_tag_chain() {
+    let mut v = vec![0xc0; 1000000];
+    v.push(0x01);
+    let value: u8 = de::from_slice(&v).unwrap();
+    assert_eq!(value, 1);
+    let error = from_reader::<Value, _>(&v[..]).unwrap_err();
+    assert_eq!(error.to_string(), "recursion limit exceeded at offset 128");
+    let value: Value = from_reader(&v[999997..]).unwrap();
+    let tagged = |value| Value::Tag(0, Box::new(value));
+    assert_eq!(value, tagged(tagged(tagged(Value::Integer(1)))));
+}
+
+// A field identifier that only accepts names borrowed from the input.
+struct BorrowedField(usize);
+
+impl<'de> Deserialize<'de> for BorrowedField {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = BorrowedField;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a borrowed field name")
+            }
+
+            fn visit_borrowed_str<E>(self, value: &'de str) -> Result<BorrowedField, E> {
+                Ok(BorrowedField(value.len()))
+            }
+        }
+
+        deserializer.deserialize_identifier(Visitor)
+    }
+}
+
+#[test]
+fn test_borrowed_identifiers() {
+    let fields: Vec<BorrowedField> = de::from_slice(b"\x82\x63foo\xd8\x20\x62ab").unwrap();
+    assert_eq!(fields.iter().map(|f| f.0).collect::<Vec<_>>(), [3, 2]);
+
+    // the reader has to copy, which this identifier refuses
+    assert!(from_reader::<Vec<BorrowedField>, _>(&b"\x81\x63foo"[..]).is_err());
+}
+
+#[derive(Deserialize)]
+struct BorrowedBytes<'a> {
+    #[serde(borrow)]
+    cow: Cow<'a, [u8]>,
+    slice: &'a [u8],
+}
+
+#[test]
+fn test_borrowed_bytes() {
+    // {"cow": 24(h'0102'), "slice": h'03'}
+    let v = b"\xa2\x63cow\xd8\x18\x42\x01\x02\x65slice\x41\x03";
+    let bytes: BorrowedBytes = de::from_slice(v).unwrap();
+    match bytes.cow {
+        Cow::Borrowed(cow) => assert_eq!(cow.as_ptr(), v[8..].as_ptr()),
+        Cow::Owned(_) => panic!("definite-length byte string was copied"),
+    }
+    assert_eq!(bytes.slice, [3]);
+
+    // indefinite-length byte strings have to be copied
+    let v = b"\xa2\x63cow\x5f\x41\x01\x41\x02\xff\x65slice\x40";
+    let bytes: BorrowedBytes = de::from_slice(v).unwrap();
+    assert_eq!(bytes.cow, Cow::Owned::<[u8]>(vec![1, 2]));
+    assert!(de::from_slice::<&[u8]>(b"\x5f\x41\x01\xff").is_err());
+
+    let mut deserializer = Deserializer::from_reader(&b"\x42\x01\x02"[..]);
+    let cow = serde_bytes::deserialize::<Cow<[u8]>, _>(&mut deserializer).unwrap();
+    assert_eq!(cow, Cow::Owned::<[u8]>(vec![1, 2]));
+}
+
+// Counts the calls to `read`.
+struct CountingReader<'a> {
+    slice: &'a [u8],
+    reads: usize,
+}
+
+impl<'a> std::io::Read for CountingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reads += 1;
+        self.slice.read(buf)
+    }
+}
+
+#[test]
+fn test_io_read_buffering() {
+    let items: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+    let mut bytes = to_vec(&items).unwrap();
+    bytes.push(0x01);
+
+    let mut reader = CountingReader { slice: &bytes, reads: 0 };
+    {
+        let mut deserializer = Deserializer::from_reader(&mut reader);
+        assert_eq!(Vec::<String>::deserialize(&mut deserializer).unwrap(), items);
+    }
+    assert_eq!(reader.reads, 1);
+
+    // A buffer of one byte reads nothing beyond the data item.
+    let mut reader = CountingReader { slice: &bytes, reads: 0 };
+    {
+        let read = de::IoRead::with_capacity(&mut reader, 1);
+        let mut deserializer = Deserializer::new(read);
+        assert_eq!(Vec::<String>::deserialize(&mut deserializer).unwrap(), items);
+    }
+    assert_eq!(reader.slice, [0x01]);
+    let mut reader = std::io::Cursor::new(b"\x01\x02");
+    for expected in 1..3 {
+        let mut deserializer = Deserializer::new(de::IoRead::with_capacity(&mut reader, 1));
+        assert_eq!(u8::deserialize(&mut deserializer).unwrap(), expected);
+    }
+
+    // Strings larger than the buffer are read around it.
+    let text = "x".repeat(100);
+    let bytes = to_vec(&(1, &text)).unwrap();
+    let read = de::IoRead::with_capacity(&bytes[..], 16);
+    let mut deserializer = Deserializer::new(read);
+    assert_eq!(<(u8, String)>::deserialize(&mut deserializer).unwrap(), (1, text));
+    deserializer.end().unwrap();
+    let read = de::IoRead::with_capacity(&bytes[..bytes.len() - 1], 16);
+    let error = <(u8, String)>::deserialize(&mut Deserializer::new(read)).unwrap_err();
+    assert!(error.is_eof());
+    assert_eq!(error.offset(), bytes.len() as u64 - 1);
+}
+
+#[test]
+fn test_ignored_any() {
+    let ignore = |slice: &[u8]| {
+        de::from_slice::<IgnoredAny>(slice)?;
+        from_reader::<IgnoredAny, _>(slice)
+    };
+    ignore(b"\x1b\x00\x00\x00\x00\x00\x00\x00\x01").unwrap();
+    ignore(b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff").unwrap();
+    ignore(b"\x63a\xffc").unwrap();
+    ignore(b"\x5f\x41\x00\x59\x00\x01\x00\xff").unwrap();
+    ignore(b"\x7f\x61a\xff").unwrap();
+    ignore(b"\x9f\x01\x82\x02\xd8\x20\x03\xbf\x61a\xf9\x00\x00\xff\xff").unwrap();
+    ignore(b"\xa2\x01\xfa\x00\x00\x00\x00\xf5\xfb\x00\x00\x00\x00\x00\x00\x00\x00").unwrap();
+    ignore(b"\x83\xe0\xf3\xf8\xff").unwrap();
+
+    // malformed input is still rejected
+    assert!(ignore(b"\x1c").unwrap_err().is_syntax());
+    assert!(ignore(b"\x5f\x61a\xff").unwrap_err().is_syntax());
+    assert!(ignore(b"\x9f\x01").unwrap_err().is_eof());
+    assert!(ignore(b"\xbf\x01\xff").unwrap_err().is_syntax());
+    assert!(ignore(b"\x82\x01").unwrap_err().is_eof());
+    assert!(ignore(b"\x5a\xff\xff\xff\xff\x00").unwrap_err().is_eof());
+    assert!(ignore(b"\xbf\x01\x9f").unwrap_err().is_eof());
+    assert!(ignore(b"\xbb\xff\xff\xff\xff\xff\xff\xff\xff").is_err());
+    assert!(ignore(b"\xf8\x1f").unwrap_err().is_syntax());
+
+    // nesting is limited without recursion
+    let mut nested = vec![0x81; 127];
+    nested.push(0xa1);
+    nested.extend_from_slice(b"\x9f\xff\x80");
+    assert_eq!(ignore(&nested).unwrap_err().to_string(), "recursion limit exceeded at offset 128");
+    nested.drain(..2);
+    ignore(&nested).unwrap();
+    let mut nested = vec![0x9f; 100];
+    nested.extend(vec![0xff; 100]);
+    ignore(&nested).unwrap();
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Known {
+    known: u8,
+}
+
+#[test]
+fn test_skip_unknown_field() {
+    let mut v = b"\xa2\x67unknown\x5a\x00\x10\x00\x00".to_vec();
+    v.resize(v.len() + 0x100000, 0xff);
+    v.extend_from_slice(b"\x65known\x07");
+    assert_eq!(from_reader::<Known, _>(&v[..]).unwrap(), Known { known: 7 });
+    assert_eq!(de::from_slice::<Known>(&v).unwrap(), Known { known: 7 });
+}
+
+// Hands out the input one byte per call, like a slow socket.
+struct Trickle<'a>(&'a [u8]);
+
+impl<'a> std::io::Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.0.is_empty() || buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = self.0[0];
+        self.0 = &self.0[1..];
+        Ok(1)
+    }
+}
+
+#[test]
+fn test_short_reads() {
+    let values = vec![
+        Value::Integer(500),
+        Value::Integer(70000),
+        Value::Integer(1 << 40),
+        Value::String("x".repeat(300)),
+        Value::Array(vec![Value::Integer(-1000); 30]),
+    ];
+    let v = to_vec(&values).unwrap();
+    let actual: Vec<Value> = from_reader(Trickle(&v)).unwrap();
+    assert_eq!(values, actual);
+
+    // an indefinite-length string, where the deserializer peeks for the break code
+    let actual: String = from_reader(Trickle(b"\x7f\x78\x03abc\x62de\xff")).unwrap();
+    assert_eq!(actual, "abcde");
+}
+
+#[test]
+fn test_no_warnings_by_default() {
+    let mut de = Deserializer::from_slice(b"\x18\x01");
+    assert_eq!(Value::deserialize(&mut de).unwrap(), Value::Integer(1));
+    assert!(de.warnings().is_empty());
+}
+
+#[test]
+fn test_warn_non_minimal() {
+    let slice = b"\x82\x19\x00\x01\x9a\x00\x00\x00\x01\x01";
+    let mut de = Deserializer::from_slice(slice).collect_warnings();
+    let value = Value::deserialize(&mut de).unwrap();
+    assert_eq!(value, Value::Array(vec![Value::Integer(1), Value::Array(vec![Value::Integer(1)])]));
+    let warnings = de.take_warnings();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].kind(), WarningKind::NonMinimalArgument);
+    assert_eq!(warnings[0].offset(), 1);
+    assert_eq!(warnings[1].kind(), WarningKind::NonMinimalArgument);
+    assert_eq!(warnings[1].offset(), 4);
+    assert!(de.warnings().is_empty());
+}
+
+#[test]
+fn test_warn_indefinite() {
+    let slice = b"\x9f\x7f\x61a\xff\xff";
+    let mut de = Deserializer::from_slice(slice).collect_warnings();
+    Value::deserialize(&mut de).unwrap();
+    let kinds: Vec<_> = de.warnings().iter().map(|w| (w.kind(), w.offset())).collect();
+    assert_eq!(kinds, vec![(WarningKind::IndefiniteLength, 0), (WarningKind::IndefiniteLength, 1)]);
+}
+
+#[test]
+fn test_warn_map_keys() {
+    // {"b": 1, "a": 2, "a": 3}
+    let slice = b"\xa3\x61b\x01\x61a\x02\x61a\x03";
+    let mut de = Deserializer::from_slice(slice).collect_warnings();
+    Value::deserialize(&mut de).unwrap();
+    let kinds: Vec<_> = de.warnings().iter().map(|w| (w.kind(), w.offset())).collect();
+    assert_eq!(kinds, vec![(WarningKind::UnsortedMapKey, 4), (WarningKind::DuplicateMapKey, 7)]);
+    assert_eq!(de.warnings()[1].key(), Some("\"a\""));
+    assert_eq!(de.warnings()[1].to_string(), "duplicate map key \"a\" at offset 7");
+
+    let mut de = Deserializer::from_reader(&slice[..]).collect_warnings();
+    Value::deserialize(&mut de).unwrap();
+    let kinds: Vec<_> = de.warnings().iter().map(|w| (w.kind(), w.offset())).collect();
+    assert_eq!(kinds, vec![(WarningKind::UnsortedMapKey, 4), (WarningKind::DuplicateMapKey, 7)]);
+}
+
+#[test]
+fn stream_deserializer_resume() {
+    let slice = b"\x01\x82\x02\x1c\x66foobar";
+    let mut it = Deserializer::from_reader(&slice[..]).into_iter::<Value>();
+    assert_eq!(Value::Integer(1), it.next().unwrap().unwrap());
+    assert_eq!(it.byte_offset(), 1);
+    assert!(it.next().unwrap().is_err());
+    assert_eq!(it.byte_offset(), 1);
+
+    let rest = &slice[it.byte_offset() as usize + 3..];
+    let mut it = Deserializer::from_slice(rest).into_iter::<Value>();
+    assert_eq!(Value::String("foobar".to_string()), it.next().unwrap().unwrap());
+    assert_eq!(it.byte_offset(), 7);
+}
+
+#[test]
+fn test_warn_duplicate_complex_key() {
+    // {[1, h'ff', -2]: 1, [1, h'ff', -2]: 2}
+    let slice = b"\xa2\x83\x01\x41\xff\x21\x01\x83\x01\x41\xff\x21\x02";
+    let mut de = Deserializer::from_reader(&slice[..]).collect_warnings();
+    let value = Value::deserialize(&mut de).unwrap();
+    assert_eq!(value.as_object().unwrap().len(), 1);
+    assert_eq!(de.warnings()[0].key(), Some("[1, h'ff', -2]"));
+
+    let mut de = Deserializer::from_slice(&slice[..]).collect_warnings();
+    assert!(<BTreeMap<(u8, ByteBuf, i8), u8>>::deserialize(&mut de).is_ok());
+    assert_eq!(de.warnings()[0].key(), Some("[1, h'ff', -2]"));
+}
+
+#[test]
+fn test_reject_duplicate_keys() {
+    #[derive(Debug, Deserialize)]
+    struct Inner {
+        #[allow(dead_code)]
+        a: u8,
+    }
+
+    let parse = |slice: &[u8]| {
+        let mut de = Deserializer::from_reader(slice).reject_duplicate_keys();
+        BTreeMap::<u8, Value>::deserialize(&mut de)
+    };
+    // {1: {1: 1}, 2: {1: 2}}: the same key in different maps is fine.
+    assert!(parse(b"\xa2\x01\xa1\x01\x01\x02\xa1\x01\x02").is_ok());
+    // {_ 1: 1, 2: 2, 1: 3}: duplicates need not be adjacent.
+    let error = parse(b"\xbf\x01\x01\x02\x02\x01\x03\xff").unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.offset(), 5);
+    assert_eq!(error.to_string(), "duplicate map key 1 at offset 5");
+
+    // {"a": 1, "a": 2}: derived structs reject duplicate fields themselves, but without an
+    // offset.
+    let slice = b"\xa2\x61a\x01\x61a\x02";
+    assert_eq!(de::from_slice::<Inner>(slice).unwrap_err().offset(), 0);
+    let mut de = Deserializer::from_slice(slice).reject_duplicate_keys();
+    assert_eq!(Inner::deserialize(&mut de).unwrap_err().offset(), 4);
+
+    // {[1, h'ff', -2]: 1, [1, h'ff', -2]: 2}
+    let slice = b"\xa2\x83\x01\x41\xff\x21\x01\x83\x01\x41\xff\x21\x02";
+    let mut de = Deserializer::from_slice(slice).reject_duplicate_keys();
+    let error = <BTreeMap<(u8, ByteBuf, i8), u8>>::deserialize(&mut de).unwrap_err();
+    assert_eq!(error.to_string(), "duplicate map key [1, h'ff', -2] at offset 7");
+}
+
+#[test]
+fn test_reject_non_minimal_arguments() {
+    let parse = |slice: &[u8]| {
+        let mut de = Deserializer::from_slice(slice).reject_non_minimal_arguments();
+        Value::deserialize(&mut de)
+    };
+    // The shortest form of each width, and the largest value of each width, is accepted.
+    let minimal: &[&[u8]] = &[
+        b"\x17",
+        b"\x18\x18",
+        b"\x39\x01\x00",
+        b"\x1a\x00\x01\x00\x00",
+        b"\x3b\x00\x00\x00\x01\x00\x00\x00\x00",
+        b"\x1b\xff\xff\xff\xff\xff\xff\xff\xff",
+        b"\x82\x18\xff\x19\xff\xff",
+    ];
+    for slice in minimal {
+        assert!(parse(slice).is_ok(), "{:?}", slice);
+    }
+
+    let cases: &[(&[u8], u64, &str)] = &[
+        (b"\x18\x17", 0, "wasting 1 byte"),
+        (b"\x82\x01\x1b\x00\x00\x00\x00\x00\x00\x00\x01", 2, "wasting 8 bytes"),
+        (b"\x39\x00\xff", 0, "wasting 1 byte"),
+        (b"\x3a\x00\x00\xff\xff", 0, "wasting 2 bytes"),
+        (b"\x78\x01a", 0, "wasting 1 byte"),
+        (b"\x59\x00\x00", 0, "wasting 2 bytes"),
+        (b"\x98\x01\x01", 0, "wasting 1 byte"),
+        (b"\xa1\x01\xb8\x00", 2, "wasting 1 byte"),
+        (b"\xd8\x01\x00", 0, "wasting 1 byte"),
+        (b"\x5f\x41a\x58\x01b\xff", 3, "wasting 1 byte"),
+    ];
+    for &(slice, offset, wasted) in cases {
+        assert!(de::from_slice::<Value>(slice).is_ok());
+        let error = parse(slice).unwrap_err();
+        assert!(error.is_data());
+        assert_eq!(error.offset(), offset, "{:?}", slice);
+        assert!(error.to_string().contains(wasted), "{}", error);
+
+        // Items that are skipped are checked as well.
+        let mut de = Deserializer::from_slice(slice).reject_non_minimal_arguments();
+        assert_eq!(IgnoredAny::deserialize(&mut de).unwrap_err().offset(), offset);
+    }
+}
+
+#[test]
+fn test_reject_indefinite_lengths() {
+    #[derive(Debug, Deserialize)]
+    enum Enum {
+        A(u8, u8),
+    }
+
+    let parse = |slice: &[u8]| {
+        let mut de = Deserializer::from_slice(slice).reject_indefinite_lengths();
+        Value::deserialize(&mut de)
+    };
+    // Definite lengths, including empty ones, are accepted.
+    assert!(parse(b"\x83\x40\x60\xa0").is_ok());
+
+    let cases: &[(&[u8], u64)] = &[
+        (b"\x5f\x41a\xff", 0),
+        (b"\x7f\x61a\xff", 0),
+        (b"\x82\x01\x9f\xff", 2),
+        (b"\xa1\x61a\xbf\x01\x02\xff", 3),
+        (b"\xd8\x18\x9f\xff", 2),
+    ];
+    for &(slice, offset) in cases {
+        assert!(de::from_slice::<Value>(slice).is_ok());
+        let error = parse(slice).unwrap_err();
+        assert!(error.is_data());
+        assert_eq!(error.offset(), offset, "{:?}", slice);
+        assert!(error.to_string().starts_with("indefinite-length item"));
+
+        let mut de = Deserializer::from_slice(slice).reject_indefinite_lengths();
+        assert_eq!(IgnoredAny::deserialize(&mut de).unwrap_err().offset(), offset);
+    }
+
+    // [_ "A", 1, 2]
+    let slice = b"\x9f\x61A\x01\x02\xff";
+    assert!(de::from_slice::<Enum>(slice).is_ok());
+    let mut de = Deserializer::from_slice(slice).reject_indefinite_lengths();
+    assert!(Enum::deserialize(&mut de).unwrap_err().is_data());
+
+    let de = Deserializer::from_slice(b"\x9f\x01\xff").reject_indefinite_lengths();
+    assert!(de.into_array_iter::<u8>().err().unwrap().is_data());
+}
+
+#[test]
+fn test_profiles() {
+    use serde_cbor::de::Profile;
+
+    let parse = |slice: &[u8], profile| {
+        let mut de = Deserializer::from_slice(slice).profile(profile);
+        Value::deserialize(&mut de).and_then(|_| de.end())
+    };
+    let profiles = [Profile::Lenient, Profile::Rfc8949Strict, Profile::Ctap2];
+
+    // [1, -500, "a", 1.5, 100000.0, 1.1, {1: 2, "b": 3, h'00': 4}] is accepted by all.
+    let good = b"\x87\x01\x39\x01\xf3\x61a\xf9\x3e\x00\xfa\x47\xc3\x50\x00\
+        \xfb\x3f\xf1\x99\x99\x99\x99\x99\x9a\xa3\x01\x02\x41\x00\x04\x61b\x03";
+    for profile in &profiles {
+        assert!(parse(good, *profile).is_ok(), "{:?}", profile);
+    }
+
+    // Each is accepted only by `Lenient`, failing at the given offset with the given message.
+    let cases: &[(&[u8], u64, &str)] = &[
+        (b"\x82\x18\x01\x01", 1, "argument not in its shortest form"),
+        (b"\x99\x00\x01\x01", 0, "argument not in its shortest form"),
+        (b"\x82\x01\x7f\x61a\xff", 2, "indefinite-length item"),
+        (b"\xa2\x01\x01\x01\x02", 3, "duplicate map key 1"),
+    ];
+    for &(slice, offset, message) in cases {
+        assert!(parse(slice, Profile::Lenient).is_ok());
+        for profile in &profiles[1..] {
+            let error = parse(slice, *profile).unwrap_err();
+            assert_eq!(error.offset(), offset, "{:?} {:?}", slice, profile);
+            assert!(error.to_string().starts_with(message), "{}", error);
+        }
+    }
+
+    // CTAP2 leaves floats as they are.
+    let floats: &[(&[u8], u64)] = &[
+        (b"\x82\x01\xfa\x3f\xc0\x00\x00", 2),
+        (b"\xfb\x7f\xf8\x00\x00\x00\x00\x00\x00", 0),
+    ];
+    for &(slice, offset) in floats {
+        assert!(parse(slice, Profile::Ctap2).is_ok());
+        let error = parse(slice, Profile::Rfc8949Strict).unwrap_err();
+        assert_eq!(error.offset(), offset);
+        assert!(error.to_string().starts_with("float not in its shortest form"));
+    }
+
+    // CTAP2 rules out tags, and nesting more than four levels deep.
+    let slice = b"\x82\x01\xc1\x01";
+    assert!(parse(slice, Profile::Rfc8949Strict).is_ok());
+    let error = parse(slice, Profile::Ctap2).unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.to_string(), "tag not allowed at offset 2 in [1]");
+    let mut de = Deserializer::from_slice(b"\x82\x01\xc1\x01").profile(Profile::Ctap2);
+    assert_eq!(IgnoredAny::deserialize(&mut de).unwrap_err().offset(), 2);
+    assert!(parse(b"\x81\x81\xa1\x01\x81\x01", Profile::Ctap2).is_ok());
+    assert!(parse(b"\x81\x81\xa1\x01\x81\x80", Profile::Ctap2).is_err());
+    let mut de = Deserializer::from_slice(b"\x81\x81\x81\x81\x80").profile(Profile::Ctap2);
+    assert!(IgnoredAny::deserialize(&mut de).is_err());
+
+    // {"b": 1, 10: 2} is sorted bytewise, but CTAP2 puts integer keys before text.
+    let slice = b"\xa2\x61b\x01\x0a\x02";
+    assert!(parse(slice, Profile::Rfc8949Strict).is_ok());
+    let error = parse(slice, Profile::Ctap2).unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.to_string(), "map key 10 out of order at offset 4");
+
+    // A later profile replaces an earlier one.
+    let mut de = Deserializer::from_slice(b"\x18\x01")
+        .profile(Profile::Ctap2)
+        .profile(Profile::Lenient);
+    assert_eq!(u8::deserialize(&mut de).unwrap(), 1);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_from_sequence() {
+    let mut seq = Vec::new();
+    let mut array = vec![0x9f];
+    for i in 0..1000u32 {
+        let item = to_vec(&(i, format!("item {}", i))).unwrap();
+        seq.extend_from_slice(&item);
+        array.extend_from_slice(&item);
+    }
+    array.push(0xff);
+    let expected: Vec<(u32, String)> = (0..1000).map(|i| (i, format!("item {}", i))).collect();
+    assert_eq!(de::par_from_sequence::<(u32, String)>(&seq).unwrap(), expected);
+    assert_eq!(de::par_from_array::<(u32, String)>(&array).unwrap(), expected);
+    let definite = to_vec(&expected).unwrap();
+    assert_eq!(de::par_from_array::<(u32, String)>(&definite).unwrap(), expected);
+
+    let err = de::par_from_sequence::<u8>(b"\x01\x02\x63ab\xff").unwrap_err();
+    assert_eq!(err.offset(), 5);
+    assert_eq!(err.to_string(), "invalid UTF-8 at offset 5 in [2]");
+    assert!(de::par_from_sequence::<u8>(b"\x01\x82\x01").unwrap_err().is_eof());
+    assert!(de::par_from_array::<u8>(b"\x82\x01\x02\x03").is_err());
+}
+
+#[cfg(feature = "unchecked-utf8")]
+#[test]
+fn test_trust_utf8() {
+    let v = to_vec(&("foo", "bär".to_owned())).unwrap();
+    let mut de = unsafe { Deserializer::from_slice(&v).trust_utf8() };
+    let value = <(&str, String)>::deserialize(&mut de).unwrap();
+    assert_eq!(value, ("foo", "bär".to_owned()));
+    let mut de = unsafe { Deserializer::from_reader(&v[..]).trust_utf8() };
+    let value = <(String, String)>::deserialize(&mut de).unwrap();
+    assert_eq!(value, ("foo".to_owned(), "bär".to_owned()));
+
+    // Embedded data is still checked.
+    let mut de = unsafe { Deserializer::from_slice(b"").trust_utf8() };
+    assert!(de.embedded::<String>(b"\x61\xff").unwrap_err().is_data());
+}
+
+#[test]
+fn test_embedded() {
+    let inner = to_vec(&vec!["a", "b"]).unwrap();
+    let middle = to_vec(&ByteBuf::from(inner)).unwrap();
+    let mut outer = vec![0xd8, 0x18];
+    outer.extend_from_slice(&to_vec(&ByteBuf::from(middle)).unwrap());
+
+    let mut de = Deserializer::from_reader(&outer[..]);
+    let middle = ByteBuf::deserialize(&mut de).unwrap();
+    let inner: ByteBuf = de.embedded(&middle).unwrap();
+    let value: Vec<String> = de.embedded(&inner).unwrap();
+    assert_eq!(value, ["a", "b"]);
+    de.end().unwrap();
+
+    // trailing data and errors are reported for the embedded data
+    assert!(de.embedded::<u8>(b"\x01\x02").unwrap_err().is_data());
+    assert_eq!(de.embedded::<String>(b"\x62a\xff").unwrap_err().offset(), 2);
+    let deep = vec![0x81; 200];
+    assert!(de.embedded::<Value>(&deep).unwrap_err().is_data());
+}
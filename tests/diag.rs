@@ -0,0 +1,201 @@
+#![cfg(feature = "std")]
+
+extern crate serde_bytes;
+extern crate serde_cbor;
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use serde_bytes::ByteBuf;
+use serde_cbor::diag::{
+    diag_to_bytes, display, from_diag_str, parse, to_diag_string, to_diag_string_pretty,
+    to_diag_writer,
+};
+use serde_cbor::Value;
+
+#[test]
+fn test_scalars() {
+    assert_eq!(to_diag_string(&-1000).unwrap(), "-1000");
+    assert_eq!(to_diag_string(&1.5f64).unwrap(), "1.5");
+    assert_eq!(to_diag_string(&::std::f64::NAN).unwrap(), "NaN");
+    assert_eq!(to_diag_string(&"a\"b\n").unwrap(), r#""a\"b\u000a""#);
+    assert_eq!(to_diag_string(&ByteBuf::from(vec![0xca, 0xfe])).unwrap(), "h'cafe'");
+    assert_eq!(to_diag_string(&Some(true)).unwrap(), "true");
+    assert_eq!(to_diag_string(&None::<u8>).unwrap(), "null");
+}
+
+#[test]
+fn test_compact() {
+    let mut map = BTreeMap::new();
+    map.insert("a", vec![Value::Integer(1), Value::Array(vec![])]);
+    map.insert("b", vec![]);
+    assert_eq!(to_diag_string(&map).unwrap(), r#"{"a": [1, []], "b": []}"#);
+}
+
+#[test]
+fn test_pretty() {
+    let mut map = BTreeMap::new();
+    map.insert("a", vec![Value::Integer(1), Value::Array(vec![])]);
+    map.insert("b", vec![]);
+    let expected = r#"{
+  "a": [
+    1,
+    []
+  ],
+  "b": []
+}"#;
+    assert_eq!(to_diag_string_pretty(&map).unwrap(), expected);
+}
+
+#[test]
+fn test_writer() {
+    let mut out = Vec::new();
+    to_diag_writer(&mut out, &(1, "x")).unwrap();
+    assert_eq!(out, br#"[1, "x"]"#);
+}
+
+// Fails once more than its capacity is written.
+struct Limited(String, usize);
+
+impl Write for Limited {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.0.len() + s.len() > self.1 {
+            return Err(fmt::Error);
+        }
+        self.0.push_str(s);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_display() {
+    let bytes = diag_to_bytes(r#"24(<<1, [2]>>)"#).unwrap();
+    assert_eq!(display(&bytes).to_string(), "24(<<1, [2]>>)");
+    let bytes = diag_to_bytes(r#"24(h'8102ff')"#).unwrap();
+    assert_eq!(display(&bytes).to_string(), "24(h'8102ff')");
+    assert_eq!(format!("{:#}", display(b"\xa1\x01\x80")), "{\n  1: []\n}");
+
+    // Malformed input is cut short.
+    assert_eq!(display(b"\x83\x01\x02").to_string(), "[1, 2, ");
+
+    let mut out = Limited(String::new(), 4);
+    assert!(write!(out, "{}", display(b"\x83\x01\x02\x03")).is_err());
+    assert_eq!(out.0, "[1, ");
+}
+
+#[test]
+fn test_display_nested_embedded() {
+    // Each level used to be rendered twice, which took forever at this depth.
+    let mut bytes = vec![0x01];
+    for _ in 0..64 {
+        let mut outer = vec![0xd8, 0x18, 0x59, (bytes.len() >> 8) as u8, bytes.len() as u8];
+        outer.extend_from_slice(&bytes);
+        bytes = outer;
+    }
+    let rendered = display(&bytes).to_string();
+    assert!(rendered.starts_with("24(<<24(<<"));
+    assert!(rendered.contains("<<1>>"));
+}
+
+#[test]
+fn test_display_depth_limit() {
+    let rendered = display(&[0x81; 200_000]).to_string();
+    assert_eq!(rendered, "[".repeat(128));
+}
+
+#[test]
+fn test_parse_scalars() {
+    assert_eq!(diag_to_bytes("0").unwrap(), b"\x00");
+    assert_eq!(diag_to_bytes("-1000").unwrap(), b"\x39\x03\xe7");
+    assert_eq!(diag_to_bytes("0x1f").unwrap(), b"\x18\x1f");
+    assert_eq!(
+        diag_to_bytes("18446744073709551616").unwrap(),
+        b"\xc2\x49\x01\0\0\0\0\0\0\0\0"
+    );
+    assert_eq!(diag_to_bytes("-18446744073709551616").unwrap(), b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff");
+    assert_eq!(diag_to_bytes("1.5").unwrap(), b"\xf9\x3e\x00");
+    assert_eq!(diag_to_bytes("-Infinity").unwrap(), b"\xf9\xfc\x00");
+    assert_eq!(diag_to_bytes("NaN").unwrap(), b"\xf9\x7e\x00");
+    assert_eq!(diag_to_bytes("simple(16)").unwrap(), b"\xf0");
+    assert_eq!(diag_to_bytes("simple(255)").unwrap(), b"\xf8\xff");
+    assert_eq!(diag_to_bytes("undefined").unwrap(), b"\xf7");
+}
+
+#[test]
+fn test_parse_strings() {
+    assert_eq!(
+        diag_to_bytes(r#""a\"\u00fc\ud834\udd1e""#).unwrap(),
+        "\x68a\"\u{fc}\u{1d11e}".as_bytes()
+    );
+    assert_eq!(diag_to_bytes("'hi'").unwrap(), b"\x42hi");
+    assert_eq!(diag_to_bytes("h'ca fe'").unwrap(), b"\x42\xca\xfe");
+    assert_eq!(diag_to_bytes("b64'yv7-'").unwrap(), b"\x43\xca\xfe\xfe");
+    assert_eq!(diag_to_bytes("b64'yv4='").unwrap(), b"\x42\xca\xfe");
+    assert_eq!(diag_to_bytes("b64'/w=='").unwrap(), b"\x41\xff");
+    assert_eq!(diag_to_bytes("b64'ab/c/d'").unwrap(), b"\x44\x69\xbf\xdc\xfd");
+    assert_eq!(diag_to_bytes("b64'yv7- /w'").unwrap(), b"\x44\xca\xfe\xfe\xff");
+    assert_eq!(diag_to_bytes("(_ h'01', h'02')").unwrap(), b"\x5f\x41\x01\x41\x02\xff");
+    assert_eq!(diag_to_bytes("<<1, [2]>>").unwrap(), b"\x43\x01\x81\x02");
+    assert_eq!(diag_to_bytes("b32'ZL7A===='").unwrap(), b"\x42\xca\xfe");
+    assert_eq!(diag_to_bytes("h32'PBV0'").unwrap(), b"\x42\xca\xfe");
+    assert_eq!(diag_to_bytes(r#""a" / and / 'b' h'63'"#).unwrap(), b"\x63abc");
+    assert_eq!(diag_to_bytes("h'01' <<2>>").unwrap(), b"\x42\x01\x02");
+}
+
+#[test]
+fn test_parse_structures() {
+    let diag = r#"
+        / a comment /
+        {_ "a": [1, 2], # to the end of the line
+           "b": 1(1363896240)}
+    "#;
+    assert_eq!(
+        diag_to_bytes(diag).unwrap(),
+        &b"\xbf\x61a\x82\x01\x02\x61b\xc1\x1a\x51\x4b\x67\xb0\xff"[..]
+    );
+    let value: BTreeMap<String, Vec<u8>> = from_diag_str(r#"{"a": [1, 2], "b": []}"#).unwrap();
+    assert_eq!(value["a"], vec![1, 2]);
+    assert!(value["b"].is_empty());
+}
+
+#[test]
+fn test_parse_round_trip() {
+    let mut map = BTreeMap::new();
+    map.insert("a", vec![Value::Integer(1), Value::F64(-2.5), Value::Null]);
+    map.insert("b\n", vec![Value::Bytes(vec![0, 255]), Value::String("\u{1}".to_string())]);
+    for diag in &[to_diag_string(&map).unwrap(), to_diag_string_pretty(&map).unwrap()] {
+        assert_eq!(diag_to_bytes(diag).unwrap(), serde_cbor::to_vec(&map).unwrap());
+    }
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = diag_to_bytes("[1, 2").unwrap_err();
+    assert!(error.is_syntax());
+    assert_eq!(error.offset(), 5);
+    assert_eq!(
+        diag_to_bytes("[1 2]").unwrap_err().to_string(),
+        "invalid diagnostic notation: expected `,` or `]` at offset 3"
+    );
+    assert_eq!(diag_to_bytes("1 2").unwrap_err().offset(), 2);
+    assert_eq!(diag_to_bytes("h'abc'").unwrap_err().offset(), 5);
+    assert_eq!(diag_to_bytes("nothing").unwrap_err().offset(), 0);
+    assert_eq!(diag_to_bytes("simple(24)").unwrap_err().offset(), 7);
+    assert_eq!(diag_to_bytes("(_ 'a', \"b\")").unwrap_err().offset(), 8);
+    assert_eq!(diag_to_bytes(&"[".repeat(1000)).unwrap_err().offset(), 128);
+    assert!(from_diag_str::<String>("1").unwrap_err().is_data());
+    assert_eq!(diag_to_bytes("b32'ZL7'").unwrap_err().offset(), 7);
+    assert_eq!(diag_to_bytes(r#"["a" h'ff']"#).unwrap_err().offset(), 1);
+}
+
+#[test]
+fn test_parse_value() {
+    let diag = r#"{1: <<-1>>, "a": [undefined, simple(99)], 2: 1(-18446744073709551617)}"#;
+    let value = parse(diag).unwrap();
+    assert_eq!(value[&Value::Integer(1)], Value::Bytes(vec![0x20]));
+    assert_eq!(value["a"], Value::Array(vec![Value::Undefined, Value::Simple(99)]));
+    let bignum = Value::Tag(3, Box::new(Value::Bytes(vec![1, 0, 0, 0, 0, 0, 0, 0, 0])));
+    assert_eq!(value[&Value::Integer(2)], Value::Tag(1, Box::new(bignum)));
+    assert_eq!(Value::from_diagnostic(diag).unwrap(), value);
+    assert_eq!(parse(&to_diag_string(&value).unwrap()).unwrap(), value);
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "std")]
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+extern crate serde_cbor;
+#[cfg(feature = "miette")]
+extern crate miette;
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io;
+
+use serde_cbor::{from_reader, from_slice, Deserializer, ObjectKey, Value};
+use serde_cbor::error::Error;
+
+#[test]
+fn test_io_error_roundtrip() {
+    let error = from_slice::<Value>(b"\x82\x01\x1c").unwrap_err();
+    let offset = error.offset();
+    let message = error.to_string();
+
+    let io_error = io::Error::from(error);
+    assert_eq!(io_error.kind(), io::ErrorKind::InvalidData);
+
+    let error = Error::from(io_error);
+    assert!(error.is_syntax());
+    assert_eq!(error.offset(), offset);
+    assert_eq!(error.to_string(), message);
+}
+
+#[test]
+fn test_io_error_eof_kind() {
+    let error = from_slice::<Value>(b"\x82\x01").unwrap_err();
+    let io_error = io::Error::from(error);
+    assert_eq!(io_error.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(Error::from(io_error).is_eof());
+}
+
+#[test]
+fn test_io_error_unwrapped() {
+    let error = Error::from(io::Error::new(io::ErrorKind::BrokenPipe, "gone"));
+    assert!(error.is_io());
+    let io_error = io::Error::from(error);
+    assert_eq!(io_error.kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(io_error.to_string(), "gone");
+}
+
+#[test]
+fn test_duplicate_field_names_key() {
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        #[allow(dead_code)]
+        port: u16,
+    }
+
+    // {"port": 1, "port": 2}
+    let error = from_slice::<Config>(b"\xa2\x64port\x01\x64port\x02").unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.to_string(), "duplicate map key \"port\"");
+}
+
+#[test]
+fn test_well_formed_but_invalid() {
+    // text string containing invalid UTF-8
+    assert!(from_slice::<Value>(b"\x62\xc3\x28").unwrap_err().is_data());
+    // -2^64 does not fit an i64
+    assert!(from_slice::<i64>(b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff").unwrap_err().is_data());
+    assert!(from_slice::<Value>(b"\x01\x02").unwrap_err().is_data());
+}
+
+#[test]
+fn test_not_well_formed() {
+    // reserved additional information
+    assert!(from_slice::<Value>(b"\x1e").unwrap_err().is_syntax());
+    // break outside of an indefinite-length item
+    assert!(from_slice::<Value>(b"\x81\xff").unwrap_err().is_syntax());
+    // definite-length chunk of the wrong major type
+    assert!(from_slice::<Value>(b"\x5f\x61a\xff").unwrap_err().is_syntax());
+}
+
+#[test]
+fn test_error_path() {
+    use serde_cbor::error::PathSegment;
+
+    // [{"a": [1], 2: [_ 1, "x"]}]
+    let slice = b"\x81\xa2\x61a\x81\x01\x02\x9f\x01\x61x\xff";
+    let error = from_slice::<Vec<BTreeMap<ObjectKey, Vec<u8>>>>(slice).unwrap_err();
+    assert_eq!(
+        error.path(),
+        &[PathSegment::Index(0), PathSegment::Key("2".to_string()), PathSegment::Index(1)]
+    );
+
+    // Keys are not copied out of a reader just to name them.
+    let error = from_reader::<Vec<BTreeMap<ObjectKey, Vec<u8>>>, _>(&slice[..]).unwrap_err();
+    assert_eq!(error.path()[1], PathSegment::Entry(1));
+    assert!(error.to_string().ends_with(" in [0][entry 1][1]"));
+    let mut de = Deserializer::from_reader(&slice[..]).reject_duplicate_keys();
+    let error = Vec::<BTreeMap<ObjectKey, Vec<u8>>>::deserialize(&mut de).unwrap_err();
+    assert_eq!(error.path()[1], PathSegment::Key("2".to_string()));
+}
+
+#[test]
+fn test_error_path_empty_at_top_level() {
+    let error = from_slice::<Value>(b"\x1c").unwrap_err();
+    assert!(error.path().is_empty());
+    assert_eq!(error.to_string(), "unassigned type at offset 1");
+}
+
+#[test]
+fn test_needs_more_data() {
+    let slice = b"\xa1\x63key\x9f\x01\x02\xff";
+    for len in 0..slice.len() {
+        let error = from_slice::<Value>(&slice[..len]).unwrap_err();
+        assert!(error.needs_more_data(), "prefix of length {}", len);
+        let error = from_reader::<Value, _>(&slice[..len]).unwrap_err();
+        assert!(error.needs_more_data(), "prefix of length {}", len);
+    }
+    assert!(from_slice::<Value>(slice).is_ok());
+    assert!(!from_slice::<Value>(b"\xa1\x63key\xff").unwrap_err().needs_more_data());
+    assert!(Error::from(io::Error::new(io::ErrorKind::UnexpectedEof, "eof")).needs_more_data());
+}
+
+#[cfg(feature = "miette")]
+#[test]
+fn test_miette_diagnostic() {
+    use miette::Diagnostic;
+
+    let err = from_slice::<Value>(b"\x82\x01").unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "serde_cbor::eof");
+    assert!(err.help().is_some());
+    let labels: Vec<_> = err.labels().unwrap().collect();
+    assert_eq!(labels.len(), 1);
+    assert_eq!(labels[0].offset(), 2);
+}
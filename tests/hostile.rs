@@ -0,0 +1,110 @@
+#![cfg(feature = "std")]
+
+// Input whose length headers claim far more than follows them. Each must fail at the end of the
+// input rather than set aside memory for what the headers claim.
+
+extern crate serde;
+extern crate serde_bytes;
+extern crate serde_cbor;
+
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde_bytes::ByteBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+
+use serde_cbor::de::{from_reader, from_slice};
+use serde_cbor::{Deserializer, Value};
+
+// The largest argument of each width, for major type 4 (arrays).
+const ARRAY_HEADS: &[&[u8]] = &[
+    b"\x98\xff",
+    b"\x99\xff\xff",
+    b"\x9a\xff\xff\xff\xff",
+    b"\x9b\x0f\xff\xff\xff\xff\xff\xff\xff",
+];
+
+fn with_major(head: &[u8], major: u8) -> Vec<u8> {
+    let mut head = head.to_vec();
+    head[0] = head[0] & 0x1f | major << 5;
+    head
+}
+
+fn check_eof<T>(bytes: &[u8])
+where
+    T: DeserializeOwned + Debug,
+{
+    let error = from_slice::<T>(bytes).unwrap_err();
+    assert!(error.is_eof(), "{:?}: {}", bytes, error);
+    let error = from_reader::<T, _>(bytes).unwrap_err();
+    assert!(error.is_eof(), "{:?}: {}", bytes, error);
+}
+
+#[test]
+fn test_arrays() {
+    for head in ARRAY_HEADS {
+        let mut bytes = head.to_vec();
+        bytes.extend_from_slice(&[0; 16]);
+        check_eof::<Value>(&bytes);
+        check_eof::<Vec<u8>>(&bytes);
+        check_eof::<Vec<Value>>(&bytes);
+        check_eof::<IgnoredAny>(&bytes);
+    }
+}
+
+#[test]
+fn test_maps() {
+    for head in ARRAY_HEADS {
+        let mut bytes = with_major(head, 5);
+        bytes.extend_from_slice(b"\x61a\x01");
+        check_eof::<Value>(&bytes);
+        check_eof::<HashMap<String, u8>>(&bytes);
+        check_eof::<BTreeMap<String, Value>>(&bytes);
+        check_eof::<IgnoredAny>(&bytes);
+    }
+}
+
+#[test]
+fn test_strings() {
+    for head in ARRAY_HEADS {
+        let mut bytes = with_major(head, 2);
+        bytes.extend_from_slice(b"abc");
+        check_eof::<ByteBuf>(&bytes);
+        check_eof::<Value>(&bytes);
+        check_eof::<IgnoredAny>(&bytes);
+
+        let mut bytes = with_major(head, 3);
+        bytes.extend_from_slice(b"abc");
+        check_eof::<String>(&bytes);
+        check_eof::<Value>(&bytes);
+        check_eof::<IgnoredAny>(&bytes);
+
+        // A chunk of an indefinite-length string.
+        let mut bytes = b"\x7f\x61a".to_vec();
+        bytes.extend(with_major(head, 3));
+        bytes.extend_from_slice(b"abc");
+        check_eof::<String>(&bytes);
+    }
+}
+
+#[test]
+fn test_nested() {
+    // Arrays claiming 2^32 - 1 elements, each starting with another such array.
+    let bytes = b"\x9a\xff\xff\xff\xff".repeat(100);
+    check_eof::<Value>(&bytes);
+    check_eof::<IgnoredAny>(&bytes);
+    let bytes = b"\xba\xff\xff\xff\xff\x61a".repeat(100);
+    check_eof::<Value>(&bytes);
+}
+
+#[test]
+fn test_small_limit() {
+    // Data that is really there still decodes with the smallest limit.
+    let text = "x".repeat(1000);
+    let bytes = serde_cbor::to_vec(&(&text, vec![1u8; 100])).unwrap();
+    for limit in &[0, 1, 7] {
+        let mut de = Deserializer::from_reader(&bytes[..]).preallocation_limit(*limit);
+        let value: (String, Vec<u8>) = serde::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(value.0, text);
+        assert_eq!(value.1.len(), 100);
+    }
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "std")]
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use serde_cbor::de::{Incremental, Status};
+use serde_cbor::{to_vec, Value};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Message<'a> {
+    id: u32,
+    body: &'a str,
+}
+
+#[test]
+fn test_feed_bytes() {
+    let mut bytes = Vec::new();
+    for id in 0..3 {
+        let body = "x".repeat(id as usize * 10);
+        bytes.extend(to_vec(&Message { id, body: &body }).unwrap());
+    }
+    let mut parser = Incremental::new();
+    let mut ids = Vec::new();
+    for byte in &bytes {
+        parser.feed(&[*byte]);
+        if let Status::Value(message) = parser.next_value::<Message>().unwrap() {
+            assert_eq!(message.body.len(), message.id as usize * 10);
+            ids.push(message.id);
+        }
+    }
+    assert_eq!(ids, [0, 1, 2]);
+    assert_eq!(parser.byte_offset(), bytes.len() as u64);
+    parser.end().unwrap();
+}
+
+#[test]
+fn test_feed_chunks() {
+    // Two data items in one chunk, the second split across.
+    let mut parser = Incremental::new();
+    parser.feed(b"\x01\x9f\x01");
+    assert_eq!(parser.next_value::<u8>().unwrap(), Status::Value(1));
+    assert_eq!(parser.next_value::<Value>().unwrap(), Status::NeedMoreData);
+    assert_eq!(parser.buffered(), 2);
+    parser.feed(b"\xff\x02");
+    assert_eq!(
+        parser.next_value::<Value>().unwrap(),
+        Status::Value(Value::Array(vec![Value::Integer(1)]))
+    );
+    assert_eq!(parser.next_value::<u8>().unwrap(), Status::Value(2));
+    assert_eq!(parser.next_value::<u8>().unwrap(), Status::NeedMoreData);
+
+    // Simple values, one of them split across.
+    parser.feed(b"\x82\xe0\xf8");
+    assert_eq!(parser.next_value::<Value>().unwrap(), Status::NeedMoreData);
+    parser.feed(b"\xff");
+    assert_eq!(
+        parser.next_value::<Value>().unwrap(),
+        Status::Value(Value::Array(vec![Value::Simple(0), Value::Simple(255)]))
+    );
+}
+
+#[test]
+fn test_errors() {
+    // A data item of the wrong type is skipped.
+    let mut parser = Incremental::new();
+    parser.feed(b"\x61a\x01");
+    let error = parser.next_value::<u8>().unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(parser.next_value::<u8>().unwrap(), Status::Value(1));
+
+    // Malformed input keeps failing.
+    let mut parser = Incremental::new();
+    parser.feed(b"\x01\x82\x1c");
+    assert_eq!(parser.next_value::<u8>().unwrap(), Status::Value(1));
+    for _ in 0..2 {
+        let error = parser.next_value::<Vec<u8>>().unwrap_err();
+        assert!(error.is_syntax());
+        assert_eq!(error.offset(), 3);
+    }
+
+    let mut parser = Incremental::new();
+    parser.feed(b"\x82\x01");
+    let error = parser.end().unwrap_err();
+    assert!(error.is_eof());
+    assert_eq!(error.offset(), 2);
+}
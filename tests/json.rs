@@ -0,0 +1,137 @@
+#![cfg(feature = "json")]
+
+#[macro_use]
+extern crate serde_json;
+extern crate serde_cbor;
+
+use serde_cbor::convert::json::{self, ByteEncoding, Options};
+use serde_cbor::diag;
+use serde_cbor::Value;
+use std::convert::TryFrom;
+
+#[test]
+fn test_cbor_to_json() {
+    let cbor = b"\xa4\x61a\x83\x01\x20\x3b\xff\xff\xff\xff\xff\xff\xff\xff\
+                 \x61b\xf9\x3e\x00\
+                 \x61c\x7f\x61x\x61y\xff\
+                 \x61d\xd8\x20\x63foo";
+    let value = json::cbor_to_json(cbor).unwrap();
+    let expected = json!({
+        "a": [1, -1, -18446744073709551616.0],
+        "b": 1.5,
+        "c": "xy",
+        "d": "foo",
+    });
+    assert_eq!(value, expected);
+}
+
+#[test]
+fn test_byte_strings() {
+    let bytes = b"\x83\x43\xfb\xff\x01\xd6\x43\xfb\xff\x01\xd7\x43\xfb\xff\x01";
+    let value = json::cbor_to_json(bytes).unwrap();
+    assert_eq!(value, json!(["-_8B", "+/8B", "fbff01"]));
+
+    let options = Options::new().byte_strings(ByteEncoding::Base64);
+    let value = options.cbor_to_json(b"\x5f\x41\x01\x41\x02\xff").unwrap();
+    assert_eq!(value, json!("AQI="));
+
+    // bignums
+    let value = json::cbor_to_json(b"\x82\xc2\x42\x01\x00\xc3\x42\x01\x00").unwrap();
+    assert_eq!(value, json!(["AQA", "~AQA"]));
+
+    // The expected conversion applies to byte strings nested in the tagged item.
+    let diag = "22([h'fbff01', {\"k\": h'fbff01'}, 23(h'fbff01'), 1(21([h'fbff01'])), 2(h'0100')])";
+    let value = json::cbor_to_json(&diag::diag_to_bytes(diag).unwrap()).unwrap();
+    assert_eq!(value, json!(["+/8B", {"k": "+/8B"}, "fbff01", ["-_8B"], "AQA"]));
+}
+
+#[test]
+fn test_non_json_items() {
+    let value = json::cbor_to_json(b"\x84\xf7\xf0\xf9\x7e\x00\xfa\x7f\x80\x00\x00").unwrap();
+    assert_eq!(value, json!([null, null, null, null]));
+    let err = Options::new()
+        .reject_non_finite()
+        .cbor_to_json(b"\x81\xf9\x7c\x00")
+        .unwrap_err();
+    assert!(err.is_data());
+    assert_eq!(err.offset(), 4);
+
+    let value = json::cbor_to_json(b"\xa3\x01\x02\x41\x01\x03\x82\x01\x02\x04").unwrap();
+    assert_eq!(value, json!({"1": 2, "h'01'": 3, "[1, 2]": 4}));
+    let err = Options::new()
+        .reject_non_text_keys()
+        .cbor_to_json(b"\xa1\x01\x02")
+        .unwrap_err();
+    assert_eq!(err.offset(), 1);
+
+    // Keys that become the same text
+    let err = json::cbor_to_json(b"\xa2\x01\x61a\x61\x31\x61b").unwrap_err();
+    assert!(err.is_data());
+    assert_eq!(err.offset(), 4);
+    assert_eq!(err.to_string(), r#"duplicate map key "1" at offset 4"#);
+    let err = Options::new()
+        .reject_non_text_keys()
+        .cbor_to_json(b"\xa2\x61a\x01\x61a\x02")
+        .unwrap_err();
+    assert_eq!(err.offset(), 4);
+    let value = diag::parse(r#"{1: "a", "1": "b"}"#).unwrap();
+    assert!(Options::new().value_to_json(&value).is_err());
+}
+
+#[test]
+fn test_malformed() {
+    assert!(json::cbor_to_json(b"\x82\x01").unwrap_err().is_eof());
+    assert!(json::cbor_to_json(b"\x01\x02").unwrap_err().is_data());
+    assert!(json::cbor_to_json(b"\x1c").unwrap_err().is_syntax());
+    assert!(json::cbor_to_json(b"\x62\xff\xff").unwrap_err().is_data());
+    assert!(json::cbor_to_json(&vec![0x81; 1000]).unwrap_err().is_data());
+}
+
+#[test]
+fn test_json_to_cbor() {
+    let value = json!({"a": [1, -2, 1.5, 1e300, null, true, "x"]});
+    let cbor = json::json_to_cbor(&value).unwrap();
+    assert_eq!(
+        cbor,
+        b"\xa1\x61a\x87\x01\x21\xf9\x3e\x00\xfb\x7e\x37\xe4\x3c\x88\x00\x75\x9c\xf6\xf5\x61x"
+            .to_vec()
+    );
+    assert_eq!(json::cbor_to_json(&cbor).unwrap(), value);
+}
+
+#[test]
+fn test_text() {
+    // {"a": h'ff', 1: -1}
+    let cbor = b"\xa2\x61a\x41\xff\x01\x20";
+    assert_eq!(json::cbor_to_json_string(cbor).unwrap(), r#"{"1":-1,"a":"_w"}"#);
+    let options = Options::new().byte_strings(ByteEncoding::Base16);
+    assert_eq!(options.cbor_to_json_string(cbor).unwrap(), r#"{"1":-1,"a":"ff"}"#);
+
+    let cbor = json::json_str_to_cbor("{\"a\": [true, null],\n \"b\": 1e3}").unwrap();
+    assert_eq!(diag::display(&cbor).to_string(), r#"{"a": [true, null], "b": 1000.0}"#);
+
+    let error = json::json_str_to_cbor("[1,\n 2 3]").unwrap_err();
+    assert!(error.is_syntax());
+    assert_eq!(error.offset(), 7);
+    assert_eq!(error.to_string(), "invalid JSON: expected `,` or `]` at offset 7");
+    let error = json::json_str_to_cbor("[1, 2").unwrap_err();
+    assert!(error.is_eof());
+    assert_eq!(error.offset(), 5);
+}
+
+#[test]
+fn test_value() {
+    let value = diag::parse(r#"{"a": 23(h'cafe'), 1: [3(h'01'), -18446744073709551616, NaN]}"#);
+    let value = value.unwrap();
+    let expected = json!({"a": "cafe", "1": ["~AQ", -18446744073709551616.0, null]});
+    assert_eq!(serde_json::Value::try_from(value.clone()).unwrap(), expected);
+    assert_eq!(Options::new().value_to_json(&value).unwrap(), expected);
+    assert!(Options::new().reject_non_text_keys().value_to_json(&value).is_err());
+    assert!(Options::new().reject_non_finite().value_to_json(&value).is_err());
+
+    let json = json!({"a": [1, -2, 1.5, null, true, "x"]});
+    let value = Value::from(json.clone());
+    assert_eq!(value, diag::parse(r#"{"a": [1, -2, 1.5, null, true, "x"]}"#).unwrap());
+    assert_eq!(Value::try_from(json.clone()).unwrap(), value);
+    assert_eq!(serde_json::Value::try_from(value).unwrap(), json);
+}
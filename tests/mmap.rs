@@ -0,0 +1,64 @@
+#![cfg(all(feature = "std", feature = "memmap2"))]
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_bytes;
+extern crate serde_cbor;
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde_cbor::de::MappedFile;
+use serde_cbor::to_vec;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Record<'a> {
+    key: &'a str,
+    #[serde(with = "serde_bytes")]
+    data: &'a [u8],
+}
+
+fn write_temp(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("serde_cbor_test_mmap_{}", name));
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+#[test]
+fn test_borrow_from_mapping() {
+    let record = Record {
+        key: "a",
+        data: &[1, 2, 3],
+    };
+    let path = write_temp("borrow", &to_vec(&record).unwrap());
+    let file = unsafe { MappedFile::open(&path).unwrap() };
+    let read: Record = file.deserialize().unwrap();
+    assert_eq!(read, record);
+    // Borrowed from the mapping itself.
+    let range = file.as_ptr_range();
+    assert!(range.contains(&read.key.as_ptr()));
+    drop(file);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_sequence() {
+    let path = write_temp("sequence", b"\x01\x02\x03");
+    let file = unsafe { MappedFile::open(&path).unwrap() };
+    let values: Vec<u8> = file
+        .deserializer()
+        .into_iter()
+        .map(|value| value.unwrap())
+        .collect();
+    assert_eq!(values, [1, 2, 3]);
+    assert!(file.deserialize::<u8>().unwrap_err().is_data());
+    drop(file);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_missing_file() {
+    let path = std::env::temp_dir().join("serde_cbor_test_mmap_missing");
+    let error = unsafe { MappedFile::open(&path).unwrap_err() };
+    assert!(error.is_io());
+}
@@ -0,0 +1,45 @@
+#![cfg(not(feature = "float"))]
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use serde_cbor::{diag, from_slice, to_slice};
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Reading {
+    count: u8,
+}
+
+#[test]
+fn test_serialize() {
+    let mut buf = [0; 16];
+    let error = to_slice(&mut buf, &(1, 1.5f64)).unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(
+        error.to_string(),
+        "floating-point number without the float feature"
+    );
+    assert!(to_slice(&mut buf, &1.0f32).is_err());
+}
+
+#[test]
+fn test_deserialize() {
+    for bytes in &[
+        &b"\xf9\x3e\x00"[..],
+        b"\xfa\x3f\xc0\x00\x00",
+        b"\xfb\x3f\xf8\0\0\0\0\0\0",
+    ] {
+        let error = from_slice::<f64>(bytes).unwrap_err();
+        assert!(error.is_data());
+        assert_eq!(error.offset(), 1);
+    }
+    // {"count": 1, "mean": 1.5}
+    let bytes = b"\xa2\x65count\x01\x64mean\xf9\x3e\x00";
+    assert_eq!(from_slice::<Reading>(bytes).unwrap(), Reading { count: 1 });
+}
+
+#[test]
+fn test_display() {
+    assert_eq!(diag::display(b"\x82\x01\xf9\x3e\x00").to_string(), "[1, ");
+}
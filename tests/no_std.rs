@@ -0,0 +1,59 @@
+#![cfg(not(feature = "std"))]
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use serde_cbor::ser::SliceWrite;
+use serde_cbor::{diag, from_slice, to_slice, Serializer};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Reading<'a> {
+    sensor: &'a str,
+    values: [u16; 2],
+}
+
+#[test]
+fn test_to_slice() {
+    let reading = Reading {
+        sensor: "t1",
+        values: [1, 1000],
+    };
+    let mut buf = [0; 32];
+    let len = to_slice(&mut buf, &reading).unwrap();
+    assert_eq!(
+        &buf[..len],
+        &b"\xa2\x66sensor\x62t1\x66values\x82\x01\x19\x03\xe8"[..]
+    );
+    assert_eq!(from_slice::<Reading>(&buf[..len]).unwrap(), reading);
+
+    let error = to_slice(&mut buf[..len - 1], &reading).unwrap_err();
+    assert!(error.is_io());
+    assert_eq!(error.offset(), 0);
+}
+
+#[test]
+fn test_slice_write() {
+    let mut buf = [0; 4];
+    let mut ser = Serializer::new(SliceWrite::new(&mut buf));
+    ser.self_describe().unwrap();
+    assert_eq!(ser.into_inner().bytes_written(), 3);
+    assert_eq!(buf, [0xd9, 0xd9, 0xf7, 0]);
+}
+
+#[test]
+fn test_indefinite_strings() {
+    // (_ "a", "b")
+    let error = from_slice::<&str>(b"\x7f\x61a\x61b\xff").unwrap_err();
+    assert!(error.is_data());
+    assert_eq!(error.to_string(), "indefinite-length string at offset 1");
+    // [_ 1, 2] needs no buffer.
+    assert_eq!(from_slice::<[u8; 2]>(b"\x9f\x01\x02\xff").unwrap(), [1, 2]);
+}
+
+#[test]
+fn test_display() {
+    let mut buf = [0; 16];
+    let len = to_slice(&mut buf, &("a", -1)).unwrap();
+    assert_eq!(diag::display(&buf[..len]).to_string(), r#"["a", -1]"#);
+}
@@ -0,0 +1,155 @@
+#![cfg(feature = "std")]
+
+#[cfg(feature = "num-rational")]
+extern crate num_rational;
+#[cfg(feature = "rust_decimal")]
+extern crate rust_decimal;
+extern crate serde;
+#[cfg(any(feature = "num-rational", feature = "rust_decimal"))]
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use serde_cbor::number::{Bigfloat, DecimalFraction, Rational};
+use serde_cbor::{diag, from_slice, to_vec};
+
+#[test]
+fn test_decimal_fraction() {
+    for &(number, expected) in &[
+        (DecimalFraction::new(-2, 27315), "4([-2, 27315])"),
+        (DecimalFraction::new(3, -1), "4([3, -1])"),
+        (
+            DecimalFraction::new(-1, 1 << 64),
+            "4([-1, 2(h'010000000000000000')])",
+        ),
+    ] {
+        assert_eq!(diag::to_diag_string(&number).unwrap(), expected);
+        let bytes = to_vec(&number).unwrap();
+        assert_eq!(from_slice::<DecimalFraction>(&bytes).unwrap(), number);
+    }
+
+    // Untagged arrays are accepted, other tags are not.
+    let number: DecimalFraction = diag::from_diag_str("[-2, 27315]").unwrap();
+    assert_eq!(number, DecimalFraction::new(-2, 27315));
+    for diag in &["5([-2, 27315])", "4([-2])", "4([1.5, 1])", "4(1)"] {
+        assert!(diag::from_diag_str::<DecimalFraction>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+#[test]
+fn test_rust_decimal() {
+    use rust_decimal::Decimal;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Price {
+        #[serde(with = "serde_cbor::with::decimal")]
+        amount: Decimal,
+    }
+
+    for decimal in &[Decimal::new(1999, 2), Decimal::new(-5, 0), Decimal::MAX, Decimal::MIN] {
+        let number = DecimalFraction::from(*decimal);
+        assert_eq!(number.to_decimal(), Some(*decimal));
+        let price = Price { amount: *decimal };
+        assert_eq!(from_slice::<Price>(&to_vec(&price).unwrap()).unwrap(), price);
+    }
+    assert_eq!(
+        diag::to_diag_string(&Price {
+            amount: Decimal::new(1999, 2)
+        })
+        .unwrap(),
+        r#"{"amount": 4([-2, 1999])}"#
+    );
+
+    let price: Price = diag::from_diag_str(r#"{"amount": 4([2, 15])}"#).unwrap();
+    assert_eq!(price.amount, Decimal::new(1500, 0));
+    assert_eq!(DecimalFraction::new(-29, 1).to_decimal(), None);
+    assert_eq!(DecimalFraction::new(30, 1).to_decimal(), None);
+    assert_eq!(DecimalFraction::new(i64::min_value(), 1).to_decimal(), None);
+    assert!(diag::from_diag_str::<Price>(r#"{"amount": 4([40, 1])}"#).is_err());
+}
+
+#[test]
+fn test_bigfloat() {
+    let number = Bigfloat::new(-1, 3);
+    assert_eq!(diag::to_diag_string(&number).unwrap(), "5([-1, 3])");
+    let bytes = to_vec(&number).unwrap();
+    assert_eq!(from_slice::<Bigfloat>(&bytes).unwrap(), number);
+    let number: Bigfloat = diag::from_diag_str("5([1, 2(h'010000000000000000')])").unwrap();
+    assert_eq!(number, Bigfloat::new(1, 1 << 64));
+    assert!(diag::from_diag_str::<Bigfloat>("4([-1, 3])").is_err());
+
+    for &value in &[
+        0.0,
+        1.0,
+        -1.5,
+        0.1,
+        1e300,
+        -4.9e-324,
+        2.2250738585072014e-308,
+        std::f64::MAX,
+        std::f64::MIN_POSITIVE / 3.0,
+    ] {
+        let number = Bigfloat::from_f64(value).unwrap();
+        assert!(number.mantissa % 2 != 0 || number.mantissa == 0, "{}", value);
+        assert_eq!(number.to_f64(), Some(value), "{}", value);
+    }
+    assert_eq!(Bigfloat::from_f64(-4.9e-324), Some(Bigfloat::new(-1074, -1)));
+    assert_eq!(Bigfloat::from_f64(1024.0), Some(Bigfloat::new(10, 1)));
+    for &value in &[std::f64::NAN, std::f64::INFINITY, -0.0] {
+        assert_eq!(Bigfloat::from_f64(value), None);
+    }
+
+    // Unnormalized numbers convert, as long as their value fits.
+    assert_eq!(Bigfloat::new(-3, 12).to_f64(), Some(1.5));
+    assert_eq!(Bigfloat::new(1023, 1).to_f64(), Some(8.98846567431158e307));
+    assert_eq!(Bigfloat::new(1024, 1).to_f64(), None);
+    assert_eq!(Bigfloat::new(-1075, 2).to_f64(), Some(4.9e-324));
+    assert_eq!(Bigfloat::new(-1075, 1).to_f64(), None);
+    assert_eq!(Bigfloat::new(0, (1 << 53) + 1).to_f64(), None);
+    assert_eq!(Bigfloat::new(i64::max_value(), 2).to_f64(), None);
+}
+
+#[test]
+fn test_rational() {
+    for &(number, expected) in &[
+        (Rational::new(-1, 3), "30([-1, 3])"),
+        (Rational::new(2, 4), "30([2, 4])"),
+        (
+            Rational::new(1, 1 << 64),
+            "30([1, 2(h'010000000000000000')])",
+        ),
+    ] {
+        assert_eq!(diag::to_diag_string(&number).unwrap(), expected);
+        let bytes = to_vec(&number).unwrap();
+        assert_eq!(from_slice::<Rational>(&bytes).unwrap(), number);
+    }
+    assert!(to_vec(&Rational::new(1, 0)).is_err());
+    for diag in &["30([1, 0])", "30([1, -3])", "30([1])", "4([1, 3])"] {
+        assert!(diag::from_diag_str::<Rational>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "num-rational")]
+#[test]
+fn test_num_rational() {
+    use num_rational::Ratio;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Share {
+        #[serde(with = "serde_cbor::with::ratio")]
+        part: Ratio<i64>,
+    }
+
+    let share = Share {
+        part: Ratio::new(2, -6),
+    };
+    assert_eq!(diag::to_diag_string(&share).unwrap(), r#"{"part": 30([-1, 3])}"#);
+    assert_eq!(from_slice::<Share>(&to_vec(&share).unwrap()).unwrap(), share);
+
+    let share: Share = diag::from_diag_str(r#"{"part": 30([2, 4])}"#).unwrap();
+    assert_eq!(share.part, Ratio::new(1, 2));
+    assert_eq!(Rational::from(Ratio::new_raw(1, -2)), Rational::new(-1, 2));
+    assert_eq!(Rational::new(1, 1 << 63).to_ratio(), None);
+    assert!(diag::from_diag_str::<Share>(r#"{"part": 30([1, 9223372036854775808])}"#).is_err());
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "std")]
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use serde_cbor::raw::{RawValue, RawValueRef};
+use serde_cbor::{from_reader, from_slice, to_vec, Value};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    signature: u8,
+    payload: RawValue,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Borrowed<'a> {
+    #[serde(borrow)]
+    payload: RawValueRef<'a>,
+}
+
+#[test]
+fn test_pass_through() {
+    // {"signature": 1, "payload": {_ "b": 1.0, "a": h''}}, with the float and the map in forms
+    // that the serializer would not write.
+    let bytes = b"\xa2\x69signature\x01\x67payload\xbf\x61b\xfb\x3f\xf0\0\0\0\0\0\0\x61a\x40\xff";
+    let envelope: Envelope = from_slice(bytes).unwrap();
+    assert_eq!(envelope.payload.as_bytes(), &bytes[20..]);
+    assert_eq!(to_vec(&envelope).unwrap(), &bytes[..]);
+
+    let envelope: Envelope = from_reader(&bytes[..]).unwrap();
+    assert_eq!(envelope.payload.as_bytes(), &bytes[20..]);
+
+}
+
+#[test]
+fn test_borrowed() {
+    let bytes = b"\xa1\x67payload\x82\x01\x02";
+    let borrowed: Borrowed = from_slice(bytes).unwrap();
+    assert_eq!(borrowed.payload.as_bytes(), b"\x82\x01\x02");
+    assert_eq!(borrowed.payload.decode::<Vec<u8>>().unwrap(), vec![1, 2]);
+    assert_eq!(to_vec(&borrowed).unwrap(), &bytes[..]);
+}
+
+#[test]
+fn test_construct() {
+    let raw = RawValue::from_slice(b"\x82\x01\x61a").unwrap();
+    assert_eq!(format!("{:?}", raw), r#"RawValue([1, "a"])"#);
+    assert_eq!(raw.decode::<(u8, String)>().unwrap(), (1, "a".to_string()));
+    assert_eq!(RawValue::from_serialize(&(1, "a")).unwrap(), raw);
+    assert_eq!(raw.clone().into_vec(), b"\x82\x01\x61a");
+    assert!(RawValue::from_slice(b"\x82\x01").unwrap_err().is_eof());
+    assert!(RawValue::from_vec(vec![0x01, 0x02]).is_err());
+    assert!(RawValueRef::from_slice(b"\x1c").unwrap_err().is_syntax());
+}
+
+#[test]
+fn test_serializers() {
+    // {"b": 1, "a": 2}, which is not in canonical order
+    let raw = RawValue::from_slice(b"\xa2\x61b\x01\x61a\x02").unwrap();
+    let canonical = serde_cbor::ser::to_vec_canonical(&vec![raw.clone()]).unwrap();
+    assert_eq!(canonical, b"\x81\xa2\x61b\x01\x61a\x02");
+    assert!(serde_cbor::stringref::to_vec(&raw).is_err());
+
+    let value = serde_cbor::value::to_value(&raw).unwrap();
+    assert_eq!(value["b"], Value::Integer(1));
+    let raw_again: RawValue = serde_cbor::value::from_value(value).unwrap();
+    assert_eq!(raw_again.decode::<Value>().unwrap(), raw.decode::<Value>().unwrap());
+}
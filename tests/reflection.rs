@@ -0,0 +1,80 @@
+#![cfg(feature = "serde-reflection")]
+
+extern crate serde_bytes;
+extern crate serde_cbor;
+#[macro_use]
+extern crate serde_derive;
+
+use std::collections::BTreeMap;
+
+use serde_bytes::ByteBuf;
+use serde_cbor::reflection::{to_cddl, to_cddl_packed, Tracer};
+
+#[derive(Serialize, Deserialize)]
+struct Message {
+    id: u32,
+    payload: ByteBuf,
+    kind: Kind,
+    extra: Option<BTreeMap<String, Vec<i8>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Kind {
+    Empty,
+    Code(u16),
+    Pair(bool, f64),
+    Named { name: String },
+}
+
+#[test]
+fn test_cddl() {
+    let mut tracer = Tracer::new();
+    tracer
+        .trace_type::<Kind>()
+        .unwrap()
+        .trace_type::<Message>()
+        .unwrap();
+    let registry = tracer.registry().unwrap();
+    let expected = r#"Kind = "Empty"
+  / ["Code", uint .size 2]
+  / ["Pair", bool, float]
+  / ["Named", {
+  "name": tstr,
+}]
+Message = {
+  "id": uint .size 4,
+  "payload": bstr,
+  "kind": Kind,
+  "extra": {* tstr => [* -128..127]} / null,
+}
+"#;
+    assert_eq!(to_cddl(&registry).unwrap(), expected);
+}
+
+#[test]
+fn test_cddl_packed() {
+    let mut tracer = Tracer::new();
+    tracer.trace_type::<Kind>().unwrap();
+    let registry = tracer.registry().unwrap();
+    let expected = "Kind = 0\n  / [1, uint .size 2]\n  / [2, bool, float]\n  / [3, {\n  0: tstr,\n}]\n";
+    assert_eq!(to_cddl_packed(&registry).unwrap(), expected);
+}
+
+#[test]
+fn test_trace_value() {
+    let mut tracer = Tracer::new();
+    tracer
+        .trace_value(&Kind::Code(5))
+        .unwrap()
+        .trace_value(&Kind::Empty)
+        .unwrap();
+    let registry = tracer.registry().unwrap();
+    assert_eq!(to_cddl(&registry).unwrap(), "Kind = \"Empty\"\n  / [\"Code\", uint .size 2]\n");
+}
+
+#[test]
+fn test_missing_variants() {
+    let mut tracer = Tracer::new();
+    tracer.trace_type::<Message>().unwrap();
+    assert!(tracer.registry().is_err());
+}
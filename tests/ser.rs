@@ -1,6 +1,10 @@
+#![cfg(feature = "std")]
+
 extern crate serde;
 extern crate serde_bytes;
 extern crate serde_cbor;
+#[macro_use]
+extern crate serde_derive;
 
 use std::collections::BTreeMap;
 
@@ -83,6 +87,24 @@ fn test_integer() {
     assert_eq!(vec, b"\x1b\xff\xff\xff\xff\xff\xff\xff\xff");
 }
 
+#[test]
+fn test_integer128() {
+    let vec = to_vec(&-1i128).unwrap();
+    assert_eq!(vec, b"\x20");
+    let vec = to_vec(&u128::from(::std::u64::MAX)).unwrap();
+    assert_eq!(vec, b"\x1b\xff\xff\xff\xff\xff\xff\xff\xff");
+    let vec = to_vec(&-(1i128 << 64)).unwrap();
+    assert_eq!(vec, b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff");
+    // Integers that need more than 64 bits become bignums.
+    let vec = to_vec(&(1u128 << 64)).unwrap();
+    assert_eq!(vec, b"\xc2\x49\x01\x00\x00\x00\x00\x00\x00\x00\x00");
+    let vec = to_vec(&(-(1i128 << 64) - 1)).unwrap();
+    assert_eq!(vec, b"\xc3\x49\x01\x00\x00\x00\x00\x00\x00\x00\x00");
+    let vec = to_vec(&::std::i128::MIN).unwrap();
+    assert_eq!(vec[..3], b"\xc3\x50\x7f"[..]);
+    assert!(ser::to_vec_ctap2(&(1u128 << 64)).is_err());
+}
+
 #[test]
 fn test_self_describing() {
     let mut vec = Vec::new();
@@ -94,6 +116,82 @@ fn test_self_describing() {
     assert_eq!(vec, b"\xd9\xd9\xf7\x09");
 }
 
+#[test]
+fn test_self_described() {
+    use serde::Serialize;
+
+    // The tag goes in front of the first data item only.
+    let mut serializer = ser::Serializer::new(Vec::new()).self_described();
+    "a".serialize(&mut serializer).unwrap();
+    "b".serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), b"\xd9\xd9\xf7\x61a\x61b");
+
+    // Canonical maps are buffered before they are written.
+    let mut map = BTreeMap::new();
+    map.insert(2, ());
+    map.insert(1, ());
+    let mut serializer = ser::Serializer::canonical(Vec::new()).self_described();
+    map.serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), b"\xd9\xd9\xf7\xa2\x01\xf6\x02\xf6");
+
+    // Writing the tag explicitly does not write it twice.
+    let mut serializer = ser::Serializer::packed(Vec::new()).self_described();
+    serializer.self_describe().unwrap();
+    true.serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), b"\xd9\xd9\xf7\xf5");
+}
+
+#[test]
+fn test_packed_format() {
+    use serde::Serialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Color {
+        Blue,
+        Yellow(u8),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: u8,
+        #[serde(skip)]
+        cached: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        celsius: Option<i8>,
+        color: Color,
+    }
+
+    let reading = Reading {
+        sensor: 3,
+        cached: 0,
+        celsius: None,
+        color: Color::Blue,
+    };
+    let mut serializer = ser::Serializer::new(Vec::new()).packed_format();
+    reading.serialize(&mut serializer).unwrap();
+    let bytes = serializer.into_inner();
+    // {0: 3, 2: 0}: the skipped field takes no index, the field left out keeps its own.
+    assert_eq!(bytes, b"\xa2\x00\x03\x02\x00");
+    assert_eq!(bytes, ser::to_vec_packed(&reading).unwrap());
+    assert_eq!(from_slice::<Reading>(&bytes).unwrap(), reading);
+
+    let reading = Reading {
+        celsius: Some(-5),
+        color: Color::Yellow(7),
+        ..reading
+    };
+    let mut serializer = ser::Serializer::canonical(Vec::new()).packed_format();
+    reading.serialize(&mut serializer).unwrap();
+    let bytes = serializer.into_inner();
+    // {0: 3, 1: -5, 2: [1, 7]}
+    assert_eq!(bytes, b"\xa3\x00\x03\x01\x24\x02\x82\x01\x07");
+    assert_eq!(from_slice::<Reading>(&bytes).unwrap(), reading);
+
+    // Names and indices can be mixed in the input.
+    let mixed = b"\xa3\x66sensor\x03\x01\x24\x02\x82\x66Yellow\x07";
+    assert_eq!(from_slice::<Reading>(mixed).unwrap(), reading);
+}
+
 #[test]
 fn test_ip_addr() {
     use std::net::Ipv4Addr;
@@ -149,3 +247,75 @@ fn test_half() {
     assert_eq!(vec, b"\xF9\x51\x50");
     assert_eq!(from_slice::<f32>(&vec[..]).unwrap(), 42.5f32);
 }
+
+// Counts the writes issued by the serializer.
+struct CountingWriter {
+    buf: Vec<u8>,
+    writes: usize,
+}
+
+impl std::io::Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writes += 1;
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_coalesced_writes() {
+    fn writes<T: serde::Serialize>(value: &T, packed: bool) -> (Vec<u8>, usize) {
+        let mut writer = CountingWriter { buf: Vec::new(), writes: 0 };
+        if packed {
+            value.serialize(&mut ser::Serializer::packed(&mut writer)).unwrap();
+        } else {
+            value.serialize(&mut ser::Serializer::new(&mut writer)).unwrap();
+        }
+        (writer.buf, writer.writes)
+    }
+
+    assert_eq!(writes(&1000000u32, false), (b"\x1a\x00\x0f\x42\x40".to_vec(), 1));
+    assert_eq!(writes(&"foobar", false), (b"ffoobar".to_vec(), 1));
+    assert_eq!(writes(&Bytes::new(&[1, 2]), false), (b"\x42\x01\x02".to_vec(), 1));
+    let long = "x".repeat(100);
+    assert_eq!(writes(&long, false).1, 2);
+    assert_eq!(writes(&long, false).0, to_vec(&long).unwrap());
+
+    // the array head and the variant identifier of `Ok(7)` go out together
+    let value: Result<u8, u8> = Ok(7);
+    assert_eq!(writes(&value, false), (b"\x82\x62Ok\x07".to_vec(), 2));
+    assert_eq!(writes(&value, true), (b"\x82\x00\x07".to_vec(), 2));
+}
+
+#[test]
+fn test_dyn_writer() {
+    let mut vec = Vec::new();
+    {
+        let writer: &mut std::io::Write = &mut vec;
+        ser::to_dyn_writer(writer, &vec![1, 2, 3]).unwrap();
+    }
+    assert_eq!(vec, b"\x83\x01\x02\x03");
+    let value: Vec<u8> = serde_cbor::de::from_dyn_reader(&mut &vec[..]).unwrap();
+    assert_eq!(value, [1, 2, 3]);
+}
+
+#[test]
+fn test_half_subnormal() {
+    // the smallest positive half-precision subnormal, 2^-24
+    let tiny = 5.960464477539063e-8f64;
+    let vec = to_vec(&tiny).unwrap();
+    assert_eq!(vec, b"\xf9\x00\x01");
+    assert_eq!(from_slice::<f64>(&vec).unwrap(), tiny);
+
+    // the largest half-precision subnormal
+    let vec = to_vec(&6.097555160522461e-5f32).unwrap();
+    assert_eq!(vec, b"\xf9\x03\xff");
+
+    // half of the smallest subnormal is not representable and must not be rounded to it
+    let vec = to_vec(&(tiny / 2.0)).unwrap();
+    assert_eq!(vec, b"\xfa\x33\x00\x00\x00");
+}
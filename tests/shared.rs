@@ -0,0 +1,105 @@
+#![cfg(feature = "std")]
+
+extern crate serde_cbor;
+#[macro_use]
+extern crate serde_derive;
+
+use serde_cbor::{diag, shared, Value};
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Node {
+    name: String,
+    #[serde(with = "serde_cbor::with::shared")]
+    child: Rc<Option<Node>>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Pair {
+    #[serde(with = "serde_cbor::with::shared")]
+    left: Rc<Node>,
+    #[serde(with = "serde_cbor::with::shared")]
+    right: Rc<Node>,
+}
+
+fn pair() -> Pair {
+    let leaf = Rc::new(Some(Node {
+        name: "leaf".to_string(),
+        child: Rc::new(None),
+    }));
+    let node = Rc::new(Node {
+        name: "node".to_string(),
+        child: leaf.clone(),
+    });
+    let other = Rc::new(Node {
+        name: "other".to_string(),
+        child: leaf,
+    });
+    Pair {
+        left: node,
+        right: other,
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let pair = pair();
+    let bytes = shared::to_vec(&pair).unwrap();
+    // Shareable values are numbered in the order they start, the outer ones first.
+    assert_eq!(
+        diag::display(&bytes).to_string(),
+        concat!(
+            r#"{"left": 28({"name": "node", "child": 28({"name": "leaf", "child": 28(null)})}), "#,
+            r#""right": 28({"name": "other", "child": 29(1)})}"#
+        )
+    );
+    let decoded: Pair = shared::from_slice(&bytes).unwrap();
+    assert_eq!(decoded, pair);
+    assert!(Rc::ptr_eq(&decoded.left.child, &decoded.right.child));
+
+    // Without sharing, the value is written in full each time.
+    let bytes = serde_cbor::to_vec(&pair).unwrap();
+    let decoded: Pair = serde_cbor::from_slice(&bytes).unwrap();
+    assert_eq!(decoded, pair);
+    assert!(!Rc::ptr_eq(&decoded.left.child, &decoded.right.child));
+}
+
+#[test]
+fn test_errors() {
+    for diag in &[
+        r#"{"left": 29(0), "right": 29(0)}"#,
+        r#"{"left": 28({"name": "a", "child": 29(0)}), "right": 29(0)}"#,
+        r#"{"left": 28({"name": "a", "child": null}), "right": 29(1)}"#,
+        r#"{"left": 28({"name": "a", "child": 28(null)}), "right": 29(1)}"#,
+        r#"{"left": 30({"name": "a", "child": null}), "right": 29(0)}"#,
+    ] {
+        let bytes = diag::diag_to_bytes(diag).unwrap();
+        assert!(shared::from_slice::<Pair>(&bytes).is_err(), "{}", diag);
+    }
+    // References cannot be resolved outside of `from_slice`.
+    let bytes = shared::to_vec(&pair()).unwrap();
+    assert!(serde_cbor::from_slice::<Pair>(&bytes).is_err());
+}
+
+#[test]
+fn test_resolve() {
+    let bytes = shared::to_vec(&pair()).unwrap();
+    let resolved = shared::resolve(&bytes, 1024).unwrap();
+    assert_eq!(resolved, serde_cbor::to_vec(&pair()).unwrap());
+    let value: Value = serde_cbor::from_slice(&resolved).unwrap();
+    assert_eq!(value, serde_cbor::value::to_value(&pair()).unwrap());
+
+    // Each level doubles the size of the result.
+    let mut items = vec!["28(h'00')".to_string()];
+    for i in 0..20 {
+        items.push(format!("28([29({}), 29({})])", i, i));
+    }
+    let bytes = diag::diag_to_bytes(&format!("[{}]", items.join(", "))).unwrap();
+    assert!(shared::resolve(&bytes, 1 << 24).is_ok());
+    assert!(shared::resolve(&bytes, 1 << 16).is_err());
+
+    for diag in &["29(0)", "28([29(0)])", "[28(1), 29(1)]", "[28(1), 29(h'00')]"] {
+        let bytes = diag::diag_to_bytes(diag).unwrap();
+        assert!(shared::resolve(&bytes, 1024).is_err(), "{}", diag);
+    }
+}
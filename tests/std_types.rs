@@ -1,3 +1,5 @@
+#![cfg(feature = "std")]
+
 extern crate serde_cbor;
 extern crate serde_bytes;
 #[macro_use]
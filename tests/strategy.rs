@@ -0,0 +1,81 @@
+#![cfg(feature = "proptest")]
+
+#[macro_use]
+extern crate proptest;
+extern crate serde;
+extern crate serde_cbor;
+
+use serde::Deserialize;
+use serde_cbor::error::WarningKind;
+use serde_cbor::strategy::{self, Options};
+use serde_cbor::{from_slice, to_vec, Deserializer, Value};
+
+fn depth(value: &Value) -> u32 {
+    match *value {
+        Value::Array(ref items) => 1 + items.iter().map(depth).max().unwrap_or(0),
+        Value::Object(ref map) => 1 + map.values().map(depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_value_round_trip(value in strategy::value()) {
+        let bytes = to_vec(&value).unwrap();
+        prop_assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn test_canonical_encoding(encoded in strategy::encoded()) {
+        let mut de = Deserializer::from_slice(&encoded.bytes).collect_warnings();
+        let value = Value::deserialize(&mut de).unwrap();
+        de.end().unwrap();
+        prop_assert_eq!(value, encoded.value);
+        prop_assert!(de.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_non_canonical_encoding(encoded in Options::new().non_canonical().encoded()) {
+        prop_assert_eq!(from_slice::<Value>(&encoded.bytes).unwrap(), encoded.value);
+    }
+
+    #[test]
+    fn test_depth(value in Options::new().depth(2).items(3).value()) {
+        prop_assert!(depth(&value) <= 2);
+    }
+
+    #[test]
+    fn test_scalars(value in Options::new().depth(0).value()) {
+        prop_assert_eq!(depth(&value), 0);
+    }
+
+    #[test]
+    fn test_key(key in Options::new().key()) {
+        let bytes = to_vec(&key).unwrap();
+        prop_assert_eq!(from_slice::<serde_cbor::ObjectKey>(&bytes).unwrap(), key);
+    }
+}
+
+#[test]
+fn test_non_canonical_forms() {
+    use proptest::strategy::{Strategy, ValueTree};
+    use proptest::test_runner::TestRunner;
+
+    // Every kind of non-canonical form shows up eventually.
+    let strategy = Options::new().non_canonical().encoded();
+    let mut runner = TestRunner::deterministic();
+    let mut kinds = Vec::new();
+    for _ in 0..1000 {
+        let encoded = strategy.new_tree(&mut runner).unwrap().current();
+        let mut de = Deserializer::from_slice(&encoded.bytes).collect_warnings();
+        Value::deserialize(&mut de).unwrap();
+        for warning in de.warnings() {
+            if !kinds.contains(&warning.kind()) {
+                kinds.push(warning.kind());
+            }
+        }
+    }
+    assert!(kinds.contains(&WarningKind::NonMinimalArgument));
+    assert!(kinds.contains(&WarningKind::UnsortedMapKey));
+    assert!(kinds.contains(&WarningKind::IndefiniteLength));
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "std")]
+
+extern crate serde;
+extern crate serde_bytes;
+extern crate serde_cbor;
+#[macro_use]
+extern crate serde_derive;
+
+use serde::Serialize;
+use serde_bytes::Bytes;
+use serde_cbor::{diag, stringref, Serializer};
+
+#[test]
+fn test_thresholds() {
+    let mut strings = vec!["1", "222", "333", "4", "555", "666", "777", "888", "999"];
+    strings.extend(&["aaa", "bbb", "ccc", "ddd", "eee", "fff", "ggg", "hhh", "iii"]);
+    strings.extend(&["jjj", "kkk", "lll", "mmm", "nnn", "ooo", "ppp", "qqq", "rrr"]);
+    strings.extend(&["333", "ssss", "qqq", "rrr", "ssss"]);
+    let bytes = stringref::to_vec(&strings).unwrap();
+    let diag = diag::display(&bytes).to_string();
+    // "rrr" is too short to be numbered 24, which "ssss" is instead.
+    assert!(diag.ends_with(r#""qqq", "rrr", 25(1), "ssss", 25(23), "rrr", 25(24)])"#));
+    assert!(diag.starts_with(r#"256(["1", "222""#));
+    let decoded: Vec<String> = stringref::from_slice(&bytes).unwrap();
+    assert_eq!(decoded, strings);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Circle { radius: u32 },
+    Square { side: u32 },
+}
+
+#[test]
+fn test_round_trip() {
+    let shapes = vec![
+        Shape::Circle { radius: 1 },
+        Shape::Square { side: 2 },
+        Shape::Circle { radius: 3 },
+    ];
+    let bytes = stringref::to_vec(&shapes).unwrap();
+    assert!(bytes.len() < serde_cbor::to_vec(&shapes).unwrap().len());
+    let decoded: Vec<Shape> = stringref::from_slice(&bytes).unwrap();
+    assert_eq!(decoded, shapes);
+
+    // Text and byte strings with the same content are numbered apart.
+    let bytes = stringref::to_vec(&("abc", Bytes::new(b"abc"), "abc", Bytes::new(b"abc"))).unwrap();
+    let diag = diag::display(&bytes).to_string();
+    assert_eq!(diag, r#"256(["abc", h'616263', 25(0), 25(1)])"#);
+}
+
+#[test]
+fn test_resolve() {
+    let diag = r#"256(["aaa", 256(["bbb", 25(0)]), 25(0), 1(25(0))])"#;
+    let bytes = diag::diag_to_bytes(diag).unwrap();
+    let resolved = stringref::resolve(&bytes, 1024).unwrap();
+    let diag = diag::display(&resolved).to_string();
+    assert_eq!(diag, r#"["aaa", ["bbb", "bbb"], "aaa", 1("aaa")]"#);
+
+    // Indefinite-length strings are not numbered, and references outside a namespace are kept.
+    let bytes = diag::diag_to_bytes(r#"[256([(_ "aaa", "b"), "ccc", 25(0)]), 25(0)]"#).unwrap();
+    let resolved = stringref::resolve(&bytes, 1024).unwrap();
+    let diag = diag::display(&resolved).to_string();
+    assert_eq!(diag, r#"[[(_ "aaa", "b"), "ccc", "ccc"], 25(0)]"#);
+
+    for diag in &["256(25(0))", r#"256(["aaa", 25(1)])"#, r#"256(["aaa", 25("aaa")])"#] {
+        let bytes = diag::diag_to_bytes(diag).unwrap();
+        assert!(stringref::resolve(&bytes, 1024).is_err(), "{}", diag);
+    }
+    assert!(stringref::resolve(b"\xd9\x01\x00", 1024).is_err());
+
+    // A few bytes can refer to a long string many times over.
+    let bytes = diag::diag_to_bytes(r#"256(["aaaaaaaaaa", 25(0), 25(0), 25(0)])"#).unwrap();
+    assert_eq!(stringref::resolve(&bytes, 1024).unwrap().len(), 45);
+    assert!(stringref::resolve(&bytes, 44).is_err());
+}
+
+#[test]
+fn test_serializer() {
+    let mut ser = Serializer::new(Vec::new()).self_described().string_refs();
+    ("abc", "abc").serialize(&mut ser).unwrap();
+    assert_eq!(ser.into_inner(), b"\xd9\xd9\xf7\xd9\x01\x00\x82\x63abc\xd8\x19\x00");
+
+    // A deterministic encoding writes strings in full.
+    let mut ser = Serializer::canonical(Vec::new()).string_refs();
+    ("abc", "abc").serialize(&mut ser).unwrap();
+    assert_eq!(ser.into_inner(), b"\x82\x63abc\x63abc");
+}
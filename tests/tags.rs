@@ -0,0 +1,124 @@
+#![cfg(feature = "std")]
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
+
+use serde_cbor::tags::{CborEncoded, Tagged};
+use serde_cbor::{diag, from_slice, to_vec, Value};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[test]
+fn test_round_trip() {
+    let tagged = Tagged::new(1000, Point { x: 1, y: -1 });
+    let bytes = to_vec(&tagged).unwrap();
+    assert_eq!(bytes, b"\xd9\x03\xe8\xa2\x61x\x01\x61y\x20");
+    assert_eq!(from_slice::<Tagged<Point>>(&bytes).unwrap(), tagged);
+
+    // The largest tag, and tags nested in each other.
+    let tagged = Tagged::new(u64::max_value(), Tagged::new(0, "a"));
+    let bytes = to_vec(&tagged).unwrap();
+    assert_eq!(bytes, b"\xdb\xff\xff\xff\xff\xff\xff\xff\xff\xc0\x61a");
+    let decoded: Tagged<Tagged<String>> = from_slice(&bytes).unwrap();
+    assert_eq!(decoded.tag, u64::max_value());
+    assert_eq!(decoded.value, Tagged::new(0, "a".to_string()));
+}
+
+#[test]
+fn test_borrowed() {
+    let bytes = b"\xd8\x20\x6bhttp://a.b/";
+    let tagged: Tagged<&str> = from_slice(bytes).unwrap();
+    assert_eq!(tagged, Tagged::new(32, "http://a.b/"));
+}
+
+#[test]
+fn test_untagged() {
+    let error = from_slice::<Tagged<u8>>(b"\x01").unwrap_err();
+    assert!(error.is_data());
+    assert!(error.to_string().starts_with("expected a tagged value"));
+
+    // Values that are not `Tagged` or `Value` ignore the tag.
+    assert_eq!(from_slice::<u8>(b"\xc1\x01").unwrap(), 1);
+    assert_eq!(from_slice::<Vec<u8>>(b"\x81\xc1\x01").unwrap(), vec![1]);
+}
+
+#[test]
+fn test_dispatch() {
+    #[derive(Debug, PartialEq)]
+    enum Item {
+        Time(i128),
+        Uri(String),
+    }
+
+    let items = vec![
+        Tagged::new(1, Value::Integer(1363896240)),
+        Tagged::new(32, Value::String("http://a.b/".to_string())),
+    ];
+    let bytes = to_vec(&items).unwrap();
+    let items: Vec<Item> = from_slice::<Vec<Tagged<Value>>>(&bytes)
+        .unwrap()
+        .into_iter()
+        .map(|item| match (item.tag, item.value) {
+            (1, Value::Integer(seconds)) => Item::Time(seconds),
+            (32, Value::String(uri)) => Item::Uri(uri),
+            _ => panic!("unexpected item"),
+        })
+        .collect();
+    assert_eq!(items, [Item::Time(1363896240), Item::Uri("http://a.b/".to_string())]);
+}
+
+#[test]
+fn test_cbor_encoded() {
+    // A COSE_Sign1 style structure with the protected header as embedded CBOR.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        protected: CborEncoded<Point>,
+        payload: u8,
+    }
+
+    let message = Message {
+        protected: CborEncoded(Point { x: 1, y: -1 }),
+        payload: 7,
+    };
+    assert_eq!(
+        diag::to_diag_string(&message).unwrap(),
+        r#"{"protected": 24(<<{"x": 1, "y": -1}>>), "payload": 7}"#
+    );
+    assert_eq!(from_slice::<Message>(&to_vec(&message).unwrap()).unwrap(), message);
+
+    let message: Message =
+        diag::from_diag_str(r#"{"protected": <<{"x": 2, "y": 3}>>, "payload": 7}"#).unwrap();
+    assert_eq!(message.protected.0, Point { x: 2, y: 3 });
+
+    for diag in &[
+        r#"{"protected": 24(h'a1'), "payload": 7}"#,
+        r#"{"protected": 24(<<{"x": 2, "y": 3}, 1>>), "payload": 7}"#,
+        r#"{"protected": 25(<<{"x": 2, "y": 3}>>), "payload": 7}"#,
+        r#"{"protected": 24({"x": 2, "y": 3}), "payload": 7}"#,
+    ] {
+        assert!(diag::from_diag_str::<Message>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[test]
+fn test_self_describe_skipped() {
+    let bytes = serde_cbor::ser::to_vec_sd(&Tagged::new(32, "http://a.b/")).unwrap();
+    assert_eq!(&bytes[..5], b"\xd9\xd9\xf7\xd8\x20");
+    let tagged: Tagged<String> = from_slice(&bytes).unwrap();
+    assert_eq!(tagged, Tagged::new(32, "http://a.b/".to_string()));
+    let tagged: Tagged<String> = serde_cbor::from_reader(&bytes[..]).unwrap();
+    assert_eq!(tagged.tag, 32);
+
+    // Only at the start of the input.
+    assert!(from_slice::<Tagged<u8>>(b"\xd9\xd9\xf7\x01").is_err());
+    let tagged: Vec<Tagged<u8>> = from_slice(b"\x81\xd9\xd9\xf7\x01").unwrap();
+    assert_eq!(tagged, vec![Tagged::new(55799, 1)]);
+    let encoded: CborEncoded<u8> = from_slice(b"\xd9\xd9\xf7\xd8\x18\x41\x01").unwrap();
+    assert_eq!(encoded.0, 1);
+}
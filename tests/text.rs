@@ -0,0 +1,140 @@
+#![cfg(feature = "std")]
+
+#[cfg(feature = "mime")]
+extern crate mime;
+#[cfg(feature = "regex")]
+extern crate regex;
+extern crate serde_cbor;
+
+use serde_cbor::text::{Base64, Base64Url, MimeMessage, Regex};
+use serde_cbor::{diag, from_slice, to_vec};
+
+#[test]
+fn test_base64url() {
+    for &(bytes, expected) in &[
+        (&b""[..], r#"33("")"#),
+        (&b"\xfb"[..], r#"33("-w")"#),
+        (&b"\xfb\xff"[..], r#"33("-_8")"#),
+        (&b"\xfb\xff\xbf"[..], r#"33("-_-_")"#),
+    ] {
+        let text = Base64Url::encode(bytes);
+        assert_eq!(diag::to_diag_string(&text).unwrap(), expected);
+        assert_eq!(text.decode(), bytes);
+        assert_eq!(from_slice::<Base64Url>(&to_vec(&text).unwrap()).unwrap(), text);
+    }
+
+    let text: Base64Url = diag::from_diag_str(r#""yv66vg""#).unwrap();
+    assert_eq!(text.as_str(), "yv66vg");
+    for diag in &[
+        r#"33("yv66vg==")"#,
+        r#"33("yv66vh")"#,
+        r#"33("y")"#,
+        r#"33("yv+/")"#,
+        r#"33("yv 6")"#,
+        r#"34("yv66vg")"#,
+        r#"33(h'cafe')"#,
+    ] {
+        assert!(diag::from_diag_str::<Base64Url>(diag).is_err(), "{}", diag);
+    }
+    assert!(Base64Url::new("yv66vh".to_string()).is_none());
+}
+
+#[test]
+fn test_base64() {
+    for &(bytes, expected) in &[
+        (&b""[..], r#"34("")"#),
+        (&b"\xfb"[..], r#"34("+w==")"#),
+        (&b"\xfb\xff"[..], r#"34("+/8=")"#),
+        (&b"\xfb\xff\xbf"[..], r#"34("+/+/")"#),
+    ] {
+        let text = Base64::encode(bytes);
+        assert_eq!(diag::to_diag_string(&text).unwrap(), expected);
+        assert_eq!(text.decode(), bytes);
+        assert_eq!(from_slice::<Base64>(&to_vec(&text).unwrap()).unwrap(), text);
+    }
+
+    let text = Base64::new("yv66vg==".to_string()).unwrap();
+    assert_eq!(text.decode(), b"\xca\xfe\xba\xbe");
+    assert_eq!(text.into_string(), "yv66vg==");
+    for diag in &[
+        r#"34("yv66vg")"#,
+        r#"34("yv66vg=")"#,
+        r#"34("yv66v===")"#,
+        r#"34("yv66vh==")"#,
+        r#"34("yv-_")"#,
+        r#"34("=AAA")"#,
+        r#"33("yv66vg==")"#,
+    ] {
+        assert!(diag::from_diag_str::<Base64>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[test]
+fn test_regex() {
+    let regex = Regex::new("^a+b$".to_string());
+    assert_eq!(diag::to_diag_string(&regex).unwrap(), r#"35("^a+b$")"#);
+    assert_eq!(from_slice::<Regex>(&to_vec(&regex).unwrap()).unwrap(), regex);
+
+    // The pattern is not checked.
+    let regex: Regex = diag::from_diag_str(r#"35("(?<=a)b")"#).unwrap();
+    assert_eq!(regex.into_string(), "(?<=a)b");
+    let regex: Regex = diag::from_diag_str(r#""^a+b$""#).unwrap();
+    assert_eq!(regex.as_str(), "^a+b$");
+    for diag in &[r#"32("^a+b$")"#, "35(1)"] {
+        assert!(diag::from_diag_str::<Regex>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn test_regex_conversion() {
+    let regex = Regex::from(regex::Regex::new("^a+b$").unwrap());
+    assert_eq!(regex.as_str(), "^a+b$");
+    assert!(regex.to_regex().unwrap().is_match("aab"));
+    assert!(Regex::new("(a".to_string()).to_regex().is_err());
+}
+
+#[test]
+fn test_mime_message() {
+    let message = MimeMessage::new(
+        "From: a@example.com\r\n\
+         content-type : multipart/mixed;\r\n\
+         \tboundary=frontier\r\n\
+         Subject: Hi\r\n\
+         \r\n\
+         Content-Type: text/html\r\n"
+            .to_string(),
+    );
+    assert_eq!(
+        message.header("Content-Type").unwrap(),
+        "multipart/mixed;\tboundary=frontier"
+    );
+    assert_eq!(message.header("SUBJECT").unwrap(), "Hi");
+    assert_eq!(message.header("To"), None);
+    let bytes = to_vec(&message).unwrap();
+    assert_eq!(&bytes[..2], b"\xd8\x24");
+    assert_eq!(from_slice::<MimeMessage>(&bytes).unwrap(), message);
+
+    let message: MimeMessage = diag::from_diag_str(r#""Hello""#).unwrap();
+    assert_eq!(message.into_string(), "Hello");
+    for diag in &[r#"35("Hello")"#, "36(1)"] {
+        assert!(diag::from_diag_str::<MimeMessage>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "mime")]
+#[test]
+fn test_mime_content_type() {
+    let message = MimeMessage::new("Content-Type: text/html; charset=utf-8\n\n<p>".to_string());
+    let content_type = message.content_type().unwrap();
+    assert_eq!(content_type.essence_str(), "text/html");
+    assert_eq!(content_type.get_param(mime::CHARSET).unwrap(), "utf-8");
+
+    let message = MimeMessage::new("Subject: Hi\n\nHello".to_string());
+    let content_type = message.content_type().unwrap();
+    assert_eq!(content_type.essence_str(), "text/plain");
+    assert_eq!(content_type.get_param(mime::CHARSET).unwrap(), "us-ascii");
+
+    let message = MimeMessage::new("Content-Type: text\n\nHello".to_string());
+    assert_eq!(message.content_type(), None);
+}
@@ -0,0 +1,133 @@
+#![cfg(feature = "std")]
+
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+extern crate serde_cbor;
+
+use serde_cbor::typed_array::{NdArray, TypedArray};
+use serde_cbor::{diag, from_slice, to_vec};
+
+#[test]
+fn test_to_vec() {
+    // 65(h'00010002'), unsigned 16-bit integers in big-endian order
+    let bytes = diag::diag_to_bytes("65(h'00010002')").unwrap();
+    let array: TypedArray = from_slice(&bytes).unwrap();
+    assert_eq!(array.tag(), Some(65));
+    assert_eq!(array.bytes(), &[0, 1, 0, 2]);
+    assert_eq!(array.to_vec::<u16>(), Some(vec![1, 2]));
+    assert_eq!(array.to_vec::<i16>(), None);
+    assert_eq!(array.to_vec::<u8>(), None);
+
+    let bytes = diag::diag_to_bytes("85(h'0000803f0000c0bf')").unwrap();
+    let array: TypedArray = from_slice(&bytes).unwrap();
+    assert_eq!(array.to_vec::<f32>(), Some(vec![1.0, -1.5]));
+
+    let bytes = diag::diag_to_bytes("68(h'00ff')").unwrap();
+    let array: TypedArray = from_slice(&bytes).unwrap();
+    assert_eq!(array.to_vec::<u8>(), Some(vec![0, 255]));
+
+    let bytes = diag::diag_to_bytes("h'fffe'").unwrap();
+    let array: TypedArray = from_slice(&bytes).unwrap();
+    assert_eq!(array.tag(), None);
+    assert_eq!(array.to_vec::<i8>(), Some(vec![-1, -2]));
+    assert_eq!(array.to_vec::<u32>(), None);
+}
+
+#[test]
+fn test_errors() {
+    let bytes = diag::diag_to_bytes("2(h'01')").unwrap();
+    assert!(from_slice::<TypedArray>(&bytes).is_err());
+    let bytes = diag::diag_to_bytes("69([1, 2])").unwrap();
+    assert!(from_slice::<TypedArray>(&bytes).is_err());
+}
+
+#[test]
+fn test_round_trip() {
+    let bytes = diag::diag_to_bytes("[82(h'3ff0000000000000'), h'']").unwrap();
+    let arrays: Vec<TypedArray> = from_slice(&bytes).unwrap();
+    assert_eq!(arrays[0].to_vec::<f64>(), Some(vec![1.0]));
+    assert_eq!(to_vec(&arrays).unwrap(), bytes);
+}
+
+#[test]
+fn test_nd_array() {
+    let matrix = NdArray::new(vec![2, 3], vec![1u8, 2, 3, 4, 5, 6]).unwrap();
+    assert_eq!(matrix.shape(), &[2, 3]);
+    assert!(!matrix.is_column_major());
+    let diag = diag::to_diag_string(&matrix).unwrap();
+    assert_eq!(diag, "40([[2, 3], [1, 2, 3, 4, 5, 6]])");
+    let decoded: NdArray<u8> = diag::from_diag_str(&diag).unwrap();
+    assert_eq!(decoded, matrix);
+
+    let matrix = NdArray::new_column_major(vec![2, 2], vec![1.5, -1.0, 0.0, 2.0]).unwrap();
+    let diag = diag::to_diag_string(&matrix).unwrap();
+    assert_eq!(diag, "1040([[2, 2], [1.5, -1.0, 0.0, 2.0]])");
+    let decoded: NdArray<f64> = diag::from_diag_str(&diag).unwrap();
+    assert!(decoded.is_column_major());
+    assert_eq!(decoded.into_data(), vec![1.5, -1.0, 0.0, 2.0]);
+
+    let decoded: NdArray<u8> = diag::from_diag_str("[[3], [1, 2, 3]]").unwrap();
+    assert!(!decoded.is_column_major());
+    assert_eq!(decoded.data(), &[1, 2, 3]);
+
+    assert!(NdArray::new(vec![2, 2], vec![1, 2, 3]).is_none());
+    assert!(NdArray::<u8>::new(vec![usize::MAX, 2], vec![]).is_none());
+    for diag in &["40([[2, 2], [1, 2, 3]])", "41([[1], [1]])", "40([1, 2])", "40([[1]])"] {
+        assert!(diag::from_diag_str::<NdArray<u8>>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "ndarray")]
+#[test]
+fn test_ndarray() {
+    use ndarray::{arr2, ArrayD, ShapeBuilder};
+
+    let array = arr2(&[[1, 2, 3], [4, 5, 6]]).into_dyn();
+    let matrix = NdArray::from(array.clone());
+    assert_eq!(matrix.data(), &[1, 2, 3, 4, 5, 6]);
+    assert_eq!(matrix.to_ndarray(), Some(array.clone()));
+
+    // The same matrix in column-major order.
+    let matrix = NdArray::new_column_major(vec![2, 3], vec![1, 4, 2, 5, 3, 6]).unwrap();
+    assert_eq!(matrix.to_ndarray(), Some(array));
+
+    let column_major = ArrayD::from_shape_vec(vec![2, 2].f(), vec![1, 3, 2, 4]).unwrap();
+    assert_eq!(NdArray::from(column_major).data(), &[1, 2, 3, 4]);
+}
+
+#[cfg(feature = "bytemuck")]
+#[test]
+fn test_view() {
+    let elements: Vec<u32> = vec![1, 2, 0xdead_beef];
+    let array = TypedArray::from_elements(&elements);
+    let bytes = to_vec(&array).unwrap();
+    let decoded: TypedArray = from_slice(&bytes).unwrap();
+    assert_eq!(decoded.to_vec::<u32>(), Some(elements.clone()));
+
+    // Copy the encoding to where the byte string is aligned for `u32`.
+    let mut storage = vec![0u32; bytes.len() / 4 + 2];
+    let start = 4 - (bytes.len() - 12) % 4;
+    let buffer = &mut bytemuck_bytes(&mut storage)[start..start + bytes.len()];
+    buffer.copy_from_slice(&bytes);
+    let aligned: TypedArray = from_slice(buffer).unwrap();
+    assert_eq!(aligned.view::<u32>(), Some(&elements[..]));
+    assert_eq!(aligned.view::<u16>(), None);
+    assert_eq!(aligned.view::<u8>(), None);
+
+    // The other byte order has to be copied.
+    let tag = if cfg!(target_endian = "little") {
+        "66"
+    } else {
+        "70"
+    };
+    let bytes = diag::diag_to_bytes(&format!("{}(h'00000001')", tag)).unwrap();
+    let swapped: TypedArray = from_slice(&bytes).unwrap();
+    assert_eq!(swapped.view::<u32>(), None);
+    assert_eq!(swapped.to_vec::<u32>(), Some(vec![1]));
+}
+
+#[cfg(feature = "bytemuck")]
+fn bytemuck_bytes(storage: &mut [u32]) -> &mut [u8] {
+    let len = storage.len() * 4;
+    unsafe { std::slice::from_raw_parts_mut(storage.as_mut_ptr() as *mut u8, len) }
+}
@@ -0,0 +1,133 @@
+#![cfg(all(feature = "std", feature = "unsealed-read"))]
+
+extern crate serde;
+extern crate serde_cbor;
+
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+use serde_cbor::de::{Read, Reference, Scratch};
+use serde_cbor::error::{Error, Result};
+use serde_cbor::Deserializer;
+
+// Input queued up by someone else, such as an interrupt handler.
+struct QueueRead {
+    queue: VecDeque<u8>,
+    offset: u64,
+    raw: Vec<u8>,
+    raw_depth: usize,
+}
+
+impl QueueRead {
+    fn new(bytes: &[u8]) -> QueueRead {
+        QueueRead {
+            queue: bytes.iter().cloned().collect(),
+            offset: 0,
+            raw: Vec::new(),
+            raw_depth: 0,
+        }
+    }
+}
+
+impl<'de> Read<'de> for QueueRead {
+    fn next(&mut self) -> Result<Option<u8>> {
+        let byte = self.queue.pop_front();
+        if let Some(byte) = byte {
+            self.offset += 1;
+            if self.raw_depth > 0 {
+                self.raw.push(byte);
+            }
+        }
+        Ok(byte)
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.queue.front().cloned())
+    }
+
+    fn read<S>(
+        &mut self,
+        n: usize,
+        scratch: &mut S,
+        offset: usize,
+        _: usize,
+    ) -> Result<Reference<'de>>
+    where
+        S: Scratch,
+    {
+        // All of the input is queued up, so the scratch buffer can grow at once.
+        if n > self.queue.len() {
+            return Err(Error::eof(self.offset + self.queue.len() as u64));
+        }
+        scratch.resize(offset + n);
+        self.read_into(&mut scratch[offset..])?;
+        Ok(Reference::Copied)
+    }
+
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<()> {
+        for byte in buf {
+            *byte = self.next()?.ok_or_else(|| Error::eof(self.offset))?;
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        for _ in 0..n {
+            self.next()?.ok_or_else(|| Error::eof(self.offset))?;
+        }
+        Ok(())
+    }
+
+    fn discard(&mut self) {
+        let _ = self.next();
+    }
+
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn begin_raw(&mut self) -> usize {
+        self.raw_depth += 1;
+        self.raw.len()
+    }
+
+    fn end_raw<S>(&mut self, start: usize, scratch: &mut S) -> Reference<'de>
+    where
+        S: Scratch,
+    {
+        scratch.clear();
+        scratch.extend_from_slice(&self.raw[start..]);
+        self.raw.truncate(start);
+        self.raw_depth -= 1;
+        Reference::Copied
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Frame {
+    id: u16,
+    text: String,
+    data: Vec<u8>,
+}
+
+#[test]
+fn test_custom_read() {
+    // {"id": 7, "text": (_ "a", "b"), "data": [1, 2]}
+    let bytes = b"\xa3\x62id\x07\x64text\x7f\x61a\x61b\xff\x64data\x82\x01\x02";
+    let mut deserializer = Deserializer::new(QueueRead::new(bytes));
+    let frame = Frame::deserialize(&mut deserializer).unwrap();
+    deserializer.end().unwrap();
+    assert_eq!(
+        frame,
+        Frame {
+            id: 7,
+            text: "ab".to_string(),
+            data: vec![1, 2],
+        }
+    );
+
+    let mut deserializer = Deserializer::new(QueueRead::new(&bytes[..20]));
+    let error = Frame::deserialize(&mut deserializer).unwrap_err();
+    assert!(error.is_eof());
+    assert_eq!(error.offset(), 20);
+}
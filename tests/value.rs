@@ -1,6 +1,10 @@
+#![cfg(feature = "std")]
+
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
 extern crate serde_cbor;
+extern crate serde_bytes;
 
 use std::collections::BTreeMap;
 
@@ -21,7 +25,9 @@ struct Struct<'a> {
 }
 
 use std::iter::FromIterator;
+use serde_cbor::error::PathSegment;
 use serde_cbor::Value;
+use serde_cbor::value::{CanonicalValue, Map, MergeStrategy};
 
 #[test]
 fn serde() {
@@ -73,3 +79,403 @@ fn serde() {
 
     assert_eq!(value, data_de_value);
 }
+
+#[test]
+fn test_object_key_try_from() {
+    use serde_cbor::ObjectKey;
+    use std::convert::TryFrom;
+
+    let key = ObjectKey::try_from(Value::Integer(-1)).unwrap();
+    assert_eq!(key, ObjectKey::Integer(-1));
+    assert_eq!(Value::from(key), Value::Integer(-1));
+    let key = ObjectKey::try_from(Value::String("a".to_string())).unwrap();
+    assert_eq!(key, ObjectKey::String("a".to_string()));
+
+    let error = ObjectKey::try_from(Value::Integer(1 << 64)).unwrap_err();
+    assert_eq!(error.to_string(), "key 18446744073709551616 out of range");
+    assert!(error.is_data());
+    assert!(ObjectKey::try_from(Value::F64(1.0)).is_err());
+    assert!(ObjectKey::try_from(Value::Array(vec![])).is_err());
+    assert!(ObjectKey::try_from(Value::Tag(1, Box::new(Value::Integer(0)))).is_err());
+}
+
+#[test]
+fn test_tag() {
+    use serde_cbor::tags::Tagged;
+
+    // 32(1("a"))
+    let bytes = b"\xd8\x20\xc1\x61a";
+    let value: Value = serde_cbor::from_slice(bytes).unwrap();
+    let inner = Value::Tag(1, Box::new(Value::String("a".to_string())));
+    assert_eq!(value, Value::Tag(32, Box::new(inner.clone())));
+    assert_eq!(value.as_tag(), Some((32, &inner)));
+    assert_eq!(serde_cbor::to_vec(&value).unwrap(), bytes);
+
+    let tagged = Tagged::new(32, Tagged::new(1, "a".to_string()));
+    assert_eq!(serde_cbor::to_value(&tagged).unwrap(), value);
+    let decoded: Tagged<Tagged<String>> = serde_cbor::from_value(value).unwrap();
+    assert_eq!(decoded, tagged);
+}
+
+#[test]
+fn test_full_data_model() {
+    let items: &[(&[u8], Value)] = &[
+        (b"\x1b\xff\xff\xff\xff\xff\xff\xff\xff", Value::from(u64::max_value())),
+        (b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff", Value::Integer(-1 << 64)),
+        (b"\xf7", Value::Undefined),
+        (b"\xf0", Value::Simple(16)),
+        (b"\xf8\xff", Value::Simple(255)),
+    ];
+    for &(bytes, ref value) in items {
+        assert_eq!(serde_cbor::from_slice::<Value>(bytes).unwrap(), *value);
+        assert_eq!(serde_cbor::to_vec(value).unwrap(), bytes);
+    }
+    assert_eq!(Value::Integer(-1 << 64).as_i64(), None);
+    let n: i128 = serde_cbor::from_value(Value::Integer(-1 << 64)).unwrap();
+    assert_eq!(n, -1 << 64);
+
+    // Simple values below 32 only have the short form.
+    assert!(serde_cbor::from_slice::<Value>(b"\xf8\x10").is_err());
+    assert!(serde_cbor::to_vec(&Value::Simple(24)).is_err());
+    // Other types do not take simple values.
+    assert!(serde_cbor::from_slice::<()>(b"\xf0").is_err());
+
+    // Integers beyond major types 0 and 1 are bignums.
+    let value = serde_cbor::to_value(&(1u128 << 64)).unwrap();
+    let bytes = Value::Bytes(vec![1, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(value, Value::Tag(2, Box::new(bytes)));
+    assert_eq!(serde_cbor::to_value(&(-1i128 << 64)).unwrap(), Value::Integer(-1 << 64));
+}
+
+#[test]
+fn test_complex_keys() {
+    // {[1, 2]: 1, 1.5: 2, 32("a"): 3, {1: 2}: 4}
+    let bytes = b"\xa4\x82\x01\x02\x01\xf9\x3e\x00\x02\xd8\x20\x61a\x03\xa1\x01\x02\x04";
+    let value: Value = serde_cbor::from_slice(bytes).unwrap();
+    let map = value.as_object().unwrap();
+    let key = Value::Array(vec![Value::Integer(1), Value::Integer(2)]);
+    assert_eq!(map[&key], Value::Integer(1));
+    assert_eq!(map[&Value::F64(1.5)], Value::Integer(2));
+    let key = Value::Tag(32, Box::new(Value::String("a".to_string())));
+    assert_eq!(map[&key], Value::Integer(3));
+    let inner = Map::from_iter(vec![(Value::Integer(1), Value::Integer(2))]);
+    assert_eq!(map[&Value::Object(inner)], Value::Integer(4));
+
+    let encoded = serde_cbor::to_vec(&value).unwrap();
+    assert_eq!(serde_cbor::from_slice::<Value>(&encoded).unwrap(), value);
+    assert_eq!(serde_cbor::to_value(map).unwrap(), value);
+}
+
+#[test]
+fn test_order() {
+    let nan = Value::F64(std::f64::NAN);
+    assert_eq!(nan, nan.clone());
+    assert!(Value::F64(-0.0) < Value::F64(0.0));
+    assert!(Value::Integer(-1) < Value::Integer(1));
+    assert!(Value::Integer(1) < Value::String("a".to_string()));
+}
+
+#[cfg(feature = "indexmap")]
+#[test]
+fn test_map_order() {
+    // {"b": 1, "a": 2}
+    let bytes = b"\xa2\x61b\x01\x61a\x02";
+    let mut value: Value = serde_cbor::from_slice(bytes).unwrap();
+    value.as_object_mut().unwrap()[&Value::String("a".to_string())] = Value::Integer(3);
+    assert_eq!(serde_cbor::to_vec(&value).unwrap(), b"\xa2\x61b\x01\x61a\x03");
+
+    // Maps with the same entries are equal in any order.
+    let other: Value = serde_cbor::from_slice(b"\xa2\x61a\x03\x61b\x01").unwrap();
+    assert_eq!(value, other);
+}
+
+#[test]
+fn test_cbor_macro() {
+    let name = "cbor";
+    let value = cbor!({
+        "key" => 1,
+        2 => [true, null],
+        99(h"deadbeef") => {},
+        "nested" => [[], [1 + 2, -4], 1(0(undefined)),],
+        name => name.len() as u64,
+    });
+    let diag = r#"{"key": 1, 2: [true, null], 99(h'deadbeef'): {},
+        "nested": [[], [3, -4], 1(0(undefined))], "cbor": 4}"#;
+    assert_eq!(value, serde_cbor::diag::from_diag_str::<Value>(diag).unwrap());
+    assert_eq!(cbor!([]), Value::Array(vec![]));
+    assert_eq!(cbor!(h""), Value::Bytes(vec![]));
+    assert_eq!(cbor!(-1), Value::Integer(-1));
+}
+
+#[test]
+fn test_index() {
+    let mut value = cbor!({
+        "config" => {"port" => 8080, "hosts" => ["a", "b"]},
+        1 => "one",
+    });
+    assert_eq!(value["config"]["port"], Value::Integer(8080));
+    assert_eq!(value["config"]["hosts"][1], Value::from("b"));
+    assert_eq!(value[&Value::Integer(1)], Value::from("one"));
+    assert_eq!(value["missing"][3], Value::Null);
+
+    value["config"]["port"] = Value::Integer(443);
+    value["config"]["hosts"][0] = Value::from("c");
+    value["new"]["nested"] = Value::Bool(true);
+    let expected = cbor!({
+        "config" => {"port" => 443, "hosts" => ["c", "b"]},
+        1 => "one",
+        "new" => {"nested" => true},
+    });
+    assert_eq!(value, expected);
+}
+
+#[test]
+fn test_pointer() {
+    let mut value = cbor!({
+        "a/b" => [{"~" => 1}],
+        "" => 2,
+        3 => 99([4]),
+        "4" => 5,
+        4 => 6,
+    });
+    assert_eq!(value.pointer(""), Some(&value.clone()));
+    assert_eq!(value.pointer("/a~1b/0/~0"), Some(&Value::Integer(1)));
+    assert_eq!(value.pointer("/"), Some(&Value::Integer(2)));
+    assert_eq!(value.pointer("/3"), Some(&cbor!(99([4]))));
+    assert_eq!(value.pointer("/4"), Some(&Value::Integer(5)));
+    assert_eq!(value.pointer("/a~1b/00"), None);
+    assert_eq!(value.pointer("/a~1b/1"), None);
+    assert_eq!(value.pointer("/3/0"), None);
+    assert_eq!(value.pointer("a~1b"), None);
+
+    *value.pointer_mut("/a~1b/0/~0").unwrap() = Value::Integer(7);
+    assert_eq!(value["a/b"][0]["~"], Value::Integer(7));
+}
+
+#[test]
+fn test_encoded_size() {
+    let diag = r#"[0, 23, 24, -256, 65536, 4294967296, 18446744073709551615,
+        -18446744073709551616, 2(h'010000000000000000'), 3(h'ffffffffffffffffff'),
+        h'', "abc", [], {1: [2]}, 1000(-1), 1.0, 100000.0, 1.1, NaN, -Infinity,
+        true, null, undefined, simple(16), simple(255)]"#;
+    let value = serde_cbor::diag::parse(diag).unwrap();
+    let encoded = serde_cbor::to_vec(&value).unwrap();
+    assert_eq!(value.encoded_size(), encoded.len());
+    assert_eq!(serde_cbor::ser::serialized_size(&value).unwrap(), encoded.len());
+    for item in value.as_array().unwrap() {
+        assert_eq!(item.encoded_size(), serde_cbor::to_vec(item).unwrap().len(), "{:?}", item);
+    }
+    assert_eq!(Value::Integer(i128::min_value()).encoded_size(), 18);
+}
+
+#[test]
+fn test_canonical_order() {
+    let value = serde_cbor::diag::parse(
+        r#"{"aa": 0, "b": 1, -1: 2, 10: 3, 100: 4, h'00': 5, [1]: 6, false: 7, 1.5: 8}"#,
+    )
+    .unwrap();
+    let encoded = serde_cbor::ser::to_vec_canonical(&value).unwrap();
+    let written: Map = serde_cbor::from_slice(&encoded).unwrap();
+    let written: Vec<_> = written.values().cloned().collect();
+
+    let mut keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+    keys.sort_by(Value::canonical_cmp);
+    let sorted: Vec<_> = keys.iter().map(|key| value[key].clone()).collect();
+    if cfg!(feature = "indexmap") {
+        assert_eq!(sorted, written);
+    }
+    let order: Vec<_> = sorted.iter().map(|v| v.as_integer().unwrap()).collect();
+    assert_eq!(order, vec![3, 4, 2, 5, 1, 0, 6, 7, 8]);
+
+    let keys: Vec<_> = keys.into_iter().map(CanonicalValue::from).collect();
+    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    let search = CanonicalValue::from(Value::from("b"));
+    assert_eq!(keys.binary_search(&search), Ok(4));
+
+    // An integer and a bignum of the same value encode the same.
+    let big = Value::Integer(1 << 64);
+    let bignum = Value::Tag(2, Box::new(Value::Bytes(vec![1, 0, 0, 0, 0, 0, 0, 0, 0])));
+    assert_ne!(big, bignum);
+    assert_eq!(CanonicalValue::from(big), CanonicalValue::from(bignum));
+}
+
+#[test]
+fn test_merge() {
+    let base = cbor!({
+        "a" => {"x" => 1, "y" => [1]},
+        "b" => [1, 2],
+        "c" => 1(0),
+        "d" => 1,
+    });
+    let layer = cbor!({
+        "a" => {"y" => [2], "z" => null},
+        "b" => [3],
+        "c" => 1(5),
+        "d" => null,
+        "e" => 2(h"00"),
+    });
+
+    let mut merged = base.clone();
+    merged.merge(layer.clone(), MergeStrategy::new());
+    let expected = cbor!({
+        "a" => {"x" => 1, "y" => [2], "z" => null},
+        "b" => [3],
+        "c" => 1(5),
+        "d" => null,
+        "e" => 2(h"00"),
+    });
+    assert_eq!(merged, expected);
+
+    let mut merged = base.clone();
+    merged.merge(layer, MergeStrategy::new().concat_arrays().null_deletes());
+    let expected = cbor!({
+        "a" => {"x" => 1, "y" => [1, 2]},
+        "b" => [1, 2, 3],
+        "c" => 1(5),
+        "e" => 2(h"00"),
+    });
+    assert_eq!(merged, expected);
+
+    let mut merged = base;
+    merged.merge(cbor!([1]), MergeStrategy::new().concat_arrays());
+    assert_eq!(merged, cbor!([1]));
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+    Empty,
+    Circle(u32),
+    Rect(u32, u32),
+    Poly { points: Vec<(i32, i32)> },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Drawing {
+    id: serde_cbor::tags::Tagged<u64>,
+    blob: serde_bytes::ByteBuf,
+    shapes: Vec<Shape>,
+    big: i128,
+    #[serde(with = "serde_cbor::with::packed")]
+    inner: Inner,
+    note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Inner {
+    a: u8,
+    b: u8,
+}
+
+#[test]
+fn test_value_round_trip() {
+    let drawing = Drawing {
+        id: serde_cbor::tags::Tagged::new(37, 7),
+        blob: serde_bytes::ByteBuf::from(vec![1, 2, 3]),
+        shapes: vec![
+            Shape::Empty,
+            Shape::Circle(1),
+            Shape::Rect(2, 3),
+            Shape::Poly { points: vec![(0, 0), (1, -1)] },
+        ],
+        big: -1 << 100,
+        inner: Inner { a: 1, b: 2 },
+        note: None,
+    };
+    // `to_value` gives what deserializing the encoding gives.
+    let value = serde_cbor::to_value(&drawing).unwrap();
+    let bytes = serde_cbor::to_vec(&drawing).unwrap();
+    assert_eq!(value, serde_cbor::from_slice::<Value>(&bytes).unwrap());
+    assert_eq!(value["id"], cbor!(37(7)));
+    assert_eq!(value["blob"], cbor!(h"010203"));
+    assert_eq!(value["shapes"][3], cbor!(["Poly", {"points" => [[0, 0], [1, -1]]}]));
+    assert_eq!(value["inner"], cbor!({0 => 1, 1 => 2}));
+    assert_eq!(serde_cbor::from_value::<Drawing>(value).unwrap(), drawing);
+
+    let value = Value::Array(vec![
+        Value::Undefined,
+        Value::Simple(16),
+        cbor!(1(h"00")),
+        cbor!(2(2(h"01"))),
+    ]);
+    assert_eq!(serde_cbor::from_value::<Value>(value.clone()).unwrap(), value);
+    let big: u128 = serde_cbor::from_value(cbor!(2(h"010000000000000000"))).unwrap();
+    assert_eq!(big, 1 << 64);
+
+    // Other types do not take simple values, and arrays have to be used up.
+    assert!(serde_cbor::from_value::<()>(Value::Simple(16)).is_err());
+    let error = serde_cbor::from_value::<(u8,)>(cbor!([1, 2])).unwrap_err();
+    assert!(error.to_string().contains("fewer elements"));
+    let error = serde_cbor::from_value::<Vec<String>>(cbor!(["a", 1])).unwrap_err();
+    assert_eq!(error.path(), &[PathSegment::Index(1)]);
+}
+
+#[test]
+fn test_direct_codec() {
+    let items: &[&[u8]] = &[
+        b"\x1b\xff\xff\xff\xff\xff\xff\xff\xff",
+        b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff",
+        b"\xc2\x49\x01\x00\x00\x00\x00\x00\x00\x00\x00",
+        b"\x5f\x41\x01\x42\x02\x03\xff",
+        b"\x7f\x61a\x62bc\xff",
+        b"\x9f\x01\xbf\x61a\xf7\xff\xff",
+        b"\xa2\x01\xf9\x3e\x00\x02\xfa\x47\xc3\x50\x00",
+        b"\x83\xfb\x3f\xf1\x99\x99\x99\x99\x99\x9a\xf9\x7c\x00\xf9\x7e\x00",
+        b"\x84\xf0\xf8\xff\xf4\xf6",
+        b"\xd8\x20\xc1\x61a",
+        b"\xd9\xd9\xf7\x80",
+    ];
+    for bytes in items {
+        let value = Value::from_slice(bytes).unwrap();
+        let expected: Value = serde_cbor::from_slice(bytes).unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(value.to_vec().unwrap(), serde_cbor::to_vec(&value).unwrap());
+    }
+    let value = Value::from_slice(b"\x82\xf9\x7e\x00\xf9\x7e\x00").unwrap();
+    assert_eq!(value.to_vec().unwrap(), b"\x82\xf9\x7e\x00\xf9\x7e\x00");
+
+    let errors: &[(&[u8], &str)] = &[
+        (b"\x82\x01", "EOF while parsing a value at offset 2"),
+        (b"\x01\x02", "trailing data at offset 1"),
+        (b"\x62\xc3\x28", "invalid UTF-8 at offset 1"),
+        (b"\xf8\x10", "unexpected code"),
+        (b"\x5f\x61a\xff", "unexpected code at offset 1"),
+        (b"\x1c", "unassigned type"),
+    ];
+    for &(bytes, message) in errors {
+        let error = Value::from_slice(bytes).unwrap_err();
+        assert_eq!(error.to_string(), message);
+        let expected = serde_cbor::from_slice::<Value>(bytes).unwrap_err();
+        let category = |e: &serde_cbor::error::Error| (e.is_syntax(), e.is_data(), e.is_eof());
+        assert_eq!(category(&error), category(&expected));
+    }
+    let deep = vec![0x81; 200];
+    assert!(Value::from_slice(&deep).is_err());
+    assert!(Value::Simple(24).to_vec().is_err());
+}
+
+#[test]
+fn test_value_ref() {
+    use serde_cbor::value::ValueRef;
+    use std::borrow::Cow;
+
+    // [h'0102', "abc", (_ "a", "b"), 1(-1), {"k": undefined}, 2(h'010000000000000000')]
+    let bytes = b"\x86\x42\x01\x02\x63abc\x7f\x61a\x61b\xff\xc1\x20\xa1\x61k\xf7\
+                  \xc2\x49\x01\x00\x00\x00\x00\x00\x00\x00\x00";
+    let value: ValueRef = serde_cbor::from_slice(bytes).unwrap();
+    let array = value.as_array().unwrap();
+    assert_eq!(array[0], ValueRef::Bytes(Cow::Borrowed(&bytes[2..4])));
+    match array[1] {
+        ValueRef::String(Cow::Borrowed(s)) => assert_eq!(s, "abc"),
+        ref other => panic!("not borrowed: {:?}", other),
+    }
+    // The chunks of an indefinite-length string are joined in a copy.
+    assert_eq!(array[2], ValueRef::String(Cow::Owned("ab".to_string())));
+    assert_eq!(array[3].as_tag(), Some((1, &ValueRef::Integer(-1))));
+    assert_eq!(array[4].get("k"), Some(&ValueRef::Undefined));
+    assert_eq!(array[5].as_tag().unwrap().1.as_bytes().unwrap().len(), 9);
+
+    let owned = Value::from(value.clone());
+    assert_eq!(owned, serde_cbor::from_slice::<Value>(bytes).unwrap());
+    assert_eq!(ValueRef::from(&owned), value);
+    assert_eq!(serde_cbor::to_vec(&value).unwrap(), serde_cbor::to_vec(&owned).unwrap());
+}
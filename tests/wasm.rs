@@ -0,0 +1,77 @@
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+extern crate js_sys;
+extern crate serde_cbor;
+extern crate wasm_bindgen;
+extern crate wasm_bindgen_test;
+
+use std::collections::BTreeMap;
+
+use js_sys::{Array, Map, Object, Reflect, Uint8Array};
+use serde_cbor::wasm::{from_jsvalue, to_jsvalue};
+use serde_cbor::Value;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn test_scalars() {
+    assert_eq!(JsValue::from(Value::Integer(1)).as_f64(), Some(1.0));
+    assert!(JsValue::from(Value::from(u64::max_value())).is_bigint());
+    assert!(JsValue::from(Value::from(i64::min_value())).is_bigint());
+    assert_eq!(JsValue::from(Value::Null), JsValue::NULL);
+    assert_eq!(
+        from_jsvalue::<Value>(&JsValue::from_f64(2.0)).unwrap(),
+        Value::Integer(2)
+    );
+    assert_eq!(
+        from_jsvalue::<Value>(&JsValue::from_f64(-2.0)).unwrap(),
+        Value::Integer(-2)
+    );
+    assert_eq!(
+        from_jsvalue::<Value>(&JsValue::from_f64(0.5)).unwrap(),
+        Value::F64(0.5)
+    );
+    assert_eq!(
+        from_jsvalue::<Value>(&JsValue::UNDEFINED).unwrap(),
+        Value::Null
+    );
+    let big = JsValue::from(u64::max_value());
+    assert_eq!(
+        from_jsvalue::<Value>(&big).unwrap(),
+        Value::from(u64::max_value())
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_bytes_and_maps() {
+    let mut entries = BTreeMap::new();
+    entries.insert(Value::Integer(1), Value::Bytes(vec![1, 2]));
+    entries.insert(
+        Value::String("a".to_string()),
+        Value::Array(vec![Value::Bool(true)]),
+    );
+    let value = Value::Object(entries);
+
+    let js = JsValue::from(&value);
+    let map = js.dyn_ref::<Map>().unwrap();
+    let bytes = map.get(&JsValue::from_f64(1.0));
+    assert_eq!(bytes.dyn_ref::<Uint8Array>().unwrap().to_vec(), vec![1, 2]);
+    assert!(Array::is_array(&map.get(&JsValue::from_str("a"))));
+    assert_eq!(from_jsvalue::<Value>(&js).unwrap(), value);
+
+    let object = Object::new();
+    Reflect::set(&object, &JsValue::from_str("x"), &JsValue::from_f64(1.0)).unwrap();
+    let map: BTreeMap<String, u8> = from_jsvalue(&object).unwrap();
+    assert_eq!(map["x"], 1);
+}
+
+#[wasm_bindgen_test]
+fn test_serde() {
+    let js = to_jsvalue(&(1, "two")).unwrap();
+    assert_eq!(
+        from_jsvalue::<(u8, String)>(&js).unwrap(),
+        (1, "two".to_string())
+    );
+    let function = js_sys::Function::new_no_args("");
+    assert!(from_jsvalue::<Value>(&function).is_err());
+}
@@ -0,0 +1,638 @@
+#![cfg(feature = "std")]
+
+#[cfg(feature = "chrono")]
+extern crate chrono;
+extern crate serde;
+extern crate serde_cbor;
+#[cfg(feature = "ipnet")]
+extern crate ipnet;
+#[cfg(feature = "num-bigint")]
+extern crate num_bigint;
+#[cfg(feature = "regex")]
+extern crate regex;
+#[cfg(feature = "time")]
+extern crate time;
+#[cfg(feature = "url")]
+extern crate url;
+#[cfg(feature = "uuid")]
+extern crate uuid;
+#[macro_use]
+extern crate serde_derive;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_cbor::{diag, from_slice, to_vec, Value};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Times {
+    #[serde(with = "serde_cbor::with::epoch")]
+    epoch: SystemTime,
+    #[serde(with = "serde_cbor::with::rfc3339")]
+    rfc3339: SystemTime,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Timeout {
+    #[serde(with = "serde_cbor::with::seconds")]
+    after: Duration,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Big(#[serde(with = "serde_cbor::with::bignum")] u128);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Hinted {
+    #[serde(with = "serde_cbor::with::base64url")]
+    url: Vec<u8>,
+    #[serde(with = "serde_cbor::with::base64")]
+    standard: Vec<u8>,
+    #[serde(with = "serde_cbor::with::base16")]
+    hex: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Claims {
+    issuer: String,
+    expiry: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Token {
+    #[serde(with = "serde_cbor::with::packed")]
+    claims: Claims,
+    signature: Vec<u8>,
+}
+
+fn at(secs: u64, nanos: u32) -> SystemTime {
+    UNIX_EPOCH + Duration::new(secs, nanos)
+}
+
+#[test]
+fn test_times() {
+    let times = Times {
+        epoch: at(1363896240, 0),
+        rfc3339: at(1363896240, 0),
+    };
+    assert_eq!(
+        diag::to_diag_string(&times).unwrap(),
+        r#"{"epoch": 1(1363896240), "rfc3339": 0("2013-03-21T20:04:00Z")}"#
+    );
+    assert_eq!(
+        from_slice::<Times>(&to_vec(&times).unwrap()).unwrap(),
+        times
+    );
+
+    let times = Times {
+        epoch: at(1363896240, 500_000_000),
+        rfc3339: UNIX_EPOCH - Duration::new(0, 250_000_000),
+    };
+    assert_eq!(
+        diag::to_diag_string(&times).unwrap(),
+        r#"{"epoch": 1(1363896240.5), "rfc3339": 0("1969-12-31T23:59:59.75Z")}"#
+    );
+    assert_eq!(
+        from_slice::<Times>(&to_vec(&times).unwrap()).unwrap(),
+        times
+    );
+
+    let times = Times {
+        epoch: UNIX_EPOCH - Duration::from_secs(86400),
+        rfc3339: at(951782400, 0),
+    };
+    assert_eq!(
+        diag::to_diag_string(&times).unwrap(),
+        r#"{"epoch": 1(-86400), "rfc3339": 0("2000-02-29T00:00:00Z")}"#
+    );
+    assert_eq!(
+        from_slice::<Times>(&to_vec(&times).unwrap()).unwrap(),
+        times
+    );
+}
+
+#[test]
+fn test_parse_times() {
+    let times: Times = diag::from_diag_str(
+        r#"{"epoch": 1363896240, "rfc3339": "2013-03-21t22:04:00.000000001+02:00"}"#,
+    )
+    .unwrap();
+    assert_eq!(times.epoch, at(1363896240, 0));
+    assert_eq!(times.rfc3339, at(1363896240, 1));
+
+    let times: Times =
+        diag::from_diag_str(r#"{"epoch": 1(-0.5), "rfc3339": 0("2016-12-31T23:59:60Z")}"#).unwrap();
+    assert_eq!(times.epoch, UNIX_EPOCH - Duration::from_millis(500));
+    assert_eq!(times.rfc3339, at(1483228800, 0));
+
+    for text in &[
+        "2013-03-21T20:04:00",
+        "2013-02-29T20:04:00Z",
+        "2013-03-21 20:04:00Z",
+        "2013-03-21T24:00:00Z",
+        "2013-03-21T20:04:00.Z",
+        "2013-03-21T20:04:00+0200",
+    ] {
+        let diag = format!(r#"{{"epoch": 0, "rfc3339": "{}"}}"#, text);
+        assert!(diag::from_diag_str::<Times>(&diag).is_err(), "{}", text);
+    }
+    let error = diag::from_diag_str::<Times>(r#"{"epoch": 0(0), "rfc3339": 0("")}"#).unwrap_err();
+    assert!(error.is_data());
+    assert!(error.to_string().starts_with("expected tag 1, found tag 0"));
+}
+
+#[test]
+fn test_seconds() {
+    for &(duration, diag) in &[
+        (Duration::from_secs(90), r#"{"after": 90}"#),
+        (Duration::from_millis(1500), r#"{"after": 1.5}"#),
+        (Duration::new(0, 1), r#"{"after": 1e-9}"#),
+    ] {
+        let timeout = Timeout { after: duration };
+        assert_eq!(diag::to_diag_string(&timeout).unwrap(), diag);
+        assert_eq!(
+            from_slice::<Timeout>(&to_vec(&timeout).unwrap()).unwrap(),
+            timeout
+        );
+    }
+    for diag in &[r#"{"after": -1}"#, r#"{"after": -0.5}"#, r#"{"after": NaN}"#] {
+        assert!(diag::from_diag_str::<Timeout>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn test_chrono() {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event {
+        #[serde(with = "serde_cbor::with::chrono::rfc3339")]
+        start: DateTime<Utc>,
+        #[serde(with = "serde_cbor::with::chrono::epoch")]
+        end: DateTime<Utc>,
+    }
+
+    let event = Event {
+        start: Utc.timestamp_opt(1363896240, 250_000_000).unwrap(),
+        end: Utc.timestamp_opt(-1, 500_000_000).unwrap(),
+    };
+    assert_eq!(
+        diag::to_diag_string(&event).unwrap(),
+        r#"{"start": 0("2013-03-21T20:04:00.250Z"), "end": 1(-0.5)}"#
+    );
+    assert_eq!(
+        from_slice::<Event>(&to_vec(&event).unwrap()).unwrap(),
+        event
+    );
+
+    // Either form is accepted, with or without its tag.
+    let event: Event = diag::from_diag_str(
+        r#"{"start": 1(1363896240), "end": "2013-03-21T22:04:00.000000001+02:00"}"#,
+    )
+    .unwrap();
+    assert_eq!(event.start, Utc.timestamp_opt(1363896240, 0).unwrap());
+    assert_eq!(event.end, Utc.timestamp_opt(1363896240, 1).unwrap());
+
+    for diag in &[
+        r#"{"start": 0(1363896240), "end": 0}"#,
+        r#"{"start": 1("2013-03-21T20:04:00Z"), "end": 0}"#,
+        r#"{"start": 2(h'01'), "end": 0}"#,
+        r#"{"start": "2013-03-21", "end": 0}"#,
+    ] {
+        assert!(diag::from_diag_str::<Event>(diag).is_err(), "{}", diag);
+    }
+    let event = Event {
+        start: Utc.timestamp_opt(253402300800, 0).unwrap(),
+        end: Utc.timestamp_opt(0, 0).unwrap(),
+    };
+    assert!(to_vec(&event).is_err());
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn test_time() {
+    use time::{Date, Month, OffsetDateTime, UtcOffset};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Entry {
+        #[serde(with = "serde_cbor::with::time::rfc3339")]
+        created: OffsetDateTime,
+        #[serde(with = "serde_cbor::with::time::epoch")]
+        changed: OffsetDateTime,
+        #[serde(with = "serde_cbor::with::time::full_date")]
+        due: Date,
+        #[serde(with = "serde_cbor::with::time::epoch_days")]
+        done: Date,
+    }
+
+    let offset = UtcOffset::from_hms(2, 0, 0).unwrap();
+    let entry = Entry {
+        created: OffsetDateTime::from_unix_timestamp_nanos(1363896240_250_000_000)
+            .unwrap()
+            .to_offset(offset),
+        changed: OffsetDateTime::from_unix_timestamp_nanos(-500_000_000).unwrap(),
+        due: Date::from_calendar_date(2000, Month::February, 29).unwrap(),
+        done: Date::from_calendar_date(1969, Month::December, 31).unwrap(),
+    };
+    assert_eq!(
+        diag::to_diag_string(&entry).unwrap(),
+        r#"{"created": 0("2013-03-21T22:04:00.25+02:00"), "changed": 1(-0.5), "#.to_string()
+            + r#""due": 1004("2000-02-29"), "done": 100(-1)}"#
+    );
+    assert_eq!(
+        from_slice::<Entry>(&to_vec(&entry).unwrap()).unwrap(),
+        entry
+    );
+
+    // Either form is accepted, with or without its tag.
+    let entry: Entry = diag::from_diag_str(
+        r#"{"created": 1(1363896240), "changed": "2013-03-21T20:04:00Z",
+            "due": 100(11016), "done": "2000-02-29"}"#,
+    )
+    .unwrap();
+    assert_eq!(entry.created.unix_timestamp(), 1363896240);
+    assert_eq!(entry.changed.unix_timestamp(), 1363896240);
+    assert_eq!(entry.due, Date::from_calendar_date(2000, Month::February, 29).unwrap());
+    assert_eq!(entry.done, entry.due);
+
+    for diag in &[
+        r#"{"created": 0(0), "changed": 0, "due": 0, "done": 0}"#,
+        r#"{"created": 0, "changed": 1("1970-01-01T00:00:00Z"), "due": 0, "done": 0}"#,
+        r#"{"created": 0, "changed": 0, "due": 1004(0), "done": 0}"#,
+        r#"{"created": 0, "changed": 0, "due": 0, "done": 100("2000-02-29")}"#,
+        r#"{"created": 0, "changed": 0, "due": 0, "done": 0(0)}"#,
+        r#"{"created": 0, "changed": 0, "due": "2013-02-29", "done": 0}"#,
+        r#"{"created": 0, "changed": 0, "due": 0.5, "done": 0}"#,
+        r#"{"created": 0, "changed": 0, "due": 0, "done": 10000000000}"#,
+    ] {
+        assert!(diag::from_diag_str::<Entry>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[test]
+fn test_bignum() {
+    assert_eq!(to_vec(&Big(1)).unwrap(), b"\x01");
+    assert_eq!(
+        to_vec(&Big(u128::from(u64::max_value()))).unwrap(),
+        b"\x1b\xff\xff\xff\xff\xff\xff\xff\xff"
+    );
+    let bytes = to_vec(&Big(1 << 64)).unwrap();
+    assert_eq!(bytes, b"\xc2\x49\x01\x00\x00\x00\x00\x00\x00\x00\x00");
+    assert_eq!(from_slice::<Big>(&bytes).unwrap(), Big(1 << 64));
+    let bytes = to_vec(&Big(u128::max_value())).unwrap();
+    assert_eq!(from_slice::<Big>(&bytes).unwrap(), Big(u128::max_value()));
+
+    assert_eq!(from_slice::<Big>(b"\x18\x2a").unwrap(), Big(42));
+    assert_eq!(from_slice::<Big>(b"\xc2\x42\x00\x2a").unwrap(), Big(42));
+    assert!(from_slice::<Big>(b"\xc3\x41\x2a").is_err());
+    assert!(from_slice::<Big>(b"\x20").is_err());
+    let too_large = diag::diag_to_bytes("2(h'010000000000000000000000000000000000')").unwrap();
+    assert!(from_slice::<Big>(&too_large).is_err());
+}
+
+#[cfg(feature = "num-bigint")]
+#[test]
+fn test_num_bigint() {
+    use num_bigint::{BigInt, BigUint};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Signed(#[serde(with = "serde_cbor::with::bigint")] BigInt);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Unsigned(#[serde(with = "serde_cbor::with::biguint")] BigUint);
+
+    let two_64 = BigInt::from(u64::max_value()) + 1u8;
+    for &(ref value, diag) in &[
+        (BigInt::from(0), "0"),
+        (BigInt::from(u64::max_value()), "18446744073709551615"),
+        (BigInt::from(i64::min_value()), "-9223372036854775808"),
+        (-two_64.clone(), "-18446744073709551616"),
+        (two_64.clone(), "2(h'010000000000000000')"),
+        (-&two_64 - 1, "3(h'010000000000000000')"),
+    ] {
+        let signed = Signed(value.clone());
+        assert_eq!(diag::to_diag_string(&signed).unwrap(), diag);
+        assert_eq!(from_slice::<Signed>(&to_vec(&signed).unwrap()).unwrap(), signed);
+    }
+    let unsigned = Unsigned(two_64.to_biguint().unwrap());
+    assert_eq!(
+        diag::to_diag_string(&unsigned).unwrap(),
+        "2(h'010000000000000000')"
+    );
+    assert_eq!(
+        from_slice::<Unsigned>(&to_vec(&unsigned).unwrap()).unwrap(),
+        unsigned
+    );
+
+    // Leading zeros and missing tags are accepted.
+    let signed: Signed = diag::from_diag_str("3(h'0000ff')").unwrap();
+    assert_eq!(signed.0, BigInt::from(-256));
+    let signed: Signed = diag::from_diag_str("h'0100'").unwrap();
+    assert_eq!(signed.0, BigInt::from(256));
+    let unsigned: Unsigned = diag::from_diag_str("2(h'')").unwrap();
+    assert_eq!(unsigned.0, BigUint::from(0u32));
+
+    for diag in &["2(1)", "4(h'01')", "\"1\""] {
+        assert!(diag::from_diag_str::<Signed>(diag).is_err(), "{}", diag);
+    }
+    for diag in &["-1", "3(h'00')"] {
+        assert!(diag::from_diag_str::<Unsigned>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn test_regex() {
+    use regex::Regex;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Route {
+        #[serde(with = "serde_cbor::with::regex")]
+        path: Regex,
+    }
+
+    let route = Route {
+        path: Regex::new(r"^/sensors/\d+$").unwrap(),
+    };
+    assert_eq!(
+        diag::to_diag_string(&route).unwrap(),
+        r#"{"path": 35("^/sensors/\\d+$")}"#
+    );
+    let route: Route = from_slice(&to_vec(&route).unwrap()).unwrap();
+    assert!(route.path.is_match("/sensors/12"));
+    let route: Route = diag::from_diag_str(r#"{"path": "^a"}"#).unwrap();
+    assert_eq!(route.path.as_str(), "^a");
+
+    for diag in &[
+        r#"{"path": 35("(a")}"#,
+        r#"{"path": 35("(?<=a)b")}"#,
+        r#"{"path": 32("^a")}"#,
+    ] {
+        assert!(diag::from_diag_str::<Route>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[test]
+fn test_typed_array() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Samples {
+        #[serde(with = "serde_cbor::with::typed_array")]
+        native: Vec<f32>,
+        #[serde(with = "serde_cbor::with::typed_array::big_endian")]
+        big: Vec<i64>,
+        #[serde(with = "serde_cbor::with::typed_array::little_endian")]
+        little: Vec<u8>,
+    }
+
+    let samples = Samples {
+        native: vec![1.0, -1.5],
+        big: vec![-2, 0x0102],
+        little: vec![7],
+    };
+    let native = if cfg!(target_endian = "little") {
+        "85(h'0000803f0000c0bf')"
+    } else {
+        "81(h'3f800000bfc00000')"
+    };
+    assert_eq!(
+        diag::to_diag_string(&samples).unwrap(),
+        format!(
+            r#"{{"native": {}, "big": 75(h'{}'), "little": 64(h'07')}}"#,
+            native, "fffffffffffffffe0000000000000102"
+        )
+    );
+    assert_eq!(from_slice::<Samples>(&to_vec(&samples).unwrap()).unwrap(), samples);
+
+    // Either byte order is accepted, as are byte strings without a tag and clamped bytes.
+    let samples: Samples = diag::from_diag_str(concat!(
+        r#"{"native": 81(h'3f800000bfc00000'), "#,
+        r#""big": 79(h'feffffffffffffff'), "little": 68(h'07')}"#
+    ))
+    .unwrap();
+    assert_eq!(samples.native, vec![1.0, -1.5]);
+    assert_eq!(samples.big, vec![-2]);
+    assert_eq!(samples.little, vec![7]);
+    let samples: Samples =
+        diag::from_diag_str(r#"{"native": h'', "big": h'', "little": h'0102'}"#).unwrap();
+    assert_eq!(samples.little, vec![1, 2]);
+
+    for diag in &[
+        r#"{"native": 82(h'3ff0000000000000'), "big": h'', "little": h''}"#,
+        r#"{"native": 85(h'0000803f00'), "big": h'', "little": h''}"#,
+        r#"{"native": h'', "big": 67(h''), "little": h''}"#,
+        r#"{"native": h'', "big": h'', "little": 72(h'07')}"#,
+        r#"{"native": h'', "big": h'', "little": 2(h'07')}"#,
+    ] {
+        assert!(diag::from_diag_str::<Samples>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "url")]
+#[test]
+fn test_url() {
+    use url::Url;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Link {
+        #[serde(with = "serde_cbor::with::url")]
+        href: Url,
+    }
+
+    let link = Link {
+        href: Url::parse("coap://[2001:db8::1]/.well-known/core?rt=temp").unwrap(),
+    };
+    assert_eq!(
+        diag::to_diag_string(&link).unwrap(),
+        r#"{"href": 32("coap://[2001:db8::1]/.well-known/core?rt=temp")}"#
+    );
+    assert_eq!(from_slice::<Link>(&to_vec(&link).unwrap()).unwrap(), link);
+    let link: Link = diag::from_diag_str(r#"{"href": "HTTP://Example.com"}"#).unwrap();
+    assert_eq!(link.href.as_str(), "http://example.com/");
+
+    for diag in &[
+        r#"{"href": 32("/relative")}"#,
+        r#"{"href": 33("http://example.com/")}"#,
+        r#"{"href": 32(h'00')}"#,
+    ] {
+        assert!(diag::from_diag_str::<Link>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[test]
+fn test_ip() {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Peer {
+        #[serde(with = "serde_cbor::with::ip")]
+        addr: IpAddr,
+        #[serde(with = "serde_cbor::with::ip::v4")]
+        v4: Ipv4Addr,
+        #[serde(with = "serde_cbor::with::ip::v6")]
+        v6: Ipv6Addr,
+    }
+
+    let peer = Peer {
+        addr: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+        v4: Ipv4Addr::new(198, 51, 100, 7),
+        v6: "2001:db8::1".parse().unwrap(),
+    };
+    assert_eq!(
+        diag::to_diag_string(&peer).unwrap(),
+        r#"{"addr": 260(h'c0000201'), "v4": 260(h'c6336407'), "#.to_string()
+            + r#""v6": 260(h'20010db8000000000000000000000001')}"#
+    );
+    assert_eq!(from_slice::<Peer>(&to_vec(&peer).unwrap()).unwrap(), peer);
+
+    let peer_diag = |addr: &str, v4: &str, v6: &str| {
+        format!(r#"{{"addr": {}, "v4": {}, "v6": {}}}"#, addr, v4, v6)
+    };
+    let localhost = "h'00000000000000000000000000000001'";
+    let peer: Peer = diag::from_diag_str(&peer_diag(
+        "h'20010db8000000000000000000000002'",
+        "h'7f000001'",
+        localhost,
+    ))
+    .unwrap();
+    assert_eq!(peer.addr, "2001:db8::2".parse::<IpAddr>().unwrap());
+    assert_eq!(peer.v4, Ipv4Addr::LOCALHOST);
+    assert_eq!(peer.v6, Ipv6Addr::LOCALHOST);
+
+    for diag in &[
+        peer_diag("260(h'c00002')", "h'7f000001'", localhost),
+        peer_diag("261(h'c0000201')", "h'7f000001'", localhost),
+        peer_diag("h'c0000201'", localhost, localhost),
+        peer_diag("h'c0000201'", "h'7f000001'", "h'7f000001'"),
+    ] {
+        assert!(diag::from_diag_str::<Peer>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "ipnet")]
+#[test]
+fn test_ipnet() {
+    use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Routes {
+        #[serde(with = "serde_cbor::with::ipnet")]
+        net: IpNet,
+        #[serde(with = "serde_cbor::with::ipnet::v4")]
+        v4: Ipv4Net,
+        #[serde(with = "serde_cbor::with::ipnet::v6")]
+        v6: Ipv6Net,
+    }
+
+    let routes = Routes {
+        net: "2001:db8::/32".parse().unwrap(),
+        v4: "10.0.0.0/8".parse().unwrap(),
+        v6: "::/0".parse().unwrap(),
+    };
+    assert_eq!(
+        diag::to_diag_string(&routes).unwrap(),
+        r#"{"net": 261({h'20010db8000000000000000000000000': 32}), "#.to_string()
+            + r#""v4": 261({h'0a000000': 8}), "#
+            + r#""v6": 261({h'00000000000000000000000000000000': 0})}"#
+    );
+    assert_eq!(from_slice::<Routes>(&to_vec(&routes).unwrap()).unwrap(), routes);
+
+    // The bits after the prefix are cleared.
+    let routes = Routes {
+        net: "192.0.2.1/24".parse().unwrap(),
+        ..routes
+    };
+    let routes: Routes = from_slice(&to_vec(&routes).unwrap()).unwrap();
+    assert_eq!(routes.net, "192.0.2.0/24".parse::<IpNet>().unwrap());
+
+    let routes_diag = |net: &str, v4: &str, v6: &str| {
+        format!(r#"{{"net": {}, "v4": {}, "v6": {}}}"#, net, v4, v6)
+    };
+    let (v4, v6) = ("{h'0a000000': 8}", "{h'00000000000000000000000000000000': 0}");
+    let routes: Routes = diag::from_diag_str(&routes_diag("{h'c0000200': 24}", v4, v6)).unwrap();
+    assert_eq!(routes.net, "192.0.2.0/24".parse::<IpNet>().unwrap());
+    for diag in &[
+        routes_diag("261({h'c0000200': 33})", v4, v6),
+        routes_diag("261({h'c00002': 24})", v4, v6),
+        routes_diag("261({})", v4, v6),
+        routes_diag("261({h'c0000200': 24, h'c0000300': 24})", v4, v6),
+        routes_diag("260({h'c0000200': 24})", v4, v6),
+        routes_diag("261(h'c0000200')", v4, v6),
+        routes_diag(v6, v6, v6),
+        routes_diag(v4, v4, v4),
+    ] {
+        assert!(diag::from_diag_str::<Routes>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn test_uuid() {
+    use uuid::Uuid;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Device {
+        #[serde(with = "serde_cbor::with::uuid")]
+        id: Uuid,
+    }
+
+    let device = Device {
+        id: Uuid::from_u128(0x8ee2_4c3a_52ad_4b34_9f2d_6a0c_1f7a_8e3b),
+    };
+    let bytes = to_vec(&device).unwrap();
+    assert_eq!(bytes[..7], b"\xa1\x62id\xd8\x25\x50"[..]);
+    assert_eq!(from_slice::<Device>(&bytes).unwrap(), device);
+    let untagged: Device =
+        diag::from_diag_str(r#"{"id": h'8ee24c3a52ad4b349f2d6a0c1f7a8e3b'}"#).unwrap();
+    assert_eq!(untagged, device);
+
+    for diag in &[
+        r#"{"id": 37(h'8ee24c3a52ad4b349f2d6a0c1f7a8e')}"#,
+        r#"{"id": 23(h'8ee24c3a52ad4b349f2d6a0c1f7a8e3b')}"#,
+        r#"{"id": "8ee24c3a-52ad-4b34-9f2d-6a0c1f7a8e3b"}"#,
+    ] {
+        assert!(diag::from_diag_str::<Device>(diag).is_err(), "{}", diag);
+    }
+}
+
+#[test]
+fn test_expected_encodings() {
+    let hinted = Hinted {
+        url: vec![1],
+        standard: vec![2],
+        hex: vec![3],
+    };
+    assert_eq!(
+        diag::to_diag_string(&hinted).unwrap(),
+        r#"{"url": 21(h'01'), "standard": 22(h'02'), "hex": 23(h'03')}"#
+    );
+    let bytes = to_vec(&hinted).unwrap();
+    assert_eq!(from_slice::<Hinted>(&bytes).unwrap(), hinted);
+    let untagged = diag::diag_to_bytes(r#"{"url": h'01', "standard": h'02', "hex": h'03'}"#);
+    assert_eq!(from_slice::<Hinted>(&untagged.unwrap()).unwrap(), hinted);
+    let mistagged = diag::diag_to_bytes(r#"{"url": 22(h'01'), "standard": h'', "hex": h''}"#);
+    assert!(from_slice::<Hinted>(&mistagged.unwrap()).is_err());
+}
+
+#[test]
+fn test_packed() {
+    let token = Token {
+        claims: Claims {
+            issuer: "a".to_string(),
+            expiry: 2,
+        },
+        signature: vec![],
+    };
+    assert_eq!(
+        diag::to_diag_string(&token).unwrap(),
+        r#"{"claims": {0: "a", 1: 2}, "signature": []}"#
+    );
+    let bytes = to_vec(&token).unwrap();
+    assert_eq!(from_slice::<Token>(&bytes).unwrap(), token);
+}
+
+#[test]
+fn test_other_formats() {
+    // Values converted to `Value` keep their content and their tags.
+    let value = serde_cbor::to_value(&Big(1 << 64)).unwrap();
+    let bytes = Value::Bytes(vec![1, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(value, Value::Tag(2, Box::new(bytes)));
+}